@@ -41,6 +41,7 @@ impl Into<String> for Region {
             Region::In865 => "IN865",
             Region::Ru864 => "RU864",
             Region::Ism2400 => "ISM2400",
+            Region::Custom => "CUSTOM",
         }
         .to_string()
     }
@@ -65,6 +66,7 @@ impl FromStr for Region {
             "IN865" => Region::In865,
             "RU864" => Region::Ru864,
             "ISM2400" => Region::Ism2400,
+            "CUSTOM" => Region::Custom,
             _ => {
                 return Err("invalid region".into());
             }
@@ -122,6 +124,7 @@ impl Into<String> for RegParamsRevision {
             RegParamsRevision::Rp002101 => "RP002_1.0.1",
             RegParamsRevision::Rp002102 => "RP002_1.0.2",
             RegParamsRevision::Rp002103 => "RP002_1.0.3",
+            RegParamsRevision::Rp002104 => "RP002_1.0.4",
         }
         .to_string()
     }
@@ -138,6 +141,7 @@ impl FromStr for RegParamsRevision {
             "RP002_1.0.1" => RegParamsRevision::Rp002101,
             "RP002_1.0.2" => RegParamsRevision::Rp002102,
             "RP002_1.0.3" => RegParamsRevision::Rp002103,
+            "RP002_1.0.4" => RegParamsRevision::Rp002104,
             _ => {
                 return Err("invalid reg param revision".into());
             }
@@ -31,6 +31,8 @@ impl Into<String> for LogCode {
             LogCode::UplinkFCntRetransmission => "UPLINK_F_CNT_RETRANSMISSION",
             LogCode::DownlinkGateway => "DOWNLINK_GATEWAY",
             LogCode::RelayNewEndDevice => "RELAY_NEW_END_DEVICE",
+            LogCode::UplinkFCntResetAccepted => "UPLINK_F_CNT_RESET_ACCEPTED",
+            LogCode::UplinkFCntRollback => "UPLINK_F_CNT_ROLLBACK",
         }
         .to_string()
     }
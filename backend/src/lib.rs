@@ -161,6 +161,10 @@ impl Client {
         Ok(ans)
     }
 
+    pub async fn join_ans(&self, pl: &JoinAnsPayload) -> Result<()> {
+        self.response_request(None, pl).await
+    }
+
     pub async fn rejoin_req(
         &self,
         pl: &mut RejoinReqPayload,
@@ -175,6 +179,10 @@ impl Client {
         Ok(ans)
     }
 
+    pub async fn rejoin_ans(&self, pl: &RejoinAnsPayload) -> Result<()> {
+        self.response_request(None, pl).await
+    }
+
     pub async fn app_s_key_req(
         &self,
         pl: &mut AppSKeyReqPayload,
@@ -189,6 +197,10 @@ impl Client {
         Ok(ans)
     }
 
+    pub async fn app_s_key_ans(&self, pl: &AppSKeyAnsPayload) -> Result<()> {
+        self.response_request(None, pl).await
+    }
+
     pub async fn pr_start_req(
         &self,
         target_role: Role,
@@ -465,6 +477,9 @@ impl BasePayload {
                 receiver_id: self.sender_id.clone(),
                 transaction_id: self.transaction_id,
                 message_type: match self.message_type {
+                    MessageType::JoinReq => MessageType::JoinAns,
+                    MessageType::RejoinReq => MessageType::RejoinAns,
+                    MessageType::AppSKeyReq => MessageType::AppSKeyAns,
                     MessageType::PRStartReq => MessageType::PRStartAns,
                     MessageType::PRStopReq => MessageType::PRStopAns,
                     MessageType::XmitDataReq => MessageType::XmitDataAns,
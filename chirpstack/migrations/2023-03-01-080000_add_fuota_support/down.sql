@@ -0,0 +1,2 @@
+drop table fuota_deployment_device;
+drop table fuota_deployment;
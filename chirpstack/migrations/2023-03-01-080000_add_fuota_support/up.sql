@@ -0,0 +1,50 @@
+create table fuota_deployment (
+    id uuid primary key,
+    created_at timestamp with time zone not null,
+    updated_at timestamp with time zone not null,
+    started_at timestamp with time zone null,
+    completed_at timestamp with time zone null,
+    name varchar(100) not null,
+    application_id uuid not null references application on delete cascade,
+    device_profile_id uuid not null references device_profile on delete cascade,
+    multicast_group_id uuid null references multicast_group on delete set null,
+    multicast_group_type char(1) not null,
+    multicast_class_c_scheduling_type varchar(20) not null,
+    multicast_dr smallint not null,
+    multicast_class_b_ping_slot_period integer not null,
+    multicast_frequency bigint not null,
+    multicast_timeout smallint not null,
+    multicast_addr bytea not null,
+    multicast_key bytea not null,
+    unicast_timeout integer not null,
+    unicast_max_retry_count smallint not null,
+    fragmentation_fragment_size smallint not null,
+    fragmentation_redundancy_percentage smallint not null,
+    fragmentation_session_index smallint not null,
+    fragmentation_matrix smallint not null,
+    fragmentation_block_ack_delay smallint not null,
+    fragmentation_descriptor bytea not null,
+    payload bytea not null,
+    next_step varchar(20) not null,
+    retry_count smallint not null,
+    scheduler_run_after timestamp with time zone not null,
+    multicast_session_start timestamp with time zone null
+);
+
+create index idx_fuota_deployment_application_id on fuota_deployment (application_id);
+create index idx_fuota_deployment_scheduler_run_after on fuota_deployment (scheduler_run_after);
+
+create table fuota_deployment_device (
+    fuota_deployment_id uuid not null references fuota_deployment on delete cascade,
+    dev_eui bytea not null references device on delete cascade,
+    created_at timestamp with time zone not null,
+    updated_at timestamp with time zone not null,
+    mc_group_setup_completed_at timestamp with time zone null,
+    mc_session_completed_at timestamp with time zone null,
+    frag_session_setup_completed_at timestamp with time zone null,
+    frag_status_completed_at timestamp with time zone null,
+    error_msg text not null,
+    primary key (fuota_deployment_id, dev_eui)
+);
+
+create index idx_fuota_deployment_device_dev_eui on fuota_deployment_device (dev_eui);
@@ -0,0 +1,5 @@
+alter table device_profile
+    add column clock_sync_enabled boolean not null default false;
+
+alter table device_profile
+    alter column clock_sync_enabled drop default;
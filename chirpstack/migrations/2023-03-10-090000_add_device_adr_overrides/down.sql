@@ -0,0 +1,8 @@
+alter table device
+    drop column adr_pinned_tx_power_index,
+    drop column adr_pinned_dr,
+    drop column adr_installation_margin,
+    drop column adr_nb_trans,
+    drop column adr_max_tx_power_index,
+    drop column adr_max_dr,
+    drop column adr_min_dr;
@@ -0,0 +1,8 @@
+alter table device
+    add column adr_min_dr smallint null,
+    add column adr_max_dr smallint null,
+    add column adr_max_tx_power_index smallint null,
+    add column adr_nb_trans smallint null,
+    add column adr_installation_margin real null,
+    add column adr_pinned_dr smallint null,
+    add column adr_pinned_tx_power_index smallint null;
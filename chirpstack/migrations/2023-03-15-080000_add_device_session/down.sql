@@ -0,0 +1,5 @@
+drop index idx_device_session_dr;
+drop index idx_device_session_f_cnt_up;
+drop index idx_device_session_pending_rejoin_dev_addr;
+drop index idx_device_session_dev_addr;
+drop table device_session;
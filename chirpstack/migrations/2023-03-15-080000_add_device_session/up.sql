@@ -0,0 +1,19 @@
+create table device_session (
+    dev_eui bytea primary key references device on delete cascade,
+    created_at timestamp with time zone not null,
+    updated_at timestamp with time zone not null,
+    dev_addr bytea not null,
+    pending_rejoin_dev_addr bytea null,
+    region_config_id varchar(100) not null,
+    f_cnt_up bigint not null,
+    n_f_cnt_down bigint not null,
+    a_f_cnt_down bigint not null,
+    dr smallint not null,
+    tx_power_index smallint not null,
+    device_session bytea not null
+);
+
+create index idx_device_session_dev_addr on device_session (dev_addr);
+create index idx_device_session_pending_rejoin_dev_addr on device_session (pending_rejoin_dev_addr);
+create index idx_device_session_f_cnt_up on device_session (f_cnt_up);
+create index idx_device_session_dr on device_session (dr);
@@ -0,0 +1 @@
+drop index idx_device_dev_addr;
@@ -0,0 +1 @@
+create index idx_device_dev_addr on device (dev_addr);
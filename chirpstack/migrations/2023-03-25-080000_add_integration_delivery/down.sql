@@ -0,0 +1,4 @@
+drop index idx_integration_delivery_updated_at;
+drop index idx_integration_delivery_next_attempt_at;
+drop index idx_integration_delivery_application_id;
+drop table integration_delivery;
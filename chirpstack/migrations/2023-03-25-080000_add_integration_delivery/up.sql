@@ -0,0 +1,20 @@
+create table integration_delivery (
+    id uuid primary key,
+    application_id uuid not null references application on delete cascade,
+    integration_kind varchar(20) null,
+    global_integration varchar(20) not null,
+    endpoint text not null,
+    created_at timestamp with time zone not null,
+    updated_at timestamp with time zone not null,
+    event_type varchar(20) not null,
+    vars jsonb not null,
+    payload bytea not null,
+    attempts smallint not null,
+    dead_letter boolean not null,
+    next_attempt_at timestamp with time zone not null,
+    last_error text not null
+);
+
+create index idx_integration_delivery_application_id on integration_delivery (application_id);
+create index idx_integration_delivery_next_attempt_at on integration_delivery (next_attempt_at) where not dead_letter;
+create index idx_integration_delivery_updated_at on integration_delivery (updated_at) where dead_letter;
@@ -0,0 +1,2 @@
+alter table application_integration
+  drop column routing;
@@ -0,0 +1,2 @@
+alter table application_integration
+  add column routing jsonb not null default '{}';
@@ -0,0 +1,6 @@
+create table js_session_key (
+    dev_eui bytea not null references device_keys on delete cascade,
+    session_key_id bytea not null,
+    created_at timestamp with time zone not null,
+    primary key (dev_eui, session_key_id)
+);
@@ -237,6 +237,7 @@ mod test {
             min_dr: 0,
             max_dr: 0,
             uplink_history: vec![],
+            gateways: vec![],
         };
 
         for i in 0..20 {
@@ -458,6 +459,7 @@ mod test {
             min_dr: 0,
             max_dr: 0,
             uplink_history: vec![],
+            gateways: vec![],
         };
         req.uplink_history.push(internal::UplinkAdrHistory {
             max_snr: 3.0,
@@ -496,6 +498,7 @@ mod test {
             min_dr: 0,
             max_dr: 0,
             uplink_history: vec![],
+            gateways: vec![],
         };
 
         struct Test {
@@ -515,7 +518,7 @@ mod test {
                     nb_trans: 1,
                     max_dr: 4,
                     max_tx_power_index: 5,
-                    ..req_template
+                    ..req_template.clone()
                 },
                 response: Response {
                     dr: 5,
@@ -537,7 +540,7 @@ mod test {
                         max_snr: 0.0,
                         ..Default::default()
                     }],
-                    ..req_template
+                    ..req_template.clone()
                 },
                 response: Response {
                     dr: 4,
@@ -560,7 +563,7 @@ mod test {
                         max_snr: -15.0,
                         ..Default::default()
                     }],
-                    ..req_template
+                    ..req_template.clone()
                 },
                 response: Response {
                     dr: 1,
@@ -0,0 +1,251 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+
+use super::default;
+use super::{Handler, Request, Response};
+use crate::{config, region};
+
+// Min. number of gateways that must receive the device with sufficient margin
+// at the higher data-rate.
+const MIN_GATEWAYS: usize = 2;
+
+// Margin (dB) that the installation margin is reduced by when the device is
+// received by multiple gateways (macro diversity).
+const DIVERSITY_GAIN: f32 = 3.0;
+
+// Additional margin (dB) that the installation margin is reduced by when one
+// of the gateways is loaded. Shorter uplinks reduce the chance of collisions.
+const LOAD_GAIN: f32 = 3.0;
+
+// Uplink channel utilization (0.0 - 1.0) above which a gateway is considered
+// to be loaded.
+const LOAD_THRESHOLD: f32 = 0.1;
+
+pub struct Algorithm {}
+
+impl Algorithm {
+    pub fn new() -> Self {
+        Algorithm {}
+    }
+
+    // Returns the margin that must remain after switching to a higher data-rate.
+    fn get_effective_margin(&self, req: &Request) -> f32 {
+        let mut margin = req.installation_margin - DIVERSITY_GAIN;
+
+        if req
+            .gateways
+            .iter()
+            .any(|gw| gw.utilization.unwrap_or(0.0) >= LOAD_THRESHOLD)
+        {
+            margin -= LOAD_GAIN;
+        }
+
+        margin
+    }
+
+    // Returns the number of gateways that would receive the device with the given
+    // required SNR and margin.
+    fn get_gateway_count(&self, req: &Request, required_snr: f32, margin: f32) -> usize {
+        req.gateways
+            .iter()
+            .filter(|gw| gw.snr - required_snr - margin >= 0.0)
+            .count()
+    }
+}
+
+#[async_trait]
+impl Handler for Algorithm {
+    fn get_name(&self) -> String {
+        "Gateway-aware ADR algorithm (LoRa only)".to_string()
+    }
+
+    fn get_id(&self) -> String {
+        "gateway_aware".to_string()
+    }
+
+    async fn handle(&self, req: &Request) -> Result<Response> {
+        let default_alg = default::Algorithm::new();
+        let mut resp = default_alg.handle(req).await?;
+
+        // Only increase the data-rate of devices that are received by multiple
+        // gateways, and only when the default algorithm did not increase the
+        // TX Power (the link budget is sufficient).
+        if !req.adr || resp.tx_power_index < req.tx_power_index || req.gateways.len() < MIN_GATEWAYS
+        {
+            return Ok(resp);
+        }
+
+        let region_conf =
+            region::get(&req.region_config_id).context("Get region config for region")?;
+        let max_lora_dr = region_conf
+            .get_enabled_uplink_data_rates()
+            .into_iter()
+            .filter_map(|dr| match region_conf.get_data_rate(dr).ok()? {
+                lrwn::region::DataRateModulation::Lora(l) if l.bandwidth == 125000 => Some(dr),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+        let max_dr = req.max_dr.min(max_lora_dr);
+        let margin = self.get_effective_margin(req);
+
+        for dr in ((resp.dr + 1)..=max_dr).rev() {
+            let required_snr = match region_conf.get_data_rate(dr)? {
+                lrwn::region::DataRateModulation::Lora(l) => {
+                    config::get_required_snr_for_sf(l.spreading_factor)?
+                }
+                _ => continue,
+            };
+
+            if self.get_gateway_count(req, required_snr, margin) >= MIN_GATEWAYS {
+                resp.dr = dr;
+                break;
+            }
+        }
+
+        Ok(resp)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::adr::Gateway;
+    use crate::test;
+    use chirpstack_api::internal;
+    use lrwn::EUI64;
+    use std::str::FromStr;
+
+    fn get_request() -> Request {
+        Request {
+            region_config_id: "eu868".into(),
+            region_common_name: lrwn::region::CommonName::EU868,
+            dev_eui: EUI64::from_str("0102030405060708").unwrap(),
+            mac_version: lrwn::region::MacVersion::LORAWAN_1_0_4,
+            reg_params_revision: lrwn::region::Revision::RP002_1_0_3,
+            adr: true,
+            dr: 0,
+            tx_power_index: 0,
+            nb_trans: 1,
+            max_tx_power_index: 0,
+            required_snr_for_dr: -20.0,
+            installation_margin: 10.0,
+            min_dr: 0,
+            max_dr: 5,
+            uplink_history: vec![internal::UplinkAdrHistory {
+                max_snr: -12.0,
+                ..Default::default()
+            }],
+            gateways: vec![],
+        }
+    }
+
+    fn get_gateway(id: u8, snr: f32, utilization: Option<f32>) -> Gateway {
+        Gateway {
+            gateway_id: EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, id]),
+            rssi: -100,
+            snr,
+            utilization,
+        }
+    }
+
+    #[test]
+    fn test_id() {
+        let a = Algorithm::new();
+        assert_eq!("gateway_aware", a.get_id());
+    }
+
+    #[test]
+    fn test_get_effective_margin() {
+        let a = Algorithm::new();
+        let mut req = get_request();
+
+        req.gateways = vec![get_gateway(1, 0.0, None), get_gateway(2, 0.0, Some(0.05))];
+        assert_eq!(7.0, a.get_effective_margin(&req));
+
+        req.gateways = vec![get_gateway(1, 0.0, None), get_gateway(2, 0.0, Some(0.2))];
+        assert_eq!(4.0, a.get_effective_margin(&req));
+    }
+
+    #[tokio::test]
+    async fn test_handle() {
+        let _guard = test::prepare().await;
+        let a = Algorithm::new();
+
+        struct Test {
+            name: String,
+            request: Request,
+            response: Response,
+        }
+
+        let tests = vec![
+            Test {
+                name: "single gateway, default algorithm result".into(),
+                request: Request {
+                    gateways: vec![get_gateway(1, -12.0, None)],
+                    ..get_request()
+                },
+                response: Response {
+                    dr: 0,
+                    tx_power_index: 0,
+                    nb_trans: 1,
+                },
+            },
+            Test {
+                name: "two gateways, increase to DR 2".into(),
+                request: Request {
+                    gateways: vec![get_gateway(1, -8.0, None), get_gateway(2, -7.0, None)],
+                    ..get_request()
+                },
+                response: Response {
+                    dr: 2,
+                    tx_power_index: 0,
+                    nb_trans: 1,
+                },
+            },
+            Test {
+                name: "two gateways, one loaded, increase to DR 3".into(),
+                request: Request {
+                    gateways: vec![get_gateway(1, -8.0, Some(0.5)), get_gateway(2, -7.0, None)],
+                    ..get_request()
+                },
+                response: Response {
+                    dr: 3,
+                    tx_power_index: 0,
+                    nb_trans: 1,
+                },
+            },
+            Test {
+                name: "two gateways, only one with sufficient margin".into(),
+                request: Request {
+                    gateways: vec![get_gateway(1, -8.0, None), get_gateway(2, -20.0, None)],
+                    ..get_request()
+                },
+                response: Response {
+                    dr: 0,
+                    tx_power_index: 0,
+                    nb_trans: 1,
+                },
+            },
+            Test {
+                name: "adr disabled".into(),
+                request: Request {
+                    adr: false,
+                    gateways: vec![get_gateway(1, -8.0, None), get_gateway(2, -7.0, None)],
+                    ..get_request()
+                },
+                response: Response {
+                    dr: 0,
+                    tx_power_index: 0,
+                    nb_trans: 1,
+                },
+            },
+        ];
+
+        for tst in &tests {
+            println!("> {}", tst.name);
+            let resp = a.handle(&tst.request).await.unwrap();
+            assert_eq!(tst.response, resp);
+        }
+    }
+}
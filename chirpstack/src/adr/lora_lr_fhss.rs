@@ -92,6 +92,7 @@ pub mod test {
             min_dr: 0,
             max_dr: 0,
             uplink_history: vec![],
+            gateways: vec![],
         };
 
         struct Test {
@@ -114,7 +115,7 @@ pub mod test {
                         max_snr: -10.0,
                         ..Default::default()
                     }],
-                    ..req_template
+                    ..req_template.clone()
                 },
                 response: Response {
                     dr: 3,
@@ -135,7 +136,7 @@ pub mod test {
                         max_snr: -12.0,
                         ..Default::default()
                     }],
-                    ..req_template
+                    ..req_template.clone()
                 },
                 response: Response {
                     dr: 10,
@@ -207,6 +207,7 @@ pub mod test {
             min_dr: 0,
             max_dr: 0,
             uplink_history: vec![],
+            gateways: vec![],
         };
 
         struct Test {
@@ -228,7 +229,7 @@ pub mod test {
                         max_rssi: -130,
                         ..Default::default()
                     }],
-                    ..req_template
+                    ..req_template.clone()
                 },
                 response: Response {
                     dr: 0,
@@ -248,7 +249,7 @@ pub mod test {
                         max_rssi: -130,
                         ..Default::default()
                     }],
-                    ..req_template
+                    ..req_template.clone()
                 },
                 response: Response {
                     dr: 0,
@@ -268,7 +269,7 @@ pub mod test {
                         max_rssi: -130,
                         ..Default::default()
                     }],
-                    ..req_template
+                    ..req_template.clone()
                 },
                 response: Response {
                     dr: 10,
@@ -290,7 +291,7 @@ pub mod test {
                             ..Default::default()
                         })
                         .collect(),
-                    ..req_template
+                    ..req_template.clone()
                 },
                 response: Response {
                     dr: 11,
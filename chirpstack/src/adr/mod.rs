@@ -10,18 +10,32 @@ use chirpstack_api::internal;
 use lrwn::EUI64;
 
 pub mod default;
+pub mod gateway_aware;
 pub mod lora_lr_fhss;
 pub mod lr_fhss;
 pub mod plugin;
+pub mod simulation;
 
 lazy_static! {
     static ref ADR_ALGORITHMS: RwLock<HashMap<String, Box<dyn Handler + Sync + Send>>> =
         RwLock::new(HashMap::new());
 }
 
+type Algorithms = HashMap<String, Box<dyn Handler + Sync + Send>>;
+
 pub async fn setup() -> Result<()> {
     info!("Setting up adr algorithms");
-    let mut algos = ADR_ALGORITHMS.write().await;
+    let conf = config::get();
+    let algos = build(&conf)?;
+    replace(algos).await;
+
+    Ok(())
+}
+
+/// Builds the ADR algorithms (including the configured plugins) for the given
+/// configuration, without activating these.
+pub fn build(conf: &config::Configuration) -> Result<Algorithms> {
+    let mut algos: Algorithms = HashMap::new();
 
     trace!("Setting up included algorithms");
     let a = default::Algorithm::new();
@@ -33,15 +47,23 @@ pub async fn setup() -> Result<()> {
     let a = lora_lr_fhss::Algorithm::new();
     algos.insert(a.get_id(), Box::new(a));
 
+    let a = gateway_aware::Algorithm::new();
+    algos.insert(a.get_id(), Box::new(a));
+
     trace!("Setting up plugins");
-    let conf = config::get();
     for file_path in &conf.network.adr_plugins {
         info!(file_path = %file_path, "Setting up ADR plugin");
-        let a = plugin::Plugin::new(file_path)?;
+        let a = plugin::Plugin::new(file_path, &conf.network.adr_plugin_runtime)?;
         algos.insert(a.get_id(), Box::new(a));
     }
 
-    Ok(())
+    Ok(algos)
+}
+
+/// Replaces all the ADR algorithms.
+pub async fn replace(algos: Algorithms) {
+    let mut algos_w = ADR_ALGORITHMS.write().await;
+    *algos_w = algos;
 }
 
 pub async fn get_algorithms() -> HashMap<String, String> {
@@ -92,6 +114,7 @@ pub trait Handler {
     async fn handle(&self, req: &Request) -> Result<Response>;
 }
 
+#[derive(Clone)]
 pub struct Request {
     pub region_config_id: String,
     pub region_common_name: lrwn::region::CommonName,
@@ -108,6 +131,17 @@ pub struct Request {
     pub min_dr: u8,
     pub max_dr: u8,
     pub uplink_history: Vec<internal::UplinkAdrHistory>,
+    pub gateways: Vec<Gateway>,
+}
+
+/// Gateway which received the last uplink of the device.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Gateway {
+    pub gateway_id: EUI64,
+    pub rssi: i32,
+    pub snr: f32,
+    // Uplink channel utilization (0.0 - 1.0), None when unknown.
+    pub utilization: Option<f32>,
 }
 
 #[derive(Debug, PartialEq, Eq)]
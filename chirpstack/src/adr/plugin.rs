@@ -1,38 +1,124 @@
 use std::fs;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::histogram::Histogram;
+use tokio::sync::oneshot;
+use tracing::{error, trace};
 
 use super::{Handler, Request, Response};
+use crate::config;
+use crate::monitoring::prometheus;
 
+// Name of the global holding the handle function of the compiled script.
+const HANDLE_FUNC: &str = "__chirpstack_adr_handle";
+
+lazy_static! {
+    static ref INVOCATION_COUNTER: Family<PluginLabels, Counter> = {
+        let counter = Family::<PluginLabels, Counter>::default();
+        prometheus::register(
+            "adr_plugin_invocations",
+            "Number of ADR plugin invocations by plugin ID",
+            counter.clone(),
+        );
+        counter
+    };
+    static ref ERROR_COUNTER: Family<PluginLabels, Counter> = {
+        let counter = Family::<PluginLabels, Counter>::default();
+        prometheus::register(
+            "adr_plugin_errors",
+            "Number of ADR plugin errors by plugin ID",
+            counter.clone(),
+        );
+        counter
+    };
+    static ref DURATION_HISTOGRAM: Family<PluginLabels, Histogram> = {
+        let histogram = Family::<PluginLabels, Histogram>::new_with_constructor(|| {
+            Histogram::new(
+                [
+                    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0,
+                ]
+                .into_iter(),
+            )
+        });
+        prometheus::register(
+            "adr_plugin_duration_seconds",
+            "Duration of ADR plugin invocations by plugin ID",
+            histogram.clone(),
+        );
+        histogram
+    };
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+struct PluginLabels {
+    plugin_id: String,
+}
+
+type Job = (Request, oneshot::Sender<Result<Response>>);
+
+/// ADR plugin (JavaScript).
+///
+/// Each plugin has a pool of worker threads, each owning a runtime with the
+/// pre-compiled plugin script. The execution time and memory usage of each
+/// runtime are limited. After an error, the runtime of the worker is
+/// re-created, such that a failing invocation does not affect the next one.
 pub struct Plugin {
-    script: String,
     id: String,
     name: String,
+    // The workers stop once the sender has been dropped.
+    jobs: Mutex<mpsc::Sender<Job>>,
 }
 
 impl Plugin {
-    pub fn new(file_path: &str) -> Result<Self> {
-        let rt = rquickjs::Runtime::new()?;
-        let ctx = rquickjs::Context::full(&rt)?;
+    pub fn new(file_path: &str, conf: &config::AdrPluginRuntime) -> Result<Self> {
         let script = fs::read_to_string(file_path).context("Read ADR plugin")?;
 
-        let (id, name) = ctx.with::<_, Result<(String, String)>>(|ctx| {
-            let m = ctx
-                .compile("script", script.clone())
-                .context("Compile script")?;
-            let id_func: rquickjs::Function = m.get("id").context("Get id function")?;
-            let name_func: rquickjs::Function = m.get("name").context("Get name function")?;
+        let (id, name) = {
+            let rt = Runtime::new(conf)?;
+            rt.with_deadline(|ctx| -> Result<(String, String)> {
+                let m = ctx
+                    .compile("script", script.clone())
+                    .context("Compile script")?;
+                let id_func: rquickjs::Function = m.get("id").context("Get id function")?;
+                let name_func: rquickjs::Function = m.get("name").context("Get name function")?;
+
+                let id: String = id_func.call(()).context("Call id function")?;
+                let name: String = name_func.call(()).context("Call name function")?;
+
+                Ok((id, name))
+            })?
+        };
 
-            let id: String = id_func.call(()).context("Call id function")?;
-            let name: String = name_func.call(()).context("Call name function")?;
+        let (tx, rx) = mpsc::channel::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
 
-            Ok((id, name))
-        })?;
+        for i in 0..conf.pool_size.max(1) {
+            let worker = Worker {
+                id: id.clone(),
+                script: script.clone(),
+                conf: conf.clone(),
+                jobs: rx.clone(),
+                runtime: None,
+            };
 
-        let p = Plugin { script, id, name };
+            thread::Builder::new()
+                .name(format!("adr-plugin-{}-{}", id, i))
+                .spawn(move || worker.run())
+                .context("Spawn ADR plugin worker")?;
+        }
 
-        Ok(p)
+        Ok(Plugin {
+            id,
+            name,
+            jobs: Mutex::new(tx),
+        })
     }
 }
 
@@ -47,14 +133,85 @@ impl Handler for Plugin {
     }
 
     async fn handle(&self, req: &Request) -> Result<Response> {
-        let rt = rquickjs::Runtime::new()?;
-        let ctx = rquickjs::Context::full(&rt)?;
+        let labels = PluginLabels {
+            plugin_id: self.id.clone(),
+        };
+        let start = Instant::now();
+
+        let (tx, rx) = oneshot::channel();
+        self.jobs
+            .lock()
+            .unwrap()
+            .send((req.clone(), tx))
+            .map_err(|_| anyhow!("ADR plugin workers have stopped"))?;
+        let resp = match rx.await {
+            Ok(v) => v,
+            Err(_) => Err(anyhow!("ADR plugin worker did not return a response")),
+        };
+
+        INVOCATION_COUNTER.get_or_create(&labels).inc();
+        DURATION_HISTOGRAM
+            .get_or_create(&labels)
+            .observe(start.elapsed().as_secs_f64());
+        if resp.is_err() {
+            ERROR_COUNTER.get_or_create(&labels).inc();
+        }
+
+        resp
+    }
+}
+
+struct Worker {
+    id: String,
+    script: String,
+    conf: config::AdrPluginRuntime,
+    jobs: Arc<Mutex<mpsc::Receiver<Job>>>,
+    runtime: Option<Runtime>,
+}
+
+impl Worker {
+    fn run(mut self) {
+        loop {
+            let job = self.jobs.lock().unwrap().recv();
+            let (req, tx) = match job {
+                Ok(v) => v,
+                Err(_) => {
+                    trace!(plugin_id = %self.id, "ADR plugin worker stopped");
+                    return;
+                }
+            };
 
-        ctx.with::<_, Result<Response>>(|ctx| {
-            let m = ctx
-                .compile("script", self.script.clone())
-                .context("Compile script")?;
-            let func: rquickjs::Function = m.get("handle").context("Get handle function")?;
+            let resp = self.handle(&req);
+            if let Err(e) = &resp {
+                error!(plugin_id = %self.id, error = %format!("{:#}", e), "ADR plugin error, re-creating runtime");
+                self.runtime = None;
+            }
+
+            // The receiver might have gone away.
+            let _ = tx.send(resp);
+        }
+    }
+
+    fn handle(&mut self, req: &Request) -> Result<Response> {
+        if self.runtime.is_none() {
+            let rt = Runtime::new(&self.conf)?;
+            rt.with_deadline(|ctx| -> Result<()> {
+                let m = ctx
+                    .compile("script", self.script.clone())
+                    .context("Compile script")?;
+                let func: rquickjs::Function = m.get("handle").context("Get handle function")?;
+                ctx.globals().set(HANDLE_FUNC, func)?;
+                Ok(())
+            })?;
+            self.runtime = Some(rt);
+        }
+
+        let rt = self.runtime.as_ref().unwrap();
+        rt.with_deadline(|ctx| -> Result<Response> {
+            let func: rquickjs::Function = ctx
+                .globals()
+                .get(HANDLE_FUNC)
+                .context("Get handle function")?;
 
             let input = rquickjs::Object::new(ctx)?;
             input.set("regionConfigId", req.region_config_id.clone())?;
@@ -86,6 +243,19 @@ impl Handler for Plugin {
 
             input.set("uplinkHistory", uplink_history)?;
 
+            let mut gateways: Vec<rquickjs::Object> = Vec::new();
+
+            for gw in &req.gateways {
+                let obj = rquickjs::Object::new(ctx)?;
+                obj.set("gatewayId", gw.gateway_id.to_string())?;
+                obj.set("rssi", gw.rssi)?;
+                obj.set("snr", gw.snr)?;
+                obj.set("utilization", gw.utilization)?;
+                gateways.push(obj);
+            }
+
+            input.set("gateways", gateways)?;
+
             let res: rquickjs::Object = func.call((input,)).context("Call handle function")?;
 
             Ok(Response {
@@ -99,19 +269,58 @@ impl Handler for Plugin {
     }
 }
 
+// Runtime with execution time and memory limits.
+struct Runtime {
+    // Note: the context must be dropped before the runtime.
+    ctx: rquickjs::Context,
+    _rt: rquickjs::Runtime,
+    max_execution_time: Duration,
+    deadline: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Runtime {
+    fn new(conf: &config::AdrPluginRuntime) -> Result<Self> {
+        let deadline: Arc<Mutex<Option<Instant>>> = Arc::new(Mutex::new(None));
+
+        let rt = rquickjs::Runtime::new()?;
+        rt.set_memory_limit(conf.max_memory);
+        rt.set_interrupt_handler(Some(Box::new({
+            let deadline = deadline.clone();
+            move || match *deadline.lock().unwrap() {
+                Some(v) => Instant::now() > v,
+                None => false,
+            }
+        })));
+        let ctx = rquickjs::Context::full(&rt)?;
+
+        Ok(Runtime {
+            ctx,
+            _rt: rt,
+            max_execution_time: conf.max_execution_time,
+            deadline,
+        })
+    }
+
+    // Executes the given function, interrupting the script when it exceeds
+    // the max. execution time.
+    fn with_deadline<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(rquickjs::Ctx) -> Result<R>,
+    {
+        *self.deadline.lock().unwrap() = Some(Instant::now() + self.max_execution_time);
+        let res = self.ctx.with(f);
+        *self.deadline.lock().unwrap() = None;
+        res
+    }
+}
+
 #[cfg(test)]
 pub mod test {
     use super::*;
     use lrwn::EUI64;
 
-    #[tokio::test]
-    async fn test_plugin() {
-        let p = Plugin::new("../examples/adr_plugins/plugin_skeleton.js").unwrap();
-
-        assert_eq!("Example plugin", p.get_name());
-        assert_eq!("example_id", p.get_id());
-
-        let req = Request {
+    fn get_request() -> Request {
+        Request {
             region_config_id: "eu868".into(),
             region_common_name: lrwn::region::CommonName::EU868,
             dev_eui: EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
@@ -127,16 +336,80 @@ pub mod test {
             min_dr: 0,
             max_dr: 5,
             uplink_history: vec![],
-        };
+            gateways: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plugin() {
+        let p = Plugin::new(
+            "../examples/adr_plugins/plugin_skeleton.js",
+            &Default::default(),
+        )
+        .unwrap();
+
+        assert_eq!("Example plugin", p.get_name());
+        assert_eq!("example_id", p.get_id());
+
+        // Multiple invocations re-use the runtimes of the pool.
+        for _ in 0..10 {
+            let resp = p.handle(&get_request()).await.unwrap();
+            assert_eq!(
+                Response {
+                    dr: 3,
+                    tx_power_index: 0,
+                    nb_trans: 1,
+                },
+                resp
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plugin_timeout() {
+        let file_path = std::env::temp_dir().join("chirpstack-test-adr-plugin-timeout.js");
+        fs::write(
+            &file_path,
+            r#"
+            export function name() {
+                return "Timeout plugin";
+            }
+
+            export function id() {
+                return "timeout";
+            }
+
+            export function handle(req) {
+                if (req.dr == 0) {
+                    while (true) {}
+                }
 
-        let resp = p.handle(&req).await.unwrap();
-        assert_eq!(
-            Response {
-                dr: 3,
-                tx_power_index: 0,
-                nb_trans: 1,
+                return {
+                    dr: req.dr,
+                    txPowerIndex: req.txPowerIndex,
+                    nbTrans: req.nbTrans
+                };
+            }
+            "#,
+        )
+        .unwrap();
+
+        let p = Plugin::new(
+            file_path.to_str().unwrap(),
+            &config::AdrPluginRuntime {
+                pool_size: 1,
+                max_execution_time: Duration::from_millis(10),
+                ..Default::default()
             },
-            resp
-        );
+        )
+        .unwrap();
+
+        let mut req = get_request();
+        req.dr = 0;
+        assert!(p.handle(&req).await.is_err());
+
+        // The runtime is re-created after the error.
+        req.dr = 3;
+        assert_eq!(3, p.handle(&req).await.unwrap().dr);
     }
 }
@@ -0,0 +1,255 @@
+use anyhow::{Context, Result};
+
+use super::{Request, ADR_ALGORITHMS};
+use crate::storage::device_profile;
+use crate::{config, region};
+use chirpstack_api::internal;
+use lrwn::region::DataRateModulation;
+use lrwn::EUI64;
+
+/// Max. number of uplinks kept in the history (same as the uplink path).
+const MAX_HISTORY_COUNT: usize = 20;
+
+/// Input of an ADR simulation.
+#[derive(Clone, Debug)]
+pub struct Simulation {
+    pub algorithm_id: String,
+    pub region_config_id: String,
+    pub dev_eui: EUI64,
+    pub mac_version: lrwn::region::MacVersion,
+    pub reg_params_revision: lrwn::region::Revision,
+    // Overrides the installation margin of the region configuration.
+    pub installation_margin: Option<f32>,
+    // Initial state of the device.
+    pub dr: u8,
+    pub tx_power_index: u8,
+    pub nb_trans: u8,
+    // Max. TX Power index supported by the device (0 = use region max).
+    pub max_supported_tx_power_index: u8,
+    // Recorded uplinks to replay.
+    pub uplink_history: Vec<internal::UplinkAdrHistory>,
+}
+
+impl Simulation {
+    /// Returns the simulation input for the given device-profile and
+    /// device-session, using the uplink history stored in the device-session.
+    pub fn new(dp: &device_profile::DeviceProfile, ds: &internal::DeviceSession) -> Result<Self> {
+        Ok(Simulation {
+            algorithm_id: dp.adr_algorithm_id.clone(),
+            region_config_id: ds.region_config_id.clone(),
+            dev_eui: EUI64::from_slice(&ds.dev_eui)?,
+            mac_version: dp.mac_version,
+            reg_params_revision: dp.reg_params_revision,
+            installation_margin: None,
+            dr: ds.dr as u8,
+            tx_power_index: ds.tx_power_index as u8,
+            nb_trans: ds.nb_trans as u8,
+            max_supported_tx_power_index: ds.max_supported_tx_power_index as u8,
+            uplink_history: ds.uplink_adr_history.clone(),
+        })
+    }
+}
+
+/// ADR decision made after replaying a single uplink.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub f_cnt: u32,
+    // Max. SNR of the uplink, corrected for the simulated TX Power.
+    pub max_snr: f32,
+    pub dr: u8,
+    pub tx_power_index: u8,
+    pub nb_trans: u8,
+    // Link margin (dB) when using the new DR and TX Power, excluding the
+    // installation margin.
+    pub link_margin: f32,
+}
+
+/// Replays the uplink history through the ADR algorithm and returns the
+/// decision for every uplink.
+///
+/// The simulated device is assumed to apply every decision before the next
+/// uplink. As the SNR of the recorded uplinks does not depend on the data-rate,
+/// only the difference in TX Power (compared to the recorded TX Power) is
+/// corrected for.
+pub async fn simulate(sim: &Simulation) -> Result<Vec<Step>> {
+    let region_conf = region::get(&sim.region_config_id)?;
+    let network_conf = config::get_region_network(&sim.region_config_id)?;
+
+    let max_tx_power_index = if sim.max_supported_tx_power_index != 0 {
+        sim.max_supported_tx_power_index
+    } else {
+        let mut max_tx_power_index: u8 = 0;
+        for n in 0..16 {
+            if region_conf.get_tx_power_offset(n).is_ok() {
+                max_tx_power_index = n as u8;
+            }
+        }
+        max_tx_power_index
+    };
+
+    let tx_power_delta = |from: u32, to: u8| -> f32 {
+        match (
+            region_conf.get_tx_power_offset(from as usize),
+            region_conf.get_tx_power_offset(to as usize),
+        ) {
+            (Ok(from), Ok(to)) => (to - from) as f32,
+            _ => 0.0,
+        }
+    };
+
+    let required_snr_for_dr = |dr: u8| -> Result<f32> {
+        Ok(match region_conf.get_data_rate(dr)? {
+            DataRateModulation::Lora(params) => {
+                config::get_required_snr_for_sf(params.spreading_factor)?
+            }
+            _ => 0.0,
+        })
+    };
+
+    let algos = ADR_ALGORITHMS.read().await;
+    let algo = algos
+        .get(&sim.algorithm_id)
+        .ok_or_else(|| anyhow!("No ADR algorithm configured with ID: {}", sim.algorithm_id))?;
+
+    let mut dr = sim.dr;
+    let mut tx_power_index = sim.tx_power_index;
+    let mut nb_trans = sim.nb_trans;
+    let mut history: Vec<internal::UplinkAdrHistory> = Vec::new();
+    let mut out: Vec<Step> = Vec::new();
+
+    for uh in &sim.uplink_history {
+        let delta = tx_power_delta(uh.tx_power_index, tx_power_index);
+        let max_snr = uh.max_snr + delta;
+
+        history.push(internal::UplinkAdrHistory {
+            f_cnt: uh.f_cnt,
+            max_snr,
+            max_rssi: uh.max_rssi + delta as i32,
+            tx_power_index: tx_power_index as u32,
+            gateway_count: uh.gateway_count,
+        });
+        if history.len() > MAX_HISTORY_COUNT {
+            history.remove(0);
+        }
+
+        let req = Request {
+            region_config_id: sim.region_config_id.clone(),
+            region_common_name: region_conf.get_name(),
+            dev_eui: sim.dev_eui,
+            mac_version: sim.mac_version,
+            reg_params_revision: sim.reg_params_revision,
+            adr: true,
+            dr,
+            tx_power_index,
+            nb_trans,
+            max_tx_power_index,
+            required_snr_for_dr: required_snr_for_dr(dr)?,
+            installation_margin: sim
+                .installation_margin
+                .unwrap_or(network_conf.installation_margin),
+            min_dr: network_conf.min_dr,
+            max_dr: network_conf.max_dr,
+            uplink_history: history.clone(),
+            // The gateway meta-data is not part of the uplink history.
+            gateways: vec![],
+        };
+
+        let resp = algo
+            .handle(&req)
+            .await
+            .context(format!("Handle ADR request, f_cnt: {}", uh.f_cnt))?;
+
+        out.push(Step {
+            f_cnt: uh.f_cnt,
+            max_snr,
+            dr: resp.dr,
+            tx_power_index: resp.tx_power_index,
+            nb_trans: resp.nb_trans,
+            link_margin: max_snr + tx_power_delta(tx_power_index as u32, resp.tx_power_index)
+                - required_snr_for_dr(resp.dr)?,
+        });
+
+        dr = resp.dr;
+        tx_power_index = resp.tx_power_index;
+        nb_trans = resp.nb_trans;
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::test;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_simulate() {
+        let _guard = test::prepare().await;
+
+        let mut sim = Simulation {
+            algorithm_id: "default".into(),
+            region_config_id: "eu868".into(),
+            dev_eui: EUI64::from_str("0102030405060708").unwrap(),
+            mac_version: lrwn::region::MacVersion::LORAWAN_1_0_4,
+            reg_params_revision: lrwn::region::Revision::RP002_1_0_3,
+            installation_margin: None,
+            dr: 0,
+            tx_power_index: 0,
+            nb_trans: 1,
+            max_supported_tx_power_index: 0,
+            uplink_history: vec![
+                internal::UplinkAdrHistory {
+                    f_cnt: 1,
+                    max_snr: 10.0,
+                    tx_power_index: 0,
+                    gateway_count: 1,
+                    ..Default::default()
+                },
+                internal::UplinkAdrHistory {
+                    f_cnt: 2,
+                    max_snr: 10.0,
+                    tx_power_index: 0,
+                    gateway_count: 1,
+                    ..Default::default()
+                },
+            ],
+        };
+
+        // The first uplink has a margin of 20dB (6 steps), the second uplink
+        // is corrected for the lower TX Power and has a margin of 5.5dB.
+        assert_eq!(
+            vec![
+                Step {
+                    f_cnt: 1,
+                    max_snr: 10.0,
+                    dr: 5,
+                    tx_power_index: 1,
+                    nb_trans: 1,
+                    link_margin: 15.5,
+                },
+                Step {
+                    f_cnt: 2,
+                    max_snr: 8.0,
+                    dr: 5,
+                    tx_power_index: 2,
+                    nb_trans: 1,
+                    link_margin: 13.5,
+                },
+            ],
+            simulate(&sim).await.unwrap()
+        );
+
+        // With a higher installation margin, the first uplink results in
+        // fewer steps.
+        sim.installation_margin = Some(20.0);
+        sim.uplink_history.truncate(1);
+        let steps = simulate(&sim).await.unwrap();
+        assert_eq!(3, steps[0].dr);
+        assert_eq!(0, steps[0].tx_power_index);
+
+        // Unknown algorithm.
+        sim.algorithm_id = "unknown".into();
+        assert!(simulate(&sim).await.is_err());
+    }
+}
@@ -8,9 +8,9 @@ use chirpstack_api::api::application_service_server::ApplicationService;
 
 use super::auth::validator;
 use super::error::ToStatus;
-use super::helpers;
+use super::helpers::{self, ToProto};
 use crate::certificate;
-use crate::storage::application;
+use crate::storage::{application, integration_delivery};
 
 pub struct Application {
     validator: validator::RequestValidator,
@@ -225,21 +225,7 @@ impl ApplicationService for Application {
         let mut items: Vec<api::IntegrationListItem> = result
             .iter()
             .map(|i| api::IntegrationListItem {
-                kind: match i.kind {
-                    application::IntegrationKind::Http => api::IntegrationKind::Http,
-                    application::IntegrationKind::InfluxDb => api::IntegrationKind::InfluxDb,
-                    application::IntegrationKind::ThingsBoard => api::IntegrationKind::ThingsBoard,
-                    application::IntegrationKind::MyDevices => api::IntegrationKind::MyDevices,
-                    application::IntegrationKind::LoraCloud => api::IntegrationKind::LoraCloud,
-                    application::IntegrationKind::GcpPubSub => api::IntegrationKind::GcpPubSub,
-                    application::IntegrationKind::AwsSns => api::IntegrationKind::AwsSns,
-                    application::IntegrationKind::AzureServiceBus => {
-                        api::IntegrationKind::AzureServiceBus
-                    }
-                    application::IntegrationKind::PilotThings => api::IntegrationKind::PilotThings,
-                    application::IntegrationKind::Ifttt => api::IntegrationKind::Ifttt,
-                }
-                .into(),
+                kind: i.kind.to_proto().into(),
             })
             .collect();
         items.push(api::IntegrationListItem {
@@ -286,6 +272,16 @@ impl ApplicationService for Application {
                         api::Encoding::Json => true,
                     },
                     event_endpoint_url: req_int.event_endpoint_url.clone(),
+                    signing_secret: req_int.signing_secret.clone(),
+                    oauth2_token_url: req_int.oauth2_token_url.clone(),
+                    oauth2_client_id: req_int.oauth2_client_id.clone(),
+                    oauth2_client_secret: req_int.oauth2_client_secret.clone(),
+                    oauth2_scopes: req_int.oauth2_scopes.clone(),
+                    tls_client_cert: req_int.tls_client_cert.clone(),
+                    tls_client_key: req_int.tls_client_key.clone(),
+                    ca_cert: req_int.ca_cert.clone(),
+                    timeout: req_int.timeout,
+                    uplink_response_downlink: req_int.uplink_response_downlink,
                 },
             ),
             ..Default::default()
@@ -333,6 +329,16 @@ impl ApplicationService for Application {
                     }
                     .into(),
                     event_endpoint_url: conf.event_endpoint_url.clone(),
+                    signing_secret: conf.signing_secret.clone(),
+                    oauth2_token_url: conf.oauth2_token_url.clone(),
+                    oauth2_client_id: conf.oauth2_client_id.clone(),
+                    oauth2_client_secret: conf.oauth2_client_secret.clone(),
+                    oauth2_scopes: conf.oauth2_scopes.clone(),
+                    tls_client_cert: conf.tls_client_cert.clone(),
+                    tls_client_key: conf.tls_client_key.clone(),
+                    ca_cert: conf.ca_cert.clone(),
+                    timeout: conf.timeout,
+                    uplink_response_downlink: conf.uplink_response_downlink,
                 }),
             });
             resp.metadata_mut()
@@ -374,6 +380,16 @@ impl ApplicationService for Application {
                         api::Encoding::Json => true,
                     },
                     event_endpoint_url: req_int.event_endpoint_url.clone(),
+                    signing_secret: req_int.signing_secret.clone(),
+                    oauth2_token_url: req_int.oauth2_token_url.clone(),
+                    oauth2_client_id: req_int.oauth2_client_id.clone(),
+                    oauth2_client_secret: req_int.oauth2_client_secret.clone(),
+                    oauth2_scopes: req_int.oauth2_scopes.clone(),
+                    tls_client_cert: req_int.tls_client_cert.clone(),
+                    tls_client_key: req_int.tls_client_key.clone(),
+                    ca_cert: req_int.ca_cert.clone(),
+                    timeout: req_int.timeout,
+                    uplink_response_downlink: req_int.uplink_response_downlink,
                 },
             ),
             ..Default::default()
@@ -1896,6 +1912,238 @@ impl ApplicationService for Application {
 
         Ok(resp)
     }
+
+    async fn get_integration_routing(
+        &self,
+        request: Request<api::GetIntegrationRoutingRequest>,
+    ) -> Result<Response<api::GetIntegrationRoutingResponse>, Status> {
+        let req = request.get_ref();
+        let app_id = Uuid::from_str(&req.application_id).map_err(|e| e.status())?;
+        let kind = integration_kind_from_proto(req.kind())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateApplicationAccess::new(validator::Flag::Read, app_id),
+            )
+            .await?;
+
+        let i = application::get_integration(&app_id, kind)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::GetIntegrationRoutingResponse {
+            routing: Some(api::IntegrationRouting {
+                event_types: i.routing.event_types.clone(),
+                device_tags: i.routing.device_tags.clone(),
+                device_profile_ids: i
+                    .routing
+                    .device_profile_ids
+                    .iter()
+                    .map(|id| id.to_string())
+                    .collect(),
+                f_ports: i.routing.f_ports.clone(),
+                object_expression: i.routing.object_expression.clone(),
+            }),
+        });
+        resp.metadata_mut()
+            .insert("x-log-application_id", req.application_id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn update_integration_routing(
+        &self,
+        request: Request<api::UpdateIntegrationRoutingRequest>,
+    ) -> Result<Response<()>, Status> {
+        let req = request.get_ref();
+        let app_id = Uuid::from_str(&req.application_id).map_err(|e| e.status())?;
+        let kind = integration_kind_from_proto(req.kind())?;
+        let req_routing = match &req.routing {
+            Some(v) => v,
+            None => {
+                return Err(Status::invalid_argument("routing is missing"));
+            }
+        };
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+            )
+            .await?;
+
+        let _ = application::update_integration_routing(
+            &app_id,
+            kind,
+            application::IntegrationRouting {
+                event_types: req_routing.event_types.clone(),
+                device_tags: req_routing.device_tags.clone(),
+                device_profile_ids: req_routing
+                    .device_profile_ids
+                    .iter()
+                    .map(|id| Uuid::from_str(id))
+                    .collect::<Result<Vec<Uuid>, _>>()
+                    .map_err(|e| e.status())?,
+                f_ports: req_routing.f_ports.clone(),
+                object_expression: req_routing.object_expression.clone(),
+            },
+        )
+        .await
+        .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(());
+        resp.metadata_mut()
+            .insert("x-log-application_id", req.application_id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn list_integration_deliveries(
+        &self,
+        request: Request<api::ListIntegrationDeliveriesRequest>,
+    ) -> Result<Response<api::ListIntegrationDeliveriesResponse>, Status> {
+        let req = request.get_ref();
+        let app_id = Uuid::from_str(&req.application_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateApplicationAccess::new(validator::Flag::Read, app_id),
+            )
+            .await?;
+
+        let filters = integration_delivery::Filters {
+            application_id: Some(app_id),
+            dead_letter: if req.dead_letter_only {
+                Some(true)
+            } else {
+                None
+            },
+        };
+
+        let count = integration_delivery::get_count(&filters)
+            .await
+            .map_err(|e| e.status())?;
+        let items = integration_delivery::list(req.limit as i64, req.offset as i64, &filters)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::ListIntegrationDeliveriesResponse {
+            total_count: count as u32,
+            result: items
+                .iter()
+                .map(|d| api::IntegrationDelivery {
+                    id: d.id.to_string(),
+                    kind: d
+                        .integration_kind
+                        .map(|k| k.to_proto().into())
+                        .unwrap_or_default(),
+                    event_type: d.event_type.clone(),
+                    attempts: d.attempts as u32,
+                    dead_letter: d.dead_letter,
+                    last_error: d.last_error.clone(),
+                    created_at: Some(helpers::datetime_to_prost_timestamp(&d.created_at)),
+                    next_attempt_at: Some(helpers::datetime_to_prost_timestamp(&d.next_attempt_at)),
+                    endpoint: d.endpoint.clone(),
+                    global_integration: d.global_integration.clone(),
+                })
+                .collect(),
+        });
+        resp.metadata_mut()
+            .insert("x-log-application_id", req.application_id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn replay_integration_deliveries(
+        &self,
+        request: Request<api::ReplayIntegrationDeliveriesRequest>,
+    ) -> Result<Response<api::ReplayIntegrationDeliveriesResponse>, Status> {
+        let req = request.get_ref();
+        let app_id = Uuid::from_str(&req.application_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+            )
+            .await?;
+
+        let ids = req
+            .ids
+            .iter()
+            .map(|id| Uuid::from_str(id))
+            .collect::<Result<Vec<Uuid>, _>>()
+            .map_err(|e| e.status())?;
+
+        let count = integration_delivery::replay(&app_id, &ids)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::ReplayIntegrationDeliveriesResponse {
+            count: count as u32,
+        });
+        resp.metadata_mut()
+            .insert("x-log-application_id", req.application_id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn delete_integration_delivery(
+        &self,
+        request: Request<api::DeleteIntegrationDeliveryRequest>,
+    ) -> Result<Response<()>, Status> {
+        let req = request.get_ref();
+        let app_id = Uuid::from_str(&req.application_id).map_err(|e| e.status())?;
+        let id = Uuid::from_str(&req.id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+            )
+            .await?;
+
+        let d = integration_delivery::get(&id)
+            .await
+            .map_err(|e| e.status())?;
+        if d.application_id != app_id {
+            return Err(Status::not_found(id.to_string()));
+        }
+
+        integration_delivery::delete(&id)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(());
+        resp.metadata_mut()
+            .insert("x-log-application_id", req.application_id.parse().unwrap());
+
+        Ok(resp)
+    }
+}
+
+fn integration_kind_from_proto(
+    kind: api::IntegrationKind,
+) -> Result<application::IntegrationKind, Status> {
+    Ok(match kind {
+        api::IntegrationKind::Http => application::IntegrationKind::Http,
+        api::IntegrationKind::InfluxDb => application::IntegrationKind::InfluxDb,
+        api::IntegrationKind::ThingsBoard => application::IntegrationKind::ThingsBoard,
+        api::IntegrationKind::MyDevices => application::IntegrationKind::MyDevices,
+        api::IntegrationKind::LoraCloud => application::IntegrationKind::LoraCloud,
+        api::IntegrationKind::GcpPubSub => application::IntegrationKind::GcpPubSub,
+        api::IntegrationKind::AwsSns => application::IntegrationKind::AwsSns,
+        api::IntegrationKind::AzureServiceBus => application::IntegrationKind::AzureServiceBus,
+        api::IntegrationKind::PilotThings => application::IntegrationKind::PilotThings,
+        api::IntegrationKind::Ifttt => application::IntegrationKind::Ifttt,
+        api::IntegrationKind::MqttGlobal => {
+            return Err(Status::invalid_argument(
+                "integration kind does not support routing rules",
+            ));
+        }
+    })
 }
 
 #[cfg(test)]
@@ -2079,6 +2327,7 @@ pub mod test {
                         .collect(),
                     encoding: api::Encoding::Json.into(),
                     event_endpoint_url: "http://example.com".into(),
+                    ..Default::default()
                 }),
             },
         );
@@ -2102,6 +2351,7 @@ pub mod test {
                     .collect(),
                 encoding: api::Encoding::Json.into(),
                 event_endpoint_url: "http://example.com".into(),
+                ..Default::default()
             }),
             get_resp.integration
         );
@@ -2118,6 +2368,14 @@ pub mod test {
                         .collect(),
                     encoding: api::Encoding::Protobuf.into(),
                     event_endpoint_url: "http://example.org".into(),
+                    signing_secret: "secret".into(),
+                    oauth2_token_url: "http://example.org/token".into(),
+                    oauth2_client_id: "client-id".into(),
+                    oauth2_client_secret: "client-secret".into(),
+                    oauth2_scopes: vec!["events:write".into()],
+                    timeout: 10,
+                    uplink_response_downlink: true,
+                    ..Default::default()
                 }),
             },
         );
@@ -2141,6 +2399,14 @@ pub mod test {
                     .collect(),
                 encoding: api::Encoding::Protobuf.into(),
                 event_endpoint_url: "http://example.org".into(),
+                signing_secret: "secret".into(),
+                oauth2_token_url: "http://example.org/token".into(),
+                oauth2_client_id: "client-id".into(),
+                oauth2_client_secret: "client-secret".into(),
+                oauth2_scopes: vec!["events:write".into()],
+                timeout: 10,
+                uplink_response_downlink: true,
+                ..Default::default()
             }),
             get_resp.integration
         );
@@ -3483,4 +3749,175 @@ pub mod test {
             list_resp
         );
     }
+
+    #[tokio::test]
+    async fn test_integration_routing() {
+        let _guard = test::prepare().await;
+        let app = get_application().await;
+        let u = get_user().await;
+        let service = Application::new(RequestValidator::new());
+
+        // create
+        let create_req = get_request(
+            &u.id,
+            api::CreateHttpIntegrationRequest {
+                integration: Some(api::HttpIntegration {
+                    application_id: app.id.to_string(),
+                    encoding: api::Encoding::Json.into(),
+                    event_endpoint_url: "http://example.com".into(),
+                    ..Default::default()
+                }),
+            },
+        );
+        let _ = service.create_http_integration(create_req).await.unwrap();
+
+        // get default
+        let get_req = get_request(
+            &u.id,
+            api::GetIntegrationRoutingRequest {
+                application_id: app.id.to_string(),
+                kind: api::IntegrationKind::Http.into(),
+            },
+        );
+        let get_resp = service.get_integration_routing(get_req).await.unwrap();
+        assert_eq!(
+            Some(api::IntegrationRouting::default()),
+            get_resp.get_ref().routing
+        );
+
+        // update
+        let routing = api::IntegrationRouting {
+            event_types: vec!["up".into(), "join".into()],
+            device_tags: [("floor".to_string(), "2".to_string())]
+                .iter()
+                .cloned()
+                .collect(),
+            device_profile_ids: vec![Uuid::new_v4().to_string()],
+            f_ports: vec![10],
+            object_expression: "temperature > 20".into(),
+        };
+        let up_req = get_request(
+            &u.id,
+            api::UpdateIntegrationRoutingRequest {
+                application_id: app.id.to_string(),
+                kind: api::IntegrationKind::Http.into(),
+                routing: Some(routing.clone()),
+            },
+        );
+        let _ = service.update_integration_routing(up_req).await.unwrap();
+
+        // get
+        let get_req = get_request(
+            &u.id,
+            api::GetIntegrationRoutingRequest {
+                application_id: app.id.to_string(),
+                kind: api::IntegrationKind::Http.into(),
+            },
+        );
+        let get_resp = service.get_integration_routing(get_req).await.unwrap();
+        assert_eq!(Some(routing), get_resp.get_ref().routing);
+
+        // invalid event type
+        let up_req = get_request(
+            &u.id,
+            api::UpdateIntegrationRoutingRequest {
+                application_id: app.id.to_string(),
+                kind: api::IntegrationKind::Http.into(),
+                routing: Some(api::IntegrationRouting {
+                    event_types: vec!["foo".into()],
+                    ..Default::default()
+                }),
+            },
+        );
+        assert!(service.update_integration_routing(up_req).await.is_err());
+
+        // invalid expression
+        let up_req = get_request(
+            &u.id,
+            api::UpdateIntegrationRoutingRequest {
+                application_id: app.id.to_string(),
+                kind: api::IntegrationKind::Http.into(),
+                routing: Some(api::IntegrationRouting {
+                    object_expression: "temperature >".into(),
+                    ..Default::default()
+                }),
+            },
+        );
+        assert!(service.update_integration_routing(up_req).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_integration_deliveries() {
+        let _guard = test::prepare().await;
+        let app = get_application().await;
+        let u = get_user().await;
+        let service = Application::new(RequestValidator::new());
+
+        let d = integration_delivery::create(integration_delivery::IntegrationDelivery {
+            application_id: app.id,
+            event_type: "up".into(),
+            attempts: 10,
+            dead_letter: true,
+            last_error: "connection refused".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // list
+        let list_req = get_request(
+            &u.id,
+            api::ListIntegrationDeliveriesRequest {
+                application_id: app.id.to_string(),
+                dead_letter_only: true,
+                limit: 10,
+                offset: 0,
+            },
+        );
+        let list_resp = service.list_integration_deliveries(list_req).await.unwrap();
+        let list_resp = list_resp.get_ref();
+        assert_eq!(1, list_resp.total_count);
+        assert_eq!(1, list_resp.result.len());
+        assert_eq!(d.id.to_string(), list_resp.result[0].id);
+        assert_eq!(api::IntegrationKind::Http, list_resp.result[0].kind());
+        assert_eq!(10, list_resp.result[0].attempts);
+        assert!(list_resp.result[0].dead_letter);
+
+        // replay
+        let replay_req = get_request(
+            &u.id,
+            api::ReplayIntegrationDeliveriesRequest {
+                application_id: app.id.to_string(),
+                ids: vec![],
+            },
+        );
+        let replay_resp = service
+            .replay_integration_deliveries(replay_req)
+            .await
+            .unwrap();
+        assert_eq!(1, replay_resp.get_ref().count);
+
+        let d_get = integration_delivery::get(&d.id).await.unwrap();
+        assert_eq!(0, d_get.attempts);
+        assert!(!d_get.dead_letter);
+
+        // delete
+        let del_req = get_request(
+            &u.id,
+            api::DeleteIntegrationDeliveryRequest {
+                application_id: app.id.to_string(),
+                id: d.id.to_string(),
+            },
+        );
+        let _ = service.delete_integration_delivery(del_req).await.unwrap();
+
+        let del_req = get_request(
+            &u.id,
+            api::DeleteIntegrationDeliveryRequest {
+                application_id: app.id.to_string(),
+                id: d.id.to_string(),
+            },
+        );
+        assert!(service.delete_integration_delivery(del_req).await.is_err());
+    }
 }
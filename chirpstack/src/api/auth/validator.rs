@@ -12,8 +12,8 @@ use super::error::Error;
 use crate::api::auth::AuthID;
 use crate::storage::get_db_conn;
 use crate::storage::schema::{
-    api_key, application, device, device_profile, gateway, multicast_group, tenant, tenant_user,
-    user,
+    api_key, application, device, device_profile, fuota_deployment, gateway, multicast_group,
+    tenant, tenant_user, user,
 };
 
 #[derive(Copy, Clone)]
@@ -2234,12 +2234,240 @@ impl Validator for ValidateMulticastGroupQueueAccess {
     }
 }
 
+pub struct ValidateFuotaDeploymentsAccess {
+    flag: Flag,
+    application_id: Uuid,
+}
+
+impl ValidateFuotaDeploymentsAccess {
+    pub fn new(flag: Flag, application_id: Uuid) -> Self {
+        ValidateFuotaDeploymentsAccess {
+            flag,
+            application_id,
+        }
+    }
+}
+
+#[async_trait]
+impl Validator for ValidateFuotaDeploymentsAccess {
+    async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
+        task::spawn_blocking({
+            let id = *id;
+            let flag = self.flag;
+            let application_id = self.application_id;
+
+            move || -> Result<i64, Error> {
+                let mut c = get_db_conn()?;
+                let mut q = user::dsl::user
+                    .select(dsl::count_star())
+                    .left_join(
+                        tenant_user::table.left_join(
+                            application::table
+                                .on(tenant_user::dsl::tenant_id.eq(application::dsl::tenant_id)),
+                        ),
+                    )
+                    .filter(user::dsl::id.eq(&id).and(user::dsl::is_active.eq(true)))
+                    .into_boxed();
+
+                match flag {
+                    // admin user
+                    // tenant admin
+                    // tenant device admin
+                    Flag::Create => {
+                        q = q.filter(
+                            user::dsl::is_admin
+                                .eq(true)
+                                .or(application::dsl::id
+                                    .eq(&application_id)
+                                    .and(tenant_user::dsl::is_admin.eq(true)))
+                                .or(application::dsl::id
+                                    .eq(&application_id)
+                                    .and(tenant_user::dsl::is_device_admin.eq(true))),
+                        );
+                    }
+                    // admin user
+                    // tenant user
+                    Flag::List => {
+                        q = q.filter(
+                            user::dsl::is_admin
+                                .eq(true)
+                                .or(application::dsl::id.eq(&application_id)),
+                        );
+                    }
+                    _ => {
+                        return Ok(0);
+                    }
+                }
+
+                Ok(q.first(&mut c)?)
+            }
+        })
+        .await?
+    }
+
+    async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
+        task::spawn_blocking({
+            let id = *id;
+            let flag = self.flag;
+            let application_id = self.application_id;
+
+            move || -> Result<i64, Error> {
+                let mut c = get_db_conn()?;
+                let mut q = api_key::dsl::api_key
+                    .select(dsl::count_star())
+                    .left_join(
+                        application::table
+                            .on(api_key::dsl::tenant_id.eq(application::dsl::tenant_id.nullable())),
+                    )
+                    .filter(api_key::dsl::id.eq(&id))
+                    .into_boxed();
+
+                match flag {
+                    // admin api key
+                    // tenant api key
+                    Flag::Create | Flag::List => {
+                        q = q.filter(
+                            api_key::dsl::is_admin
+                                .eq(true)
+                                .or(application::dsl::id.eq(&application_id)),
+                        );
+                    }
+                    _ => {
+                        return Ok(0);
+                    }
+                }
+
+                Ok(q.first(&mut c)?)
+            }
+        })
+        .await?
+    }
+}
+
+pub struct ValidateFuotaDeploymentAccess {
+    flag: Flag,
+    fuota_deployment_id: Uuid,
+}
+
+impl ValidateFuotaDeploymentAccess {
+    pub fn new(flag: Flag, fuota_deployment_id: Uuid) -> Self {
+        ValidateFuotaDeploymentAccess {
+            flag,
+            fuota_deployment_id,
+        }
+    }
+}
+
+#[async_trait]
+impl Validator for ValidateFuotaDeploymentAccess {
+    async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
+        task::spawn_blocking({
+            let id = *id;
+            let flag = self.flag;
+            let fuota_deployment_id = self.fuota_deployment_id;
+
+            move || -> Result<i64, Error> {
+                let mut c = get_db_conn()?;
+                let mut q = user::dsl::user
+                    .select(dsl::count_star())
+                    .left_join(
+                        tenant_user::table.left_join(
+                            application::table
+                                .on(tenant_user::dsl::tenant_id.eq(application::dsl::tenant_id)),
+                        ),
+                    )
+                    .left_join(
+                        fuota_deployment::table
+                            .on(application::dsl::id.eq(fuota_deployment::dsl::application_id)),
+                    )
+                    .filter(user::dsl::id.eq(&id).and(user::dsl::is_active.eq(true)))
+                    .into_boxed();
+
+                match flag {
+                    // admin user
+                    // tenant user
+                    Flag::Read => {
+                        q = q.filter(
+                            user::dsl::is_admin
+                                .eq(true)
+                                .or(fuota_deployment::dsl::id.eq(&fuota_deployment_id)),
+                        );
+                    }
+                    // admin user
+                    // tenant admin
+                    // tenant device admin
+                    Flag::Update | Flag::Delete => {
+                        q = q.filter(
+                            user::dsl::is_admin
+                                .eq(true)
+                                .or(fuota_deployment::dsl::id
+                                    .eq(&fuota_deployment_id)
+                                    .and(tenant_user::dsl::is_admin.eq(true)))
+                                .or(fuota_deployment::dsl::id
+                                    .eq(&fuota_deployment_id)
+                                    .and(tenant_user::dsl::is_device_admin.eq(true))),
+                        );
+                    }
+                    _ => {
+                        return Ok(0);
+                    }
+                }
+
+                Ok(q.first(&mut c)?)
+            }
+        })
+        .await?
+    }
+
+    async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
+        task::spawn_blocking({
+            let id = *id;
+            let flag = self.flag;
+            let fuota_deployment_id = self.fuota_deployment_id;
+
+            move || -> Result<i64, Error> {
+                let mut c = get_db_conn()?;
+                let mut q = api_key::dsl::api_key
+                    .select(dsl::count_star())
+                    .left_join(
+                        application::table
+                            .on(api_key::dsl::tenant_id.eq(application::dsl::tenant_id.nullable())),
+                    )
+                    .left_join(
+                        fuota_deployment::table
+                            .on(application::dsl::id.eq(fuota_deployment::dsl::application_id)),
+                    )
+                    .filter(api_key::dsl::id.eq(&id))
+                    .into_boxed();
+
+                match flag {
+                    // admin api key
+                    // tenant api key
+                    Flag::Read | Flag::Update | Flag::Delete => {
+                        q = q.filter(
+                            api_key::dsl::is_admin
+                                .eq(true)
+                                .or(fuota_deployment::dsl::id.eq(&fuota_deployment_id)),
+                        );
+                    }
+                    _ => {
+                        return Ok(0);
+                    }
+                }
+
+                Ok(q.first(&mut c)?)
+            }
+        })
+        .await?
+    }
+}
+
 #[cfg(test)]
 pub mod test {
     use super::*;
     use crate::storage::{
-        api_key, application, device, device_profile, device_profile_template, gateway, multicast,
-        tenant, user,
+        api_key, application, device, device_profile, device_profile_template, fuota, gateway,
+        multicast, tenant, user,
     };
     use crate::test;
     use std::str::FromStr;
@@ -4688,4 +4916,285 @@ pub mod test {
         ];
         run_tests(tests).await;
     }
+
+    #[tokio::test]
+    async fn fuota_deployment() {
+        let _guard = test::prepare().await;
+
+        let user_active = user::User {
+            email: "user@user".into(),
+            is_active: true,
+            ..Default::default()
+        };
+        let user_admin = user::User {
+            email: "admin@user".into(),
+            is_active: true,
+            is_admin: true,
+            ..Default::default()
+        };
+        let tenant_admin = user::User {
+            email: "tenant-admin@user".into(),
+            is_active: true,
+            ..Default::default()
+        };
+        let tenant_device_admin = user::User {
+            email: "tenant-device-admin@user".into(),
+            is_active: true,
+            ..Default::default()
+        };
+        let tenant_gateway_admin = user::User {
+            email: "tenant-gateway-admin@user".into(),
+            is_active: true,
+            ..Default::default()
+        };
+        let tenant_user = user::User {
+            email: "tenant-user@user".into(),
+            is_active: true,
+            ..Default::default()
+        };
+
+        for u in vec![
+            &user_active,
+            &user_admin,
+            &tenant_admin,
+            &tenant_gateway_admin,
+            &tenant_device_admin,
+            &tenant_user,
+        ] {
+            user::create(u.clone()).await.unwrap();
+        }
+
+        let api_key_admin = api_key::test::create_api_key(true, false).await;
+        let api_key_tenant = api_key::test::create_api_key(false, true).await;
+        let api_key_other_tenant = api_key::test::create_api_key(false, true).await;
+
+        let app =
+            application::test::create_application(Some(api_key_tenant.tenant_id.unwrap())).await;
+
+        tenant::add_user(tenant::TenantUser {
+            tenant_id: api_key_tenant.tenant_id.unwrap(),
+            user_id: tenant_admin.id,
+            is_admin: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        tenant::add_user(tenant::TenantUser {
+            tenant_id: api_key_tenant.tenant_id.unwrap(),
+            user_id: tenant_device_admin.id,
+            is_device_admin: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        tenant::add_user(tenant::TenantUser {
+            tenant_id: api_key_tenant.tenant_id.unwrap(),
+            user_id: tenant_gateway_admin.id,
+            is_gateway_admin: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        tenant::add_user(tenant::TenantUser {
+            tenant_id: api_key_tenant.tenant_id.unwrap(),
+            user_id: tenant_user.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // fuota deployments with user
+        let tests = vec![
+            // admin user can create and list
+            ValidatorTest {
+                validators: vec![
+                    ValidateFuotaDeploymentsAccess::new(Flag::Create, app.id),
+                    ValidateFuotaDeploymentsAccess::new(Flag::List, app.id),
+                ],
+                id: AuthID::User(user_admin.id),
+                ok: true,
+            },
+            // tenant admin can create and list
+            ValidatorTest {
+                validators: vec![
+                    ValidateFuotaDeploymentsAccess::new(Flag::Create, app.id),
+                    ValidateFuotaDeploymentsAccess::new(Flag::List, app.id),
+                ],
+                id: AuthID::User(tenant_admin.id),
+                ok: true,
+            },
+            // tenant device admin can create and list
+            ValidatorTest {
+                validators: vec![
+                    ValidateFuotaDeploymentsAccess::new(Flag::Create, app.id),
+                    ValidateFuotaDeploymentsAccess::new(Flag::List, app.id),
+                ],
+                id: AuthID::User(tenant_device_admin.id),
+                ok: true,
+            },
+            // tenant user can list
+            ValidatorTest {
+                validators: vec![ValidateFuotaDeploymentsAccess::new(Flag::List, app.id)],
+                id: AuthID::User(tenant_user.id),
+                ok: true,
+            },
+            // tenant user can not create
+            ValidatorTest {
+                validators: vec![ValidateFuotaDeploymentsAccess::new(Flag::Create, app.id)],
+                id: AuthID::User(tenant_user.id),
+                ok: false,
+            },
+            // other user can not create or list
+            ValidatorTest {
+                validators: vec![
+                    ValidateFuotaDeploymentsAccess::new(Flag::Create, app.id),
+                    ValidateFuotaDeploymentsAccess::new(Flag::List, app.id),
+                ],
+                id: AuthID::User(user_active.id),
+                ok: false,
+            },
+        ];
+        run_tests(tests).await;
+
+        // fuota deployments with api key
+        let tests = vec![
+            // admin api key can create and list
+            ValidatorTest {
+                validators: vec![
+                    ValidateFuotaDeploymentsAccess::new(Flag::Create, app.id),
+                    ValidateFuotaDeploymentsAccess::new(Flag::List, app.id),
+                ],
+                id: AuthID::Key(api_key_admin.id),
+                ok: true,
+            },
+            // tenant api key can create and list
+            ValidatorTest {
+                validators: vec![
+                    ValidateFuotaDeploymentsAccess::new(Flag::Create, app.id),
+                    ValidateFuotaDeploymentsAccess::new(Flag::List, app.id),
+                ],
+                id: AuthID::Key(api_key_tenant.id),
+                ok: true,
+            },
+            // tenant api key can not create or list for other tenant
+            ValidatorTest {
+                validators: vec![
+                    ValidateFuotaDeploymentsAccess::new(Flag::Create, app.id),
+                    ValidateFuotaDeploymentsAccess::new(Flag::List, app.id),
+                ],
+                id: AuthID::Key(api_key_other_tenant.id),
+                ok: false,
+            },
+        ];
+        run_tests(tests).await;
+
+        let dp =
+            device_profile::test::create_device_profile(Some(api_key_tenant.tenant_id.unwrap()))
+                .await;
+        let fd = fuota::create(fuota::FuotaDeployment {
+            name: "test-fd".into(),
+            application_id: app.id,
+            device_profile_id: dp.id,
+            fragmentation_fragment_size: 50,
+            payload: vec![1, 2, 3],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // fuota deployment with user
+        let tests = vec![
+            // admin user can read, update and delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateFuotaDeploymentAccess::new(Flag::Read, fd.id),
+                    ValidateFuotaDeploymentAccess::new(Flag::Update, fd.id),
+                    ValidateFuotaDeploymentAccess::new(Flag::Delete, fd.id),
+                ],
+                id: AuthID::User(user_admin.id),
+                ok: true,
+            },
+            // tenant admin can read, update and delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateFuotaDeploymentAccess::new(Flag::Read, fd.id),
+                    ValidateFuotaDeploymentAccess::new(Flag::Update, fd.id),
+                    ValidateFuotaDeploymentAccess::new(Flag::Delete, fd.id),
+                ],
+                id: AuthID::User(tenant_admin.id),
+                ok: true,
+            },
+            // tenant device admin can read, update and delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateFuotaDeploymentAccess::new(Flag::Read, fd.id),
+                    ValidateFuotaDeploymentAccess::new(Flag::Update, fd.id),
+                    ValidateFuotaDeploymentAccess::new(Flag::Delete, fd.id),
+                ],
+                id: AuthID::User(tenant_device_admin.id),
+                ok: true,
+            },
+            // tenant user can read
+            ValidatorTest {
+                validators: vec![ValidateFuotaDeploymentAccess::new(Flag::Read, fd.id)],
+                id: AuthID::User(tenant_user.id),
+                ok: true,
+            },
+            // tenant user can not update or delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateFuotaDeploymentAccess::new(Flag::Update, fd.id),
+                    ValidateFuotaDeploymentAccess::new(Flag::Delete, fd.id),
+                ],
+                id: AuthID::User(tenant_user.id),
+                ok: false,
+            },
+            // other user can not read, update or delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateFuotaDeploymentAccess::new(Flag::Read, fd.id),
+                    ValidateFuotaDeploymentAccess::new(Flag::Update, fd.id),
+                    ValidateFuotaDeploymentAccess::new(Flag::Delete, fd.id),
+                ],
+                id: AuthID::User(user_active.id),
+                ok: false,
+            },
+        ];
+        run_tests(tests).await;
+
+        // fuota deployment with api key
+        let tests = vec![
+            // admin api key can read, update and delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateFuotaDeploymentAccess::new(Flag::Read, fd.id),
+                    ValidateFuotaDeploymentAccess::new(Flag::Update, fd.id),
+                    ValidateFuotaDeploymentAccess::new(Flag::Delete, fd.id),
+                ],
+                id: AuthID::Key(api_key_admin.id),
+                ok: true,
+            },
+            // tenant api key can read, update and delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateFuotaDeploymentAccess::new(Flag::Read, fd.id),
+                    ValidateFuotaDeploymentAccess::new(Flag::Update, fd.id),
+                    ValidateFuotaDeploymentAccess::new(Flag::Delete, fd.id),
+                ],
+                id: AuthID::Key(api_key_tenant.id),
+                ok: true,
+            },
+            // other api key can not read, update or delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateFuotaDeploymentAccess::new(Flag::Read, fd.id),
+                    ValidateFuotaDeploymentAccess::new(Flag::Update, fd.id),
+                    ValidateFuotaDeploymentAccess::new(Flag::Delete, fd.id),
+                ],
+                id: AuthID::Key(api_key_other_tenant.id),
+                ok: false,
+            },
+        ];
+        run_tests(tests).await;
+    }
 }
@@ -0,0 +1,557 @@
+use anyhow::{Context, Result};
+use tracing::info;
+
+use crate::backend::{applicationserver, keywrap, networkserver};
+use crate::storage::{device, device_keys, error::Error as StorageError, js_session_key};
+use lrwn::{
+    keys, AES128Key, CFList, DLSettings, DevAddr, JoinAcceptPayload, JoinType, MType, Major, NetID,
+    Payload, PhyPayload, EUI64, MHDR,
+};
+
+// The SessionKeyID contains all the parameters needed to re-derive the AppSKey
+// from the device root-keys, such that the AppSKeyReq can be handled without
+// storing the session-keys. Only the issued SessionKeyIDs are stored, such that
+// an AppSKeyReq can only be made for sessions that have been established:
+// OptNeg (1) | NetID (3) | JoinEUI (8) | JoinNonce (3) | DevNonce or RJcount (2)
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SessionKeyId {
+    pub opt_neg: bool,
+    pub net_id: NetID,
+    pub join_eui: EUI64,
+    pub join_nonce: u32,
+    pub dev_nonce: u16,
+}
+
+impl SessionKeyId {
+    pub fn from_slice(b: &[u8]) -> Result<Self> {
+        if b.len() != 17 {
+            return Err(anyhow!("SessionKeyID must be exactly 17 bytes"));
+        }
+
+        Ok(SessionKeyId {
+            opt_neg: b[0] != 0,
+            net_id: NetID::from_slice(&b[1..4])?,
+            join_eui: EUI64::from_slice(&b[4..12])?,
+            join_nonce: u32::from_be_bytes([0, b[12], b[13], b[14]]),
+            dev_nonce: u16::from_be_bytes([b[15], b[16]]),
+        })
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut b = Vec::with_capacity(17);
+        b.push(self.opt_neg as u8);
+        b.extend_from_slice(&self.net_id.to_vec());
+        b.extend_from_slice(&self.join_eui.to_vec());
+        b.extend_from_slice(&self.join_nonce.to_be_bytes()[1..]);
+        b.extend_from_slice(&self.dev_nonce.to_be_bytes());
+        b
+    }
+}
+
+struct SessionKeys {
+    f_nwk_s_int_key: AES128Key,
+    s_nwk_s_int_key: AES128Key,
+    nwk_s_enc_key: AES128Key,
+    app_s_key: AES128Key,
+}
+
+pub fn err_to_result_code(e: &anyhow::Error) -> backend::ResultCode {
+    if let Some(e) = e.downcast_ref::<StorageError>() {
+        return match e {
+            StorageError::NotFound(_) => backend::ResultCode::UnknownDevEUI,
+            StorageError::InvalidMIC | StorageError::InvalidDevNonce => {
+                backend::ResultCode::MICFailed
+            }
+            _ => backend::ResultCode::JoinReqFailed,
+        };
+    }
+    backend::ResultCode::JoinReqFailed
+}
+
+pub async fn handle_join_req(pl: &backend::JoinReqPayload) -> Result<backend::JoinAnsPayload> {
+    let net_id = NetID::from_slice(&pl.base.sender_id)?;
+    let dev_eui = EUI64::from_slice(&pl.dev_eui)?;
+    let phy = PhyPayload::from_slice(&pl.phy_payload)?;
+    let jr = if let Payload::JoinRequest(v) = &phy.payload {
+        v
+    } else {
+        return Err(anyhow!("PHYPayload does not contain a JoinRequest"));
+    };
+
+    if jr.dev_eui != dev_eui {
+        return Err(anyhow!("DevEUI of PHYPayload does not match DevEUI"));
+    }
+
+    let dev = device::get(&dev_eui).await?;
+    if dev.is_disabled {
+        return Err(anyhow!("Device is disabled"));
+    }
+
+    let dk = device_keys::get(&dev_eui).await?;
+    if !phy.validate_join_request_mic(&dk.nwk_key)? {
+        return Err(StorageError::InvalidMIC.into());
+    }
+
+    let mut dk = device_keys::validate_and_store_dev_nonce(&dev_eui, jr.dev_nonce as i32).await?;
+    if dk.join_nonce == (1 << 24) - 1 {
+        return Err(anyhow!("Join-nonce overflow"));
+    }
+
+    // The opt_neg flag is set for devices other than 1.0.x.
+    let opt_neg = !pl.mac_version.starts_with("1.0");
+    let sk_id = SessionKeyId {
+        opt_neg,
+        net_id,
+        join_eui: jr.join_eui,
+        join_nonce: dk.join_nonce as u32,
+        dev_nonce: jr.dev_nonce,
+    };
+
+    let mut join_accept = get_join_accept(
+        &sk_id,
+        &pl.dev_addr,
+        &pl.dl_settings,
+        pl.rx_delay,
+        &pl.cf_list,
+    )?;
+
+    if opt_neg {
+        let js_int_key = keys::get_js_int_key(&dev_eui, &dk.nwk_key)?;
+        join_accept.set_join_accept_mic(JoinType::Join, &jr.join_eui, jr.dev_nonce, &js_int_key)?;
+    } else {
+        join_accept.set_join_accept_mic(JoinType::Join, &jr.join_eui, jr.dev_nonce, &dk.nwk_key)?;
+    }
+    join_accept.encrypt_join_accept_payload(&dk.nwk_key)?;
+
+    let session_keys = get_session_keys(&dk, &sk_id)?;
+
+    dk.join_nonce += 1;
+    device_keys::update(dk).await?;
+    js_session_key::create(&dev_eui, &sk_id.to_vec()).await?;
+
+    info!(dev_eui = %dev_eui, net_id = %net_id, "JoinReq handled");
+
+    let kek_label = networkserver::get_kek_label(net_id)?;
+    let app_s_key_kek_label = networkserver::get_app_s_key_kek_label(net_id)?;
+
+    Ok(backend::JoinAnsPayload {
+        base: pl
+            .base
+            .to_base_payload_result(backend::ResultCode::Success, ""),
+        phy_payload: join_accept.to_vec()?,
+        nwk_s_key: if opt_neg {
+            None
+        } else {
+            Some(keywrap::wrap(&kek_label, session_keys.nwk_s_enc_key)?)
+        },
+        f_nwk_s_int_key: if opt_neg {
+            Some(keywrap::wrap(&kek_label, session_keys.f_nwk_s_int_key)?)
+        } else {
+            None
+        },
+        s_nwk_s_int_key: if opt_neg {
+            Some(keywrap::wrap(&kek_label, session_keys.s_nwk_s_int_key)?)
+        } else {
+            None
+        },
+        nwk_s_enc_key: if opt_neg {
+            Some(keywrap::wrap(&kek_label, session_keys.nwk_s_enc_key)?)
+        } else {
+            None
+        },
+        app_s_key: Some(keywrap::wrap(&app_s_key_kek_label, session_keys.app_s_key)?),
+        session_key_id: sk_id.to_vec(),
+        ..Default::default()
+    })
+}
+
+pub async fn handle_rejoin_req(
+    pl: &backend::RejoinReqPayload,
+) -> Result<backend::RejoinAnsPayload> {
+    let net_id = NetID::from_slice(&pl.base.sender_id)?;
+    let dev_eui = EUI64::from_slice(&pl.dev_eui)?;
+    let phy = PhyPayload::from_slice(&pl.phy_payload)?;
+
+    if !pl.mac_version.starts_with("1.1") {
+        return Err(anyhow!("RejoinReq requires LoRaWAN 1.1 or later"));
+    }
+
+    let dev = device::get(&dev_eui).await?;
+    if dev.is_disabled {
+        return Err(anyhow!("Device is disabled"));
+    }
+
+    let mut dk = device_keys::get(&dev_eui).await?;
+    let js_int_key = keys::get_js_int_key(&dev_eui, &dk.nwk_key)?;
+    let js_enc_key = keys::get_js_enc_key(&dev_eui, &dk.nwk_key)?;
+
+    // The MIC of the rejoin-request type 0 and 2 is validated by the Network
+    // Server, as it is signed using the SNwkSIntKey. The MIC of the type 1 is
+    // signed using the JSIntKey.
+    let (join_type, join_eui, rj_count) = match &phy.payload {
+        Payload::RejoinRequestType02(v) => {
+            if v.dev_eui != dev_eui {
+                return Err(anyhow!("DevEUI of PHYPayload does not match DevEUI"));
+            }
+            (v.rejoin_type.clone(), dev.join_eui, v.rj_count_0)
+        }
+        Payload::RejoinRequestType1(v) => {
+            if v.dev_eui != dev_eui {
+                return Err(anyhow!("DevEUI of PHYPayload does not match DevEUI"));
+            }
+            if !phy.validate_join_request_mic(&js_int_key)? {
+                return Err(StorageError::InvalidMIC.into());
+            }
+            (v.rejoin_type.clone(), v.join_eui, v.rj_count_1)
+        }
+        _ => {
+            return Err(anyhow!("PHYPayload does not contain a RejoinRequest"));
+        }
+    };
+
+    if dk.join_nonce == (1 << 24) - 1 {
+        return Err(anyhow!("Join-nonce overflow"));
+    }
+
+    let sk_id = SessionKeyId {
+        opt_neg: true,
+        net_id,
+        join_eui,
+        join_nonce: dk.join_nonce as u32,
+        dev_nonce: rj_count,
+    };
+
+    let mut join_accept = get_join_accept(
+        &sk_id,
+        &pl.dev_addr,
+        &pl.dl_settings,
+        pl.rx_delay,
+        &pl.cf_list,
+    )?;
+    join_accept.set_join_accept_mic(join_type.clone(), &join_eui, rj_count, &js_int_key)?;
+    join_accept.encrypt_join_accept_payload(&js_enc_key)?;
+
+    let session_keys = get_session_keys(&dk, &sk_id)?;
+
+    dk.join_nonce += 1;
+    device_keys::update(dk).await?;
+    js_session_key::create(&dev_eui, &sk_id.to_vec()).await?;
+
+    info!(dev_eui = %dev_eui, net_id = %net_id, join_type = ?join_type, "RejoinReq handled");
+
+    let kek_label = networkserver::get_kek_label(net_id)?;
+    let app_s_key_kek_label = networkserver::get_app_s_key_kek_label(net_id)?;
+
+    Ok(backend::RejoinAnsPayload {
+        base: pl
+            .base
+            .to_base_payload_result(backend::ResultCode::Success, ""),
+        phy_payload: join_accept.to_vec()?,
+        f_nwk_s_int_key: Some(keywrap::wrap(&kek_label, session_keys.f_nwk_s_int_key)?),
+        s_nwk_s_int_key: Some(keywrap::wrap(&kek_label, session_keys.s_nwk_s_int_key)?),
+        nwk_s_enc_key: Some(keywrap::wrap(&kek_label, session_keys.nwk_s_enc_key)?),
+        app_s_key: Some(keywrap::wrap(&app_s_key_kek_label, session_keys.app_s_key)?),
+        session_key_id: sk_id.to_vec(),
+        ..Default::default()
+    })
+}
+
+pub async fn handle_app_s_key_req(
+    pl: &backend::AppSKeyReqPayload,
+) -> Result<backend::AppSKeyAnsPayload> {
+    let net_id = NetID::from_slice(&pl.base.sender_id)?;
+    let dev_eui = EUI64::from_slice(&pl.dev_eui)?;
+    let sk_id = SessionKeyId::from_slice(&pl.session_key_id).context("Decode SessionKeyID")?;
+
+    // The Application Server can only request the AppSKey of sessions of its own
+    // NetID, that have been issued by this Join Server.
+    if sk_id.net_id != net_id {
+        return Err(anyhow!(
+            "NetID {} of SessionKeyID does not match SenderID {}",
+            sk_id.net_id,
+            net_id
+        ));
+    }
+    if !js_session_key::exists(&dev_eui, &pl.session_key_id).await? {
+        return Err(StorageError::NotFound(hex::encode(&pl.session_key_id)).into());
+    }
+
+    let dk = device_keys::get(&dev_eui).await?;
+    let session_keys = get_session_keys(&dk, &sk_id)?;
+    let app_s_key_kek_label = applicationserver::get_app_s_key_kek_label(net_id)?;
+
+    info!(dev_eui = %dev_eui, net_id = %net_id, "AppSKeyReq handled");
+
+    Ok(backend::AppSKeyAnsPayload {
+        base: pl
+            .base
+            .to_base_payload_result(backend::ResultCode::Success, ""),
+        dev_eui: pl.dev_eui.clone(),
+        app_s_key: Some(keywrap::wrap(&app_s_key_kek_label, session_keys.app_s_key)?),
+        session_key_id: pl.session_key_id.clone(),
+    })
+}
+
+fn get_join_accept(
+    sk_id: &SessionKeyId,
+    dev_addr: &[u8],
+    dl_settings: &[u8],
+    rx_delay: u8,
+    cf_list: &[u8],
+) -> Result<PhyPayload> {
+    let mut dl_settings = DLSettings::from_le_bytes(
+        dl_settings
+            .try_into()
+            .map_err(|_| anyhow!("DLSettings must be exactly 1 byte"))?,
+    );
+    dl_settings.opt_neg = sk_id.opt_neg;
+
+    Ok(PhyPayload {
+        mhdr: MHDR {
+            m_type: MType::JoinAccept,
+            major: Major::LoRaWANR1,
+        },
+        payload: Payload::JoinAccept(JoinAcceptPayload {
+            join_nonce: sk_id.join_nonce,
+            home_netid: sk_id.net_id,
+            devaddr: DevAddr::from_slice(dev_addr)?,
+            dl_settings,
+            rx_delay,
+            cflist: if cf_list.is_empty() {
+                None
+            } else {
+                Some(CFList::from_bytes(
+                    cf_list
+                        .try_into()
+                        .map_err(|_| anyhow!("CFList must be exactly 16 bytes"))?,
+                )?)
+            },
+        }),
+        mic: None,
+    })
+}
+
+fn get_session_keys(dk: &device_keys::DeviceKeys, sk_id: &SessionKeyId) -> Result<SessionKeys> {
+    let f_nwk_s_int_key = keys::get_f_nwk_s_int_key(
+        sk_id.opt_neg,
+        &dk.nwk_key,
+        &sk_id.net_id,
+        &sk_id.join_eui,
+        sk_id.join_nonce,
+        sk_id.dev_nonce,
+    )?;
+
+    // For LoRaWAN 1.0.x, SNwkSIntKey = NwkSEncKey = FNwkSIntKey = NwkSKey and
+    // the AppSKey is derived from the NwkKey (the AppKey in LoRaWAN 1.0.x).
+    if !sk_id.opt_neg {
+        return Ok(SessionKeys {
+            f_nwk_s_int_key,
+            s_nwk_s_int_key: f_nwk_s_int_key,
+            nwk_s_enc_key: f_nwk_s_int_key,
+            app_s_key: keys::get_app_s_key(
+                sk_id.opt_neg,
+                &dk.nwk_key,
+                &sk_id.net_id,
+                &sk_id.join_eui,
+                sk_id.join_nonce,
+                sk_id.dev_nonce,
+            )?,
+        });
+    }
+
+    Ok(SessionKeys {
+        f_nwk_s_int_key,
+        s_nwk_s_int_key: keys::get_s_nwk_s_int_key(
+            sk_id.opt_neg,
+            &dk.nwk_key,
+            &sk_id.net_id,
+            &sk_id.join_eui,
+            sk_id.join_nonce,
+            sk_id.dev_nonce,
+        )?,
+        nwk_s_enc_key: keys::get_nwk_s_enc_key(
+            sk_id.opt_neg,
+            &dk.nwk_key,
+            &sk_id.net_id,
+            &sk_id.join_eui,
+            sk_id.join_nonce,
+            sk_id.dev_nonce,
+        )?,
+        app_s_key: keys::get_app_s_key(
+            sk_id.opt_neg,
+            &dk.app_key,
+            &sk_id.net_id,
+            &sk_id.join_eui,
+            sk_id.join_nonce,
+            sk_id.dev_nonce,
+        )?,
+    })
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::storage::{application, device_profile, tenant};
+    use crate::{config, test};
+    use lrwn::JoinRequestPayload;
+
+    #[test]
+    fn test_session_key_id() {
+        let sk_id = SessionKeyId {
+            opt_neg: true,
+            net_id: NetID::from_be_bytes([1, 2, 3]),
+            join_eui: EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
+            join_nonce: 0x0a0b0c,
+            dev_nonce: 0x0d0e,
+        };
+
+        let b = sk_id.to_vec();
+        assert_eq!(
+            vec![1, 1, 2, 3, 1, 2, 3, 4, 5, 6, 7, 8, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e],
+            b
+        );
+        assert_eq!(sk_id, SessionKeyId::from_slice(&b).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_join_req_and_app_s_key_req() {
+        let _guard = test::prepare().await;
+
+        let mut conf: config::Configuration = (*config::get()).clone();
+        conf.join_server.network_servers = vec![config::JoinServerNetworkServer {
+            net_id: NetID::from_be_bytes([1, 2, 3]),
+            ..Default::default()
+        }];
+        conf.join_server.application_servers = vec![config::JoinServerApplicationServer {
+            net_id: NetID::from_be_bytes([1, 2, 3]),
+            ..Default::default()
+        }];
+        config::set(conf);
+
+        let t = tenant::create(tenant::Tenant {
+            name: "tenant".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let app = application::create(application::Application {
+            name: "app".into(),
+            tenant_id: t.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let dp = device_profile::create(device_profile::DeviceProfile {
+            name: "dp".into(),
+            tenant_id: t.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let dev = device::create(device::Device {
+            name: "dev".into(),
+            application_id: app.id,
+            device_profile_id: dp.id,
+            dev_eui: EUI64::from_be_bytes([1, 1, 1, 1, 1, 1, 1, 1]),
+            join_eui: EUI64::from_be_bytes([2, 2, 2, 2, 2, 2, 2, 2]),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let dk = device_keys::create(device_keys::DeviceKeys {
+            dev_eui: dev.dev_eui,
+            nwk_key: AES128Key::from_bytes([1, 2, 3, 4, 5, 6, 7, 8, 1, 2, 3, 4, 5, 6, 7, 8]),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let mut jr_phy = PhyPayload {
+            mhdr: MHDR {
+                m_type: MType::JoinRequest,
+                major: Major::LoRaWANR1,
+            },
+            payload: Payload::JoinRequest(JoinRequestPayload {
+                join_eui: dev.join_eui,
+                dev_eui: dev.dev_eui,
+                dev_nonce: 1,
+            }),
+            mic: None,
+        };
+        jr_phy.set_join_request_mic(&dk.nwk_key).unwrap();
+
+        let join_req = backend::JoinReqPayload {
+            base: backend::BasePayload {
+                sender_id: vec![1, 2, 3],
+                receiver_id: dev.join_eui.to_vec(),
+                message_type: backend::MessageType::JoinReq,
+                ..Default::default()
+            },
+            mac_version: "1.0.3".into(),
+            phy_payload: jr_phy.to_vec().unwrap(),
+            dev_eui: dev.dev_eui.to_vec(),
+            dev_addr: vec![1, 2, 3, 4],
+            dl_settings: vec![0x00],
+            rx_delay: 1,
+            cf_list: vec![],
+        };
+
+        let join_ans = handle_join_req(&join_req).await.unwrap();
+        assert_eq!(
+            backend::ResultCode::Success,
+            join_ans.base.result.result_code
+        );
+        assert_eq!(
+            backend::MessageType::JoinAns,
+            join_ans.base.base.message_type
+        );
+        assert!(join_ans.nwk_s_key.is_some());
+        assert!(join_ans.f_nwk_s_int_key.is_none());
+
+        // The join-accept must be decryptable by the device.
+        let mut ja_phy = PhyPayload::from_slice(&join_ans.phy_payload).unwrap();
+        ja_phy.decrypt_join_accept_payload(&dk.nwk_key).unwrap();
+        assert!(ja_phy
+            .validate_join_accept_mic(JoinType::Join, &dev.join_eui, 1, &dk.nwk_key)
+            .unwrap());
+
+        // The join-nonce must have been incremented.
+        let dk_get = device_keys::get(&dev.dev_eui).await.unwrap();
+        assert_eq!(1, dk_get.join_nonce);
+
+        // Re-using the same DevNonce must fail.
+        let err = handle_join_req(&join_req).await.unwrap_err();
+        assert_eq!(backend::ResultCode::MICFailed, err_to_result_code(&err));
+
+        // AppSKeyReq must return the same AppSKey.
+        let app_s_key_req = backend::AppSKeyReqPayload {
+            base: backend::BasePayload {
+                sender_id: vec![1, 2, 3],
+                receiver_id: dev.join_eui.to_vec(),
+                message_type: backend::MessageType::AppSKeyReq,
+                ..Default::default()
+            },
+            dev_eui: dev.dev_eui.to_vec(),
+            session_key_id: join_ans.session_key_id.clone(),
+        };
+        let app_s_key_ans = handle_app_s_key_req(&app_s_key_req).await.unwrap();
+        assert_eq!(join_ans.app_s_key, app_s_key_ans.app_s_key);
+
+        // AppSKeyReq for the SessionKeyID of an other NetID must fail.
+        let mut req = app_s_key_req.clone();
+        req.base.sender_id = vec![3, 2, 1];
+        assert!(handle_app_s_key_req(&req).await.is_err());
+
+        // AppSKeyReq for a SessionKeyID that has not been issued must fail.
+        let mut sk_id = SessionKeyId::from_slice(&join_ans.session_key_id).unwrap();
+        sk_id.join_nonce += 1;
+        let mut req = app_s_key_req.clone();
+        req.session_key_id = sk_id.to_vec();
+        let err = handle_app_s_key_req(&req).await.unwrap_err();
+        assert_eq!(backend::ResultCode::UnknownDevEUI, err_to_result_code(&err));
+    }
+}
@@ -12,7 +12,7 @@ use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 use warp::{http::StatusCode, Filter, Reply};
 
-use crate::backend::{joinserver, keywrap, roaming};
+use crate::backend::{applicationserver, joinserver, keywrap, networkserver, roaming};
 use crate::downlink::data_fns;
 use crate::storage::{
     device_session, error::Error as StorageError, get_redis_conn, passive_roaming, redis_key,
@@ -23,6 +23,8 @@ use backend::{BasePayload, MessageType};
 use lrwn::region::CommonName;
 use lrwn::{AES128Key, NetID, EUI64};
 
+mod js;
+
 pub async fn setup() -> Result<()> {
     let conf = config::get();
     if conf.backend_interfaces.bind.is_empty() {
@@ -115,7 +117,16 @@ pub async fn handle_request(mut body: impl warp::Buf) -> http::Response<hyper::B
                 }
             };
 
-            match roaming::get(&sender_id) {
+            // JoinReq, RejoinReq and AppSKeyReq messages are handled by the Join
+            // Server role, other messages are roaming related. The AppSKeyReq is
+            // only accepted from the configured Application Servers.
+            let client = match bp.message_type {
+                MessageType::JoinReq | MessageType::RejoinReq => networkserver::get(&sender_id),
+                MessageType::AppSKeyReq => applicationserver::get(&sender_id),
+                _ => roaming::get(&sender_id),
+            };
+
+            match client {
                 Ok(v) => v,
                 Err(_) => {
                     error!(sender_id = %sender_id, "Unknown SenderID");
@@ -149,6 +160,9 @@ pub async fn handle_request(mut body: impl warp::Buf) -> http::Response<hyper::B
         MessageType::PRStartReq => handle_pr_start_req(sender_client, bp, &b).await,
         MessageType::PRStopReq => handle_pr_stop_req(sender_client, bp, &b).await,
         MessageType::XmitDataReq => handle_xmit_data_req(sender_client, bp, &b).await,
+        MessageType::JoinReq => handle_join_req(sender_client, bp, &b).await,
+        MessageType::RejoinReq => handle_rejoin_req(sender_client, bp, &b).await,
+        MessageType::AppSKeyReq => handle_app_s_key_req(sender_client, bp, &b).await,
         // Unknown message
         _ => warp::reply::with_status(
             "Handler for {:?} is not implemented",
@@ -471,6 +485,129 @@ async fn _handle_xmit_data_req(
     })
 }
 
+fn js_err_to_response(e: anyhow::Error, bp: &backend::BasePayload) -> http::Response<hyper::Body> {
+    let msg = format!("{}", e);
+    let pl = bp.to_base_payload_result(js::err_to_result_code(&e), &msg);
+    warp::reply::json(&pl).into_response()
+}
+
+async fn handle_join_req(
+    sender_client: Arc<backend::Client>,
+    bp: backend::BasePayload,
+    b: &[u8],
+) -> http::Response<hyper::Body> {
+    let pl: backend::JoinReqPayload = match serde_json::from_slice(b) {
+        Ok(v) => v,
+        Err(e) => {
+            return js_err_to_response(anyhow::Error::new(e), &bp);
+        }
+    };
+
+    if sender_client.is_async() {
+        task::spawn(async move {
+            let ans = match js::handle_join_req(&pl).await {
+                Ok(v) => v,
+                Err(e) => {
+                    let msg = e.to_string();
+                    backend::JoinAnsPayload {
+                        base: bp.to_base_payload_result(js::err_to_result_code(&e), &msg),
+                        ..Default::default()
+                    }
+                }
+            };
+
+            if let Err(e) = sender_client.join_ans(&ans).await {
+                error!(error = %e, "Send async JoinAns error");
+            }
+        });
+
+        warp::reply::with_status("", StatusCode::OK).into_response()
+    } else {
+        match js::handle_join_req(&pl).await {
+            Ok(v) => warp::reply::json(&v).into_response(),
+            Err(e) => js_err_to_response(e, &bp),
+        }
+    }
+}
+
+async fn handle_rejoin_req(
+    sender_client: Arc<backend::Client>,
+    bp: backend::BasePayload,
+    b: &[u8],
+) -> http::Response<hyper::Body> {
+    let pl: backend::RejoinReqPayload = match serde_json::from_slice(b) {
+        Ok(v) => v,
+        Err(e) => {
+            return js_err_to_response(anyhow::Error::new(e), &bp);
+        }
+    };
+
+    if sender_client.is_async() {
+        task::spawn(async move {
+            let ans = match js::handle_rejoin_req(&pl).await {
+                Ok(v) => v,
+                Err(e) => {
+                    let msg = e.to_string();
+                    backend::RejoinAnsPayload {
+                        base: bp.to_base_payload_result(js::err_to_result_code(&e), &msg),
+                        ..Default::default()
+                    }
+                }
+            };
+
+            if let Err(e) = sender_client.rejoin_ans(&ans).await {
+                error!(error = %e, "Send async RejoinAns error");
+            }
+        });
+
+        warp::reply::with_status("", StatusCode::OK).into_response()
+    } else {
+        match js::handle_rejoin_req(&pl).await {
+            Ok(v) => warp::reply::json(&v).into_response(),
+            Err(e) => js_err_to_response(e, &bp),
+        }
+    }
+}
+
+async fn handle_app_s_key_req(
+    sender_client: Arc<backend::Client>,
+    bp: backend::BasePayload,
+    b: &[u8],
+) -> http::Response<hyper::Body> {
+    let pl: backend::AppSKeyReqPayload = match serde_json::from_slice(b) {
+        Ok(v) => v,
+        Err(e) => {
+            return js_err_to_response(anyhow::Error::new(e), &bp);
+        }
+    };
+
+    if sender_client.is_async() {
+        task::spawn(async move {
+            let ans = match js::handle_app_s_key_req(&pl).await {
+                Ok(v) => v,
+                Err(e) => {
+                    let msg = e.to_string();
+                    backend::AppSKeyAnsPayload {
+                        base: bp.to_base_payload_result(js::err_to_result_code(&e), &msg),
+                        ..Default::default()
+                    }
+                }
+            };
+
+            if let Err(e) = sender_client.app_s_key_ans(&ans).await {
+                error!(error = %e, "Send async AppSKeyAns error");
+            }
+        });
+
+        warp::reply::with_status("", StatusCode::OK).into_response()
+    } else {
+        match js::handle_app_s_key_req(&pl).await {
+            Ok(v) => warp::reply::json(&v).into_response(),
+            Err(e) => js_err_to_response(e, &bp),
+        }
+    }
+}
+
 async fn handle_async_ans(bp: &BasePayload, b: &[u8]) -> Result<http::Response<hyper::Body>> {
     task::spawn_blocking({
         let b = b.to_vec();
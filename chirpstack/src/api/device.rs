@@ -4,7 +4,9 @@ use std::time::SystemTime;
 
 use bigdecimal::ToPrimitive;
 use chrono::{DateTime, Local, Utc};
-use tonic::{Request, Response, Status};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
 use uuid::Uuid;
 
 use chirpstack_api::api::device_service_server::DeviceService;
@@ -16,9 +18,9 @@ use super::error::ToStatus;
 use super::helpers::{self, FromProto, ToProto};
 use crate::storage::error::Error;
 use crate::storage::{
-    device, device_keys, device_profile, device_queue, device_session, fields, metrics,
+    device, device_keys, device_profile, device_queue, device_session, fields, metrics, tenant,
 };
-use crate::{codec, devaddr::get_random_dev_addr};
+use crate::{adr, bulk, codec, config, devaddr, region};
 
 pub struct Device {
     validator: validator::RequestValidator,
@@ -59,6 +61,10 @@ impl DeviceService for Device {
             )
             .await?;
 
+        let adr_overrides = req_d.adr_overrides.clone().unwrap_or_default();
+        let dp = device_profile::get(&dp_id).await.map_err(|e| e.status())?;
+        validate_adr_overrides(&dp, &adr_overrides)?;
+
         let d = device::Device {
             dev_eui,
             application_id: app_id,
@@ -70,6 +76,11 @@ impl DeviceService for Device {
             tags: fields::KeyValue::new(req_d.tags.clone()),
             variables: fields::KeyValue::new(req_d.variables.clone()),
             join_eui,
+            adr_min_dr: adr_overrides.min_dr.map(|v| v as i16),
+            adr_max_dr: adr_overrides.max_dr.map(|v| v as i16),
+            adr_max_tx_power_index: adr_overrides.max_tx_power_index.map(|v| v as i16),
+            adr_nb_trans: adr_overrides.nb_trans.map(|v| v as i16),
+            adr_installation_margin: adr_overrides.installation_margin,
             ..Default::default()
         };
 
@@ -114,6 +125,13 @@ impl DeviceService for Device {
                 variables: d.variables.into_hashmap(),
                 tags: d.tags.into_hashmap(),
                 join_eui: d.join_eui.to_string(),
+                adr_overrides: Some(api::DeviceAdrOverrides {
+                    min_dr: d.adr_min_dr.map(|v| v as u32),
+                    max_dr: d.adr_max_dr.map(|v| v as u32),
+                    max_tx_power_index: d.adr_max_tx_power_index.map(|v| v as u32),
+                    nb_trans: d.adr_nb_trans.map(|v| v as u32),
+                    installation_margin: d.adr_installation_margin,
+                }),
             }),
             created_at: Some(helpers::datetime_to_prost_timestamp(&d.created_at)),
             updated_at: Some(helpers::datetime_to_prost_timestamp(&d.updated_at)),
@@ -133,6 +151,8 @@ impl DeviceService for Device {
                 false => None,
             },
             class_enabled: d.enabled_class.to_proto().into(),
+            pinned_dr: d.adr_pinned_dr.map(|v| v as u32),
+            pinned_tx_power_index: d.adr_pinned_tx_power_index.map(|v| v as u32),
         });
         resp.metadata_mut()
             .insert("x-log-dev_eui", req.dev_eui.parse().unwrap());
@@ -185,6 +205,10 @@ impl DeviceService for Device {
             )
             .await?;
 
+        let adr_overrides = req_d.adr_overrides.clone().unwrap_or_default();
+        let dp = device_profile::get(&dp_id).await.map_err(|e| e.status())?;
+        validate_adr_overrides(&dp, &adr_overrides)?;
+
         // update
         let _ = device::update(device::Device {
             dev_eui,
@@ -197,6 +221,11 @@ impl DeviceService for Device {
             tags: fields::KeyValue::new(req_d.tags.clone()),
             variables: fields::KeyValue::new(req_d.variables.clone()),
             join_eui,
+            adr_min_dr: adr_overrides.min_dr.map(|v| v as i16),
+            adr_max_dr: adr_overrides.max_dr.map(|v| v as i16),
+            adr_max_tx_power_index: adr_overrides.max_tx_power_index.map(|v| v as i16),
+            adr_nb_trans: adr_overrides.nb_trans.map(|v| v as i16),
+            adr_installation_margin: adr_overrides.installation_margin,
             ..Default::default()
         })
         .await
@@ -316,6 +345,72 @@ impl DeviceService for Device {
         Ok(resp)
     }
 
+    async fn list_sessions(
+        &self,
+        request: Request<api::ListDeviceSessionsRequest>,
+    ) -> Result<Response<api::ListDeviceSessionsResponse>, Status> {
+        let req = request.get_ref();
+        let app_id = Uuid::from_str(&req.application_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateDevicesAccess::new(validator::Flag::List, app_id),
+            )
+            .await?;
+
+        if !config::get().network.device_session_persistence {
+            return Err(Status::failed_precondition(
+                "Device-session persistence is not enabled",
+            ));
+        }
+
+        let filters = device_session::Filters {
+            application_id: Some(app_id),
+            dev_addr: if req.dev_addr.is_empty() {
+                None
+            } else {
+                Some(DevAddr::from_str(&req.dev_addr).map_err(|e| e.status())?)
+            },
+            dr: req.dr.map(|v| v as u8),
+            min_f_cnt_up: if req.min_f_cnt_up == 0 {
+                None
+            } else {
+                Some(req.min_f_cnt_up)
+            },
+            ..Default::default()
+        };
+
+        let count = device_session::get_count(&filters)
+            .await
+            .map_err(|e| e.status())?;
+        let items = device_session::list(req.limit as i64, req.offset as i64, &filters)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::ListDeviceSessionsResponse {
+            total_count: count as u32,
+            result: items
+                .iter()
+                .map(|ds| api::DeviceSessionListItem {
+                    dev_eui: ds.dev_eui.to_string(),
+                    dev_addr: ds.dev_addr.to_string(),
+                    region_config_id: ds.region_config_id.clone(),
+                    f_cnt_up: ds.f_cnt_up as u32,
+                    n_f_cnt_down: ds.n_f_cnt_down as u32,
+                    a_f_cnt_down: ds.a_f_cnt_down as u32,
+                    dr: ds.dr as u32,
+                    tx_power_index: ds.tx_power_index as u32,
+                    updated_at: Some(helpers::datetime_to_prost_timestamp(&ds.updated_at)),
+                })
+                .collect(),
+        });
+        resp.metadata_mut()
+            .insert("x-log-application_id", req.application_id.parse().unwrap());
+
+        Ok(resp)
+    }
+
     async fn create_keys(
         &self,
         request: Request<api::CreateDeviceKeysRequest>,
@@ -343,6 +438,11 @@ impl DeviceService for Device {
             } else {
                 AES128Key::null()
             },
+            gen_app_key: if !req_dk.gen_app_key.is_empty() {
+                AES128Key::from_str(&req_dk.gen_app_key).map_err(|e| e.status())?
+            } else {
+                AES128Key::null()
+            },
             ..Default::default()
         };
 
@@ -376,6 +476,7 @@ impl DeviceService for Device {
                 dev_eui: dk.dev_eui.to_string(),
                 nwk_key: dk.nwk_key.to_string(),
                 app_key: dk.app_key.to_string(),
+                gen_app_key: dk.gen_app_key.to_string(),
             }),
             created_at: Some(helpers::datetime_to_prost_timestamp(&dk.created_at)),
             updated_at: Some(helpers::datetime_to_prost_timestamp(&dk.updated_at)),
@@ -417,6 +518,11 @@ impl DeviceService for Device {
             } else {
                 AES128Key::null()
             },
+            gen_app_key: if !req_dk.gen_app_key.is_empty() {
+                AES128Key::from_str(&req_dk.gen_app_key).map_err(|e| e.status())?
+            } else {
+                AES128Key::null()
+            },
             ..Default::default()
         };
         let _ = device_keys::update(dk).await.map_err(|e| e.status())?;
@@ -478,6 +584,38 @@ impl DeviceService for Device {
         Ok(resp)
     }
 
+    async fn arm_f_cnt_reset(
+        &self,
+        request: Request<api::ArmDeviceFCntResetRequest>,
+    ) -> Result<Response<()>, Status> {
+        let req = request.get_ref();
+        let dev_eui = EUI64::from_str(&req.dev_eui).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateDeviceAccess::new(validator::Flag::Update, dev_eui),
+            )
+            .await?;
+
+        let window = req.window.as_ref().cloned().unwrap_or_default();
+        if window.seconds < 0 || window.nanos < 0 {
+            return Err(Status::invalid_argument("window must not be negative"));
+        }
+        let window = chrono::Duration::seconds(window.seconds)
+            + chrono::Duration::nanoseconds(window.nanos.into());
+
+        device::set_f_cnt_reset_window(&dev_eui, req.max_f_cnt, window)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(());
+        resp.metadata_mut()
+            .insert("x-log-dev_eui", req.dev_eui.parse().unwrap());
+
+        Ok(resp)
+    }
+
     async fn activate(
         &self,
         request: Request<api::ActivateDeviceRequest>,
@@ -625,11 +763,37 @@ impl DeviceService for Device {
 
     async fn get_random_dev_addr(
         &self,
-        _request: Request<api::GetRandomDevAddrRequest>,
+        request: Request<api::GetRandomDevAddrRequest>,
     ) -> Result<Response<api::GetRandomDevAddrResponse>, Status> {
-        Ok(Response::new(api::GetRandomDevAddrResponse {
-            dev_addr: get_random_dev_addr().to_string(),
-        }))
+        let req = request.get_ref();
+        let dev_eui = EUI64::from_str(&req.dev_eui).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateDeviceAccess::new(validator::Flag::Update, dev_eui),
+            )
+            .await?;
+
+        let d = device::get(&dev_eui).await.map_err(|e| e.status())?;
+        let dp = device_profile::get(&d.device_profile_id)
+            .await
+            .map_err(|e| e.status())?;
+
+        let t = tenant::get(&dp.tenant_id).await.map_err(|e| e.status())?;
+
+        let dev_addr =
+            devaddr::allocate_dev_addr(Some(&t), dp.region_config_id.as_deref(), dev_eui)
+                .await
+                .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::GetRandomDevAddrResponse {
+            dev_addr: dev_addr.to_string(),
+        });
+        resp.metadata_mut()
+            .insert("x-log-dev_eui", req.dev_eui.parse().unwrap());
+
+        Ok(resp)
     }
 
     async fn get_metrics(
@@ -1125,6 +1289,419 @@ impl DeviceService for Device {
 
         Ok(resp)
     }
+
+    async fn set_link_parameters(
+        &self,
+        request: Request<api::SetDeviceLinkParametersRequest>,
+    ) -> Result<Response<()>, Status> {
+        let req = request.get_ref();
+        let dev_eui = EUI64::from_str(&req.dev_eui).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateDeviceAccess::new(validator::Flag::Update, dev_eui),
+            )
+            .await?;
+
+        let d = device::get(&dev_eui).await.map_err(|e| e.status())?;
+        let dp = device_profile::get(&d.device_profile_id)
+            .await
+            .map_err(|e| e.status())?;
+        let region_conf = get_region_conf(&dp)?;
+
+        if let Some(dr) = req.dr {
+            validate_dr(&region_conf, "dr", dr)?;
+        }
+        if let Some(tx_power_index) = req.tx_power_index {
+            validate_tx_power_index(&region_conf, "tx_power_index", tx_power_index)?;
+        }
+
+        device::set_link_parameters(
+            &dev_eui,
+            req.dr.map(|v| v as i16),
+            req.tx_power_index.map(|v| v as i16),
+        )
+        .await
+        .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(());
+        resp.metadata_mut()
+            .insert("x-log-dev_eui", req.dev_eui.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn simulate_adr(
+        &self,
+        request: Request<api::SimulateDeviceAdrRequest>,
+    ) -> Result<Response<api::SimulateDeviceAdrResponse>, Status> {
+        let req = request.get_ref();
+        let dev_eui = EUI64::from_str(&req.dev_eui).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateDeviceAccess::new(validator::Flag::Read, dev_eui),
+            )
+            .await?;
+
+        let d = device::get(&dev_eui).await.map_err(|e| e.status())?;
+        let dp = device_profile::get(&d.device_profile_id)
+            .await
+            .map_err(|e| e.status())?;
+        let ds = match device_session::get(&dev_eui).await {
+            Ok(v) => v,
+            Err(Error::NotFound(_)) => {
+                return Err(Status::failed_precondition("Device is not activated"));
+            }
+            Err(e) => {
+                return Err(e.status());
+            }
+        };
+
+        let mut sim = adr::simulation::Simulation::new(&dp, &ds).map_err(|e| e.status())?;
+        if !req.adr_algorithm_id.is_empty() {
+            sim.algorithm_id = req.adr_algorithm_id.clone();
+        }
+        if req.override_installation_margin {
+            sim.installation_margin = Some(req.installation_margin);
+        }
+        if !req.uplink_history.is_empty() {
+            sim.uplink_history = req
+                .uplink_history
+                .iter()
+                .map(|uh| internal::UplinkAdrHistory {
+                    f_cnt: uh.f_cnt,
+                    max_snr: uh.max_snr,
+                    max_rssi: uh.max_rssi,
+                    tx_power_index: uh.tx_power_index,
+                    gateway_count: uh.gateway_count,
+                })
+                .collect();
+        }
+
+        if !adr::get_algorithms().await.contains_key(&sim.algorithm_id) {
+            return Err(Status::invalid_argument(format!(
+                "Unknown ADR algorithm: {}",
+                sim.algorithm_id
+            )));
+        }
+
+        let steps = adr::simulation::simulate(&sim)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::SimulateDeviceAdrResponse {
+            result: steps
+                .iter()
+                .map(|s| api::AdrSimulationStep {
+                    f_cnt: s.f_cnt,
+                    max_snr: s.max_snr,
+                    dr: s.dr as u32,
+                    tx_power_index: s.tx_power_index as u32,
+                    nb_trans: s.nb_trans as u32,
+                    link_margin: s.link_margin,
+                })
+                .collect(),
+        });
+        resp.metadata_mut()
+            .insert("x-log-dev_eui", req.dev_eui.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    type ImportStream = ReceiverStream<Result<api::ImportDevicesResponse, Status>>;
+
+    async fn import(
+        &self,
+        request: Request<Streaming<api::ImportDevicesRequest>>,
+    ) -> Result<Response<Self::ImportStream>, Status> {
+        let (_, extensions, mut stream) = request.into_parts();
+
+        let req = match stream.message().await? {
+            Some(v) => v,
+            None => {
+                return Err(Status::invalid_argument("import options are missing"));
+            }
+        };
+        let app_id = Uuid::from_str(&req.application_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                &extensions,
+                validator::ValidateDevicesAccess::new(validator::Flag::Create, app_id),
+            )
+            .await?;
+
+        let mut importer = bulk::device::Importer::new(app_id, req.dry_run)
+            .await
+            .map_err(|e| e.status())?;
+
+        // The records are imported while the data is being received.
+        let format = req.format().from_proto();
+        let rdr =
+            helpers::import_data_reader(req.data, stream, |r: api::ImportDevicesRequest| r.data);
+        let (mut records, parser) =
+            bulk::parse_stream::<bulk::device::DeviceRecord, _>(format, rdr);
+
+        let (tx, rx) = mpsc::channel(1);
+        tokio::spawn(async move {
+            let mut row = 0;
+            while let Some(rec) = records.recv().await {
+                row += 1;
+                let res = importer.import(row, rec).await;
+                let resp = api::ImportDevicesResponse {
+                    row: res.row as u32,
+                    dev_eui: res.id,
+                    action: res.action.to_proto().into(),
+                    error: res.error,
+                };
+
+                if tx.send(Ok(resp)).await.is_err() {
+                    return;
+                }
+            }
+
+            // The data as a whole could not be parsed.
+            if let Err(e) = parser.await.map_err(anyhow::Error::new).and_then(|r| r) {
+                let _ = tx
+                    .send(Err(Status::invalid_argument(format!("{:#}", e))))
+                    .await;
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    type ExportStream = ReceiverStream<Result<api::ExportDevicesResponse, Status>>;
+
+    async fn export(
+        &self,
+        request: Request<api::ExportDevicesRequest>,
+    ) -> Result<Response<Self::ExportStream>, Status> {
+        let req = request.get_ref();
+        let app_id = Uuid::from_str(&req.application_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateDevicesAccess::new(validator::Flag::List, app_id),
+            )
+            .await?;
+
+        let mut exporter = bulk::device::Exporter::new(app_id, req.format().from_proto())
+            .await
+            .map_err(|e| e.status())?;
+
+        // The records are exported page by page, while the data is being sent.
+        let (tx, rx) = mpsc::channel(1);
+        tokio::spawn(async move {
+            loop {
+                match exporter.next().await {
+                    Ok(Some(b)) => {
+                        for c in b.chunks(bulk::EXPORT_CHUNK_SIZE) {
+                            let resp = api::ExportDevicesResponse { data: c.to_vec() };
+                            if tx.send(Ok(resp)).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                    Ok(None) => return,
+                    Err(e) => {
+                        let _ = tx.send(Err(e.status())).await;
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    type ImportSessionsStream = ReceiverStream<Result<api::ImportDeviceSessionsResponse, Status>>;
+
+    async fn import_sessions(
+        &self,
+        request: Request<Streaming<api::ImportDeviceSessionsRequest>>,
+    ) -> Result<Response<Self::ImportSessionsStream>, Status> {
+        let (_, extensions, mut stream) = request.into_parts();
+
+        self.validator
+            .validate(&extensions, validator::ValidateIsAdmin::new())
+            .await?;
+
+        let req = match stream.message().await? {
+            Some(v) => v,
+            None => {
+                return Err(Status::invalid_argument("import options are missing"));
+            }
+        };
+
+        let mut data = req.data.clone();
+        while let Some(r) = stream.message().await? {
+            data.extend_from_slice(&r.data);
+        }
+
+        let records = bulk::device_session::parse(&data)
+            .map_err(|e| Status::invalid_argument(format!("{:#}", e)))?;
+        let mut importer = bulk::device_session::Importer::new(req.dry_run);
+
+        let (tx, rx) = mpsc::channel(1);
+        tokio::spawn(async move {
+            for (i, rec) in records.into_iter().enumerate() {
+                let res = importer.import(i + 1, rec).await;
+                let resp = api::ImportDeviceSessionsResponse {
+                    row: res.row as u32,
+                    dev_eui: res.id,
+                    action: res.action.to_proto().into(),
+                    error: res.error,
+                };
+
+                if tx.send(Ok(resp)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    type ExportSessionsStream = futures::stream::Iter<
+        std::vec::IntoIter<Result<api::ExportDeviceSessionsResponse, Status>>,
+    >;
+
+    async fn export_sessions(
+        &self,
+        request: Request<api::ExportDeviceSessionsRequest>,
+    ) -> Result<Response<Self::ExportSessionsStream>, Status> {
+        self.validator
+            .validate(request.extensions(), validator::ValidateIsAdmin::new())
+            .await?;
+
+        let req = request.get_ref();
+        let application_ids = req
+            .application_ids
+            .iter()
+            .map(|v| Uuid::from_str(v))
+            .collect::<Result<Vec<Uuid>, _>>()
+            .map_err(|e| e.status())?;
+        let dev_euis = req
+            .dev_euis
+            .iter()
+            .map(|v| EUI64::from_str(v))
+            .collect::<Result<Vec<EUI64>, _>>()
+            .map_err(|e| e.status())?;
+
+        bulk::device_session::validate_kek_label(&req.kek_label)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let records = bulk::device_session::export(&application_ids, &dev_euis, &req.kek_label)
+            .await
+            .map_err(|e| e.status())?;
+        let b = bulk::device_session::write(&records).map_err(|e| e.status())?;
+
+        let out: Vec<Result<api::ExportDeviceSessionsResponse, Status>> = b
+            .chunks(bulk::EXPORT_CHUNK_SIZE)
+            .map(|c| Ok(api::ExportDeviceSessionsResponse { data: c.to_vec() }))
+            .collect();
+
+        Ok(Response::new(futures::stream::iter(out)))
+    }
+}
+
+// Returns the region configuration of the device-profile. In case the device-profile is not
+// bound to a region configuration, the first configuration matching the region is returned.
+fn get_region_conf(
+    dp: &device_profile::DeviceProfile,
+) -> Result<std::sync::Arc<Box<dyn lrwn::region::Region + Sync + Send>>, Status> {
+    let region_config_id = match &dp.region_config_id {
+        Some(v) => v.clone(),
+        None => region::get_region_config_id(dp.region)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?,
+    };
+
+    region::get(&region_config_id).map_err(|e| Status::invalid_argument(e.to_string()))
+}
+
+// Validates that the given data-rate is an enabled uplink data-rate of the region.
+fn validate_dr(
+    region_conf: &(dyn lrwn::region::Region + Sync + Send),
+    field: &str,
+    dr: u32,
+) -> Result<(), Status> {
+    if dr > u8::MAX as u32
+        || !region_conf
+            .get_enabled_uplink_data_rates()
+            .contains(&(dr as u8))
+    {
+        return Err(Status::invalid_argument(format!(
+            "{} {} is not an enabled uplink data-rate of the region",
+            field, dr
+        )));
+    }
+    Ok(())
+}
+
+// Validates that the given tx-power index is defined by the region.
+fn validate_tx_power_index(
+    region_conf: &(dyn lrwn::region::Region + Sync + Send),
+    field: &str,
+    tx_power_index: u32,
+) -> Result<(), Status> {
+    if tx_power_index > 15
+        || region_conf
+            .get_tx_power_offset(tx_power_index as usize)
+            .is_err()
+    {
+        return Err(Status::invalid_argument(format!(
+            "{} {} is not a valid tx-power index for the region",
+            field, tx_power_index
+        )));
+    }
+    Ok(())
+}
+
+// Validates the ADR overrides against the region configuration of the device-profile.
+fn validate_adr_overrides(
+    dp: &device_profile::DeviceProfile,
+    o: &api::DeviceAdrOverrides,
+) -> Result<(), Status> {
+    if o.min_dr.is_none()
+        && o.max_dr.is_none()
+        && o.max_tx_power_index.is_none()
+        && o.nb_trans.is_none()
+    {
+        return Ok(());
+    }
+
+    let region_conf = get_region_conf(dp)?;
+
+    if let Some(v) = o.min_dr {
+        validate_dr(&region_conf, "min_dr", v)?;
+    }
+    if let Some(v) = o.max_dr {
+        validate_dr(&region_conf, "max_dr", v)?;
+    }
+    if let (Some(min_dr), Some(max_dr)) = (o.min_dr, o.max_dr) {
+        if min_dr > max_dr {
+            return Err(Status::invalid_argument(
+                "min_dr must be less than or equal to max_dr",
+            ));
+        }
+    }
+    if let Some(v) = o.max_tx_power_index {
+        validate_tx_power_index(&region_conf, "max_tx_power_index", v)?;
+    }
+    if let Some(v) = o.nb_trans {
+        if !(1..=15).contains(&v) {
+            return Err(Status::invalid_argument(
+                "nb_trans must be between 1 and 15",
+            ));
+        }
+    }
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -1211,6 +1788,7 @@ pub mod test {
                 name: "test-device".into(),
                 dev_eui: "0102030405060708".into(),
                 join_eui: "0000000000000000".into(),
+                adr_overrides: Some(api::DeviceAdrOverrides::default()),
                 ..Default::default()
             }),
             get_resp.get_ref().device
@@ -1226,12 +1804,98 @@ pub mod test {
                     name: "test-device-updated".into(),
                     dev_eui: "0102030405060708".into(),
                     join_eui: "0807060504030201".into(),
+                    adr_overrides: Some(api::DeviceAdrOverrides {
+                        max_dr: Some(3),
+                        nb_trans: Some(2),
+                        ..Default::default()
+                    }),
                     ..Default::default()
                 }),
             },
         );
         let _ = service.update(update_req).await.unwrap();
 
+        // set link parameters
+        let set_req = get_request(
+            &u.id,
+            api::SetDeviceLinkParametersRequest {
+                dev_eui: "0102030405060708".into(),
+                dr: Some(2),
+                tx_power_index: None,
+            },
+        );
+        let _ = service.set_link_parameters(set_req).await.unwrap();
+
+        // set invalid link parameters
+        for (dr, tx_power_index) in [(Some(15), None), (None, Some(99)), (Some(70000), None)] {
+            let set_req = get_request(
+                &u.id,
+                api::SetDeviceLinkParametersRequest {
+                    dev_eui: "0102030405060708".into(),
+                    dr,
+                    tx_power_index,
+                },
+            );
+            let err = service.set_link_parameters(set_req).await.unwrap_err();
+            assert_eq!(tonic::Code::InvalidArgument, err.code());
+        }
+
+        // export sessions without (known) kek_label
+        for kek_label in ["", "unknown-kek"] {
+            let export_req = get_request(
+                &u.id,
+                api::ExportDeviceSessionsRequest {
+                    dev_euis: vec!["0102030405060708".into()],
+                    kek_label: kek_label.into(),
+                    ..Default::default()
+                },
+            );
+            let err = service.export_sessions(export_req).await.err().unwrap();
+            assert_eq!(tonic::Code::InvalidArgument, err.code());
+        }
+
+        // update with invalid adr overrides
+        for adr_overrides in [
+            api::DeviceAdrOverrides {
+                min_dr: Some(4),
+                max_dr: Some(2),
+                ..Default::default()
+            },
+            api::DeviceAdrOverrides {
+                max_dr: Some(65536),
+                ..Default::default()
+            },
+            api::DeviceAdrOverrides {
+                max_tx_power_index: Some(32768),
+                ..Default::default()
+            },
+            api::DeviceAdrOverrides {
+                nb_trans: Some(0),
+                ..Default::default()
+            },
+            api::DeviceAdrOverrides {
+                nb_trans: Some(16),
+                ..Default::default()
+            },
+        ] {
+            let update_req = get_request(
+                &u.id,
+                api::UpdateDeviceRequest {
+                    device: Some(api::Device {
+                        application_id: app.id.to_string(),
+                        device_profile_id: dp.id.to_string(),
+                        name: "test-device-updated".into(),
+                        dev_eui: "0102030405060708".into(),
+                        join_eui: "0807060504030201".into(),
+                        adr_overrides: Some(adr_overrides),
+                        ..Default::default()
+                    }),
+                },
+            );
+            let err = service.update(update_req).await.unwrap_err();
+            assert_eq!(tonic::Code::InvalidArgument, err.code());
+        }
+
         // get
         let get_req = get_request(
             &u.id,
@@ -1247,10 +1911,17 @@ pub mod test {
                 name: "test-device-updated".into(),
                 dev_eui: "0102030405060708".into(),
                 join_eui: "0807060504030201".into(),
+                adr_overrides: Some(api::DeviceAdrOverrides {
+                    max_dr: Some(3),
+                    nb_trans: Some(2),
+                    ..Default::default()
+                }),
                 ..Default::default()
             }),
             get_resp.get_ref().device
         );
+        assert_eq!(Some(2), get_resp.get_ref().pinned_dr);
+        assert_eq!(None, get_resp.get_ref().pinned_tx_power_index);
 
         // list
         let list_req = get_request(
@@ -1275,6 +1946,7 @@ pub mod test {
                     dev_eui: "0102030405060708".into(),
                     nwk_key: "01020304050607080102030405060708".into(),
                     app_key: "02020304050607080202030405060708".into(),
+                    gen_app_key: "04020304050607080402030405060708".into(),
                 }),
             },
         );
@@ -1293,6 +1965,7 @@ pub mod test {
                 dev_eui: "0102030405060708".into(),
                 nwk_key: "01020304050607080102030405060708".into(),
                 app_key: "02020304050607080202030405060708".into(),
+                gen_app_key: "04020304050607080402030405060708".into(),
             }),
             get_keys_resp.get_ref().device_keys
         );
@@ -1305,6 +1978,7 @@ pub mod test {
                     dev_eui: "0102030405060708".into(),
                     nwk_key: "01020304050607080102030405060708".into(),
                     app_key: "03020304050607080302030405060708".into(),
+                    gen_app_key: "04020304050607080402030405060708".into(),
                 }),
             },
         );
@@ -1323,6 +1997,7 @@ pub mod test {
                 dev_eui: "0102030405060708".into(),
                 nwk_key: "01020304050607080102030405060708".into(),
                 app_key: "03020304050607080302030405060708".into(),
+                gen_app_key: "04020304050607080402030405060708".into(),
             }),
             get_keys_resp.get_ref().device_keys
         );
@@ -1349,6 +2024,43 @@ pub mod test {
             .unwrap();
         assert_eq!(0, dk.dev_nonces.len());
 
+        // arm frame-counter reset
+        let arm_req = get_request(
+            &u.id,
+            api::ArmDeviceFCntResetRequest {
+                dev_eui: "0102030405060708".into(),
+                window: Some(prost_types::Duration {
+                    seconds: 60,
+                    nanos: 0,
+                }),
+                max_f_cnt: 10,
+            },
+        );
+        let _ = service.arm_f_cnt_reset(arm_req).await.unwrap();
+        assert_eq!(
+            Some(10),
+            device::get_f_cnt_reset_window(&EUI64::from_str("0102030405060708").unwrap())
+                .await
+                .unwrap()
+        );
+
+        // disarm frame-counter reset
+        let arm_req = get_request(
+            &u.id,
+            api::ArmDeviceFCntResetRequest {
+                dev_eui: "0102030405060708".into(),
+                window: None,
+                max_f_cnt: 0,
+            },
+        );
+        let _ = service.arm_f_cnt_reset(arm_req).await.unwrap();
+        assert_eq!(
+            None,
+            device::get_f_cnt_reset_window(&EUI64::from_str("0102030405060708").unwrap())
+                .await
+                .unwrap()
+        );
+
         // delete keys
         let del_keys_req = get_request(
             &u.id,
@@ -10,8 +10,8 @@ use super::auth::validator;
 use super::error::ToStatus;
 use super::helpers;
 use super::helpers::{FromProto, ToProto};
-use crate::adr;
 use crate::storage::{device_profile, fields};
+use crate::{adr, codec};
 
 pub struct DeviceProfile {
     validator: validator::RequestValidator,
@@ -112,6 +112,7 @@ impl DeviceProfileService for DeviceProfile {
             relay_global_uplink_limit_bucket_size: req_dp.relay_global_uplink_limit_bucket_size
                 as i16,
             relay_overall_limit_bucket_size: req_dp.relay_overall_limit_bucket_size as i16,
+            clock_sync_enabled: req_dp.clock_sync_enabled,
             ..Default::default()
         };
 
@@ -212,6 +213,7 @@ impl DeviceProfileService for DeviceProfile {
                 relay_global_uplink_limit_bucket_size: dp.relay_global_uplink_limit_bucket_size
                     as u32,
                 relay_overall_limit_bucket_size: dp.relay_overall_limit_bucket_size as u32,
+                clock_sync_enabled: dp.clock_sync_enabled,
             }),
             created_at: Some(helpers::datetime_to_prost_timestamp(&dp.created_at)),
             updated_at: Some(helpers::datetime_to_prost_timestamp(&dp.updated_at)),
@@ -310,6 +312,7 @@ impl DeviceProfileService for DeviceProfile {
             relay_global_uplink_limit_bucket_size: req_dp.relay_global_uplink_limit_bucket_size
                 as i16,
             relay_overall_limit_bucket_size: req_dp.relay_overall_limit_bucket_size as i16,
+            clock_sync_enabled: req_dp.clock_sync_enabled,
             ..Default::default()
         })
         .await
@@ -427,6 +430,61 @@ impl DeviceProfileService for DeviceProfile {
             result,
         }))
     }
+
+    async fn test_codec(
+        &self,
+        request: Request<api::TestDeviceProfileCodecRequest>,
+    ) -> Result<Response<api::TestDeviceProfileCodecResponse>, Status> {
+        let req = request.get_ref();
+        let tenant_id = Uuid::from_str(&req.tenant_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateDeviceProfilesAccess::new(validator::Flag::Create, tenant_id),
+            )
+            .await?;
+
+        let codec_runtime = req.payload_codec_runtime().from_proto();
+        let f_port = req.f_port as u8;
+
+        let res = match &req.payload {
+            Some(api::test_device_profile_codec_request::Payload::Bytes(b)) => {
+                codec::test_binary_to_struct(
+                    codec_runtime,
+                    f_port,
+                    &req.variables,
+                    &req.payload_codec_script,
+                    b,
+                )
+                .await
+            }
+            Some(api::test_device_profile_codec_request::Payload::Object(obj)) => {
+                codec::test_struct_to_binary(
+                    codec_runtime,
+                    f_port,
+                    &req.variables,
+                    &req.payload_codec_script,
+                    obj,
+                )
+                .await
+            }
+            None => {
+                return Err(Status::invalid_argument("bytes or object must be set"));
+            }
+        };
+
+        Ok(Response::new(api::TestDeviceProfileCodecResponse {
+            object: res.object.as_ref().map(codec::convert::pb_json_to_prost),
+            bytes: res.bytes,
+            errors: res.errors,
+            warnings: res.warnings,
+            execution_time: Some(prost_types::Duration {
+                seconds: res.execution_time.as_secs() as i64,
+                nanos: res.execution_time.subsec_nanos() as i32,
+            }),
+        }))
+    }
 }
 
 #[cfg(test)]
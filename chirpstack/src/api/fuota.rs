@@ -0,0 +1,591 @@
+use std::str::FromStr;
+
+use chrono::Utc;
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+
+use chirpstack_api::api;
+use chirpstack_api::api::fuota_service_server::FuotaService;
+use lrwn::{AES128Key, EUI64};
+
+use super::auth::validator;
+use super::error::ToStatus;
+use super::helpers::{self, FromProto, ToProto};
+use crate::devaddr::get_random_dev_addr;
+use crate::storage::error::Error as StorageError;
+use crate::storage::{application, device_profile, fields, fuota, multicast};
+
+pub struct Fuota {
+    validator: validator::RequestValidator,
+}
+
+impl Fuota {
+    pub fn new(validator: validator::RequestValidator) -> Self {
+        Fuota { validator }
+    }
+}
+
+#[tonic::async_trait]
+impl FuotaService for Fuota {
+    async fn create_deployment(
+        &self,
+        request: Request<api::CreateFuotaDeploymentRequest>,
+    ) -> Result<Response<api::CreateFuotaDeploymentResponse>, Status> {
+        let req_fd = match &request.get_ref().deployment {
+            Some(v) => v,
+            None => {
+                return Err(Status::invalid_argument("deployment is missing"));
+            }
+        };
+
+        let app_id = Uuid::from_str(&req_fd.application_id).map_err(|e| e.status())?;
+        let dp_id = Uuid::from_str(&req_fd.device_profile_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateFuotaDeploymentsAccess::new(validator::Flag::Create, app_id),
+            )
+            .await?;
+
+        let app = application::get(&app_id).await.map_err(|e| e.status())?;
+        let dp = device_profile::get(&dp_id).await.map_err(|e| e.status())?;
+        if app.tenant_id != dp.tenant_id {
+            return Err(Status::invalid_argument(
+                "Application and device-profile must be under the same tenant",
+            ));
+        }
+
+        let fd = fuota::create(fuota::FuotaDeployment {
+            name: req_fd.name.clone(),
+            application_id: app_id,
+            device_profile_id: dp_id,
+            multicast_group_type: match req_fd.multicast_group_type() {
+                api::MulticastGroupType::ClassB => "B",
+                api::MulticastGroupType::ClassC => "C",
+            }
+            .to_string(),
+            multicast_class_c_scheduling_type: req_fd
+                .multicast_class_c_scheduling_type()
+                .from_proto(),
+            multicast_dr: req_fd.multicast_dr as i16,
+            multicast_class_b_ping_slot_period: req_fd.multicast_class_b_ping_slot_period as i32,
+            multicast_frequency: req_fd.multicast_frequency as i64,
+            multicast_timeout: req_fd.multicast_timeout as i16,
+            multicast_addr: get_random_dev_addr(),
+            multicast_key: AES128Key::from_bytes(rand::random()),
+            unicast_timeout: req_fd.unicast_timeout as i32,
+            unicast_max_retry_count: req_fd.unicast_max_retry_count as i16,
+            fragmentation_fragment_size: req_fd.fragmentation_fragment_size as i16,
+            fragmentation_redundancy_percentage: req_fd.fragmentation_redundancy_percentage as i16,
+            fragmentation_session_index: req_fd.fragmentation_session_index as i16,
+            fragmentation_matrix: req_fd.fragmentation_matrix as i16,
+            fragmentation_block_ack_delay: req_fd.fragmentation_block_ack_delay as i16,
+            fragmentation_descriptor: req_fd.fragmentation_descriptor.clone(),
+            payload: req_fd.payload.clone(),
+            ..Default::default()
+        })
+        .await
+        .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::CreateFuotaDeploymentResponse {
+            id: fd.id.to_string(),
+        });
+        resp.metadata_mut().insert(
+            "x-log-fuota_deployment_id",
+            fd.id.to_string().parse().unwrap(),
+        );
+
+        Ok(resp)
+    }
+
+    async fn get_deployment(
+        &self,
+        request: Request<api::GetFuotaDeploymentRequest>,
+    ) -> Result<Response<api::GetFuotaDeploymentResponse>, Status> {
+        let req = request.get_ref();
+        let fd_id = Uuid::from_str(&req.id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateFuotaDeploymentAccess::new(validator::Flag::Read, fd_id),
+            )
+            .await?;
+
+        let fd = fuota::get(&fd_id).await.map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::GetFuotaDeploymentResponse {
+            deployment: Some(api::FuotaDeployment {
+                id: fd.id.to_string(),
+                application_id: fd.application_id.to_string(),
+                device_profile_id: fd.device_profile_id.to_string(),
+                name: fd.name.clone(),
+                multicast_group_type: match fd.multicast_group_type.as_ref() {
+                    "B" => api::MulticastGroupType::ClassB,
+                    "C" => api::MulticastGroupType::ClassC,
+                    _ => {
+                        return Err(Status::invalid_argument("Invalid multicast_group_type"));
+                    }
+                }
+                .into(),
+                multicast_class_c_scheduling_type: fd
+                    .multicast_class_c_scheduling_type
+                    .to_proto()
+                    .into(),
+                multicast_dr: fd.multicast_dr as u32,
+                multicast_class_b_ping_slot_period: fd.multicast_class_b_ping_slot_period as u32,
+                multicast_frequency: fd.multicast_frequency as u32,
+                multicast_timeout: fd.multicast_timeout as u32,
+                unicast_timeout: fd.unicast_timeout as u32,
+                unicast_max_retry_count: fd.unicast_max_retry_count as u32,
+                fragmentation_fragment_size: fd.fragmentation_fragment_size as u32,
+                fragmentation_redundancy_percentage: fd.fragmentation_redundancy_percentage as u32,
+                fragmentation_session_index: fd.fragmentation_session_index as u32,
+                fragmentation_matrix: fd.fragmentation_matrix as u32,
+                fragmentation_block_ack_delay: fd.fragmentation_block_ack_delay as u32,
+                fragmentation_descriptor: fd.fragmentation_descriptor.clone(),
+                payload: fd.payload.clone(),
+            }),
+            created_at: Some(helpers::datetime_to_prost_timestamp(&fd.created_at)),
+            updated_at: Some(helpers::datetime_to_prost_timestamp(&fd.updated_at)),
+            started_at: fd
+                .started_at
+                .as_ref()
+                .map(helpers::datetime_to_prost_timestamp),
+            completed_at: fd
+                .completed_at
+                .as_ref()
+                .map(helpers::datetime_to_prost_timestamp),
+        });
+        resp.metadata_mut()
+            .insert("x-log-fuota_deployment_id", req.id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn delete_deployment(
+        &self,
+        request: Request<api::DeleteFuotaDeploymentRequest>,
+    ) -> Result<Response<()>, Status> {
+        let req = request.get_ref();
+        let fd_id = Uuid::from_str(&req.id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateFuotaDeploymentAccess::new(validator::Flag::Delete, fd_id),
+            )
+            .await?;
+
+        let fd = fuota::get(&fd_id).await.map_err(|e| e.status())?;
+
+        // Remove the multicast-group created for this deployment.
+        if let Some(mg_id) = &fd.multicast_group_id {
+            match multicast::delete(mg_id).await {
+                Ok(_) | Err(StorageError::NotFound(_)) => {}
+                Err(e) => {
+                    return Err(e.status());
+                }
+            }
+        }
+
+        fuota::delete(&fd_id).await.map_err(|e| e.status())?;
+
+        let mut resp = Response::new(());
+        resp.metadata_mut()
+            .insert("x-log-fuota_deployment_id", req.id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn start_deployment(
+        &self,
+        request: Request<api::StartFuotaDeploymentRequest>,
+    ) -> Result<Response<()>, Status> {
+        let req = request.get_ref();
+        let fd_id = Uuid::from_str(&req.id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateFuotaDeploymentAccess::new(validator::Flag::Update, fd_id),
+            )
+            .await?;
+
+        let mut fd = fuota::get(&fd_id).await.map_err(|e| e.status())?;
+        if fd.started_at.is_some() {
+            return Err(Status::failed_precondition(
+                "Deployment has already been started",
+            ));
+        }
+
+        let count = fuota::get_device_count(&fd_id)
+            .await
+            .map_err(|e| e.status())?;
+        if count == 0 {
+            return Err(Status::failed_precondition("Deployment has no devices"));
+        }
+
+        let now = Utc::now();
+        fd.started_at = Some(now);
+        fd.next_step = fields::FuotaDeploymentStep::MC_GROUP_SETUP;
+        fd.retry_count = 0;
+        fd.scheduler_run_after = now;
+        fuota::update(fd).await.map_err(|e| e.status())?;
+
+        let mut resp = Response::new(());
+        resp.metadata_mut()
+            .insert("x-log-fuota_deployment_id", req.id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn list_deployments(
+        &self,
+        request: Request<api::ListFuotaDeploymentsRequest>,
+    ) -> Result<Response<api::ListFuotaDeploymentsResponse>, Status> {
+        let req = request.get_ref();
+        let app_id = Uuid::from_str(&req.application_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateFuotaDeploymentsAccess::new(validator::Flag::List, app_id),
+            )
+            .await?;
+
+        let filters = fuota::Filters {
+            application_id: Some(app_id),
+        };
+
+        let count = fuota::get_count(&filters).await.map_err(|e| e.status())?;
+        let items = fuota::list(req.limit as i64, req.offset as i64, &filters)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::ListFuotaDeploymentsResponse {
+            total_count: count as u32,
+            result: items
+                .iter()
+                .map(|fd| api::FuotaDeploymentListItem {
+                    id: fd.id.to_string(),
+                    created_at: Some(helpers::datetime_to_prost_timestamp(&fd.created_at)),
+                    updated_at: Some(helpers::datetime_to_prost_timestamp(&fd.updated_at)),
+                    started_at: fd
+                        .started_at
+                        .as_ref()
+                        .map(helpers::datetime_to_prost_timestamp),
+                    completed_at: fd
+                        .completed_at
+                        .as_ref()
+                        .map(helpers::datetime_to_prost_timestamp),
+                    name: fd.name.clone(),
+                })
+                .collect(),
+        });
+        resp.metadata_mut()
+            .insert("x-log-application_id", req.application_id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn add_devices(
+        &self,
+        request: Request<api::AddDevicesToFuotaDeploymentRequest>,
+    ) -> Result<Response<()>, Status> {
+        let req = request.get_ref();
+        let fd_id = Uuid::from_str(&req.fuota_deployment_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateFuotaDeploymentAccess::new(validator::Flag::Update, fd_id),
+            )
+            .await?;
+
+        let mut dev_euis = Vec::with_capacity(req.dev_euis.len());
+        for dev_eui in &req.dev_euis {
+            dev_euis.push(EUI64::from_str(dev_eui).map_err(|e| e.status())?);
+        }
+
+        fuota::add_devices(&fd_id, &dev_euis)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(());
+        resp.metadata_mut().insert(
+            "x-log-fuota_deployment_id",
+            req.fuota_deployment_id.parse().unwrap(),
+        );
+
+        Ok(resp)
+    }
+
+    async fn list_devices(
+        &self,
+        request: Request<api::ListFuotaDeploymentDevicesRequest>,
+    ) -> Result<Response<api::ListFuotaDeploymentDevicesResponse>, Status> {
+        let req = request.get_ref();
+        let fd_id = Uuid::from_str(&req.fuota_deployment_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateFuotaDeploymentAccess::new(validator::Flag::Read, fd_id),
+            )
+            .await?;
+
+        let count = fuota::get_device_count(&fd_id)
+            .await
+            .map_err(|e| e.status())?;
+        let items = fuota::list_devices(&fd_id, req.limit as i64, req.offset as i64)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::ListFuotaDeploymentDevicesResponse {
+            total_count: count as u32,
+            result: items
+                .iter()
+                .map(|d| api::FuotaDeploymentDeviceListItem {
+                    dev_eui: d.dev_eui.to_string(),
+                    created_at: Some(helpers::datetime_to_prost_timestamp(&d.created_at)),
+                    updated_at: Some(helpers::datetime_to_prost_timestamp(&d.updated_at)),
+                    mc_group_setup_completed_at: d
+                        .mc_group_setup_completed_at
+                        .as_ref()
+                        .map(helpers::datetime_to_prost_timestamp),
+                    mc_session_completed_at: d
+                        .mc_session_completed_at
+                        .as_ref()
+                        .map(helpers::datetime_to_prost_timestamp),
+                    frag_session_setup_completed_at: d
+                        .frag_session_setup_completed_at
+                        .as_ref()
+                        .map(helpers::datetime_to_prost_timestamp),
+                    frag_status_completed_at: d
+                        .frag_status_completed_at
+                        .as_ref()
+                        .map(helpers::datetime_to_prost_timestamp),
+                    error_msg: d.error_msg.clone(),
+                })
+                .collect(),
+        });
+        resp.metadata_mut().insert(
+            "x-log-fuota_deployment_id",
+            req.fuota_deployment_id.parse().unwrap(),
+        );
+
+        Ok(resp)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::api::auth::validator::RequestValidator;
+    use crate::api::auth::AuthID;
+    use crate::storage::{device, tenant, user};
+    use crate::test;
+
+    #[tokio::test]
+    async fn test_fuota() {
+        let _guard = test::prepare().await;
+
+        // setup admin user
+        let u = user::create(user::User {
+            is_admin: true,
+            is_active: true,
+            email: "admin@admin".into(),
+            email_verified: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // create tenant
+        let t = tenant::create(tenant::Tenant {
+            name: "test-tenant".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // create application
+        let app = application::create(application::Application {
+            name: "test-app".into(),
+            tenant_id: t.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // create device-profile
+        let dp = device_profile::create(device_profile::DeviceProfile {
+            name: "test-dp".into(),
+            tenant_id: t.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // create device
+        let d = device::create(device::Device {
+            application_id: app.id,
+            device_profile_id: dp.id,
+            dev_eui: EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
+            name: "test-dev".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // setup api
+        let service = Fuota::new(RequestValidator::new());
+
+        // create
+        let create_req = get_request(
+            &u.id,
+            api::CreateFuotaDeploymentRequest {
+                deployment: Some(api::FuotaDeployment {
+                    application_id: app.id.to_string(),
+                    device_profile_id: dp.id.to_string(),
+                    name: "test-fuota".into(),
+                    multicast_group_type: api::MulticastGroupType::ClassC.into(),
+                    multicast_dr: 5,
+                    multicast_frequency: 868100000,
+                    multicast_timeout: 6,
+                    unicast_timeout: 60,
+                    unicast_max_retry_count: 1,
+                    fragmentation_fragment_size: 50,
+                    fragmentation_redundancy_percentage: 10,
+                    fragmentation_descriptor: vec![1, 2, 3, 4],
+                    payload: vec![1, 2, 3, 4],
+                    ..Default::default()
+                }),
+            },
+        );
+        let create_resp = service.create_deployment(create_req).await.unwrap();
+        let create_resp = create_resp.get_ref();
+
+        // get
+        let get_req = get_request(
+            &u.id,
+            api::GetFuotaDeploymentRequest {
+                id: create_resp.id.clone(),
+            },
+        );
+        let get_resp = service.get_deployment(get_req).await.unwrap();
+        let get_resp = get_resp.get_ref();
+        assert_eq!(
+            Some(api::FuotaDeployment {
+                id: create_resp.id.clone(),
+                application_id: app.id.to_string(),
+                device_profile_id: dp.id.to_string(),
+                name: "test-fuota".into(),
+                multicast_group_type: api::MulticastGroupType::ClassC.into(),
+                multicast_dr: 5,
+                multicast_frequency: 868100000,
+                multicast_timeout: 6,
+                unicast_timeout: 60,
+                unicast_max_retry_count: 1,
+                fragmentation_fragment_size: 50,
+                fragmentation_redundancy_percentage: 10,
+                fragmentation_descriptor: vec![1, 2, 3, 4],
+                payload: vec![1, 2, 3, 4],
+                ..Default::default()
+            }),
+            get_resp.deployment
+        );
+        assert!(get_resp.started_at.is_none());
+
+        // start without devices
+        let start_req = get_request(
+            &u.id,
+            api::StartFuotaDeploymentRequest {
+                id: create_resp.id.clone(),
+            },
+        );
+        assert!(service.start_deployment(start_req).await.is_err());
+
+        // add devices
+        let add_req = get_request(
+            &u.id,
+            api::AddDevicesToFuotaDeploymentRequest {
+                fuota_deployment_id: create_resp.id.clone(),
+                dev_euis: vec![d.dev_eui.to_string()],
+            },
+        );
+        let _ = service.add_devices(add_req).await.unwrap();
+
+        // list devices
+        let list_devices_req = get_request(
+            &u.id,
+            api::ListFuotaDeploymentDevicesRequest {
+                fuota_deployment_id: create_resp.id.clone(),
+                limit: 10,
+                offset: 0,
+            },
+        );
+        let list_devices_resp = service.list_devices(list_devices_req).await.unwrap();
+        let list_devices_resp = list_devices_resp.get_ref();
+        assert_eq!(1, list_devices_resp.total_count);
+        assert_eq!(d.dev_eui.to_string(), list_devices_resp.result[0].dev_eui);
+
+        // start
+        let start_req = get_request(
+            &u.id,
+            api::StartFuotaDeploymentRequest {
+                id: create_resp.id.clone(),
+            },
+        );
+        let _ = service.start_deployment(start_req).await.unwrap();
+
+        // start again
+        let start_req = get_request(
+            &u.id,
+            api::StartFuotaDeploymentRequest {
+                id: create_resp.id.clone(),
+            },
+        );
+        assert!(service.start_deployment(start_req).await.is_err());
+
+        // list
+        let list_req = get_request(
+            &u.id,
+            api::ListFuotaDeploymentsRequest {
+                application_id: app.id.to_string(),
+                limit: 10,
+                offset: 0,
+            },
+        );
+        let list_resp = service.list_deployments(list_req).await.unwrap();
+        let list_resp = list_resp.get_ref();
+        assert_eq!(1, list_resp.total_count);
+        assert_eq!(create_resp.id, list_resp.result[0].id);
+        assert!(list_resp.result[0].started_at.is_some());
+
+        // delete
+        let del_req = get_request(
+            &u.id,
+            api::DeleteFuotaDeploymentRequest {
+                id: create_resp.id.clone(),
+            },
+        );
+        let _ = service.delete_deployment(del_req).await.unwrap();
+        let del_req = get_request(
+            &u.id,
+            api::DeleteFuotaDeploymentRequest {
+                id: create_resp.id.clone(),
+            },
+        );
+        let del_resp = service.delete_deployment(del_req).await;
+        assert!(del_resp.is_err());
+    }
+
+    fn get_request<T>(user_id: &Uuid, req: T) -> Request<T> {
+        let mut req = Request::new(req);
+        req.extensions_mut().insert(AuthID::User(user_id.clone()));
+        req
+    }
+}
@@ -3,7 +3,9 @@ use std::str::FromStr;
 use std::time::SystemTime;
 
 use chrono::{DateTime, Duration, Local, Utc};
-use tonic::{Request, Response, Status};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
 use uuid::Uuid;
 
 use chirpstack_api::api::gateway_service_server::GatewayService;
@@ -12,9 +14,9 @@ use lrwn::EUI64;
 
 use super::auth::validator;
 use super::error::ToStatus;
-use super::helpers::{self, FromProto};
-use crate::certificate;
+use super::helpers::{self, FromProto, ToProto};
 use crate::storage::{fields, gateway, metrics};
+use crate::{bulk, certificate};
 
 pub struct Gateway {
     validator: validator::RequestValidator,
@@ -607,6 +609,114 @@ impl GatewayService for Gateway {
 
         Ok(resp)
     }
+
+    type ImportStream = ReceiverStream<Result<api::ImportGatewaysResponse, Status>>;
+
+    async fn import(
+        &self,
+        request: Request<Streaming<api::ImportGatewaysRequest>>,
+    ) -> Result<Response<Self::ImportStream>, Status> {
+        let (_, extensions, mut stream) = request.into_parts();
+
+        let req = match stream.message().await? {
+            Some(v) => v,
+            None => {
+                return Err(Status::invalid_argument("import options are missing"));
+            }
+        };
+        let tenant_id = Uuid::from_str(&req.tenant_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                &extensions,
+                validator::ValidateGatewaysAccess::new(validator::Flag::Create, tenant_id),
+            )
+            .await?;
+
+        let mut importer = bulk::gateway::Importer::new(tenant_id, req.dry_run)
+            .await
+            .map_err(|e| e.status())?;
+
+        // The records are imported while the data is being received.
+        let format = req.format().from_proto();
+        let rdr =
+            helpers::import_data_reader(req.data, stream, |r: api::ImportGatewaysRequest| r.data);
+        let (mut records, parser) =
+            bulk::parse_stream::<bulk::gateway::GatewayRecord, _>(format, rdr);
+
+        let (tx, rx) = mpsc::channel(1);
+        tokio::spawn(async move {
+            let mut row = 0;
+            while let Some(rec) = records.recv().await {
+                row += 1;
+                let res = importer.import(row, rec).await;
+                let resp = api::ImportGatewaysResponse {
+                    row: res.row as u32,
+                    gateway_id: res.id,
+                    action: res.action.to_proto().into(),
+                    error: res.error,
+                };
+
+                if tx.send(Ok(resp)).await.is_err() {
+                    return;
+                }
+            }
+
+            // The data as a whole could not be parsed.
+            if let Err(e) = parser.await.map_err(anyhow::Error::new).and_then(|r| r) {
+                let _ = tx
+                    .send(Err(Status::invalid_argument(format!("{:#}", e))))
+                    .await;
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    type ExportStream = ReceiverStream<Result<api::ExportGatewaysResponse, Status>>;
+
+    async fn export(
+        &self,
+        request: Request<api::ExportGatewaysRequest>,
+    ) -> Result<Response<Self::ExportStream>, Status> {
+        let req = request.get_ref();
+        let tenant_id = Uuid::from_str(&req.tenant_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateGatewaysAccess::new(validator::Flag::List, tenant_id),
+            )
+            .await?;
+
+        let mut exporter = bulk::gateway::Exporter::new(tenant_id, req.format().from_proto())
+            .await
+            .map_err(|e| e.status())?;
+
+        // The records are exported page by page, while the data is being sent.
+        let (tx, rx) = mpsc::channel(1);
+        tokio::spawn(async move {
+            loop {
+                match exporter.next().await {
+                    Ok(Some(b)) => {
+                        for c in b.chunks(bulk::EXPORT_CHUNK_SIZE) {
+                            let resp = api::ExportGatewaysResponse { data: c.to_vec() };
+                            if tx.send(Ok(resp)).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                    Ok(None) => return,
+                    Err(e) => {
+                        let _ = tx.send(Err(e.status())).await;
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
 }
 
 #[cfg(test)]
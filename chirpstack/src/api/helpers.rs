@@ -1,6 +1,10 @@
 use chrono::{DateTime, Utc};
+use tokio::sync::mpsc;
+use tonic::Streaming;
 
+use crate::bulk;
 use crate::codec::Codec;
+use crate::storage::application::IntegrationKind;
 use crate::storage::fields::{MeasurementKind, MulticastGroupSchedulingType};
 use crate::storage::{device::DeviceClass, metrics::Aggregation};
 use chirpstack_api::{api, common};
@@ -32,6 +36,7 @@ impl FromProto<CommonName> for common::Region {
             common::Region::In865 => CommonName::IN865,
             common::Region::Ru864 => CommonName::RU864,
             common::Region::Ism2400 => CommonName::ISM2400,
+            common::Region::Custom => CommonName::CUSTOM,
         }
     }
 }
@@ -53,6 +58,7 @@ impl ToProto<common::Region> for CommonName {
             CommonName::IN865 => common::Region::In865,
             CommonName::RU864 => common::Region::Ru864,
             CommonName::ISM2400 => common::Region::Ism2400,
+            CommonName::CUSTOM => common::Region::Custom,
         }
     }
 }
@@ -66,6 +72,7 @@ impl FromProto<Revision> for common::RegParamsRevision {
             common::RegParamsRevision::Rp002101 => Revision::RP002_1_0_1,
             common::RegParamsRevision::Rp002102 => Revision::RP002_1_0_2,
             common::RegParamsRevision::Rp002103 => Revision::RP002_1_0_3,
+            common::RegParamsRevision::Rp002104 => Revision::RP002_1_0_4,
         }
     }
 }
@@ -78,7 +85,8 @@ impl ToProto<common::RegParamsRevision> for Revision {
             Revision::RP002_1_0_0 => common::RegParamsRevision::Rp002100,
             Revision::RP002_1_0_1 => common::RegParamsRevision::Rp002101,
             Revision::RP002_1_0_2 => common::RegParamsRevision::Rp002102,
-            Revision::RP002_1_0_3 | Revision::Latest => common::RegParamsRevision::Rp002103,
+            Revision::RP002_1_0_3 => common::RegParamsRevision::Rp002103,
+            Revision::RP002_1_0_4 | Revision::Latest => common::RegParamsRevision::Rp002104,
         }
     }
 }
@@ -201,6 +209,23 @@ impl ToProto<common::MType> for lrwn::MType {
     }
 }
 
+impl ToProto<api::IntegrationKind> for IntegrationKind {
+    fn to_proto(self) -> api::IntegrationKind {
+        match self {
+            IntegrationKind::Http => api::IntegrationKind::Http,
+            IntegrationKind::InfluxDb => api::IntegrationKind::InfluxDb,
+            IntegrationKind::ThingsBoard => api::IntegrationKind::ThingsBoard,
+            IntegrationKind::MyDevices => api::IntegrationKind::MyDevices,
+            IntegrationKind::LoraCloud => api::IntegrationKind::LoraCloud,
+            IntegrationKind::GcpPubSub => api::IntegrationKind::GcpPubSub,
+            IntegrationKind::AwsSns => api::IntegrationKind::AwsSns,
+            IntegrationKind::AzureServiceBus => api::IntegrationKind::AzureServiceBus,
+            IntegrationKind::PilotThings => api::IntegrationKind::PilotThings,
+            IntegrationKind::Ifttt => api::IntegrationKind::Ifttt,
+        }
+    }
+}
+
 impl ToProto<api::MulticastGroupSchedulingType> for MulticastGroupSchedulingType {
     fn to_proto(self) -> api::MulticastGroupSchedulingType {
         match self {
@@ -259,6 +284,25 @@ impl ToProto<common::DeviceClass> for DeviceClass {
     }
 }
 
+impl FromProto<bulk::Format> for common::BulkFormat {
+    fn from_proto(self) -> bulk::Format {
+        match self {
+            common::BulkFormat::Csv => bulk::Format::Csv,
+            common::BulkFormat::Json => bulk::Format::Json,
+        }
+    }
+}
+
+impl ToProto<common::BulkAction> for bulk::Action {
+    fn to_proto(self) -> common::BulkAction {
+        match self {
+            bulk::Action::Create => common::BulkAction::Create,
+            bulk::Action::Update => common::BulkAction::Update,
+            bulk::Action::Error => common::BulkAction::Error,
+        }
+    }
+}
+
 pub fn datetime_to_prost_timestamp(dt: &DateTime<Utc>) -> prost_types::Timestamp {
     let ts = dt.timestamp_nanos();
 
@@ -267,3 +311,37 @@ pub fn datetime_to_prost_timestamp(dt: &DateTime<Utc>) -> prost_types::Timestamp
         nanos: (ts % 1_000_000_000) as i32,
     }
 }
+
+/// Returns a reader for the bulk import data. The given data (of the first request) is
+/// read first, the data of the remaining requests is read while it is being received.
+pub fn import_data_reader<T, F>(
+    data: Vec<u8>,
+    mut stream: Streaming<T>,
+    get_data: F,
+) -> bulk::ChunkReader
+where
+    T: Send + 'static,
+    F: Fn(T) -> Vec<u8> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        if tx.send(Ok(data)).await.is_err() {
+            return;
+        }
+
+        loop {
+            let data = match stream.message().await {
+                Ok(Some(v)) => Ok(get_data(v)),
+                Ok(None) => return,
+                Err(e) => Err(anyhow!("Receive import data: {}", e.message())),
+            };
+
+            let is_err = data.is_err();
+            if tx.send(data).await.is_err() || is_err {
+                return;
+            }
+        }
+    });
+
+    bulk::ChunkReader::new(rx)
+}
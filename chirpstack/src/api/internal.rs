@@ -22,7 +22,7 @@ use super::error::ToStatus;
 use super::helpers::ToProto;
 use super::{helpers, oidc};
 use crate::storage::{api_key, device, error::Error, gateway, redis_key, search, tenant, user};
-use crate::{config, eventlog, framelog, region};
+use crate::{config, eventlog, framelog, region, reload};
 use lrwn::EUI64;
 
 pub struct Internal {
@@ -863,4 +863,14 @@ impl InternalService for Internal {
 
         Ok(Response::new(out))
     }
+
+    async fn reload_configuration(&self, request: Request<()>) -> Result<Response<()>, Status> {
+        self.validator
+            .validate(request.extensions(), validator::ValidateIsAdmin::new())
+            .await?;
+
+        reload::reload().await.map_err(|e| e.status())?;
+
+        Ok(Response::new(()))
+    }
 }
@@ -30,6 +30,7 @@ use chirpstack_api::api::application_service_server::ApplicationServiceServer;
 use chirpstack_api::api::device_profile_service_server::DeviceProfileServiceServer;
 use chirpstack_api::api::device_profile_template_service_server::DeviceProfileTemplateServiceServer;
 use chirpstack_api::api::device_service_server::DeviceServiceServer;
+use chirpstack_api::api::fuota_service_server::FuotaServiceServer;
 use chirpstack_api::api::gateway_service_server::GatewayServiceServer;
 use chirpstack_api::api::internal_service_server::InternalServiceServer;
 use chirpstack_api::api::multicast_group_service_server::MulticastGroupServiceServer;
@@ -49,6 +50,7 @@ pub mod device;
 pub mod device_profile;
 pub mod device_profile_template;
 pub mod error;
+pub mod fuota;
 pub mod gateway;
 pub mod helpers;
 pub mod internal;
@@ -56,6 +58,7 @@ pub mod monitoring;
 pub mod multicast;
 pub mod oidc;
 pub mod relay;
+pub mod rest;
 pub mod tenant;
 pub mod user;
 
@@ -157,6 +160,10 @@ pub async fn setup() -> Result<()> {
                 relay::Relay::new(validator::RequestValidator::new()),
                 auth::auth_interceptor,
             ))
+            .add_service(FuotaServiceServer::with_interceptor(
+                fuota::Fuota::new(validator::RequestValidator::new()),
+                auth::auth_interceptor,
+            ))
             .into_service();
         let mut tonic_service = ServiceBuilder::new()
             .layer(
@@ -180,6 +187,12 @@ pub async fn setup() -> Result<()> {
                 .or(warp::path!("auth" / "oidc" / "callback")
                     .and(warp::query::<oidc::CallbackArgs>())
                     .and_then(oidc::callback_handler))
+                .or(warp::path!("api" / "devices" / String / "queue")
+                    .and(warp::post())
+                    .and(warp::header::optional::<String>("authorization"))
+                    .and(warp::body::content_length_limit(64 * 1024))
+                    .and(warp::body::bytes())
+                    .and_then(rest::enqueue_handler))
                 .or(warp::path::tail().and_then(http_serve)),
         );
         let mut warp_service = ServiceBuilder::new()
@@ -199,19 +212,24 @@ pub async fn setup() -> Result<()> {
             .service(warp_service);
 
         future::ok::<_, Infallible>(tower::service_fn(
-            move |req: hyper::Request<hyper::Body>| match req.method() {
-                &hyper::Method::GET => Either::Left(
-                    warp_service
-                        .call(req)
-                        .map_ok(|res| res.map(EitherBody::Right))
-                        .map_err(Error::from),
-                ),
-                _ => Either::Right(
-                    tonic_service
-                        .call(req)
-                        .map_ok(|res| res.map(EitherBody::Left))
-                        .map_err(Error::from),
-                ),
+            move |req: hyper::Request<hyper::Body>| {
+                // The gRPC paths are formatted as /package.Service/Method, REST endpoints
+                // are served under /api/.
+                if req.method() == hyper::Method::GET || req.uri().path().starts_with("/api/") {
+                    Either::Left(
+                        warp_service
+                            .call(req)
+                            .map_ok(|res| res.map(EitherBody::Right))
+                            .map_err(Error::from),
+                    )
+                } else {
+                    Either::Right(
+                        tonic_service
+                            .call(req)
+                            .map_ok(|res| res.map(EitherBody::Left))
+                            .map_err(Error::from),
+                    )
+                }
             },
         ))
     });
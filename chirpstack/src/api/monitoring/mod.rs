@@ -7,9 +7,9 @@ use tokio::task;
 use tracing::info;
 use warp::{http::Response, http::StatusCode, Filter};
 
-use crate::config;
 use crate::monitoring::prometheus;
-use crate::storage::{get_db_conn, get_redis_conn};
+use crate::storage::{get_db_conn, get_redis_conn, tenant};
+use crate::{config, devaddr};
 
 pub async fn setup() {
     let conf = config::get();
@@ -28,7 +28,13 @@ pub async fn setup() {
         .and(warp::path!("health"))
         .and_then(health_handler);
 
-    let routes = prom_endpoint.or(health_endpoint);
+    let dev_addr_prefixes_endpoint = warp::get()
+        .and(warp::path!("dev-addr-prefixes"))
+        .and_then(dev_addr_prefixes_handler);
+
+    let routes = prom_endpoint
+        .or(health_endpoint)
+        .or(dev_addr_prefixes_endpoint);
 
     warp::serve(routes).run(addr).await;
 }
@@ -38,6 +44,44 @@ async fn prometheus_handler() -> Result<impl warp::Reply, Infallible> {
     Ok(Response::builder().body(body))
 }
 
+async fn dev_addr_prefixes_handler() -> Result<impl warp::Reply, Infallible> {
+    match _dev_addr_prefixes_handler().await {
+        Ok(v) => Ok(warp::reply::with_status(
+            warp::reply::json(&v),
+            StatusCode::OK,
+        )),
+        Err(e) => Ok(warp::reply::with_status(
+            warp::reply::json(&e.to_string()),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )),
+    }
+}
+
+async fn _dev_addr_prefixes_handler() -> Result<Vec<devaddr::PrefixUtilization>> {
+    let conf = config::get();
+
+    let mut prefixes = devaddr::get_dev_addr_prefixes(None)?;
+    for region in &conf.regions {
+        for prefix in &region.network.dev_addr_prefixes {
+            if !prefixes.contains(prefix) {
+                prefixes.push(*prefix);
+            }
+        }
+    }
+    for prefix in tenant::get_all_dev_addr_prefixes().await? {
+        if !prefixes.contains(&prefix) {
+            prefixes.push(prefix);
+        }
+    }
+
+    let mut out = Vec::new();
+    for prefix in prefixes {
+        out.push(devaddr::get_prefix_utilization(prefix).await?);
+    }
+
+    Ok(out)
+}
+
 async fn health_handler() -> Result<impl warp::Reply, Infallible> {
     if let Err(e) = _health_handler().await {
         return Ok(warp::reply::with_status(
@@ -0,0 +1,180 @@
+use std::str::FromStr;
+
+use serde::Serialize;
+use tonic::{Code, Request, Status};
+use tracing::{error, info};
+use warp::http::StatusCode;
+use warp::hyper::body::Bytes;
+use warp::{Rejection, Reply};
+
+use super::auth::{self, validator};
+use super::error::ToStatus;
+use crate::integration;
+use crate::storage::{self, device};
+use chirpstack_api::integration as integration_pb;
+use lrwn::EUI64;
+
+#[derive(Serialize)]
+struct EnqueueResponse {
+    id: String,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+/// Enqueues the JSON encoded DownlinkCommand in the request body for the given device.
+/// The request must contain an API token in the Authorization header ("Bearer <TOKEN>").
+pub async fn enqueue_handler(
+    dev_eui: String,
+    authorization: Option<String>,
+    body: Bytes,
+) -> Result<impl Reply, Rejection> {
+    match enqueue(&dev_eui, authorization, &body).await {
+        Ok(id) => Ok(warp::reply::with_status(
+            warp::reply::json(&EnqueueResponse { id }),
+            StatusCode::OK,
+        )),
+        Err(e) => Ok(warp::reply::with_status(
+            warp::reply::json(&ErrorResponse {
+                error: e.message().to_string(),
+            }),
+            status_code(e.code()),
+        )),
+    }
+}
+
+async fn enqueue(
+    dev_eui: &str,
+    authorization: Option<String>,
+    body: &[u8],
+) -> Result<String, Status> {
+    let dev_eui = EUI64::from_str(dev_eui).map_err(|e| e.status())?;
+
+    let mut req = Request::new(());
+    if let Some(v) = authorization {
+        req.metadata_mut().insert(
+            "authorization",
+            v.parse()
+                .map_err(|_| Status::unauthenticated("invalid authorization header"))?,
+        );
+    }
+    let req = auth::auth_interceptor(req)?;
+
+    validator::RequestValidator::new()
+        .validate(
+            req.extensions(),
+            validator::ValidateDeviceQueueAccess::new(validator::Flag::Create, dev_eui),
+        )
+        .await?;
+
+    let mut cmd: integration_pb::DownlinkCommand = serde_json::from_slice(body)
+        .map_err(|e| Status::invalid_argument(format!("invalid request body: {}", e)))?;
+    if !cmd.dev_eui.is_empty() && !cmd.dev_eui.eq_ignore_ascii_case(&dev_eui.to_string()) {
+        return Err(Status::invalid_argument(
+            "dev_eui in request body does not match dev_eui in path",
+        ));
+    }
+    cmd.dev_eui = dev_eui.to_string();
+
+    let dev = device::get(&dev_eui).await.map_err(|e| e.status())?;
+    let id = integration::enqueue_down_command(&dev, &cmd)
+        .await
+        .map_err(|e| {
+            error!(dev_eui = %dev_eui, error = %e, "Enqueue downlink command error");
+            match e.downcast_ref::<storage::error::Error>() {
+                Some(e) => e.status(),
+                // E.g. payload codec errors.
+                None => Status::invalid_argument(e.to_string()),
+            }
+        })?;
+
+    info!(dev_eui = %dev_eui, id = %id, "Downlink command enqueued");
+
+    Ok(id.to_string())
+}
+
+fn status_code(code: Code) -> StatusCode {
+    match code {
+        Code::InvalidArgument => StatusCode::BAD_REQUEST,
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::AlreadyExists => StatusCode::CONFLICT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::api::auth::claims;
+    use crate::storage::{device_profile, device_queue, user};
+    use crate::{config, test};
+
+    #[test]
+    fn test_status_code() {
+        assert_eq!(StatusCode::BAD_REQUEST, status_code(Code::InvalidArgument));
+        assert_eq!(StatusCode::UNAUTHORIZED, status_code(Code::Unauthenticated));
+        assert_eq!(StatusCode::NOT_FOUND, status_code(Code::NotFound));
+        assert_eq!(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            status_code(Code::Internal)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_enqueue() {
+        let _guard = test::prepare().await;
+
+        let u = user::create(user::User {
+            is_admin: true,
+            is_active: true,
+            email: "admin@admin".into(),
+            email_verified: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let token = claims::AuthClaim::new_for_user(&u.id)
+            .encode(config::get().api.secret.as_ref())
+            .unwrap();
+
+        let dp = device_profile::test::create_device_profile(None).await;
+        let dev = device::test::create_device(
+            EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
+            dp.id,
+            None,
+        )
+        .await;
+
+        let body = br#"{"confirmed": true, "fPort": 10, "data": "AQID"}"#;
+
+        // no authorization
+        let err = enqueue("0102030405060708", None, body).await.unwrap_err();
+        assert_eq!(Code::Unauthenticated, err.code());
+
+        // dev_eui mismatch
+        let err = enqueue(
+            "0102030405060708",
+            Some(format!("Bearer {}", token)),
+            br#"{"devEui": "0807060504030201", "fPort": 10, "data": "AQID"}"#,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(Code::InvalidArgument, err.code());
+
+        // enqueue
+        let id = enqueue("0102030405060708", Some(format!("Bearer {}", token)), body)
+            .await
+            .unwrap();
+
+        let items = device_queue::get_for_dev_eui(&dev.dev_eui).await.unwrap();
+        assert_eq!(1, items.len());
+        assert_eq!(id, items[0].id.to_string());
+        assert_eq!(10, items[0].f_port);
+        assert!(items[0].confirmed);
+        assert_eq!(vec![1, 2, 3], items[0].data);
+    }
+}
@@ -49,6 +49,11 @@ impl TenantService for Tenant {
             max_gateway_count: req_tenant.max_gateway_count as i32,
             private_gateways_up: req_tenant.private_gateways_up,
             private_gateways_down: req_tenant.private_gateways_down,
+            dev_addr_prefixes: req_tenant
+                .dev_addr_prefixes
+                .iter()
+                .map(|p| Some(p.clone()))
+                .collect(),
             ..Default::default()
         };
 
@@ -89,6 +94,7 @@ impl TenantService for Tenant {
                 max_device_count: t.max_device_count as u32,
                 private_gateways_up: t.private_gateways_up,
                 private_gateways_down: t.private_gateways_down,
+                dev_addr_prefixes: t.dev_addr_prefixes.into_iter().flatten().collect(),
             }),
             created_at: Some(helpers::datetime_to_prost_timestamp(&t.created_at)),
             updated_at: Some(helpers::datetime_to_prost_timestamp(&t.updated_at)),
@@ -128,6 +134,11 @@ impl TenantService for Tenant {
             max_gateway_count: req_tenant.max_gateway_count as i32,
             private_gateways_up: req_tenant.private_gateways_up,
             private_gateways_down: req_tenant.private_gateways_down,
+            dev_addr_prefixes: req_tenant
+                .dev_addr_prefixes
+                .iter()
+                .map(|p| Some(p.clone()))
+                .collect(),
             ..Default::default()
         })
         .await
@@ -513,6 +524,7 @@ pub mod test {
                 can_have_gateways: true,
                 max_device_count: 10,
                 max_gateway_count: 3,
+                dev_addr_prefixes: vec!["01000000/8".into()],
                 ..Default::default()
             }),
         };
@@ -535,6 +547,7 @@ pub mod test {
                 can_have_gateways: true,
                 max_device_count: 10,
                 max_gateway_count: 3,
+                dev_addr_prefixes: vec!["01000000/8".into()],
                 ..Default::default()
             }),
             get_resp.get_ref().tenant
@@ -0,0 +1,166 @@
+use anyhow::Result;
+use tracing::info;
+
+use crate::gpstime::ToGpsTime;
+use crate::storage::{device, device_queue};
+use crate::uplink::helpers;
+use chirpstack_api::gw;
+use lrwn::applayer::clocksync;
+
+pub async fn handle_uplink(
+    dev: &device::Device,
+    rx_info: &[gw::UplinkRxInfo],
+    data: &[u8],
+) -> Result<()> {
+    match clocksync::Payload::from_slice(true, data)? {
+        clocksync::Payload::AppTimeReq(pl) => handle_app_time_req(dev, rx_info, pl).await,
+        _ => Ok(()),
+    }
+}
+
+async fn handle_app_time_req(
+    dev: &device::Device,
+    rx_info: &[gw::UplinkRxInfo],
+    pl: clocksync::AppTimeReqPayload,
+) -> Result<()> {
+    let ans = match get_app_time_ans(rx_info, &pl) {
+        Some(v) => v,
+        None => {
+            info!(dev_eui = %dev.dev_eui, "Device clock is in sync and no answer is required");
+            return Ok(());
+        }
+    };
+
+    info!(dev_eui = %dev.dev_eui, time_correction = ans.time_correction, "Enqueueing AppTimeAns");
+
+    device_queue::enqueue_item(device_queue::DeviceQueueItem {
+        dev_eui: dev.dev_eui,
+        f_port: lrwn::applayer::FPORT_CLOCK_SYNC as i16,
+        data: clocksync::Payload::AppTimeAns(ans).to_vec()?,
+        ..Default::default()
+    })
+    .await?;
+
+    Ok(())
+}
+
+// Returns the AppTimeAns for the given AppTimeReq. This returns None when the
+// device clock is in sync and the device does not require an answer.
+fn get_app_time_ans(
+    rx_info: &[gw::UplinkRxInfo],
+    pl: &clocksync::AppTimeReqPayload,
+) -> Option<clocksync::AppTimeAnsPayload> {
+    // Prefer the GPS time of the gateway(s), fall back on the receive timestamp.
+    let gps_time = match helpers::get_time_since_gps_epoch_chrono(rx_info) {
+        Some(v) => v,
+        None => helpers::get_rx_timestamp_chrono(rx_info).to_gps_time(),
+    };
+
+    // Both values are the number of seconds since GPS epoch (modulo 2^32).
+    let time_correction = (gps_time.num_seconds() as u32).wrapping_sub(pl.device_time) as i32;
+
+    if time_correction == 0 && !pl.ans_required {
+        return None;
+    }
+
+    Some(clocksync::AppTimeAnsPayload {
+        time_correction,
+        token_ans: pl.token_req,
+    })
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use chrono::Duration;
+
+    fn rx_info_with_gps_time(secs: i64) -> Vec<gw::UplinkRxInfo> {
+        vec![gw::UplinkRxInfo {
+            time_since_gps_epoch: Some(pbjson_types::Duration::from(
+                Duration::seconds(secs).to_std().unwrap(),
+            )),
+            ..Default::default()
+        }]
+    }
+
+    #[test]
+    fn test_get_app_time_ans() {
+        struct Test {
+            name: String,
+            gps_time: i64,
+            req: clocksync::AppTimeReqPayload,
+            expected: Option<clocksync::AppTimeAnsPayload>,
+        }
+
+        let tests = vec![
+            Test {
+                name: "device clock behind".into(),
+                gps_time: 1000,
+                req: clocksync::AppTimeReqPayload {
+                    device_time: 990,
+                    token_req: 2,
+                    ans_required: false,
+                },
+                expected: Some(clocksync::AppTimeAnsPayload {
+                    time_correction: 10,
+                    token_ans: 2,
+                }),
+            },
+            Test {
+                name: "device clock ahead".into(),
+                gps_time: 1000,
+                req: clocksync::AppTimeReqPayload {
+                    device_time: 1005,
+                    token_req: 3,
+                    ans_required: false,
+                },
+                expected: Some(clocksync::AppTimeAnsPayload {
+                    time_correction: -5,
+                    token_ans: 3,
+                }),
+            },
+            Test {
+                name: "in sync, answer not required".into(),
+                gps_time: 1000,
+                req: clocksync::AppTimeReqPayload {
+                    device_time: 1000,
+                    token_req: 1,
+                    ans_required: false,
+                },
+                expected: None,
+            },
+            Test {
+                name: "in sync, answer required".into(),
+                gps_time: 1000,
+                req: clocksync::AppTimeReqPayload {
+                    device_time: 1000,
+                    token_req: 1,
+                    ans_required: true,
+                },
+                expected: Some(clocksync::AppTimeAnsPayload {
+                    time_correction: 0,
+                    token_ans: 1,
+                }),
+            },
+            Test {
+                name: "device time wrapped".into(),
+                gps_time: (1 << 32) + 5,
+                req: clocksync::AppTimeReqPayload {
+                    device_time: u32::MAX,
+                    token_req: 0,
+                    ans_required: false,
+                },
+                expected: Some(clocksync::AppTimeAnsPayload {
+                    time_correction: 6,
+                    token_ans: 0,
+                }),
+            },
+        ];
+
+        for tst in &tests {
+            println!("> {}", tst.name);
+            let rx_info = rx_info_with_gps_time(tst.gps_time);
+            assert_eq!(tst.expected, get_app_time_ans(&rx_info, &tst.req));
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use anyhow::Result;
+use chrono::Utc;
+use tracing::{info, warn};
+
+use crate::storage::error::Error as StorageError;
+use crate::storage::{device, fuota};
+use lrwn::applayer::fragmentation;
+
+pub async fn handle_uplink(dev: &device::Device, data: &[u8]) -> Result<()> {
+    let fd = match fuota::get_active_deployment_for_dev_eui(&dev.dev_eui).await {
+        Ok(v) => v,
+        Err(StorageError::NotFound(_)) => {
+            return Ok(());
+        }
+        Err(e) => {
+            return Err(e.into());
+        }
+    };
+    let fdd = fuota::get_device(&fd.id, &dev.dev_eui).await?;
+
+    match fragmentation::Payload::from_slice(true, data)? {
+        fragmentation::Payload::FragSessionSetupAns(pl) => {
+            handle_frag_session_setup_ans(fdd, pl).await
+        }
+        fragmentation::Payload::FragSessionStatusAns(pl) => {
+            handle_frag_session_status_ans(fdd, pl).await
+        }
+        _ => Ok(()),
+    }
+}
+
+async fn handle_frag_session_setup_ans(
+    mut fdd: fuota::FuotaDeploymentDevice,
+    pl: fragmentation::FragSessionSetupAnsPayload,
+) -> Result<()> {
+    info!(dev_eui = %fdd.dev_eui, "Handling FragSessionSetupAns");
+
+    if pl.encoding_unsupported
+        || pl.not_enough_memory
+        || pl.frag_session_index_not_supported
+        || pl.wrong_descriptor
+    {
+        warn!(dev_eui = %fdd.dev_eui, frag_index = pl.frag_index, "FragSessionSetupAns contains errors");
+        fdd.error_msg = format!("Error: FragSessionSetupAns response encoding_unsupported={}, not_enough_memory={}, frag_session_index_not_supported={}, wrong_descriptor={}", pl.encoding_unsupported, pl.not_enough_memory, pl.frag_session_index_not_supported, pl.wrong_descriptor);
+    } else {
+        fdd.frag_session_setup_completed_at = Some(Utc::now());
+    }
+
+    fuota::update_device(fdd).await?;
+    Ok(())
+}
+
+async fn handle_frag_session_status_ans(
+    mut fdd: fuota::FuotaDeploymentDevice,
+    pl: fragmentation::FragSessionStatusAnsPayload,
+) -> Result<()> {
+    info!(dev_eui = %fdd.dev_eui, "Handling FragSessionStatusAns");
+
+    if pl.missing_frag != 0 || pl.not_enough_matrix_memory {
+        warn!(dev_eui = %fdd.dev_eui, frag_index = pl.frag_index, missing_frag = pl.missing_frag, not_enough_matrix_memory = pl.not_enough_matrix_memory, "FragSessionStatusAns contains errors");
+        fdd.error_msg = format!(
+            "Error: FragSessionStatusAns response nb_frag_received={}, missing_frag={}, not_enough_matrix_memory={}",
+            pl.nb_frag_received, pl.missing_frag, pl.not_enough_matrix_memory,
+        );
+    } else {
+        fdd.frag_status_completed_at = Some(Utc::now());
+    }
+
+    fuota::update_device(fdd).await?;
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use anyhow::Result;
+use tracing::warn;
+
+use crate::storage::{device, device_profile};
+use chirpstack_api::gw;
+
+pub mod clocksync;
+pub mod fragmentation;
+pub mod multicastsetup;
+
+// Handles the uplink payloads of the LoRaWAN application-layer packages.
+// Uplinks on other FPorts are ignored. Errors are logged, as these must not
+// affect the handling of the uplink itself.
+pub async fn handle_uplink(
+    dev: &device::Device,
+    dp: &device_profile::DeviceProfile,
+    rx_info: &[gw::UplinkRxInfo],
+    f_port: u8,
+    data: &[u8],
+) {
+    if let Err(e) = _handle_uplink(dev, dp, rx_info, f_port, data).await {
+        warn!(dev_eui = %dev.dev_eui, f_port = f_port, error = %e, "Handling application-layer uplink failed");
+    }
+}
+
+async fn _handle_uplink(
+    dev: &device::Device,
+    dp: &device_profile::DeviceProfile,
+    rx_info: &[gw::UplinkRxInfo],
+    f_port: u8,
+    data: &[u8],
+) -> Result<()> {
+    match f_port {
+        lrwn::applayer::FPORT_CLOCK_SYNC if dp.clock_sync_enabled => {
+            clocksync::handle_uplink(dev, rx_info, data).await
+        }
+        lrwn::applayer::FPORT_MULTICAST_SETUP => multicastsetup::handle_uplink(dev, data).await,
+        lrwn::applayer::FPORT_FRAGMENTATION => fragmentation::handle_uplink(dev, data).await,
+        _ => Ok(()),
+    }
+}
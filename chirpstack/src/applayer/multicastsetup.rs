@@ -0,0 +1,64 @@
+use anyhow::Result;
+use chrono::Utc;
+use tracing::{info, warn};
+
+use crate::storage::error::Error as StorageError;
+use crate::storage::{device, fuota};
+use lrwn::applayer::multicastsetup;
+
+pub async fn handle_uplink(dev: &device::Device, data: &[u8]) -> Result<()> {
+    let fd = match fuota::get_active_deployment_for_dev_eui(&dev.dev_eui).await {
+        Ok(v) => v,
+        Err(StorageError::NotFound(_)) => {
+            return Ok(());
+        }
+        Err(e) => {
+            return Err(e.into());
+        }
+    };
+    let fdd = fuota::get_device(&fd.id, &dev.dev_eui).await?;
+
+    match multicastsetup::Payload::from_slice(true, data)? {
+        multicastsetup::Payload::McGroupSetupAns(pl) => handle_mc_group_setup_ans(fdd, pl).await,
+        multicastsetup::Payload::McClassBSessionAns(pl)
+        | multicastsetup::Payload::McClassCSessionAns(pl) => handle_mc_session_ans(fdd, pl).await,
+        _ => Ok(()),
+    }
+}
+
+async fn handle_mc_group_setup_ans(
+    mut fdd: fuota::FuotaDeploymentDevice,
+    pl: multicastsetup::McGroupSetupAnsPayload,
+) -> Result<()> {
+    info!(dev_eui = %fdd.dev_eui, "Handling McGroupSetupAns");
+
+    if pl.id_error {
+        warn!(dev_eui = %fdd.dev_eui, mc_group_id = pl.mc_group_id, "McGroupSetupAns contains errors");
+        fdd.error_msg = "Error: McGroupSetupAns response id_error=true".into();
+    } else {
+        fdd.mc_group_setup_completed_at = Some(Utc::now());
+    }
+
+    fuota::update_device(fdd).await?;
+    Ok(())
+}
+
+async fn handle_mc_session_ans(
+    mut fdd: fuota::FuotaDeploymentDevice,
+    pl: multicastsetup::McClassSessionAnsPayload,
+) -> Result<()> {
+    info!(dev_eui = %fdd.dev_eui, "Handling McClassB/CSessionAns");
+
+    if pl.dr_error || pl.freq_error || pl.mc_group_undefined {
+        warn!(dev_eui = %fdd.dev_eui, dr_error = pl.dr_error, freq_error = pl.freq_error, mc_group_undefined = pl.mc_group_undefined, "McClassB/CSessionAns contains errors");
+        fdd.error_msg = format!(
+            "Error: McClassB/CSessionAns response dr_error={}, freq_error={}, mc_group_undefined={}",
+            pl.dr_error, pl.freq_error, pl.mc_group_undefined,
+        );
+    } else {
+        fdd.mc_session_completed_at = Some(Utc::now());
+    }
+
+    fuota::update_device(fdd).await?;
+    Ok(())
+}
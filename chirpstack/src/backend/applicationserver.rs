@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use anyhow::Result;
+use tracing::{info, span, Level};
+
+use crate::config;
+use backend::{Client, ClientConfig};
+use lrwn::NetID;
+
+// Clients for the Application Servers that are allowed to request the AppSKey from
+// ChirpStack as Join Server. These are used to send the (async) AppSKeyAns messages.
+lazy_static! {
+    static ref CLIENTS: RwLock<HashMap<NetID, Arc<Client>>> = RwLock::new(HashMap::new());
+}
+
+type Clients = HashMap<NetID, Arc<Client>>;
+
+pub fn setup() -> Result<()> {
+    info!("Setting up Application Server clients");
+    let conf = config::get();
+    let clients = build(&conf)?;
+    replace(clients);
+
+    Ok(())
+}
+
+/// Builds the Application Server clients for the given configuration, without
+/// activating these.
+pub fn build(conf: &config::Configuration) -> Result<Clients> {
+    let mut clients: Clients = HashMap::new();
+
+    for app_s in &conf.join_server.application_servers {
+        let span = span!(Level::INFO, "setup", net_id = %app_s.net_id);
+        let _guard = span.enter();
+
+        info!(server = %app_s.server, async_timeout = ?app_s.async_timeout, "Configuring Application Server");
+
+        let c = Client::new(ClientConfig {
+            receiver_id: app_s.net_id.to_vec(),
+            server: app_s.server.clone(),
+            ca_cert: app_s.ca_cert.clone(),
+            tls_cert: app_s.tls_cert.clone(),
+            tls_key: app_s.tls_key.clone(),
+            authorization: if app_s.authorization_header.is_empty() {
+                None
+            } else {
+                Some(app_s.authorization_header.clone())
+            },
+            async_timeout: app_s.async_timeout,
+            ..Default::default()
+        })?;
+
+        clients.insert(app_s.net_id, Arc::new(c));
+    }
+
+    Ok(clients)
+}
+
+/// Replaces all the Application Server clients.
+pub fn replace(clients: Clients) {
+    let mut clients_w = CLIENTS.write().unwrap();
+    *clients_w = clients;
+}
+
+pub fn get(net_id: &NetID) -> Result<Arc<Client>> {
+    let clients_r = CLIENTS.read().unwrap();
+    Ok(clients_r
+        .get(net_id)
+        .ok_or_else(|| {
+            anyhow!(
+                "Application Server client for net_id {} does not exist",
+                net_id
+            )
+        })?
+        .clone())
+}
+
+pub fn get_app_s_key_kek_label(net_id: NetID) -> Result<String> {
+    let conf = config::get();
+
+    for app_s in &conf.join_server.application_servers {
+        if app_s.net_id == net_id {
+            return Ok(app_s.app_s_key_kek_label.clone());
+        }
+    }
+
+    Err(anyhow!(
+        "AppSKey KEK label for net_id {} does not exist",
+        net_id
+    ))
+}
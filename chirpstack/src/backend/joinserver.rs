@@ -12,9 +12,21 @@ lazy_static! {
     static ref CLIENTS: RwLock<HashMap<EUI64, Arc<Client>>> = RwLock::new(HashMap::new());
 }
 
+type Clients = HashMap<EUI64, Arc<Client>>;
+
 pub fn setup() -> Result<()> {
     info!("Setting up Join Server clients");
     let conf = config::get();
+    let clients = build(&conf)?;
+    replace(clients);
+
+    Ok(())
+}
+
+/// Builds the Join Server clients for the given configuration, without
+/// activating these.
+pub fn build(conf: &config::Configuration) -> Result<Clients> {
+    let mut clients: Clients = HashMap::new();
 
     for js in &conf.join_server.servers {
         let span = span!(Level::INFO, "setup", join_eui = %js.join_eui);
@@ -33,10 +45,16 @@ pub fn setup() -> Result<()> {
             ..Default::default()
         })?;
 
-        set(&js.join_eui, c);
+        clients.insert(js.join_eui, Arc::new(c));
     }
 
-    Ok(())
+    Ok(clients)
+}
+
+/// Replaces all the Join Server clients.
+pub fn replace(clients: Clients) {
+    let mut clients_w = CLIENTS.write().unwrap();
+    *clients_w = clients;
 }
 
 pub fn set(join_eui: &EUI64, c: Client) {
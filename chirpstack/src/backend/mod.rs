@@ -1,11 +1,15 @@
 use anyhow::Result;
 
+pub mod applicationserver;
 pub mod joinserver;
 pub mod keywrap;
+pub mod networkserver;
 pub mod roaming;
 
 pub fn setup() -> Result<()> {
     joinserver::setup()?;
+    applicationserver::setup()?;
+    networkserver::setup()?;
     roaming::setup()?;
 
     Ok(())
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use anyhow::Result;
+use tracing::{info, span, Level};
+
+use crate::config;
+use backend::{Client, ClientConfig};
+use lrwn::NetID;
+
+// Clients for the Network Servers that are allowed to use ChirpStack as Join Server.
+// These are used to send the (async) JoinAns, RejoinAns and AppSKeyAns messages.
+lazy_static! {
+    static ref CLIENTS: RwLock<HashMap<NetID, Arc<Client>>> = RwLock::new(HashMap::new());
+}
+
+type Clients = HashMap<NetID, Arc<Client>>;
+
+pub fn setup() -> Result<()> {
+    info!("Setting up Network Server clients");
+    let conf = config::get();
+    let clients = build(&conf)?;
+    replace(clients);
+
+    Ok(())
+}
+
+/// Builds the Network Server clients for the given configuration, without
+/// activating these.
+pub fn build(conf: &config::Configuration) -> Result<Clients> {
+    let mut clients: Clients = HashMap::new();
+
+    for ns in &conf.join_server.network_servers {
+        let span = span!(Level::INFO, "setup", net_id = %ns.net_id);
+        let _guard = span.enter();
+
+        info!(server = %ns.server, async_timeout = ?ns.async_timeout, "Configuring Network Server");
+
+        let c = Client::new(ClientConfig {
+            receiver_id: ns.net_id.to_vec(),
+            server: ns.server.clone(),
+            ca_cert: ns.ca_cert.clone(),
+            tls_cert: ns.tls_cert.clone(),
+            tls_key: ns.tls_key.clone(),
+            authorization: if ns.authorization_header.is_empty() {
+                None
+            } else {
+                Some(ns.authorization_header.clone())
+            },
+            async_timeout: ns.async_timeout,
+            ..Default::default()
+        })?;
+
+        clients.insert(ns.net_id, Arc::new(c));
+    }
+
+    Ok(clients)
+}
+
+/// Replaces all the Network Server clients.
+pub fn replace(clients: Clients) {
+    let mut clients_w = CLIENTS.write().unwrap();
+    *clients_w = clients;
+}
+
+pub fn set(net_id: &NetID, c: Client) {
+    let mut clients_w = CLIENTS.write().unwrap();
+    clients_w.insert(*net_id, Arc::new(c));
+}
+
+pub fn get(net_id: &NetID) -> Result<Arc<Client>> {
+    let clients_r = CLIENTS.read().unwrap();
+    Ok(clients_r
+        .get(net_id)
+        .ok_or_else(|| anyhow!("Network Server client for net_id {} does not exist", net_id))?
+        .clone())
+}
+
+pub fn get_kek_label(net_id: NetID) -> Result<String> {
+    let conf = config::get();
+
+    for ns in &conf.join_server.network_servers {
+        if ns.net_id == net_id {
+            return Ok(ns.kek_label.clone());
+        }
+    }
+
+    Err(anyhow!("KEK label for net_id {} does not exist", net_id))
+}
+
+pub fn get_app_s_key_kek_label(net_id: NetID) -> Result<String> {
+    let conf = config::get();
+
+    for ns in &conf.join_server.network_servers {
+        if ns.net_id == net_id {
+            return Ok(ns.app_s_key_kek_label.clone());
+        }
+    }
+
+    Err(anyhow!(
+        "AppSKey KEK label for net_id {} does not exist",
+        net_id
+    ))
+}
+
+#[cfg(test)]
+pub fn reset() {
+    let mut clients_w = CLIENTS.write().unwrap();
+    *clients_w = HashMap::new();
+}
@@ -18,9 +18,21 @@ lazy_static! {
     static ref CLIENTS: RwLock<HashMap<NetID, Arc<Client>>> = RwLock::new(HashMap::new());
 }
 
+type Clients = HashMap<NetID, Arc<Client>>;
+
 pub fn setup() -> Result<()> {
     info!("Setting up roaming clients");
     let conf = config::get();
+    let clients = build(&conf)?;
+    replace(clients);
+
+    Ok(())
+}
+
+/// Builds the roaming clients for the given configuration, without activating
+/// these.
+pub fn build(conf: &config::Configuration) -> Result<Clients> {
+    let mut clients: Clients = HashMap::new();
 
     for s in &conf.roaming.servers {
         let span = span!(Level::INFO, "setup", net_id  = %s.net_id);
@@ -58,10 +70,16 @@ pub fn setup() -> Result<()> {
             async_timeout: s.async_timeout,
         })?;
 
-        set(&s.net_id, c);
+        clients.insert(s.net_id, Arc::new(c));
     }
 
-    Ok(())
+    Ok(clients)
+}
+
+/// Replaces all the roaming clients.
+pub fn replace(clients: Clients) {
+    let mut clients_w = CLIENTS.write().unwrap();
+    *clients_w = clients;
 }
 
 pub fn set(net_id: &NetID, c: Client) {
@@ -0,0 +1,468 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{
+    Action, Format, Record, RowResult, Writer, EXPORT_PAGE_SIZE, TAG_PREFIX, VARIABLE_PREFIX,
+};
+use crate::storage::error::Error;
+use crate::storage::{application, device, device_keys, device_profile, fields};
+use lrwn::{AES128Key, EUI64};
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct DeviceRecord {
+    pub dev_eui: EUI64,
+    pub name: String,
+    pub description: String,
+    pub device_profile_id: Uuid,
+    pub join_eui: EUI64,
+    pub skip_fcnt_check: bool,
+    pub is_disabled: bool,
+    pub nwk_key: Option<AES128Key>,
+    pub app_key: Option<AES128Key>,
+    pub tags: BTreeMap<String, String>,
+    pub variables: BTreeMap<String, String>,
+}
+
+impl Record for DeviceRecord {
+    fn csv_columns() -> &'static [&'static str] {
+        &[
+            "dev_eui",
+            "name",
+            "description",
+            "device_profile_id",
+            "join_eui",
+            "skip_fcnt_check",
+            "is_disabled",
+            "nwk_key",
+            "app_key",
+        ]
+    }
+
+    fn from_csv_row(row: &HashMap<String, String>) -> Result<Self> {
+        let get_key = |column: &str| -> Result<Option<AES128Key>> {
+            let v = super::get_column(row, column);
+            if v.is_empty() {
+                Ok(None)
+            } else {
+                Ok(Some(
+                    AES128Key::from_str(&v).context(format!("Parse {}", column))?,
+                ))
+            }
+        };
+        let join_eui = super::get_column(row, "join_eui");
+
+        Ok(DeviceRecord {
+            dev_eui: EUI64::from_str(&super::get_column(row, "dev_eui"))
+                .context("Parse dev_eui")?,
+            name: super::get_column(row, "name"),
+            description: super::get_column(row, "description"),
+            device_profile_id: Uuid::from_str(&super::get_column(row, "device_profile_id"))
+                .context("Parse device_profile_id")?,
+            join_eui: if join_eui.is_empty() {
+                EUI64::default()
+            } else {
+                EUI64::from_str(&join_eui).context("Parse join_eui")?
+            },
+            skip_fcnt_check: super::get_bool_column(row, "skip_fcnt_check")?,
+            is_disabled: super::get_bool_column(row, "is_disabled")?,
+            nwk_key: get_key("nwk_key")?,
+            app_key: get_key("app_key")?,
+            tags: super::get_prefixed_columns(row, TAG_PREFIX),
+            variables: super::get_prefixed_columns(row, VARIABLE_PREFIX),
+        })
+    }
+
+    fn to_csv_row(&self) -> HashMap<String, String> {
+        let mut row: HashMap<String, String> = [
+            ("dev_eui", self.dev_eui.to_string()),
+            ("name", self.name.clone()),
+            ("description", self.description.clone()),
+            ("device_profile_id", self.device_profile_id.to_string()),
+            ("join_eui", self.join_eui.to_string()),
+            ("skip_fcnt_check", self.skip_fcnt_check.to_string()),
+            ("is_disabled", self.is_disabled.to_string()),
+            (
+                "nwk_key",
+                self.nwk_key.map(|v| v.to_string()).unwrap_or_default(),
+            ),
+            (
+                "app_key",
+                self.app_key.map(|v| v.to_string()).unwrap_or_default(),
+            ),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+
+        super::set_prefixed_columns(&mut row, TAG_PREFIX, &self.tags);
+        super::set_prefixed_columns(&mut row, VARIABLE_PREFIX, &self.variables);
+
+        row
+    }
+}
+
+/// Imports devices into a single application.
+/// Existing devices (within the same application) are updated.
+pub struct Importer {
+    application: application::Application,
+    dry_run: bool,
+    // Device-profile validation result by device-profile ID.
+    device_profiles: HashMap<Uuid, Result<(), String>>,
+    // Imported DevEUIs, to detect duplicates.
+    seen: HashSet<EUI64>,
+}
+
+impl Importer {
+    pub async fn new(application_id: Uuid, dry_run: bool) -> Result<Self> {
+        Ok(Importer {
+            application: application::get(&application_id).await?,
+            dry_run,
+            device_profiles: HashMap::new(),
+            seen: HashSet::new(),
+        })
+    }
+
+    /// Imports the given record. When the importer is in dry-run mode, the
+    /// record is validated, but nothing is written to the database.
+    pub async fn import(&mut self, row: usize, rec: Result<DeviceRecord>) -> RowResult {
+        let rec = match rec {
+            Ok(v) => v,
+            Err(e) => return RowResult::error(row, "".into(), e),
+        };
+
+        match self.import_record(&rec).await {
+            Ok(action) => RowResult {
+                row,
+                id: rec.dev_eui.to_string(),
+                action,
+                error: "".into(),
+            },
+            Err(e) => RowResult::error(row, rec.dev_eui.to_string(), e),
+        }
+    }
+
+    async fn import_record(&mut self, rec: &DeviceRecord) -> Result<Action> {
+        if rec.name.is_empty() {
+            return Err(anyhow!("name is not set"));
+        }
+
+        if !self.seen.insert(rec.dev_eui) {
+            return Err(anyhow!("Duplicate DevEUI"));
+        }
+
+        self.validate_device_profile(&rec.device_profile_id)
+            .await
+            .map_err(|e| anyhow!("{}", e))?;
+
+        let (action, mut d) = match device::get(&rec.dev_eui).await {
+            Ok(d) => {
+                if d.application_id != self.application.id {
+                    return Err(anyhow!("Device exists within a different application"));
+                }
+                (Action::Update, d)
+            }
+            Err(Error::NotFound(_)) => (
+                Action::Create,
+                device::Device {
+                    dev_eui: rec.dev_eui,
+                    application_id: self.application.id,
+                    ..Default::default()
+                },
+            ),
+            Err(e) => return Err(e.into()),
+        };
+
+        if self.dry_run {
+            return Ok(action);
+        }
+
+        d.device_profile_id = rec.device_profile_id;
+        d.name = rec.name.clone();
+        d.description = rec.description.clone();
+        d.join_eui = rec.join_eui;
+        d.skip_fcnt_check = rec.skip_fcnt_check;
+        d.is_disabled = rec.is_disabled;
+        d.tags = fields::KeyValue::new(rec.tags.clone().into_iter().collect());
+        d.variables = fields::KeyValue::new(rec.variables.clone().into_iter().collect());
+
+        match action {
+            Action::Create => device::create(d).await?,
+            _ => device::update(d).await?,
+        };
+
+        if rec.nwk_key.is_some() || rec.app_key.is_some() {
+            match device_keys::get(&rec.dev_eui).await {
+                Ok(mut dk) => {
+                    if let Some(v) = rec.nwk_key {
+                        dk.nwk_key = v;
+                    }
+                    if let Some(v) = rec.app_key {
+                        dk.app_key = v;
+                    }
+                    device_keys::update(dk).await?;
+                }
+                Err(Error::NotFound(_)) => {
+                    device_keys::create(device_keys::DeviceKeys {
+                        dev_eui: rec.dev_eui,
+                        nwk_key: rec.nwk_key.unwrap_or_default(),
+                        app_key: rec.app_key.unwrap_or_default(),
+                        ..Default::default()
+                    })
+                    .await?;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok(action)
+    }
+
+    async fn validate_device_profile(&mut self, id: &Uuid) -> Result<(), String> {
+        if let Some(res) = self.device_profiles.get(id) {
+            return res.clone();
+        }
+
+        let res = match device_profile::get(id).await {
+            Ok(dp) => {
+                if dp.tenant_id == self.application.tenant_id {
+                    Ok(())
+                } else {
+                    Err("Device-profile belongs to a different tenant".to_string())
+                }
+            }
+            Err(Error::NotFound(_)) => Err(format!("Device-profile {} does not exist", id)),
+            Err(e) => return Err(e.to_string()),
+        };
+
+        self.device_profiles.insert(*id, res.clone());
+        res
+    }
+}
+
+/// Exports the devices (including keys) of a single application. The devices
+/// are exported page by page, such that not all devices are kept in memory.
+pub struct Exporter {
+    application_id: Uuid,
+    writer: Writer,
+    offset: i64,
+    done: bool,
+}
+
+impl Exporter {
+    pub async fn new(application_id: Uuid, format: Format) -> Result<Self> {
+        // The CSV header must contain the tags and variables of all devices.
+        let mut columns: BTreeSet<String> = BTreeSet::new();
+        for k in device::get_tag_keys(&application_id).await? {
+            columns.insert(format!("{}{}", TAG_PREFIX, k));
+        }
+        for k in device::get_variable_keys(&application_id).await? {
+            columns.insert(format!("{}{}", VARIABLE_PREFIX, k));
+        }
+
+        Ok(Exporter {
+            application_id,
+            writer: Writer::new::<DeviceRecord>(format, columns),
+            offset: 0,
+            done: false,
+        })
+    }
+
+    /// Returns the next chunk of export data, or None when all devices have
+    /// been exported.
+    pub async fn next(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let mut out = if self.offset == 0 {
+            self.writer.start()?
+        } else {
+            Vec::new()
+        };
+
+        let records = self.get_records().await?;
+        if records.is_empty() {
+            self.done = true;
+            out.extend(self.writer.finish());
+        } else {
+            out.extend(self.writer.write(&records)?);
+        }
+
+        Ok(Some(out))
+    }
+
+    /// Returns the number of exported devices.
+    pub fn count(&self) -> usize {
+        self.writer.count()
+    }
+
+    async fn get_records(&mut self) -> Result<Vec<DeviceRecord>> {
+        let filters = device::Filters {
+            application_id: Some(self.application_id),
+            ..Default::default()
+        };
+
+        let items = device::list(EXPORT_PAGE_SIZE, self.offset, &filters).await?;
+        self.offset += items.len() as i64;
+
+        let mut out: Vec<DeviceRecord> = Vec::with_capacity(items.len());
+        for item in &items {
+            let d = device::get(&item.dev_eui).await?;
+            let dk = match device_keys::get(&d.dev_eui).await {
+                Ok(v) => Some(v),
+                Err(Error::NotFound(_)) => None,
+                Err(e) => return Err(e.into()),
+            };
+
+            out.push(DeviceRecord {
+                dev_eui: d.dev_eui,
+                name: d.name.clone(),
+                description: d.description.clone(),
+                device_profile_id: d.device_profile_id,
+                join_eui: d.join_eui,
+                skip_fcnt_check: d.skip_fcnt_check,
+                is_disabled: d.is_disabled,
+                nwk_key: dk.as_ref().map(|v| v.nwk_key),
+                app_key: dk.as_ref().map(|v| v.app_key),
+                tags: d.tags.into_hashmap().into_iter().collect(),
+                variables: d.variables.into_hashmap().into_iter().collect(),
+            });
+        }
+
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::bulk::{self, Format};
+    use crate::{storage, test};
+
+    #[test]
+    fn test_csv() {
+        let data = "dev_eui,name,device_profile_id,is_disabled,nwk_key,tag:foo,variable:bar\n\
+                    0102030405060708,dev-1,6ba7b810-9dad-11d1-80b4-00c04fd430c8,true,01020304050607080102030405060708,foo-value,bar-value\n\
+                    0102030405060709,dev-2,6ba7b810-9dad-11d1-80b4-00c04fd430c8,maybe,,,\n";
+
+        let records: Vec<Result<DeviceRecord>> = bulk::parse(Format::Csv, data.as_bytes()).unwrap();
+        assert_eq!(2, records.len());
+        assert!(records[1].is_err());
+
+        let rec = records[0].as_ref().unwrap();
+        assert_eq!(
+            &DeviceRecord {
+                dev_eui: EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
+                name: "dev-1".into(),
+                device_profile_id: Uuid::from_str("6ba7b810-9dad-11d1-80b4-00c04fd430c8").unwrap(),
+                is_disabled: true,
+                nwk_key: Some(AES128Key::from_bytes([
+                    1, 2, 3, 4, 5, 6, 7, 8, 1, 2, 3, 4, 5, 6, 7, 8
+                ])),
+                tags: [("foo".to_string(), "foo-value".to_string())]
+                    .into_iter()
+                    .collect(),
+                variables: [("bar".to_string(), "bar-value".to_string())]
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            },
+            rec
+        );
+
+        // Write and parse again.
+        let b = bulk::write(Format::Csv, &[rec.clone()]).unwrap();
+        let records: Vec<Result<DeviceRecord>> = bulk::parse(Format::Csv, &b).unwrap();
+        assert_eq!(rec, records[0].as_ref().unwrap());
+    }
+
+    #[test]
+    fn test_json() {
+        let data =
+            r#"[{"devEui": "0102030405060708"}, {"dev_eui": "0102030405060708", "name": "dev-1"}]"#;
+        let records: Vec<Result<DeviceRecord>> =
+            bulk::parse(Format::Json, data.as_bytes()).unwrap();
+        assert_eq!(2, records.len());
+        assert_eq!("dev-1", records[1].as_ref().unwrap().name);
+
+        assert!(bulk::parse::<DeviceRecord>(Format::Json, b"{}").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_import_export() {
+        let _guard = test::prepare().await;
+
+        let t = storage::tenant::test::create_tenant().await;
+        let app = storage::application::test::create_application(Some(t.id)).await;
+        let dp = storage::device_profile::test::create_device_profile(Some(t.id)).await;
+
+        let rec = DeviceRecord {
+            dev_eui: EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
+            name: "dev-1".into(),
+            device_profile_id: dp.id,
+            nwk_key: Some(AES128Key::from_bytes([1; 16])),
+            tags: [("foo".to_string(), "bar".to_string())]
+                .into_iter()
+                .collect(),
+            variables: [("baz".to_string(), "qux".to_string())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+
+        // Dry-run does not create the device.
+        let mut importer = Importer::new(app.id, true).await.unwrap();
+        let res = importer.import(1, Ok(rec.clone())).await;
+        assert_eq!(Action::Create, res.action);
+        assert!(device::get(&rec.dev_eui).await.is_err());
+
+        // Create.
+        let mut importer = Importer::new(app.id, false).await.unwrap();
+        let res = importer.import(1, Ok(rec.clone())).await;
+        assert_eq!(Action::Create, res.action);
+
+        // Duplicate within the same import.
+        let res = importer.import(2, Ok(rec.clone())).await;
+        assert_eq!(Action::Error, res.action);
+
+        // Update.
+        let mut importer = Importer::new(app.id, false).await.unwrap();
+        let mut rec2 = rec.clone();
+        rec2.name = "dev-1-updated".into();
+        let res = importer.import(2, Ok(rec2.clone())).await;
+        assert_eq!(Action::Update, res.action);
+
+        // Invalid device-profile.
+        let mut rec3 = rec.clone();
+        rec3.dev_eui = EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 9]);
+        rec3.device_profile_id = Uuid::new_v4();
+        let res = importer.import(3, Ok(rec3)).await;
+        assert_eq!(Action::Error, res.action);
+        assert!(res.error.contains("does not exist"));
+
+        // The AppKey is set to the default value on create.
+        rec2.app_key = Some(AES128Key::default());
+        for format in [Format::Csv, Format::Json] {
+            assert_eq!(vec![rec2.clone()], export(app.id, format).await);
+        }
+    }
+
+    async fn export(application_id: Uuid, format: Format) -> Vec<DeviceRecord> {
+        let mut exporter = Exporter::new(application_id, format).await.unwrap();
+        let mut b = Vec::new();
+        while let Some(v) = exporter.next().await.unwrap() {
+            b.extend(v);
+        }
+
+        bulk::parse(format, &b)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.unwrap())
+            .collect()
+    }
+}
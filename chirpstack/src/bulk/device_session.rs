@@ -0,0 +1,367 @@
+use std::collections::HashSet;
+use std::io::Cursor;
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose, Engine as _};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{Action, RowResult};
+use crate::backend::keywrap;
+use crate::config;
+use crate::storage::error::Error;
+use crate::storage::{device, device_session};
+use backend::KeyEnvelope;
+use chirpstack_api::{common, internal};
+use lrwn::{AES128Key, DevAddr, EUI64};
+
+/// Exported device-session.
+///
+/// The session keys are not part of the encoded device-session, these are
+/// wrapped using the KEK with the label given on export. An AppSKey which has
+/// already been wrapped by the join-server is kept as-is.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeviceSessionRecord {
+    pub dev_eui: EUI64,
+    pub keys: SessionKeys,
+    pub pending_rejoin_keys: Option<SessionKeys>,
+    // Device-session (Protobuf, base64 encoded) without the session keys.
+    pub device_session: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionKeys {
+    pub f_nwk_s_int_key: KeyEnvelope,
+    pub s_nwk_s_int_key: KeyEnvelope,
+    pub nwk_s_enc_key: KeyEnvelope,
+    pub app_s_key: Option<KeyEnvelope>,
+}
+
+impl DeviceSessionRecord {
+    /// Returns the record for the given device-session, wrapping the session
+    /// keys using the KEK with the given label. When the label is empty, the
+    /// keys are exported in plain-text.
+    pub fn new(ds: &internal::DeviceSession, kek_label: &str) -> Result<Self> {
+        let mut ds = ds.clone();
+        let keys = wrap_keys(&mut ds, kek_label)?;
+        let pending_rejoin_keys = match &mut ds.pending_rejoin_device_session {
+            Some(v) => Some(wrap_keys(v, kek_label)?),
+            None => None,
+        };
+
+        Ok(DeviceSessionRecord {
+            dev_eui: EUI64::from_slice(&ds.dev_eui)?,
+            keys,
+            pending_rejoin_keys,
+            device_session: general_purpose::STANDARD.encode(ds.encode_to_vec()),
+        })
+    }
+
+    /// Returns the device-session, unwrapping the session keys using the
+    /// configured KEKs.
+    pub fn device_session(&self) -> Result<internal::DeviceSession> {
+        let b = general_purpose::STANDARD
+            .decode(&self.device_session)
+            .context("Decode base64")?;
+        let mut ds = internal::DeviceSession::decode(&mut Cursor::new(b))
+            .context("Decode device-session")?;
+
+        if EUI64::from_slice(&ds.dev_eui)? != self.dev_eui {
+            return Err(anyhow!("dev_eui does not match the device-session DevEUI"));
+        }
+
+        unwrap_keys(&mut ds, &self.keys)?;
+        match (
+            &mut ds.pending_rejoin_device_session,
+            &self.pending_rejoin_keys,
+        ) {
+            (Some(pending_ds), Some(keys)) => unwrap_keys(pending_ds, keys)?,
+            (None, None) => {}
+            _ => {
+                return Err(anyhow!(
+                    "pending_rejoin_keys does not match the pending rejoin device-session"
+                ))
+            }
+        }
+
+        Ok(ds)
+    }
+}
+
+fn wrap_keys(ds: &mut internal::DeviceSession, kek_label: &str) -> Result<SessionKeys> {
+    let wrap = |key: &[u8]| -> Result<KeyEnvelope> {
+        keywrap::wrap(kek_label, AES128Key::from_slice(key)?)
+    };
+
+    let keys = SessionKeys {
+        f_nwk_s_int_key: wrap(&ds.f_nwk_s_int_key).context("Wrap FNwkSIntKey")?,
+        s_nwk_s_int_key: wrap(&ds.s_nwk_s_int_key).context("Wrap SNwkSIntKey")?,
+        nwk_s_enc_key: wrap(&ds.nwk_s_enc_key).context("Wrap NwkSEncKey")?,
+        app_s_key: match &ds.app_s_key {
+            Some(v) if v.kek_label.is_empty() => Some(wrap(&v.aes_key).context("Wrap AppSKey")?),
+            _ => None,
+        },
+    };
+
+    ds.f_nwk_s_int_key = vec![];
+    ds.s_nwk_s_int_key = vec![];
+    ds.nwk_s_enc_key = vec![];
+    if keys.app_s_key.is_some() {
+        ds.app_s_key = None;
+    }
+
+    Ok(keys)
+}
+
+fn unwrap_keys(ds: &mut internal::DeviceSession, keys: &SessionKeys) -> Result<()> {
+    ds.f_nwk_s_int_key = keywrap::unwrap(&keys.f_nwk_s_int_key)
+        .context("Unwrap FNwkSIntKey")?
+        .to_vec();
+    ds.s_nwk_s_int_key = keywrap::unwrap(&keys.s_nwk_s_int_key)
+        .context("Unwrap SNwkSIntKey")?
+        .to_vec();
+    ds.nwk_s_enc_key = keywrap::unwrap(&keys.nwk_s_enc_key)
+        .context("Unwrap NwkSEncKey")?
+        .to_vec();
+    if let Some(app_s_key) = &keys.app_s_key {
+        ds.app_s_key = Some(common::KeyEnvelope {
+            kek_label: "".into(),
+            aes_key: keywrap::unwrap(app_s_key)
+                .context("Unwrap AppSKey")?
+                .to_vec(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Parses the given JSON data. An error is returned when the data as a whole
+/// can't be parsed, else a result per record is returned.
+pub fn parse(data: &[u8]) -> Result<Vec<Result<DeviceSessionRecord>>> {
+    let mut out = Vec::new();
+    super::parse_json_from(data, |rec| {
+        out.push(rec);
+        true
+    })?;
+    Ok(out)
+}
+
+/// Writes the given records as JSON.
+pub fn write(records: &[DeviceSessionRecord]) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec_pretty(records)?)
+}
+
+/// Imports device-sessions. The devices must already exist.
+/// Existing device-sessions are replaced.
+pub struct Importer {
+    dry_run: bool,
+    // Imported DevEUIs, to detect duplicates.
+    seen: HashSet<EUI64>,
+}
+
+impl Importer {
+    pub fn new(dry_run: bool) -> Self {
+        Importer {
+            dry_run,
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Imports the given record. When the importer is in dry-run mode, the
+    /// record is validated, but nothing is written to the database.
+    pub async fn import(&mut self, row: usize, rec: Result<DeviceSessionRecord>) -> RowResult {
+        let rec = match rec {
+            Ok(v) => v,
+            Err(e) => return RowResult::error(row, "".into(), e),
+        };
+
+        match self.import_record(&rec).await {
+            Ok(action) => RowResult {
+                row,
+                id: rec.dev_eui.to_string(),
+                action,
+                error: "".into(),
+            },
+            Err(e) => RowResult::error(row, rec.dev_eui.to_string(), e),
+        }
+    }
+
+    async fn import_record(&mut self, rec: &DeviceSessionRecord) -> Result<Action> {
+        if !self.seen.insert(rec.dev_eui) {
+            return Err(anyhow!("Duplicate DevEUI"));
+        }
+
+        let ds = rec.device_session()?;
+
+        match device::get(&rec.dev_eui).await {
+            Ok(_) => {}
+            Err(Error::NotFound(_)) => return Err(anyhow!("Device does not exist")),
+            Err(e) => return Err(e.into()),
+        }
+
+        let action = match device_session::get(&rec.dev_eui).await {
+            Ok(_) => Action::Update,
+            Err(Error::NotFound(_)) => Action::Create,
+            Err(e) => return Err(e.into()),
+        };
+
+        if self.dry_run {
+            return Ok(action);
+        }
+
+        device_session::save(&ds).await?;
+        device::set_dev_addr(rec.dev_eui, DevAddr::from_slice(&ds.dev_addr)?).await?;
+
+        Ok(action)
+    }
+}
+
+/// Validates that the given KEK label is set and configured. Session keys must not be
+/// exported in plain-text.
+pub fn validate_kek_label(kek_label: &str) -> Result<()> {
+    if kek_label.is_empty() {
+        return Err(anyhow!("kek_label must be set"));
+    }
+
+    let conf = config::get();
+    if !conf.keks.iter().any(|k| k.label == kek_label) {
+        return Err(anyhow!("KEK label {} does not exist", kek_label));
+    }
+
+    Ok(())
+}
+
+/// Returns the device-sessions of the given applications and devices.
+/// Devices without device-session (not activated) are skipped.
+pub async fn export(
+    application_ids: &[Uuid],
+    dev_euis: &[EUI64],
+    kek_label: &str,
+) -> Result<Vec<DeviceSessionRecord>> {
+    validate_kek_label(kek_label)?;
+
+    let mut all_dev_euis: Vec<EUI64> = dev_euis.to_vec();
+
+    for application_id in application_ids {
+        let filters = device::Filters {
+            application_id: Some(*application_id),
+            ..Default::default()
+        };
+
+        let mut offset = 0;
+        loop {
+            let items = device::list(1000, offset, &filters).await?;
+            if items.is_empty() {
+                break;
+            }
+
+            offset += items.len() as i64;
+            all_dev_euis.extend(items.iter().map(|d| d.dev_eui));
+        }
+    }
+
+    let mut seen: HashSet<EUI64> = HashSet::new();
+    let mut out: Vec<DeviceSessionRecord> = Vec::new();
+    for dev_eui in &all_dev_euis {
+        if !seen.insert(*dev_eui) {
+            continue;
+        }
+
+        let ds = match device_session::get(dev_eui).await {
+            Ok(v) => v,
+            Err(Error::NotFound(_)) => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        out.push(
+            DeviceSessionRecord::new(&ds, kek_label)
+                .context(format!("Export device-session, dev_eui: {}", dev_eui))?,
+        );
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::{storage, test};
+
+    #[tokio::test]
+    async fn test_import_export() {
+        let _guard = test::prepare().await;
+
+        let mut conf = (*config::get()).clone();
+        conf.keks.push(config::Kek {
+            label: "kek-1".into(),
+            kek: AES128Key::from_bytes([1; 16]),
+        });
+        config::set(conf);
+
+        let dp = storage::device_profile::test::create_device_profile(None).await;
+        let d = storage::device::test::create_device(
+            EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
+            dp.id,
+            None,
+        )
+        .await;
+
+        let ds = internal::DeviceSession {
+            dev_eui: d.dev_eui.to_vec(),
+            dev_addr: vec![1, 2, 3, 4],
+            f_nwk_s_int_key: vec![2; 16],
+            s_nwk_s_int_key: vec![3; 16],
+            nwk_s_enc_key: vec![4; 16],
+            app_s_key: Some(common::KeyEnvelope {
+                kek_label: "".into(),
+                aes_key: vec![5; 16],
+            }),
+            f_cnt_up: 10,
+            ..Default::default()
+        };
+        device_session::save(&ds).await.unwrap();
+
+        // Export.
+        let records = export(&[d.application_id], &[], "kek-1").await.unwrap();
+        assert_eq!(1, records.len());
+        assert_eq!("kek-1", records[0].keys.f_nwk_s_int_key.kek_label);
+        assert_ne!(vec![2; 16], records[0].keys.f_nwk_s_int_key.aes_key);
+
+        // Unknown or missing KEK.
+        assert!(export(&[d.application_id], &[], "kek-2").await.is_err());
+        assert!(export(&[d.application_id], &[], "").await.is_err());
+
+        // Serialization.
+        let b = write(&records).unwrap();
+        let parsed = parse(&b).unwrap();
+        assert_eq!(&records[0], parsed[0].as_ref().unwrap());
+
+        // Import (dry-run).
+        device_session::delete(&d.dev_eui).await.unwrap();
+        let mut importer = Importer::new(true);
+        let res = importer.import(1, Ok(records[0].clone())).await;
+        assert_eq!(Action::Create, res.action);
+        assert!(device_session::get(&d.dev_eui).await.is_err());
+
+        // Import.
+        let mut importer = Importer::new(false);
+        let res = importer.import(1, Ok(records[0].clone())).await;
+        assert_eq!(Action::Create, res.action);
+        assert_eq!(ds, device_session::get(&d.dev_eui).await.unwrap());
+        assert_eq!(
+            Some(DevAddr::from_be_bytes([1, 2, 3, 4])),
+            device::get(&d.dev_eui).await.unwrap().dev_addr
+        );
+
+        // Duplicate.
+        let res = importer.import(2, Ok(records[0].clone())).await;
+        assert_eq!(Action::Error, res.action);
+
+        // Device does not exist.
+        device::delete(&d.dev_eui).await.unwrap();
+        let mut importer = Importer::new(false);
+        let res = importer.import(1, Ok(records[0].clone())).await;
+        assert_eq!(Action::Error, res.action);
+    }
+}
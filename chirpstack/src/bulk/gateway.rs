@@ -0,0 +1,367 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{Action, Format, Record, RowResult, Writer, EXPORT_PAGE_SIZE, TAG_PREFIX};
+use crate::storage::error::Error;
+use crate::storage::{fields, gateway, tenant};
+use lrwn::EUI64;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct GatewayRecord {
+    pub gateway_id: EUI64,
+    pub name: String,
+    pub description: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: f32,
+    pub stats_interval_secs: u32,
+    pub tags: BTreeMap<String, String>,
+}
+
+impl Default for GatewayRecord {
+    fn default() -> Self {
+        GatewayRecord {
+            gateway_id: EUI64::default(),
+            name: "".into(),
+            description: "".into(),
+            latitude: 0.0,
+            longitude: 0.0,
+            altitude: 0.0,
+            stats_interval_secs: 30,
+            tags: BTreeMap::new(),
+        }
+    }
+}
+
+impl Record for GatewayRecord {
+    fn csv_columns() -> &'static [&'static str] {
+        &[
+            "gateway_id",
+            "name",
+            "description",
+            "latitude",
+            "longitude",
+            "altitude",
+            "stats_interval_secs",
+        ]
+    }
+
+    fn from_csv_row(row: &HashMap<String, String>) -> Result<Self> {
+        fn parse_or<T: FromStr>(row: &HashMap<String, String>, column: &str, def: T) -> Result<T>
+        where
+            T::Err: std::error::Error + Send + Sync + 'static,
+        {
+            let v = super::get_column(row, column);
+            if v.is_empty() {
+                Ok(def)
+            } else {
+                v.parse().context(format!("Parse {}", column))
+            }
+        }
+
+        let def = GatewayRecord::default();
+
+        Ok(GatewayRecord {
+            gateway_id: EUI64::from_str(&super::get_column(row, "gateway_id"))
+                .context("Parse gateway_id")?,
+            name: super::get_column(row, "name"),
+            description: super::get_column(row, "description"),
+            latitude: parse_or(row, "latitude", def.latitude)?,
+            longitude: parse_or(row, "longitude", def.longitude)?,
+            altitude: parse_or(row, "altitude", def.altitude)?,
+            stats_interval_secs: parse_or(row, "stats_interval_secs", def.stats_interval_secs)?,
+            tags: super::get_prefixed_columns(row, TAG_PREFIX),
+        })
+    }
+
+    fn to_csv_row(&self) -> HashMap<String, String> {
+        let mut row: HashMap<String, String> = [
+            ("gateway_id", self.gateway_id.to_string()),
+            ("name", self.name.clone()),
+            ("description", self.description.clone()),
+            ("latitude", self.latitude.to_string()),
+            ("longitude", self.longitude.to_string()),
+            ("altitude", self.altitude.to_string()),
+            ("stats_interval_secs", self.stats_interval_secs.to_string()),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+
+        super::set_prefixed_columns(&mut row, TAG_PREFIX, &self.tags);
+
+        row
+    }
+}
+
+/// Imports gateways into a single tenant.
+/// Existing gateways (within the same tenant) are updated.
+pub struct Importer {
+    tenant: tenant::Tenant,
+    dry_run: bool,
+    // Imported Gateway IDs, to detect duplicates.
+    seen: HashSet<EUI64>,
+}
+
+impl Importer {
+    pub async fn new(tenant_id: Uuid, dry_run: bool) -> Result<Self> {
+        Ok(Importer {
+            tenant: tenant::get(&tenant_id).await?,
+            dry_run,
+            seen: HashSet::new(),
+        })
+    }
+
+    /// Imports the given record. When the importer is in dry-run mode, the
+    /// record is validated, but nothing is written to the database.
+    pub async fn import(&mut self, row: usize, rec: Result<GatewayRecord>) -> RowResult {
+        let rec = match rec {
+            Ok(v) => v,
+            Err(e) => return RowResult::error(row, "".into(), e),
+        };
+
+        match self.import_record(&rec).await {
+            Ok(action) => RowResult {
+                row,
+                id: rec.gateway_id.to_string(),
+                action,
+                error: "".into(),
+            },
+            Err(e) => RowResult::error(row, rec.gateway_id.to_string(), e),
+        }
+    }
+
+    async fn import_record(&mut self, rec: &GatewayRecord) -> Result<Action> {
+        if rec.name.is_empty() {
+            return Err(anyhow!("name is not set"));
+        }
+
+        if !self.tenant.can_have_gateways {
+            return Err(anyhow!("Tenant can not have gateways"));
+        }
+
+        if !self.seen.insert(rec.gateway_id) {
+            return Err(anyhow!("Duplicate Gateway ID"));
+        }
+
+        let (action, mut gw) = match gateway::get(&rec.gateway_id).await {
+            Ok(gw) => {
+                if gw.tenant_id != self.tenant.id {
+                    return Err(anyhow!("Gateway exists within a different tenant"));
+                }
+                (Action::Update, gw)
+            }
+            Err(Error::NotFound(_)) => (
+                Action::Create,
+                gateway::Gateway {
+                    gateway_id: rec.gateway_id,
+                    tenant_id: self.tenant.id,
+                    ..Default::default()
+                },
+            ),
+            Err(e) => return Err(e.into()),
+        };
+
+        if self.dry_run {
+            return Ok(action);
+        }
+
+        gw.name = rec.name.clone();
+        gw.description = rec.description.clone();
+        gw.latitude = rec.latitude;
+        gw.longitude = rec.longitude;
+        gw.altitude = rec.altitude;
+        gw.stats_interval_secs = rec.stats_interval_secs as i32;
+        gw.tags = fields::KeyValue::new(rec.tags.clone().into_iter().collect());
+
+        match action {
+            Action::Create => gateway::create(gw).await?,
+            _ => gateway::update(gw).await?,
+        };
+
+        Ok(action)
+    }
+}
+
+/// Exports the gateways of a single tenant. The gateways are exported page by
+/// page, such that not all gateways are kept in memory.
+pub struct Exporter {
+    tenant_id: Uuid,
+    writer: Writer,
+    offset: i64,
+    done: bool,
+}
+
+impl Exporter {
+    pub async fn new(tenant_id: Uuid, format: Format) -> Result<Self> {
+        // The CSV header must contain the tags of all gateways.
+        let columns: BTreeSet<String> = gateway::get_tag_keys(&tenant_id)
+            .await?
+            .into_iter()
+            .map(|k| format!("{}{}", TAG_PREFIX, k))
+            .collect();
+
+        Ok(Exporter {
+            tenant_id,
+            writer: Writer::new::<GatewayRecord>(format, columns),
+            offset: 0,
+            done: false,
+        })
+    }
+
+    /// Returns the next chunk of export data, or None when all gateways have
+    /// been exported.
+    pub async fn next(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let mut out = if self.offset == 0 {
+            self.writer.start()?
+        } else {
+            Vec::new()
+        };
+
+        let records = self.get_records().await?;
+        if records.is_empty() {
+            self.done = true;
+            out.extend(self.writer.finish());
+        } else {
+            out.extend(self.writer.write(&records)?);
+        }
+
+        Ok(Some(out))
+    }
+
+    /// Returns the number of exported gateways.
+    pub fn count(&self) -> usize {
+        self.writer.count()
+    }
+
+    async fn get_records(&mut self) -> Result<Vec<GatewayRecord>> {
+        let filters = gateway::Filters {
+            tenant_id: Some(self.tenant_id),
+            ..Default::default()
+        };
+
+        let items = gateway::list(EXPORT_PAGE_SIZE, self.offset, &filters).await?;
+        self.offset += items.len() as i64;
+
+        let mut out: Vec<GatewayRecord> = Vec::with_capacity(items.len());
+        for item in &items {
+            let gw = gateway::get(&item.gateway_id).await?;
+
+            out.push(GatewayRecord {
+                gateway_id: gw.gateway_id,
+                name: gw.name.clone(),
+                description: gw.description.clone(),
+                latitude: gw.latitude,
+                longitude: gw.longitude,
+                altitude: gw.altitude,
+                stats_interval_secs: gw.stats_interval_secs as u32,
+                tags: gw.tags.into_hashmap().into_iter().collect(),
+            });
+        }
+
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::bulk::{self, Format};
+    use crate::{storage, test};
+
+    #[test]
+    fn test_csv() {
+        let data = "gateway_id,name,latitude,tag:foo\n\
+                    0102030405060708,gw-1,1.5,bar\n\
+                    0102030405060709,gw-2,north,\n";
+
+        let records: Vec<Result<GatewayRecord>> =
+            bulk::parse(Format::Csv, data.as_bytes()).unwrap();
+        assert_eq!(2, records.len());
+        assert!(records[1].is_err());
+
+        let rec = records[0].as_ref().unwrap();
+        assert_eq!(
+            &GatewayRecord {
+                gateway_id: EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
+                name: "gw-1".into(),
+                latitude: 1.5,
+                tags: [("foo".to_string(), "bar".to_string())]
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            },
+            rec
+        );
+
+        let b = bulk::write(Format::Json, &[rec.clone()]).unwrap();
+        let records: Vec<Result<GatewayRecord>> = bulk::parse(Format::Json, &b).unwrap();
+        assert_eq!(rec, records[0].as_ref().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_import_export() {
+        let _guard = test::prepare().await;
+
+        let t = storage::tenant::test::create_tenant().await;
+        let rec = GatewayRecord {
+            gateway_id: EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
+            name: "gw-1".into(),
+            tags: [("foo".to_string(), "bar".to_string())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+
+        // Dry-run.
+        let mut importer = Importer::new(t.id, true).await.unwrap();
+        let res = importer.import(1, Ok(rec.clone())).await;
+        assert_eq!(Action::Create, res.action);
+        assert!(gateway::get(&rec.gateway_id).await.is_err());
+
+        // Create.
+        let mut importer = Importer::new(t.id, false).await.unwrap();
+        let res = importer.import(1, Ok(rec.clone())).await;
+        assert_eq!(Action::Create, res.action);
+
+        // Update.
+        let mut importer = Importer::new(t.id, false).await.unwrap();
+        let mut rec2 = rec.clone();
+        rec2.name = "gw-1-updated".into();
+        let res = importer.import(1, Ok(rec2.clone())).await;
+        assert_eq!(Action::Update, res.action);
+
+        // Gateway of other tenant.
+        let t2 = storage::tenant::test::create_tenant().await;
+        let mut importer = Importer::new(t2.id, false).await.unwrap();
+        let res = importer.import(1, Ok(rec.clone())).await;
+        assert_eq!(Action::Error, res.action);
+
+        for format in [Format::Csv, Format::Json] {
+            assert_eq!(vec![rec2.clone()], export(t.id, format).await);
+        }
+    }
+
+    async fn export(tenant_id: Uuid, format: Format) -> Vec<GatewayRecord> {
+        let mut exporter = Exporter::new(tenant_id, format).await.unwrap();
+        let mut b = Vec::new();
+        while let Some(v) = exporter.next().await.unwrap() {
+            b.extend(v);
+        }
+
+        bulk::parse(format, &b)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.unwrap())
+            .collect()
+    }
+}
@@ -0,0 +1,483 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::io::{self, Read};
+use std::marker::PhantomData;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::de::{self, DeserializeOwned, SeqAccess, Visitor};
+use serde::{Deserializer, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::{self, JoinHandle};
+
+pub mod device;
+pub mod device_session;
+pub mod gateway;
+
+// Column prefixes used to store the tags and variables in CSV files.
+const TAG_PREFIX: &str = "tag:";
+const VARIABLE_PREFIX: &str = "variable:";
+
+/// Max. size of a single export data chunk (API).
+pub const EXPORT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Number of records which are exported at once.
+const EXPORT_PAGE_SIZE: i64 = 250;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl Format {
+    /// Returns the format based on the extension of the given file.
+    pub fn from_path(p: &Path) -> Result<Format> {
+        match p.extension().and_then(|v| v.to_str()) {
+            Some("csv") => Ok(Format::Csv),
+            Some("json") => Ok(Format::Json),
+            _ => Err(anyhow!(
+                "Unable to detect format of {}, expected .csv or .json extension",
+                p.display()
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Create,
+    Update,
+    Error,
+}
+
+/// Import result of a single row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowResult {
+    /// Row number, starting at 1 (the CSV header is not counted).
+    pub row: usize,
+    /// ID of the record (DevEUI or Gateway ID).
+    pub id: String,
+    pub action: Action,
+    pub error: String,
+}
+
+impl RowResult {
+    fn error(row: usize, id: String, e: anyhow::Error) -> Self {
+        RowResult {
+            row,
+            id,
+            action: Action::Error,
+            error: format!("{:#}", e),
+        }
+    }
+}
+
+/// Record which can be imported and exported.
+pub trait Record: Serialize + DeserializeOwned {
+    /// The fixed CSV columns (in order). Tags and variables are stored in
+    /// the tag:<key> and variable:<key> columns.
+    fn csv_columns() -> &'static [&'static str];
+
+    /// Creates the record from the given CSV row (column name to value).
+    fn from_csv_row(row: &HashMap<String, String>) -> Result<Self>;
+
+    /// Returns the CSV row (column name to value) for the record.
+    fn to_csv_row(&self) -> HashMap<String, String>;
+}
+
+/// Parses the given data. An error is returned when the data as a whole
+/// can't be parsed, else a result per row is returned.
+pub fn parse<T: Record>(format: Format, data: &[u8]) -> Result<Vec<Result<T>>> {
+    let mut out = Vec::new();
+    parse_from(format, data, |rec| {
+        out.push(rec);
+        true
+    })?;
+    Ok(out)
+}
+
+/// Parses the data read from the given reader, calling f with the result of
+/// each row as soon as it has been parsed. An error is returned when the data
+/// as a whole can't be parsed. Parsing stops when f returns false.
+pub fn parse_from<T, R, F>(format: Format, rdr: R, mut f: F) -> Result<()>
+where
+    T: Record,
+    R: Read,
+    F: FnMut(Result<T>) -> bool,
+{
+    match format {
+        Format::Csv => {
+            let mut rdr = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(rdr);
+            let headers = rdr.headers().context("Read CSV header")?.clone();
+
+            for r in rdr.records() {
+                let rec = match r {
+                    Ok(r) => {
+                        let row: HashMap<String, String> = headers
+                            .iter()
+                            .zip(r.iter())
+                            .map(|(k, v)| (k.to_string(), v.to_string()))
+                            .collect();
+                        T::from_csv_row(&row)
+                    }
+                    // Reading the data failed, the remaining rows can't be parsed.
+                    Err(e) if matches!(e.kind(), csv::ErrorKind::Io(_)) => {
+                        return Err(e).context("Read CSV");
+                    }
+                    Err(e) => Err(e.into()),
+                };
+
+                if !f(rec) {
+                    break;
+                }
+            }
+
+            Ok(())
+        }
+        Format::Json => parse_json_from(rdr, f),
+    }
+}
+
+/// Parses the data read from the given reader within a blocking task, such
+/// that the records can be imported while the data is being read. The result
+/// of each row is sent to the returned channel. The task returns an error when
+/// the data as a whole can't be parsed.
+pub fn parse_stream<T, R>(
+    format: Format,
+    rdr: R,
+) -> (mpsc::Receiver<Result<T>>, JoinHandle<Result<()>>)
+where
+    T: Record + Send + 'static,
+    R: Read + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(1);
+    let handle =
+        task::spawn_blocking(move || parse_from(format, rdr, |rec| tx.blocking_send(rec).is_ok()));
+
+    (rx, handle)
+}
+
+/// Reader which reads the data chunks received through the given channel.
+/// This makes it possible to parse the import data while it is being received.
+pub struct ChunkReader {
+    rx: mpsc::Receiver<Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl ChunkReader {
+    pub fn new(rx: mpsc::Receiver<Result<Vec<u8>>>) -> Self {
+        ChunkReader {
+            rx,
+            chunk: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            match self.rx.blocking_recv() {
+                Some(Ok(v)) => {
+                    self.chunk = v;
+                    self.pos = 0;
+                }
+                Some(Err(e)) => {
+                    return Err(io::Error::new(io::ErrorKind::Other, format!("{:#}", e)));
+                }
+                None => return Ok(0),
+            }
+        }
+
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+// Parses the JSON array read from the given reader, calling f with the result
+// of each item. Parsing stops when f returns false.
+fn parse_json_from<T, R, F>(rdr: R, f: F) -> Result<()>
+where
+    T: DeserializeOwned,
+    R: Read,
+    F: FnMut(Result<T>) -> bool,
+{
+    let mut stopped = false;
+    let mut de = serde_json::Deserializer::from_reader(rdr);
+    let res = de.deserialize_seq(JsonArrayVisitor {
+        f,
+        stopped: &mut stopped,
+        item: PhantomData,
+    });
+
+    // The remaining data is not needed.
+    if stopped {
+        return Ok(());
+    }
+
+    res.context("Parse JSON, expected an array")?;
+    de.end().context("Parse JSON")?;
+    Ok(())
+}
+
+// Visits the items of a JSON array, without keeping the array in memory.
+struct JsonArrayVisitor<'a, T, F> {
+    f: F,
+    stopped: &'a mut bool,
+    item: PhantomData<T>,
+}
+
+impl<'de, 'a, T, F> Visitor<'de> for JsonArrayVisitor<'a, T, F>
+where
+    T: DeserializeOwned,
+    F: FnMut(Result<T>) -> bool,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(v) = seq.next_element::<serde_json::Value>()? {
+            if !(self.f)(serde_json::from_value(v).map_err(anyhow::Error::new)) {
+                *self.stopped = true;
+                return Err(de::Error::custom("parsing stopped"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes records in the given format. The records can be written in batches,
+/// such that not all records have to be kept in memory.
+pub struct Writer {
+    format: Format,
+    // CSV columns (in order).
+    columns: Vec<String>,
+    count: usize,
+}
+
+impl Writer {
+    /// Creates a new writer. As the CSV header is written first, the tag:<key>
+    /// and variable:<key> columns must be known up-front.
+    pub fn new<T: Record>(format: Format, extra_columns: BTreeSet<String>) -> Self {
+        let fixed = T::csv_columns();
+        let columns: Vec<String> = fixed
+            .iter()
+            .map(|v| v.to_string())
+            .chain(
+                extra_columns
+                    .into_iter()
+                    .filter(|c| !fixed.contains(&c.as_str())),
+            )
+            .collect();
+
+        Writer {
+            format,
+            columns,
+            count: 0,
+        }
+    }
+
+    /// Returns the start of the output.
+    pub fn start(&self) -> Result<Vec<u8>> {
+        match self.format {
+            Format::Csv => {
+                let mut wtr = csv::Writer::from_writer(Vec::new());
+                wtr.write_record(&self.columns)?;
+                Ok(wtr.into_inner()?)
+            }
+            Format::Json => Ok(b"[".to_vec()),
+        }
+    }
+
+    /// Returns the output of the given records.
+    pub fn write<T: Record>(&mut self, records: &[T]) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+
+        match self.format {
+            Format::Csv => {
+                let mut wtr = csv::WriterBuilder::new()
+                    .has_headers(false)
+                    .from_writer(&mut out);
+                for rec in records {
+                    let row = rec.to_csv_row();
+                    wtr.write_record(
+                        self.columns
+                            .iter()
+                            .map(|c| row.get(c).map(|v| v.as_str()).unwrap_or("")),
+                    )?;
+                }
+                wtr.flush()?;
+            }
+            Format::Json => {
+                for (i, rec) in records.iter().enumerate() {
+                    if self.count + i != 0 {
+                        out.push(b',');
+                    }
+                    out.push(b'\n');
+                    out.extend(serde_json::to_vec_pretty(rec)?);
+                }
+            }
+        }
+
+        self.count += records.len();
+        Ok(out)
+    }
+
+    /// Returns the end of the output.
+    pub fn finish(&self) -> Vec<u8> {
+        match self.format {
+            Format::Csv => Vec::new(),
+            Format::Json if self.count == 0 => b"]\n".to_vec(),
+            Format::Json => b"\n]\n".to_vec(),
+        }
+    }
+
+    /// Returns the number of written records.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+/// Writes the given records in the given format.
+pub fn write<T: Record>(format: Format, records: &[T]) -> Result<Vec<u8>> {
+    // The tag and variable columns depend on the exported records.
+    let extra: BTreeSet<String> = records
+        .iter()
+        .flat_map(|r| r.to_csv_row().into_keys())
+        .collect();
+
+    let mut wtr = Writer::new::<T>(format, extra);
+    let mut out = wtr.start()?;
+    out.extend(wtr.write(records)?);
+    out.extend(wtr.finish());
+    Ok(out)
+}
+
+fn get_column(row: &HashMap<String, String>, column: &str) -> String {
+    row.get(column).cloned().unwrap_or_default()
+}
+
+fn get_bool_column(row: &HashMap<String, String>, column: &str) -> Result<bool> {
+    match get_column(row, column).to_lowercase().as_str() {
+        "" | "false" | "0" => Ok(false),
+        "true" | "1" => Ok(true),
+        v => Err(anyhow!("{}: invalid boolean value: {}", column, v)),
+    }
+}
+
+fn get_prefixed_columns(row: &HashMap<String, String>, prefix: &str) -> BTreeMap<String, String> {
+    row.iter()
+        .filter(|(k, v)| k.starts_with(prefix) && !v.is_empty())
+        .map(|(k, v)| (k[prefix.len()..].to_string(), v.clone()))
+        .collect()
+}
+
+fn set_prefixed_columns(
+    row: &mut HashMap<String, String>,
+    prefix: &str,
+    values: &BTreeMap<String, String>,
+) {
+    for (k, v) in values {
+        row.insert(format!("{}{}", prefix, k), v.clone());
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::bulk::gateway::GatewayRecord;
+    use lrwn::EUI64;
+
+    #[tokio::test]
+    async fn test_parse_stream() {
+        let tests = [
+            (
+                Format::Csv,
+                "gateway_id,name\n0102030405060708,gw-1\n0102030405060709,gw-2\n",
+            ),
+            (
+                Format::Json,
+                r#"[{"gateway_id": "0102030405060708", "name": "gw-1"}, {"gateway_id": "0102030405060709", "name": "gw-2"}]"#,
+            ),
+        ];
+
+        for (format, data) in tests {
+            // Send the data in small chunks, such that records span multiple chunks.
+            let (chunk_tx, chunk_rx) = mpsc::channel(1);
+            tokio::spawn(async move {
+                for c in data.as_bytes().chunks(7) {
+                    chunk_tx.send(Ok(c.to_vec())).await.unwrap();
+                }
+            });
+
+            let (mut records, parser) =
+                parse_stream::<GatewayRecord, _>(format, ChunkReader::new(chunk_rx));
+            let mut names = Vec::new();
+            while let Some(rec) = records.recv().await {
+                names.push(rec.unwrap().name);
+            }
+            parser.await.unwrap().unwrap();
+            assert_eq!(vec!["gw-1", "gw-2"], names);
+
+            // Stop after the first record.
+            let mut count = 0;
+            parse_from::<GatewayRecord, _, _>(format, data.as_bytes(), |_| {
+                count += 1;
+                false
+            })
+            .unwrap();
+            assert_eq!(1, count);
+        }
+
+        // Receiving the data failed.
+        let (chunk_tx, chunk_rx) = mpsc::channel(1);
+        chunk_tx.send(Err(anyhow!("stream error"))).await.unwrap();
+        drop(chunk_tx);
+
+        let (mut records, parser) =
+            parse_stream::<GatewayRecord, _>(Format::Json, ChunkReader::new(chunk_rx));
+        assert!(records.recv().await.is_none());
+        assert!(parser.await.unwrap().is_err());
+    }
+
+    #[test]
+    fn test_writer() {
+        let records: Vec<GatewayRecord> = (1..=3u8)
+            .map(|i| GatewayRecord {
+                gateway_id: EUI64::from_be_bytes([i; 8]),
+                name: format!("gw-{}", i),
+                tags: [("foo".to_string(), i.to_string())].into_iter().collect(),
+                ..Default::default()
+            })
+            .collect();
+
+        // Writing the records in batches results in the same output.
+        for format in [Format::Csv, Format::Json] {
+            let mut wtr =
+                Writer::new::<GatewayRecord>(format, ["tag:foo".to_string()].into_iter().collect());
+            let mut b = wtr.start().unwrap();
+            b.extend(wtr.write(&records[..1]).unwrap());
+            b.extend(wtr.write(&records[1..]).unwrap());
+            b.extend(wtr.finish());
+
+            assert_eq!(3, wtr.count());
+            assert_eq!(write(format, &records).unwrap(), b);
+        }
+
+        // Without records, the output is still a valid JSON array.
+        let wtr = Writer::new::<GatewayRecord>(Format::Json, BTreeSet::new());
+        let mut b = wtr.start().unwrap();
+        b.extend(wtr.finish());
+        assert!(parse::<GatewayRecord>(Format::Json, &b).unwrap().is_empty());
+    }
+}
@@ -79,6 +79,19 @@ async fn generate_client_certificate(
     Ok((cert, key_pair))
 }
 
+// This returns the common-name of the given DER encoded (client-)certificate. This is
+// the ID for which the certificate has been generated (e.g. the gateway ID).
+pub fn common_name_from_der(der: &[u8]) -> Result<String> {
+    let cert = X509::from_der(der).context("Parse certificate")?;
+    let cn = cert
+        .subject_name()
+        .entries_by_nid(Nid::COMMONNAME)
+        .next()
+        .ok_or_else(|| anyhow!("Certificate does not contain a common-name"))?;
+
+    Ok(cn.data().as_utf8()?.to_string())
+}
+
 // This returns the CA, certificate and private-key as PEM encoded strings.
 pub async fn client_cert_for_gateway_id(
     gateway_id: &EUI64,
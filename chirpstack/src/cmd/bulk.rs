@@ -0,0 +1,131 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use uuid::Uuid;
+
+use crate::bulk::{self, device, device_session, gateway, Action, RowResult};
+use crate::storage;
+use lrwn::EUI64;
+
+pub async fn import_devices(application_id: &Uuid, file: &Path, dry_run: bool) -> Result<()> {
+    storage::setup().await.context("Setup storage")?;
+
+    let format = bulk::Format::from_path(file)?;
+    let f = fs::File::open(file).context("Open file")?;
+    let mut importer = device::Importer::new(*application_id, dry_run).await?;
+
+    // The records are imported while the file is being parsed.
+    let (mut records, parser) = bulk::parse_stream::<device::DeviceRecord, _>(format, f);
+    let mut results = Vec::new();
+    while let Some(rec) = records.recv().await {
+        results.push(importer.import(results.len() + 1, rec).await);
+    }
+    parser.await??;
+
+    print_results(&results, dry_run)
+}
+
+pub async fn export_devices(application_id: &Uuid, file: &Path) -> Result<()> {
+    storage::setup().await.context("Setup storage")?;
+
+    let mut exporter =
+        device::Exporter::new(*application_id, bulk::Format::from_path(file)?).await?;
+    let mut f = fs::File::create(file).context("Create file")?;
+    while let Some(b) = exporter.next().await? {
+        f.write_all(&b).context("Write file")?;
+    }
+    println!("{} devices exported", exporter.count());
+
+    Ok(())
+}
+
+pub async fn import_gateways(tenant_id: &Uuid, file: &Path, dry_run: bool) -> Result<()> {
+    storage::setup().await.context("Setup storage")?;
+
+    let format = bulk::Format::from_path(file)?;
+    let f = fs::File::open(file).context("Open file")?;
+    let mut importer = gateway::Importer::new(*tenant_id, dry_run).await?;
+
+    // The records are imported while the file is being parsed.
+    let (mut records, parser) = bulk::parse_stream::<gateway::GatewayRecord, _>(format, f);
+    let mut results = Vec::new();
+    while let Some(rec) = records.recv().await {
+        results.push(importer.import(results.len() + 1, rec).await);
+    }
+    parser.await??;
+
+    print_results(&results, dry_run)
+}
+
+pub async fn export_gateways(tenant_id: &Uuid, file: &Path) -> Result<()> {
+    storage::setup().await.context("Setup storage")?;
+
+    let mut exporter = gateway::Exporter::new(*tenant_id, bulk::Format::from_path(file)?).await?;
+    let mut f = fs::File::create(file).context("Create file")?;
+    while let Some(b) = exporter.next().await? {
+        f.write_all(&b).context("Write file")?;
+    }
+    println!("{} gateways exported", exporter.count());
+
+    Ok(())
+}
+
+pub async fn import_device_sessions(file: &Path, dry_run: bool) -> Result<()> {
+    storage::setup().await.context("Setup storage")?;
+
+    let data = fs::read(file).context("Read file")?;
+    let records = device_session::parse(&data)?;
+    let mut importer = device_session::Importer::new(dry_run);
+
+    let mut results = Vec::new();
+    for (i, rec) in records.into_iter().enumerate() {
+        results.push(importer.import(i + 1, rec).await);
+    }
+
+    print_results(&results, dry_run)
+}
+
+pub async fn export_device_sessions(
+    application_ids: &[Uuid],
+    dev_euis: &[EUI64],
+    kek_label: &str,
+    file: &Path,
+) -> Result<()> {
+    storage::setup().await.context("Setup storage")?;
+
+    let records = device_session::export(application_ids, dev_euis, kek_label).await?;
+    fs::write(file, device_session::write(&records)?).context("Write file")?;
+    println!("{} device-sessions exported", records.len());
+
+    Ok(())
+}
+
+fn print_results(results: &[RowResult], dry_run: bool) -> Result<()> {
+    let mut errors = 0;
+
+    for res in results {
+        match res.action {
+            Action::Create => println!("row {}: {}: create", res.row, res.id),
+            Action::Update => println!("row {}: {}: update", res.row, res.id),
+            Action::Error => {
+                errors += 1;
+                println!("row {}: {}: error: {}", res.row, res.id, res.error);
+            }
+        }
+    }
+
+    println!(
+        "{} rows processed, {} errors{}",
+        results.len(),
+        errors,
+        if dry_run { " (dry-run)" } else { "" }
+    );
+
+    if errors != 0 {
+        return Err(anyhow!("Import contains {} errors", errors));
+    }
+
+    Ok(())
+}
@@ -127,8 +127,10 @@ pub fn run() {
   # This makes it possible to configure one or multiple sub-ranges within
   # the configured NetID. If left blank, then the complete DevAddr space
   # provided by the configured NetID will be used.
-  # If multiple prefixes are configured, a random prefix will be chosen when
-  # generating a DevAddr.
+  # If multiple prefixes are configured, the DevAddr of a device (OTAA) is
+  # allocated within the least utilized prefix. DevAddrs which are already in
+  # use by other devices are avoided. The prefix utilization is exposed by the
+  # monitoring endpoint.
   #
   # Example configuration:
   # dev_addr_prefixes=["0000ff00/24"]
@@ -158,6 +160,24 @@ pub fn run() {
   # after no activity.
   device_session_ttl="{{ network.device_session_ttl }}"
 
+  # Persist device-sessions in PostgreSQL.
+  #
+  # When enabled, device-sessions are stored in PostgreSQL and Redis is used
+  # as cache. Device-sessions that are not found in Redis (e.g. after a Redis
+  # flush or eviction) are restored from PostgreSQL. This also enables listing
+  # and filtering device-sessions. Note that existing device-sessions are
+  # persisted on the next device activity.
+  device_session_persistence={{ network.device_session_persistence }}
+
+  # Frame-counter rollback threshold.
+  #
+  # Uplinks with a frame-counter lower than expected are rejected. When the
+  # frame-counter of such an uplink is above this threshold, it is not
+  # considered a device reset (e.g. battery swap), but a suspicious rollback
+  # (e.g. replay attempt) and an error log event is sent. This is also the
+  # default max. frame-counter when arming a frame-counter reset window.
+  f_cnt_rollback_threshold={{ network.f_cnt_rollback_threshold }}
+
   # Time to wait for uplink de-duplication.
   #
   # This is the time that ChirpStack will wait for other gateways to receive
@@ -180,6 +200,38 @@ pub fn run() {
   # Mac-commands disabled.
   mac_commands_disabled={{ network.mac_commands_disabled }}
 
+  # Custom ADR plugins.
+  #
+  # The custom ADR plugin must be implemented in JavaScript. For an example
+  # skeleton, please see:
+  # https://github.com/chirpstack/chirpstack/blob/master/examples/adr_plugins/plugin_skeleton.js
+  adr_plugins=[
+    {{#each network.adr_plugins}}
+    "{{this}}",
+    {{/each}}
+  ]
+
+
+  # ADR plugin runtime settings.
+  [network.adr_plugin_runtime]
+
+    # Pool size.
+    #
+    # Each ADR plugin keeps this number of runtimes (with the pre-compiled
+    # plugin script), which can handle ADR requests in parallel.
+    pool_size={{ network.adr_plugin_runtime.pool_size }}
+
+    # Maximum execution time.
+    #
+    # The ADR plugin is interrupted when it exceeds this execution time. In
+    # this case the current TX parameters of the device are kept.
+    max_execution_time="{{ network.adr_plugin_runtime.max_execution_time }}"
+
+    # Maximum memory (bytes).
+    #
+    # The maximum memory that can be allocated by a single runtime.
+    max_memory={{ network.adr_plugin_runtime.max_memory }}
+
 
   # Scheduler settings.
   [network.scheduler]
@@ -227,6 +279,7 @@ pub fn run() {
   #
   # /health  - Returns 200 in case the healthchecks have passed.
   # /metrics - Returns metrics which can be scraped by Prometheus.
+  # /dev-addr-prefixes - Returns the DevAddr prefix utilization (JSON).
   #
   # If not set, this endpoint will be disabled.
   bind="{{ monitoring.bind }}"
@@ -436,6 +489,34 @@ pub fn run() {
     # Use JSON encoding instead of Protobuf (binary).
     json={{ integration.amqp.json }}
 
+    # Command queue.
+    #
+    # When set, ChirpStack declares this (durable) queue, binds it to the
+    # amq.topic exchange using the command routing-key and consumes the
+    # downlink commands from it. Leave this empty to disable consuming
+    # commands.
+    command_queue="{{ integration.amqp.command_queue }}"
+
+    # Command routing key.
+    #
+    # Commands must be published to the amq.topic exchange using this
+    # routing-key. Currently only the 'down' command is supported. The
+    # payload must use the same encoding as events (see json option).
+    command_routing_key="{{ integration.amqp.command_routing_key }}"
+
+    # Max. number of attempts for handling a command.
+    #
+    # Commands which fail because of a transient error (e.g. the database is
+    # not available) are retried up to this number of attempts.
+    command_max_attempts={{ integration.amqp.command_max_attempts }}
+
+    # Command dead-letter queue.
+    #
+    # When set, commands which could not be handled within the max. number of
+    # attempts are published to this (durable) queue, with the error in the
+    # 'error' header. Leave this empty to drop these commands.
+    command_dead_letter_queue="{{ integration.amqp.command_dead_letter_queue }}"
+
 
   # Kafka integration configuration.
   [integration.kafka]
@@ -480,6 +561,64 @@ pub fn run() {
     # Use JSON encoding instead of Protobuf (binary).
     json={{ integration.kafka.json }}
 
+    # Topic for commands.
+    #
+    # When set, ChirpStack consumes the downlink commands from this topic.
+    # Leave this empty to disable consuming commands.
+    command_topic="{{ integration.kafka.command_topic }}"
+
+    # Template for keys of command messages.
+    #
+    # The key of each command message must match this template. Currently
+    # only the 'down' command is supported. The payload must use the same
+    # encoding as events (see json option).
+    command_key="{{ integration.kafka.command_key }}"
+
+    # Max. number of attempts for handling a command.
+    #
+    # Commands which fail because of a transient error (e.g. the database is
+    # not available) are retried up to this number of attempts.
+    command_max_attempts={{ integration.kafka.command_max_attempts }}
+
+    # Command dead-letter topic.
+    #
+    # When set, commands which could not be handled within the max. number of
+    # attempts are published to this topic, with the error in the 'error'
+    # header. Leave this empty to drop these commands.
+    command_dead_letter_topic="{{ integration.kafka.command_dead_letter_topic }}"
+
+    # Consumer group ID used for consuming commands.
+    consumer_group="{{ integration.kafka.consumer_group }}"
+
+  # Delivery configuration for application and global integrations.
+  #
+  # Events are stored before these are sent to the integrations. Events which
+  # could not be delivered (e.g. because the HTTP endpoint or the MQTT broker
+  # is unavailable) are retried with an exponential backoff. After
+  # max_attempts, the delivery is moved to the dead-letter storage, from where
+  # it can be replayed using the API.
+  [integration.delivery]
+
+    # Max. number of delivery attempts.
+    max_attempts={{ integration.delivery.max_attempts }}
+
+    # Backoff after the first failed delivery attempt.
+    #
+    # The backoff is doubled for every subsequent attempt.
+    min_backoff="{{ integration.delivery.min_backoff }}"
+
+    # Max. backoff between delivery attempts.
+    max_backoff="{{ integration.delivery.max_backoff }}"
+
+    # Interval in which pending deliveries are retried.
+    interval="{{ integration.delivery.interval }}"
+
+    # Max. number of deliveries to retry in a single interval.
+    batch_size={{ integration.delivery.batch_size }}
+
+    # Time after which dead-letter deliveries are removed.
+    dead_letter_ttl="{{ integration.delivery.dead_letter_ttl }}"
+
 
 # Codec configuration.
 [codec]
@@ -599,14 +738,131 @@ pub fn run() {
       tls_key="{{ this.tls_key }}"
     {{/each}}
 
+    # Network Servers allowed to use ChirpStack as Join Server (this can be
+    # repeated).
+    #
+    # ChirpStack will handle the JoinReq and RejoinReq messages received
+    # through the Backend Interfaces API from these Network Servers,
+    # using the device-keys stored in the database. Note that this requires
+    # the Backend Interfaces API to be enabled.
+    #
+    # Example:
+    # [[join_server.network_servers]]
+    #
+    #   # NetID of the Network Server.
+    #   net_id="010203"
+    #
+    #   # Server endpoint.
+    #   #
+    #   # This is only used to send the answers when the async interface
+    #   # is used.
+    #   server="https://example.com:1234"
+    #
+    #   # Async timeout (set to 0 to disable async interface).
+    #   async_timeout="0s"
+    #
+    #   # CA certificate (path).
+    #   ca_cert=""
+    #
+    #   # TLS certificate (path).
+    #   tls_cert=""
+    #
+    #   # TLS key (path).
+    #   tls_key=""
+    #
+    #   # Authorization header.
+    #   #
+    #   # Optional value of the Authorization header, e.g. token or password.
+    #   authorization_header=""
+    #
+    #   # KEK label (optional).
+    #   #
+    #   # If set, the network session-keys will be encrypted using the given
+    #   # KEK.
+    #   kek_label=""
+    #
+    #   # AppSKey KEK label (optional).
+    #   #
+    #   # If set, the AppSKey will be encrypted using the given KEK.
+    #   app_s_key_kek_label=""
+    {{#each join_server.network_servers}}
+
+    [[join_server.network_servers]]
+      net_id="{{ this.net_id }}"
+      server="{{ this.server }}"
+      async_timeout="{{ this.async_timeout }}"
+      ca_cert="{{ this.ca_cert }}"
+      tls_cert="{{ this.tls_cert }}"
+      tls_key="{{ this.tls_key }}"
+      authorization_header="{{ this.authorization_header }}"
+      kek_label="{{ this.kek_label }}"
+      app_s_key_kek_label="{{ this.app_s_key_kek_label }}"
+    {{/each}}
+
+    # Application Servers allowed to request the AppSKey from ChirpStack as
+    # Join Server (this can be repeated).
+    #
+    # ChirpStack will handle the AppSKeyReq messages received through the
+    # Backend Interfaces API from these Application Servers. An Application
+    # Server can only request the AppSKey of sessions that have been
+    # established by ChirpStack for the NetID of the Application Server.
+    #
+    # Example:
+    # [[join_server.application_servers]]
+    #
+    #   # NetID of the Network Server for which the Application Server handles
+    #   # the application payloads. This must be used as SenderID.
+    #   net_id="010203"
+    #
+    #   # Server endpoint.
+    #   #
+    #   # This is only used to send the answers when the async interface
+    #   # is used.
+    #   server="https://example.com:1234"
+    #
+    #   # Async timeout (set to 0 to disable async interface).
+    #   async_timeout="0s"
+    #
+    #   # CA certificate (path).
+    #   ca_cert=""
+    #
+    #   # TLS certificate (path).
+    #   tls_cert=""
+    #
+    #   # TLS key (path).
+    #   tls_key=""
+    #
+    #   # Authorization header.
+    #   #
+    #   # Optional value of the Authorization header, e.g. token or password.
+    #   authorization_header=""
+    #
+    #   # AppSKey KEK label (optional).
+    #   #
+    #   # If set, the AppSKey will be encrypted using the given KEK.
+    #   app_s_key_kek_label=""
+    {{#each join_server.application_servers}}
+
+    [[join_server.application_servers]]
+      net_id="{{ this.net_id }}"
+      server="{{ this.server }}"
+      async_timeout="{{ this.async_timeout }}"
+      ca_cert="{{ this.ca_cert }}"
+      tls_cert="{{ this.tls_cert }}"
+      tls_key="{{ this.tls_key }}"
+      authorization_header="{{ this.authorization_header }}"
+      app_s_key_kek_label="{{ this.app_s_key_kek_label }}"
+    {{/each}}
+
 
 # Backend Interfaces configuration (optional).
 [backend_interfaces]
 
   # interface:port to bind the Backend Interfaces API to.
   #
-  # Note: this interface is used both for passive-roaming and when
-  # integrating with Join Servers that implement the async interface.
+  # Note: this interface is used for passive-roaming, when integrating
+  # with Join Servers that implement the async interface and when
+  # ChirpStack is used as Join Server by other Network Servers.
   # Leaving this option blank will disable the Backend Interfaces API,
   # which is fine in most cases.
   bind="{{ backend_interfaces.bind }}"
@@ -294,7 +294,7 @@ async fn import_device(
     Ok(())
 }
 
-fn truncate(s: &str, max_chars: usize) -> &str {
+pub fn truncate(s: &str, max_chars: usize) -> &str {
     match s.char_indices().nth(max_chars) {
         None => s,
         Some((idx, _)) => &s[..idx],
@@ -0,0 +1,527 @@
+use std::collections::HashMap;
+use std::fs::{read_to_string, File};
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use super::import_legacy_lorawan_devices_repository::truncate;
+use crate::codec::{self, Codec};
+use crate::storage::{self, device_profile_template};
+use lrwn::region::{CommonName, MacVersion, Revision};
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Vendors {
+    pub vendors: Vec<Vendor>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Vendor {
+    pub id: String,
+    pub name: String,
+    pub draft: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Devices {
+    #[serde(rename = "endDevices")]
+    pub end_devices: Vec<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Device {
+    pub name: String,
+    pub description: String,
+    pub draft: bool,
+    #[serde(rename = "firmwareVersions")]
+    pub firmware_versions: Vec<FirmwareVersion>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct FirmwareVersion {
+    pub version: String,
+    pub profiles: HashMap<String, ProfileMeta>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ProfileMeta {
+    #[serde(rename = "vendorID")]
+    pub vendor_id: String,
+    pub id: String,
+    pub codec: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Profile {
+    #[serde(rename = "macVersion")]
+    pub mac_version: String,
+    #[serde(rename = "regionalParametersVersion")]
+    pub region_parameters_version: String,
+    #[serde(rename = "supportsJoin")]
+    pub supports_join: bool,
+    #[serde(rename = "rx1Delay")]
+    pub rx1_delay: u8,
+    #[serde(rename = "rx1DataRateOffset")]
+    pub rx1_data_rate_offset: u8,
+    #[serde(rename = "rx2DataRateIndex")]
+    pub rx2_data_rate_index: u8,
+    #[serde(rename = "rx2Frequency")]
+    pub rx2_frequency: f64,
+    #[serde(rename = "supportsClassB")]
+    pub supports_class_b: bool,
+    #[serde(rename = "classBTimeout")]
+    pub class_b_timeout: usize, // seconds
+    #[serde(rename = "pingSlotPeriod")]
+    pub ping_slot_period: usize,
+    #[serde(rename = "pingSlotDataRateIndex")]
+    pub ping_slot_data_rate_index: u8,
+    #[serde(rename = "pingSlotFrequency")]
+    pub ping_slot_frequency: f64,
+    #[serde(rename = "supportsClassC")]
+    pub supports_class_c: bool,
+    #[serde(rename = "classCTimeout")]
+    pub class_c_timeout: usize,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct JsCodec {
+    #[serde(rename = "uplinkDecoder")]
+    pub uplink_decoder: Option<CodecFunction>,
+    #[serde(rename = "downlinkEncoder")]
+    pub downlink_encoder: Option<CodecFunction>,
+    #[serde(rename = "downlinkDecoder")]
+    pub downlink_decoder: Option<CodecFunction>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct CodecFunction {
+    #[serde(rename = "fileName")]
+    pub filename: String,
+    pub examples: Vec<CodecExample>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct CodecExample {
+    pub description: String,
+    pub input: CodecExampleData,
+    pub output: CodecExampleData,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct CodecExampleData {
+    #[serde(rename = "fPort")]
+    pub f_port: Option<u8>,
+    pub bytes: Vec<u8>,
+    pub data: Option<serde_json::Value>,
+    pub errors: Vec<String>,
+}
+
+pub async fn run(dir: &Path) -> Result<()> {
+    storage::setup().await?;
+
+    let vendor_index_yml = dir.join("vendor").join("index.yaml");
+    info!(path = ?vendor_index_yml, "Reading vendor index file");
+
+    let vendors: Vendors = serde_yaml::from_reader(File::open(&vendor_index_yml)?)?;
+    for vendor in &vendors.vendors {
+        if vendor.id == "example" || vendor.draft {
+            continue;
+        }
+
+        info!(vendor_id = %vendor.id, vendor_name = %vendor.name, "Found vendor");
+        let vendor_dir = dir.join("vendor").join(&vendor.id);
+
+        let devices_index_yml = vendor_dir.join("index.yaml");
+        info!(path = ?devices_index_yml, "Reading devices index file");
+        let devices: Devices = serde_yaml::from_reader(match File::open(&devices_index_yml) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!(path = ?devices_index_yml, error = %e, "Failed opening index.yaml within vendor folder, it might not have any devices");
+                continue;
+            }
+        })?;
+
+        for device_id in &devices.end_devices {
+            let device_yml = vendor_dir.join(format!("{}.yaml", device_id));
+            info!(path = ?device_yml, "Reading device file");
+            let dev: Device = serde_yaml::from_reader(File::open(&device_yml)?)?;
+            if dev.draft {
+                continue;
+            }
+
+            import_device(&vendor_dir, vendor, device_id, &dev).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn import_device(
+    dir: &Path,
+    vendor: &Vendor,
+    device_id: &str,
+    device: &Device,
+) -> Result<()> {
+    info!(vendor_id = %vendor.id, device_id = %device_id, "Importing device");
+    let id_regex = regex::Regex::new(r"[^\w-]+").unwrap();
+
+    for fw in &device.firmware_versions {
+        for (region, profile) in &fw.profiles {
+            info!(fw_version = %fw.version, region = %region, vendor_id = %profile.vendor_id, profile = %profile.id, codec = %profile.codec, "Found profile");
+            let profile_yml = if profile.vendor_id.is_empty() {
+                dir.join(format!("{}.yaml", profile.id))
+            } else {
+                dir.join("..")
+                    .join(&profile.vendor_id)
+                    .join(format!("{}.yaml", profile.id))
+            };
+
+            info!(path = ?profile_yml, "Reading profile");
+            let prof: Profile = serde_yaml::from_reader(File::open(&profile_yml)?)?;
+
+            let codec = if profile.codec.is_empty() {
+                None
+            } else {
+                let codec_yml = dir.join(format!("{}.yaml", profile.codec));
+                info!(path = ?codec_yml, "Reading codec");
+
+                let codec: JsCodec = serde_yaml::from_reader(File::open(&codec_yml)?)?;
+                let script = read_codec_script(dir, &codec)?;
+
+                if let Err(e) = verify_codec(&script, &codec).await {
+                    warn!(path = ?codec_yml, error = %format!("{:#}", e), "Codec examples failed, skipping profile");
+                    continue;
+                }
+
+                Some(script)
+            };
+
+            let regions: Vec<CommonName> = match region.as_ref() {
+                "EU863-870" => vec![CommonName::EU868],
+                "US902-928" => vec![CommonName::US915],
+                "AU915-928" => vec![CommonName::AU915],
+                "AS923" | "AS923-1" => vec![CommonName::AS923],
+                "AS923-2" => vec![CommonName::AS923_2],
+                "AS923-3" => vec![CommonName::AS923_3],
+                "AS923-4" => vec![CommonName::AS923_4],
+                "CN779-787" => vec![CommonName::CN779],
+                "EU433" => vec![CommonName::EU433],
+                "CN470-510" => vec![CommonName::CN470],
+                "KR920-923" => vec![CommonName::KR920],
+                "IN865-867" => vec![CommonName::IN865],
+                "RU864-870" => vec![CommonName::RU864],
+                "ISM2400" => vec![CommonName::ISM2400],
+                _ => {
+                    warn!(region = %region, "Unexpected region, skipping profile");
+                    continue;
+                }
+            };
+
+            for region in regions {
+                let id = format!(
+                    "{}-{}-{}-{}-{}",
+                    vendor.id, device_id, fw.version, region, profile.id
+                );
+                let id = id_regex.replace_all(&id, "-").to_string();
+
+                let dp = device_profile_template::DeviceProfileTemplate {
+                    id,
+                    name: truncate(&device.name, 100).to_string(),
+                    description: format!(
+                        "{}\n\nSource: https://github.com/TheThingsNetwork/lorawan-devices",
+                        device.description
+                    ),
+                    vendor: vendor.name.clone(),
+                    firmware: fw.version.clone(),
+                    region,
+                    mac_version: MacVersion::from_str(&prof.mac_version)?,
+                    reg_params_revision: get_revision(&prof.region_parameters_version)?,
+                    adr_algorithm_id: "default".into(),
+                    payload_codec_runtime: match &codec {
+                        None => Codec::NONE,
+                        Some(_) => Codec::JS,
+                    },
+                    payload_codec_script: match &codec {
+                        None => "".to_string(),
+                        Some(v) => v.to_string(),
+                    },
+                    uplink_interval: 60 * 60,
+                    device_status_req_interval: 1,
+                    flush_queue_on_activate: true,
+                    supports_otaa: prof.supports_join,
+                    supports_class_b: prof.supports_class_b,
+                    supports_class_c: prof.supports_class_c,
+                    class_b_timeout: prof.class_b_timeout as i32,
+                    class_b_ping_slot_nb_k: match prof.ping_slot_period {
+                        128 => 7,
+                        64 => 6,
+                        32 => 5,
+                        16 => 4,
+                        8 => 3,
+                        4 => 2,
+                        2 => 1,
+                        1 => 0,
+                        _ => 0,
+                    },
+                    class_b_ping_slot_dr: prof.ping_slot_data_rate_index as i16,
+                    class_b_ping_slot_freq: (prof.ping_slot_frequency * 1_000_000.0) as i64,
+                    class_c_timeout: prof.class_c_timeout as i32,
+                    abp_rx1_delay: prof.rx1_delay as i16,
+                    abp_rx1_dr_offset: prof.rx1_data_rate_offset as i16,
+                    abp_rx2_dr: prof.rx2_data_rate_index as i16,
+                    abp_rx2_freq: (prof.rx2_frequency * 1_000_000.0) as i64,
+                    ..Default::default()
+                };
+
+                device_profile_template::upsert(dp).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn get_revision(s: &str) -> Result<Revision> {
+    Ok(match s {
+        "TS001-1.0" => Revision::A,
+        "TS001-1.0.1" => Revision::A,
+        "RP001-1.0.2" => Revision::A,
+        "RP001-1.0.2-RevB" => Revision::B,
+        "RP001-1.0.3-RevA" => Revision::A,
+        "RP001-1.1-RevA" => Revision::A,
+        "RP001-1.1-RevB" => Revision::B,
+        _ => Revision::from_str(s)?,
+    })
+}
+
+// Returns the codec script. As the functions can be defined in the same or
+// in different files, each file is only included once.
+fn read_codec_script(dir: &Path, codec: &JsCodec) -> Result<String> {
+    let mut files: Vec<&str> = Vec::new();
+    for f in [
+        &codec.uplink_decoder,
+        &codec.downlink_encoder,
+        &codec.downlink_decoder,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        if !files.contains(&f.filename.as_str()) {
+            files.push(&f.filename);
+        }
+    }
+
+    let mut script = String::new();
+    for f in files {
+        let codec_f = dir.join(f);
+        info!(path = ?codec_f, "Reading codec function code");
+        script.push_str(&read_to_string(&codec_f).context(format!("Read {}", codec_f.display()))?);
+        script.push('\n');
+    }
+
+    Ok(script)
+}
+
+// Runs the bundled uplink decoder and downlink encoder examples through the
+// JS codec runtime.
+async fn verify_codec(script: &str, codec: &JsCodec) -> Result<()> {
+    let vars = HashMap::new();
+
+    if let Some(f) = &codec.uplink_decoder {
+        for ex in &f.examples {
+            let res = codec::binary_to_struct(
+                Codec::JS,
+                Utc::now(),
+                ex.input.f_port.unwrap_or(1),
+                &vars,
+                script,
+                &ex.input.bytes,
+            )
+            .await;
+
+            if !ex.output.errors.is_empty() {
+                if res.is_ok() {
+                    return Err(anyhow!(
+                        "uplinkDecoder example '{}': expected errors",
+                        ex.description
+                    ));
+                }
+                continue;
+            }
+
+            let res = res.context(format!("uplinkDecoder example '{}'", ex.description))?;
+            if let Some(expected) = &ex.output.data {
+                let data = serde_json::to_value(&res)?;
+                if !json_eq(expected, &data) {
+                    return Err(anyhow!(
+                        "uplinkDecoder example '{}': expected {}, got {}",
+                        ex.description,
+                        expected,
+                        data
+                    ));
+                }
+            }
+        }
+    }
+
+    if let Some(f) = &codec.downlink_encoder {
+        for ex in &f.examples {
+            let data = ex.input.data.clone().unwrap_or_default();
+            let obj: pbjson_types::Struct = serde_json::from_value(data)
+                .context(format!("downlinkEncoder example '{}'", ex.description))?;
+
+            let res = codec::struct_to_binary(
+                Codec::JS,
+                ex.output.f_port.unwrap_or(1),
+                &vars,
+                script,
+                &codec::convert::pb_json_to_prost(&obj),
+            )
+            .await;
+
+            if !ex.output.errors.is_empty() {
+                if res.is_ok() {
+                    return Err(anyhow!(
+                        "downlinkEncoder example '{}': expected errors",
+                        ex.description
+                    ));
+                }
+                continue;
+            }
+
+            let b = res.context(format!("downlinkEncoder example '{}'", ex.description))?;
+            if b != ex.output.bytes {
+                return Err(anyhow!(
+                    "downlinkEncoder example '{}': expected {:?}, got {:?}",
+                    ex.description,
+                    ex.output.bytes,
+                    b
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Compares the two JSON values. Numbers are compared as float, as the JS
+// runtime does not distinguish between integers and floats.
+fn json_eq(a: &serde_json::Value, b: &serde_json::Value) -> bool {
+    use serde_json::Value;
+
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => match (a.as_f64(), b.as_f64()) {
+            (Some(a), Some(b)) => (a - b).abs() <= f64::EPSILON * a.abs().max(b.abs()).max(1.0),
+            _ => false,
+        },
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| json_eq(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(k, v)| b.get(k).map(|bv| json_eq(v, bv)).unwrap_or(false))
+        }
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn test_json_eq() {
+        let a = serde_json::json!({"temperature": 21, "labels": ["a", "b"]});
+        let b = serde_json::json!({"temperature": 21.0, "labels": ["a", "b"]});
+        assert!(json_eq(&a, &b));
+
+        let b = serde_json::json!({"temperature": 21.5, "labels": ["a", "b"]});
+        assert!(!json_eq(&a, &b));
+
+        let b = serde_json::json!({"temperature": 21});
+        assert!(!json_eq(&a, &b));
+    }
+
+    #[tokio::test]
+    async fn test_verify_codec() {
+        let script = r#"
+            function decodeUplink(input) {
+                if (input.bytes.length == 0) {
+                    return { errors: ["no data"] };
+                }
+                return { data: { temperature: input.bytes[0] } };
+            }
+
+            function encodeDownlink(input) {
+                return { bytes: [input.data.level], fPort: 1 };
+            }
+        "#;
+
+        let codec: JsCodec = serde_yaml::from_str(
+            r#"
+uplinkDecoder:
+  fileName: codec.js
+  examples:
+    - description: Temperature
+      input:
+        fPort: 1
+        bytes: [21]
+      output:
+        data:
+          temperature: 21
+    - description: Empty
+      input:
+        fPort: 1
+        bytes: []
+      output:
+        errors: ["no data"]
+downlinkEncoder:
+  fileName: codec.js
+  examples:
+    - description: Level
+      input:
+        data:
+          level: 3
+      output:
+        fPort: 1
+        bytes: [3]
+"#,
+        )
+        .unwrap();
+
+        verify_codec(script, &codec).await.unwrap();
+
+        let codec: JsCodec = serde_yaml::from_str(
+            r#"
+uplinkDecoder:
+  fileName: codec.js
+  examples:
+    - description: Temperature
+      input:
+        fPort: 1
+        bytes: [21]
+      output:
+        data:
+          temperature: 22
+"#,
+        )
+        .unwrap();
+
+        assert!(verify_codec(script, &codec).await.is_err());
+    }
+}
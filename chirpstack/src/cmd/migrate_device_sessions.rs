@@ -0,0 +1,91 @@
+use std::io::Cursor;
+
+use anyhow::{Context, Result};
+use prost::Message;
+use redis::Commands;
+
+use crate::storage;
+use crate::storage::{device, device_session};
+use chirpstack_api::internal;
+use lrwn::{DevAddr, EUI64};
+
+/// Copies the device-sessions from the given Redis servers (using the given
+/// key prefix) to the configured storage. In case of a Redis Cluster, the URL
+/// of every master node must be given.
+pub async fn run(from_urls: &[String], from_key_prefix: &str, dry_run: bool) -> Result<()> {
+    storage::setup().await.context("Setup storage")?;
+
+    let mut count = 0;
+    let mut errors = 0;
+
+    for url in from_urls {
+        for ds in read_device_sessions(url, from_key_prefix)? {
+            let dev_eui = EUI64::from_slice(&ds.dev_eui)?;
+            count += 1;
+
+            if dry_run {
+                println!("{}: migrate", dev_eui);
+                continue;
+            }
+
+            match migrate(&ds).await {
+                Ok(_) => println!("{}: migrate", dev_eui),
+                Err(e) => {
+                    errors += 1;
+                    println!("{}: error: {:#}", dev_eui, e);
+                }
+            }
+        }
+    }
+
+    println!(
+        "{} device-sessions processed, {} errors{}",
+        count,
+        errors,
+        if dry_run { " (dry-run)" } else { "" }
+    );
+
+    if errors != 0 {
+        return Err(anyhow!("Migration contains {} errors", errors));
+    }
+
+    Ok(())
+}
+
+// Saves the device-session and updates the DevAddr of the device, such that the device
+// can be found by its DevAddr.
+async fn migrate(ds: &internal::DeviceSession) -> Result<()> {
+    device_session::save(ds).await?;
+    device::set_dev_addr(
+        EUI64::from_slice(&ds.dev_eui)?,
+        DevAddr::from_slice(&ds.dev_addr)?,
+    )
+    .await?;
+    Ok(())
+}
+
+fn read_device_sessions(url: &str, key_prefix: &str) -> Result<Vec<internal::DeviceSession>> {
+    let client = redis::Client::open(url).context("Open Redis client")?;
+    let mut c = client.get_connection().context("Get Redis connection")?;
+
+    let keys: Vec<String> = c
+        .scan_match(format!("{}device:{{*}}:ds", key_prefix))
+        .context("Scan device-session keys")?
+        .collect();
+
+    let mut out = Vec::new();
+    for key in &keys {
+        let b: Vec<u8> = c.get(key).context("Get device-session")?;
+        // The key might have expired after the scan.
+        if b.is_empty() {
+            continue;
+        }
+
+        out.push(
+            internal::DeviceSession::decode(&mut Cursor::new(b))
+                .context(format!("Decode device-session, key: {}", key))?,
+        );
+    }
+
+    Ok(out)
+}
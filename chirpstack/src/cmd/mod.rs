@@ -1,4 +1,9 @@
+pub mod bulk;
 pub mod configfile;
 pub mod import_legacy_lorawan_devices_repository;
+pub mod import_lorawan_devices_repository;
+pub mod migrate_device_sessions;
 pub mod print_ds;
 pub mod root;
+pub mod simulate_adr;
+pub mod validate_config;
@@ -2,7 +2,7 @@ use anyhow::Result;
 use tracing::info;
 
 use crate::gateway;
-use crate::{adr, api, backend, downlink, integration, region, storage};
+use crate::{adr, api, backend, downlink, fuota, integration, region, reload, storage};
 
 pub async fn run() -> Result<()> {
     info!(
@@ -16,8 +16,11 @@ pub async fn run() -> Result<()> {
     backend::setup()?;
     adr::setup().await?;
     integration::setup().await?;
+    integration::outbox::setup().await;
     gateway::backend::setup().await?;
     downlink::setup().await;
+    fuota::setup().await;
+    reload::setup()?;
     api::setup().await?;
 
     Ok(())
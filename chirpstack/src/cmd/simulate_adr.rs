@@ -0,0 +1,62 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::adr::simulation::{self, Simulation};
+use crate::storage::{device, device_profile, device_session};
+use crate::{adr, region, storage};
+use chirpstack_api::internal;
+use lrwn::EUI64;
+
+/// Replays the uplink history of the device through the ADR algorithm. The
+/// history file (optional) must contain a JSON array of uplink history items,
+/// using the same format as the uplinkAdrHistory of the print-ds output.
+pub async fn run(
+    dev_eui: &EUI64,
+    algorithm_id: Option<&str>,
+    installation_margin: Option<f32>,
+    history_file: Option<&Path>,
+) -> Result<()> {
+    storage::setup().await.context("Setup storage")?;
+    region::setup().context("Setup regions")?;
+    adr::setup().await.context("Setup ADR algorithms")?;
+
+    let d = device::get(dev_eui).await.context("Get device")?;
+    let dp = device_profile::get(&d.device_profile_id)
+        .await
+        .context("Get device-profile")?;
+    let ds = device_session::get(dev_eui)
+        .await
+        .context("Get device-session")?;
+
+    let mut sim = Simulation::new(&dp, &ds)?;
+    if let Some(v) = algorithm_id {
+        sim.algorithm_id = v.to_string();
+    }
+    sim.installation_margin = installation_margin;
+    if let Some(p) = history_file {
+        let b = fs::read(p).context("Read history file")?;
+        sim.uplink_history = serde_json::from_slice::<Vec<internal::UplinkAdrHistory>>(&b)
+            .context("Parse history file")?;
+    }
+
+    let steps = simulation::simulate(&sim).await?;
+
+    println!(
+        "algorithm: {}, initial dr: {}, tx_power_index: {}, nb_trans: {}",
+        sim.algorithm_id, sim.dr, sim.tx_power_index, sim.nb_trans
+    );
+    println!(
+        "{:>10} {:>8} {:>4} {:>14} {:>8} {:>11}",
+        "f_cnt", "max_snr", "dr", "tx_power_index", "nb_trans", "link_margin"
+    );
+    for s in &steps {
+        println!(
+            "{:>10} {:>8.1} {:>4} {:>14} {:>8} {:>11.1}",
+            s.f_cnt, s.max_snr, s.dr, s.tx_power_index, s.nb_trans, s.link_margin
+        );
+    }
+
+    Ok(())
+}
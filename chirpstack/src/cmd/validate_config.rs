@@ -0,0 +1,733 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+use crate::{config, region};
+use lrwn::region::CommonName;
+use lrwn::DevAddr;
+
+// Configuration keys which refer to a file on disk.
+const FILE_KEYS: [&str; 4] = ["ca_cert", "ca_key", "tls_cert", "tls_key"];
+
+// Fields which are used to identify array items (e.g. [[regions]]) in keys.
+const ITEM_ID_FIELDS: [&str; 4] = ["id", "net_id", "join_eui", "label"];
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Segment {
+    Key(String),
+    // Array item, identified by the value of the given field.
+    Item(String, String),
+    // Array item, identified by its (global) index.
+    Index(usize),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Problem {
+    file: Option<PathBuf>,
+    path: Vec<Segment>,
+    message: String,
+}
+
+impl Problem {
+    fn new(path: Vec<Segment>, message: String) -> Self {
+        Problem {
+            file: None,
+            path,
+            message,
+        }
+    }
+
+    fn key(&self) -> String {
+        let mut out = String::new();
+        for s in &self.path {
+            match s {
+                Segment::Key(k) => {
+                    if !out.is_empty() {
+                        out.push('.');
+                    }
+                    out.push_str(k);
+                }
+                Segment::Item(f, v) => out.push_str(&format!("[{}={}]", f, v)),
+                Segment::Index(i) => out.push_str(&format!("[{}]", i)),
+            }
+        }
+        out
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let file = match &self.file {
+            Some(v) => v.display().to_string(),
+            None => "<unknown file>".to_string(),
+        };
+        write!(f, "{}: {}: {}", file, self.key(), self.message)
+    }
+}
+
+fn key(path: &str) -> Vec<Segment> {
+    path.split('.')
+        .map(|k| Segment::Key(k.to_string()))
+        .collect()
+}
+
+fn region_key(region_id: &str, path: &str) -> Vec<Segment> {
+    let mut out = vec![
+        Segment::Key("regions".into()),
+        Segment::Item("id".into(), region_id.to_string()),
+    ];
+    out.extend(key(path));
+    out
+}
+
+fn with_index(mut path: Vec<Segment>, i: usize) -> Vec<Segment> {
+    path.push(Segment::Index(i));
+    path
+}
+
+// The individual configuration files, used to find the file in which a
+// configuration key has been defined.
+struct ConfigFiles(Vec<(PathBuf, toml::Value)>);
+
+impl ConfigFiles {
+    // Reads the files in the same order as config::read, such that the
+    // index of array items matches the index within the merged configuration.
+    fn read(config_dir: &Path) -> Result<(Self, Vec<Problem>)> {
+        let mut files = Vec::new();
+        let mut problems = Vec::new();
+
+        for path in fs::read_dir(config_dir)? {
+            let path = path?.path();
+            if path.extension().map(|v| v != "toml").unwrap_or(true) {
+                continue;
+            }
+
+            let mut content = match fs::read_to_string(&path) {
+                Ok(v) => v,
+                Err(e) => {
+                    problems.push(Problem {
+                        file: Some(path),
+                        path: vec![],
+                        message: format!("read file error: {}", e),
+                    });
+                    continue;
+                }
+            };
+
+            for (k, v) in env::vars() {
+                content = content.replace(&format!("${}", k), &v);
+            }
+
+            match toml::from_str::<toml::Value>(&content) {
+                Ok(v) => files.push((path, v)),
+                Err(e) => problems.push(Problem {
+                    file: Some(path),
+                    path: vec![],
+                    message: format!("parse error: {}", e),
+                }),
+            }
+        }
+
+        Ok((ConfigFiles(files), problems))
+    }
+
+    // Returns the file which matches the longest part of the given path.
+    fn locate(&self, path: &[Segment]) -> Option<PathBuf> {
+        let mut seen: HashMap<usize, usize> = HashMap::new();
+        let mut out: Option<(PathBuf, usize)> = None;
+
+        for (file, value) in &self.0 {
+            let depth = locate_depth(value, path, 0, &mut seen);
+            if depth > 0 && out.as_ref().map(|(_, d)| depth > *d).unwrap_or(true) {
+                out = Some((file.clone(), depth));
+            }
+        }
+
+        out.map(|(f, _)| f)
+    }
+}
+
+fn locate_depth(
+    value: &toml::Value,
+    path: &[Segment],
+    pos: usize,
+    seen: &mut HashMap<usize, usize>,
+) -> usize {
+    if pos == path.len() {
+        return pos;
+    }
+
+    match &path[pos] {
+        Segment::Key(k) => match value.get(k) {
+            Some(v) => locate_depth(v, path, pos + 1, seen),
+            None => pos,
+        },
+        Segment::Item(f, id) => {
+            let items = match value.as_array() {
+                Some(v) => v,
+                None => return pos,
+            };
+
+            for item in items {
+                if item_matches(item, f, id) {
+                    return locate_depth(item, path, pos + 1, seen);
+                }
+            }
+
+            pos
+        }
+        Segment::Index(i) => {
+            let items = match value.as_array() {
+                Some(v) => v,
+                None => return pos,
+            };
+
+            // Arrays are concatenated over the configuration files.
+            let offset = seen.entry(pos).or_insert(0);
+            if *i >= *offset && *i < *offset + items.len() {
+                let item = &items[*i - *offset];
+                locate_depth(item, path, pos + 1, seen)
+            } else {
+                *offset += items.len();
+                pos
+            }
+        }
+    }
+}
+
+fn item_matches(item: &toml::Value, field: &str, id: &str) -> bool {
+    let mut fields = vec![field];
+    if field == "id" {
+        fields.push("name");
+    }
+
+    fields.iter().any(|f| {
+        item.get(f)
+            .and_then(|v| v.as_str())
+            .map(|v| v.eq_ignore_ascii_case(id))
+            .unwrap_or(false)
+    })
+}
+
+/// Loads the configuration from the given directory and validates it.
+/// Every problem is printed to stdout. It returns false if problems were
+/// found.
+pub fn run(config_dir: &Path) -> Result<bool> {
+    let (files, mut problems) = ConfigFiles::read(config_dir)?;
+
+    if problems.is_empty() {
+        match config::load(config_dir) {
+            Ok(_) => {
+                problems = validate(&config::get());
+                for p in problems.iter_mut() {
+                    p.file = files.locate(&p.path);
+                }
+            }
+            Err(e) => {
+                problems.push(Problem::new(vec![], format!("load error: {:#}", e)));
+            }
+        }
+    }
+
+    for p in &problems {
+        println!("{}", p);
+    }
+
+    Ok(problems.is_empty())
+}
+
+/// Validates the given configuration, which has been read from the given
+/// directory (e.g. on reload). It returns an error listing all the problems
+/// if problems were found.
+pub fn check(config_dir: &Path, conf: &config::Configuration) -> Result<()> {
+    let (files, mut problems) = ConfigFiles::read(config_dir)?;
+
+    if problems.is_empty() {
+        problems = validate(conf);
+        for p in problems.iter_mut() {
+            p.file = files.locate(&p.path);
+        }
+    }
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    Err(anyhow!(
+        "Configuration contains {} problem(s):\n{}",
+        problems.len(),
+        problems
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<String>>()
+            .join("\n")
+    ))
+}
+
+fn validate(conf: &config::Configuration) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    problems.extend(validate_regions(conf));
+    problems.extend(validate_dev_addr_prefixes(conf));
+    problems.extend(validate_kek_labels(conf));
+    problems.extend(validate_files(conf));
+
+    problems
+}
+
+fn validate_regions(conf: &config::Configuration) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    for (i, region_id) in conf.network.enabled_regions.iter().enumerate() {
+        if !conf.regions.iter().any(|r| &r.id == region_id) {
+            problems.push(Problem::new(
+                with_index(key("network.enabled_regions"), i),
+                format!("region '{}' is enabled, but not configured", region_id),
+            ));
+        }
+    }
+
+    for r in &conf.regions {
+        if !conf.network.enabled_regions.contains(&r.id) {
+            continue;
+        }
+
+        let mut region_conf = match region::new_region_conf(r) {
+            Ok(v) => v,
+            Err(e) => {
+                problems.push(Problem::new(
+                    region_key(&r.id, "custom"),
+                    format!("{:#}", e),
+                ));
+                continue;
+            }
+        };
+
+        for (k, dr) in [
+            ("network.rx2_dr", r.network.rx2_dr),
+            ("network.min_dr", r.network.min_dr),
+            ("network.max_dr", r.network.max_dr),
+            (
+                "network.class_b.ping_slot_dr",
+                r.network.class_b.ping_slot_dr,
+            ),
+        ] {
+            if region_conf.get_data_rate(dr).is_err() {
+                problems.push(Problem::new(
+                    region_key(&r.id, k),
+                    format!("data-rate {} does not exist for {}", dr, r.common_name),
+                ));
+            }
+        }
+
+        if r.network.min_dr > r.network.max_dr {
+            problems.push(Problem::new(
+                region_key(&r.id, "network.min_dr"),
+                format!(
+                    "min_dr ({}) is greater than max_dr ({})",
+                    r.network.min_dr, r.network.max_dr
+                ),
+            ));
+        }
+
+        let (band_min, band_max) = get_band_frequency_range(r);
+        for (i, ec) in r.network.extra_channels.iter().enumerate() {
+            let path = with_index(region_key(&r.id, "network.extra_channels"), i);
+
+            if ec.frequency < band_min || ec.frequency > band_max {
+                problems.push(Problem::new(
+                    path.clone(),
+                    format!(
+                        "frequency {} is outside the {} band ({} - {})",
+                        ec.frequency, r.common_name, band_min, band_max
+                    ),
+                ));
+            }
+
+            for dr in [ec.min_dr, ec.max_dr] {
+                if region_conf.get_data_rate(dr).is_err() {
+                    problems.push(Problem::new(
+                        path.clone(),
+                        format!("data-rate {} does not exist for {}", dr, r.common_name),
+                    ));
+                }
+            }
+
+            if ec.min_dr > ec.max_dr {
+                problems.push(Problem::new(
+                    path.clone(),
+                    format!(
+                        "min_dr ({}) is greater than max_dr ({})",
+                        ec.min_dr, ec.max_dr
+                    ),
+                ));
+            }
+
+            if let Err(e) = region_conf.add_channel(ec.frequency, ec.min_dr, ec.max_dr) {
+                problems.push(Problem::new(path, e.to_string()));
+            }
+        }
+
+        for (i, c) in r.network.enabled_uplink_channels.iter().enumerate() {
+            if region_conf.get_uplink_channel(*c).is_err() {
+                problems.push(Problem::new(
+                    with_index(region_key(&r.id, "network.enabled_uplink_channels"), i),
+                    format!("uplink channel {} does not exist", c),
+                ));
+            }
+        }
+    }
+
+    problems
+}
+
+fn validate_dev_addr_prefixes(conf: &config::Configuration) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let net_id = conf.network.net_id;
+    let net_id_prefix = net_id.dev_addr_prefix();
+
+    for (i, prefix) in conf.network.dev_addr_prefixes.iter().enumerate() {
+        if prefix.size() < net_id_prefix.size()
+            || !DevAddr::from_be_bytes(prefix.prefix()).is_net_id(net_id)
+        {
+            problems.push(Problem::new(
+                with_index(key("network.dev_addr_prefixes"), i),
+                format!(
+                    "prefix {} is not within the NetID {} prefix {}",
+                    prefix, net_id, net_id_prefix
+                ),
+            ));
+        }
+    }
+
+    problems
+}
+
+fn validate_kek_labels(conf: &config::Configuration) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut labels: Vec<(Vec<Segment>, &str)> = Vec::new();
+
+    for s in &conf.roaming.servers {
+        labels.push((
+            vec![
+                Segment::Key("roaming".into()),
+                Segment::Key("servers".into()),
+                Segment::Item("net_id".into(), s.net_id.to_string()),
+                Segment::Key("passive_roaming_kek_label".into()),
+            ],
+            &s.passive_roaming_kek_label,
+        ));
+    }
+
+    labels.push((
+        key("roaming.default.passive_roaming_kek_label"),
+        &conf.roaming.default.passive_roaming_kek_label,
+    ));
+
+    for s in &conf.join_server.network_servers {
+        for (k, label) in [
+            ("kek_label", &s.kek_label),
+            ("app_s_key_kek_label", &s.app_s_key_kek_label),
+        ] {
+            labels.push((
+                vec![
+                    Segment::Key("join_server".into()),
+                    Segment::Key("network_servers".into()),
+                    Segment::Item("net_id".into(), s.net_id.to_string()),
+                    Segment::Key(k.into()),
+                ],
+                label,
+            ));
+        }
+    }
+
+    for s in &conf.join_server.application_servers {
+        labels.push((
+            vec![
+                Segment::Key("join_server".into()),
+                Segment::Key("application_servers".into()),
+                Segment::Item("net_id".into(), s.net_id.to_string()),
+                Segment::Key("app_s_key_kek_label".into()),
+            ],
+            &s.app_s_key_kek_label,
+        ));
+    }
+
+    for (path, label) in labels {
+        if !label.is_empty() && !conf.keks.iter().any(|k| k.label == label) {
+            problems.push(Problem::new(
+                path,
+                format!("KEK label '{}' does not exist in keks", label),
+            ));
+        }
+    }
+
+    problems
+}
+
+fn validate_files(conf: &config::Configuration) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    for (i, plugin) in conf.network.adr_plugins.iter().enumerate() {
+        if !Path::new(plugin).is_file() {
+            problems.push(Problem::new(
+                with_index(key("network.adr_plugins"), i),
+                format!("file '{}' does not exist", plugin),
+            ));
+        }
+    }
+
+    match serde_json::to_value(conf) {
+        Ok(v) => validate_file_keys(&v, &mut vec![], &mut problems),
+        Err(e) => problems.push(Problem::new(vec![], format!("serialize error: {}", e))),
+    }
+
+    problems
+}
+
+fn validate_file_keys(
+    value: &serde_json::Value,
+    path: &mut Vec<Segment>,
+    problems: &mut Vec<Problem>,
+) {
+    match value {
+        serde_json::Value::Object(obj) => {
+            for (k, v) in obj {
+                path.push(Segment::Key(k.clone()));
+
+                match v.as_str() {
+                    Some(file) if FILE_KEYS.contains(&k.as_str()) => {
+                        if !file.is_empty() && !Path::new(file).is_file() {
+                            problems.push(Problem::new(
+                                path.clone(),
+                                format!("file '{}' does not exist", file),
+                            ));
+                        }
+                    }
+                    _ => validate_file_keys(v, path, problems),
+                }
+
+                path.pop();
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                let id = ITEM_ID_FIELDS.iter().find_map(|f| {
+                    item.get(f)
+                        .and_then(|v| v.as_str())
+                        .map(|v| Segment::Item(f.to_string(), v.to_string()))
+                });
+
+                path.push(id.unwrap_or(Segment::Index(i)));
+                validate_file_keys(item, path, problems);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+// Returns the frequency range (Hz) of the band, as defined by the LoRaWAN
+// Regional Parameters or by the custom region definition.
+fn get_band_frequency_range(r: &config::Region) -> (u32, u32) {
+    match r.common_name {
+        CommonName::EU868 => (863000000, 870000000),
+        CommonName::US915 => (902000000, 928000000),
+        CommonName::CN779 => (779000000, 787000000),
+        CommonName::EU433 => (433175000, 434665000),
+        CommonName::AU915 => (915000000, 928000000),
+        CommonName::CN470 => (470000000, 510000000),
+        CommonName::AS923 | CommonName::AS923_2 | CommonName::AS923_3 | CommonName::AS923_4 => {
+            (915000000, 928000000)
+        }
+        CommonName::KR920 => (920900000, 923300000),
+        CommonName::IN865 => (865000000, 867000000),
+        CommonName::RU864 => (864000000, 870000000),
+        CommonName::ISM2400 => (2400000000, 2500000000),
+        CommonName::CUSTOM => (
+            r.custom.min_frequency,
+            match r.custom.max_frequency {
+                0 => u32::MAX,
+                v => v,
+            },
+        ),
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use lrwn::{DevAddrPrefix, NetID};
+
+    fn get_conf() -> config::Configuration {
+        config::Configuration {
+            network: config::Network {
+                net_id: NetID::from_be_bytes([0x00, 0x00, 0x00]),
+                enabled_regions: vec!["eu868".into()],
+                ..Default::default()
+            },
+            regions: vec![config::Region {
+                id: "eu868".into(),
+                common_name: CommonName::EU868,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn get_keys(problems: &[Problem]) -> Vec<String> {
+        problems.iter().map(|p| p.key()).collect()
+    }
+
+    #[test]
+    fn test_validate_valid() {
+        let conf = get_conf();
+        assert!(validate(&conf).is_empty());
+    }
+
+    #[test]
+    fn test_validate_regions() {
+        let mut conf = get_conf();
+        conf.network.enabled_regions.push("us915_0".into());
+        conf.regions[0].network.rx2_dr = 15;
+        conf.regions[0].network.min_dr = 6;
+        conf.regions[0].network.max_dr = 5;
+        conf.regions[0].network.extra_channels = vec![config::ExtraChannel {
+            frequency: 915000000,
+            min_dr: 0,
+            max_dr: 5,
+        }];
+
+        assert_eq!(
+            vec![
+                "network.enabled_regions[1]".to_string(),
+                "regions[id=eu868].network.rx2_dr".to_string(),
+                "regions[id=eu868].network.min_dr".to_string(),
+                "regions[id=eu868].network.extra_channels[0]".to_string(),
+            ],
+            get_keys(&validate_regions(&conf))
+        );
+    }
+
+    #[test]
+    fn test_validate_custom_region() {
+        let mut conf = get_conf();
+        conf.regions[0].common_name = CommonName::CUSTOM;
+
+        // The network data-rates do not exist in the (empty) definition.
+        assert_eq!(
+            vec![
+                "regions[id=eu868].network.rx2_dr".to_string(),
+                "regions[id=eu868].network.min_dr".to_string(),
+                "regions[id=eu868].network.max_dr".to_string(),
+                "regions[id=eu868].network.class_b.ping_slot_dr".to_string(),
+            ],
+            get_keys(&validate_regions(&conf))
+        );
+
+        conf.regions[0].custom.data_rates = vec![lrwn::region::custom::DataRateDefinition {
+            dr: 0,
+            modulation: "FOO".into(),
+            ..Default::default()
+        }];
+        assert_eq!(
+            vec!["regions[id=eu868].custom".to_string()],
+            get_keys(&validate_regions(&conf))
+        );
+    }
+
+    #[test]
+    fn test_validate_dev_addr_prefixes() {
+        let mut conf = get_conf();
+        conf.network.dev_addr_prefixes = vec![
+            DevAddrPrefix::new([0x01, 0x00, 0x00, 0x00], 8),
+            DevAddrPrefix::new([0x81, 0x00, 0x00, 0x00], 8),
+            DevAddrPrefix::new([0x00, 0x00, 0x00, 0x00], 4),
+        ];
+
+        assert_eq!(
+            vec![
+                "network.dev_addr_prefixes[1]".to_string(),
+                "network.dev_addr_prefixes[2]".to_string(),
+            ],
+            get_keys(&validate_dev_addr_prefixes(&conf))
+        );
+    }
+
+    #[test]
+    fn test_validate_kek_labels() {
+        let mut conf = get_conf();
+        conf.keks = vec![config::Kek {
+            label: "kek-1".into(),
+            ..Default::default()
+        }];
+        conf.roaming.servers = vec![
+            config::RoamingServer {
+                net_id: NetID::from_be_bytes([0x01, 0x02, 0x03]),
+                passive_roaming_kek_label: "kek-1".into(),
+                ..Default::default()
+            },
+            config::RoamingServer {
+                net_id: NetID::from_be_bytes([0x03, 0x02, 0x01]),
+                passive_roaming_kek_label: "kek-2".into(),
+                ..Default::default()
+            },
+        ];
+
+        assert_eq!(
+            vec!["roaming.servers[net_id=030201].passive_roaming_kek_label".to_string()],
+            get_keys(&validate_kek_labels(&conf))
+        );
+    }
+
+    #[test]
+    fn test_validate_files() {
+        let mut conf = get_conf();
+        conf.network.adr_plugins = vec!["/does/not/exist.js".into()];
+        conf.regions[0].gateway.backend.mqtt.ca_cert = "/does/not/exist.pem".into();
+
+        assert_eq!(
+            vec![
+                "network.adr_plugins[0]".to_string(),
+                "regions[id=eu868].gateway.backend.mqtt.ca_cert".to_string(),
+            ],
+            get_keys(&validate_files(&conf))
+        );
+    }
+
+    #[test]
+    fn test_config_files_locate() {
+        let dir = env::temp_dir().join("chirpstack-test-validate-config");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("chirpstack.toml"),
+            "[network]\nenabled_regions=[\"eu868\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("region_eu868.toml"),
+            "[[regions]]\nid=\"eu868\"\n[regions.network]\nrx2_dr=15\n",
+        )
+        .unwrap();
+
+        let (files, problems) = ConfigFiles::read(&dir).unwrap();
+        assert!(problems.is_empty());
+
+        assert_eq!(
+            Some(dir.join("region_eu868.toml")),
+            files.locate(&region_key("eu868", "network.rx2_dr"))
+        );
+        assert_eq!(
+            Some(dir.join("chirpstack.toml")),
+            files.locate(&with_index(key("network.enabled_regions"), 0))
+        );
+        assert_eq!(None, files.locate(&key("roaming.servers")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -19,6 +19,19 @@ pub async fn decode(
     decode_config: &str,
     b: &[u8],
 ) -> Result<pbjson_types::Struct> {
+    let (data, _) = decode_with_warnings(recv_time, f_port, variables, decode_config, b).await?;
+    Ok(data)
+}
+
+/// Decodes the given payload, also returning the warnings returned by
+/// decodeUplink.
+pub async fn decode_with_warnings(
+    recv_time: DateTime<Utc>,
+    f_port: u8,
+    variables: &HashMap<String, String>,
+    decode_config: &str,
+    b: &[u8],
+) -> Result<(pbjson_types::Struct, Vec<String>)> {
     let conf = config::get();
     let max_run_ts = SystemTime::now() + conf.codec.js.max_execution_time;
 
@@ -47,7 +60,7 @@ pub async fn decode(
     );
     let b = b.to_vec();
 
-    let out = ctx.with(|ctx| -> Result<pbjson_types::Struct> {
+    let out = ctx.with(|ctx| -> Result<(pbjson_types::Struct, Vec<String>)> {
         // We need to export the Buffer class, as eval / eval_with_options
         // does not allow using import statement.
         let buff: rquickjs::Module = ctx.compile(
@@ -87,12 +100,15 @@ pub async fn decode(
             }
         }
 
-        Ok(convert::rquickjs_to_struct(&res))
+        let warnings: Vec<String> = res.get("warnings").unwrap_or_default();
+
+        Ok((convert::rquickjs_to_struct(&res), warnings))
     })?;
 
+    let (out, warnings) = out;
     let data = out.fields.get("data").cloned().unwrap_or_default();
     if let Some(pbjson_types::value::Kind::StructValue(v)) = data.kind {
-        return Ok(v);
+        return Ok((v, warnings));
     }
 
     Err(anyhow!("decodeUplink did not return 'data'"))
@@ -104,6 +120,18 @@ pub async fn encode(
     encode_config: &str,
     s: &prost_types::Struct,
 ) -> Result<Vec<u8>> {
+    let (b, _) = encode_with_warnings(f_port, variables, encode_config, s).await?;
+    Ok(b)
+}
+
+/// Encodes the given object, also returning the warnings returned by
+/// encodeDownlink.
+pub async fn encode_with_warnings(
+    f_port: u8,
+    variables: &HashMap<String, String>,
+    encode_config: &str,
+    s: &prost_types::Struct,
+) -> Result<(Vec<u8>, Vec<String>)> {
     let conf = config::get();
     let max_run_ts = SystemTime::now() + conf.codec.js.max_execution_time;
 
@@ -174,8 +202,9 @@ pub async fn encode(
         // Error converting from js 'float' into type 'i32'
         let v: Vec<f64> = res.get("bytes")?;
         let v: Vec<u8> = v.iter().map(|v| *v as u8).collect();
+        let warnings: Vec<String> = res.get("warnings").unwrap_or_default();
 
-        Ok(v)
+        Ok((v, warnings))
     })
 }
 
@@ -299,6 +328,43 @@ pub mod test {
         assert_eq!(expected, out);
     }
 
+    #[tokio::test]
+    pub async fn test_decode_with_warnings() {
+        let decoder = r#"
+            function decodeUplink(input) {
+                return {
+                    data: {
+                        length: input.bytes.length
+                    },
+                    warnings: ["unexpected length"]
+                };
+            }
+        "#
+        .to_string();
+
+        let vars: HashMap<String, String> = HashMap::new();
+        let (out, warnings) =
+            decode_with_warnings(Utc::now(), 10, &vars, &decoder, &[0x01, 0x02, 0x03])
+                .await
+                .unwrap();
+
+        assert_eq!(
+            pbjson_types::Struct {
+                fields: [(
+                    "length".to_string(),
+                    pbjson_types::Value {
+                        kind: Some(pbjson_types::value::Kind::NumberValue(3.0)),
+                    },
+                )]
+                .iter()
+                .cloned()
+                .collect(),
+            },
+            out
+        );
+        assert_eq!(vec!["unexpected length".to_string()], warnings);
+    }
+
     #[tokio::test]
     pub async fn test_encode_timeout() {
         let encoder = r#"
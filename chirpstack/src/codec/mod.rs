@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::fmt;
 use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
@@ -93,6 +94,88 @@ pub async fn struct_to_binary(
     })
 }
 
+/// Result of a codec test run.
+#[derive(Default, Debug)]
+pub struct TestResult {
+    pub object: Option<pbjson_types::Struct>,
+    pub bytes: Vec<u8>,
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+    pub execution_time: Duration,
+}
+
+/// Decodes the given payload for testing the codec. Unlike binary_to_struct,
+/// errors are returned as part of the result, together with the warnings and
+/// the execution time.
+pub async fn test_binary_to_struct(
+    codec: Codec,
+    f_port: u8,
+    variables: &HashMap<String, String>,
+    decoder_config: &str,
+    b: &[u8],
+) -> TestResult {
+    let start = Instant::now();
+    let res = match codec {
+        Codec::NONE => Ok((None, Vec::new())),
+        Codec::CAYENNE_LPP => cayenne_lpp::decode(b)
+            .context("CayenneLpp decode")
+            .map(|v| (Some(v), Vec::new())),
+        Codec::JS => js::decode_with_warnings(Utc::now(), f_port, variables, decoder_config, b)
+            .await
+            .map(|(v, w)| (Some(v), w)),
+    };
+    let execution_time = start.elapsed();
+
+    match res {
+        Ok((object, warnings)) => TestResult {
+            object,
+            warnings,
+            execution_time,
+            ..Default::default()
+        },
+        Err(e) => TestResult {
+            errors: vec![format!("{:#}", e)],
+            execution_time,
+            ..Default::default()
+        },
+    }
+}
+
+/// Encodes the given object for testing the codec. Unlike struct_to_binary,
+/// errors are returned as part of the result, together with the warnings and
+/// the execution time.
+pub async fn test_struct_to_binary(
+    codec: Codec,
+    f_port: u8,
+    variables: &HashMap<String, String>,
+    encoder_config: &str,
+    obj: &prost_types::Struct,
+) -> TestResult {
+    let start = Instant::now();
+    let res = match codec {
+        Codec::NONE => Ok((Vec::new(), Vec::new())),
+        Codec::CAYENNE_LPP => cayenne_lpp::encode(obj)
+            .context("CayenneLpp encode")
+            .map(|v| (v, Vec::new())),
+        Codec::JS => js::encode_with_warnings(f_port, variables, encoder_config, obj).await,
+    };
+    let execution_time = start.elapsed();
+
+    match res {
+        Ok((bytes, warnings)) => TestResult {
+            bytes,
+            warnings,
+            execution_time,
+            ..Default::default()
+        },
+        Err(e) => TestResult {
+            errors: vec![format!("{:#}", e)],
+            execution_time,
+            ..Default::default()
+        },
+    }
+}
+
 pub fn get_measurements(s: &pbjson_types::Struct) -> HashMap<String, pbjson_types::value::Kind> {
     let mut out: HashMap<String, pbjson_types::value::Kind> = HashMap::new();
 
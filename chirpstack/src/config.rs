@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use std::{env, fs};
@@ -6,11 +6,12 @@ use std::{env, fs};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
-use lrwn::region::CommonName;
+use lrwn::region::{self, CommonName};
 use lrwn::{AES128Key, DevAddrPrefix, NetID, EUI64};
 
 lazy_static! {
     static ref CONFIG: Mutex<Arc<Configuration>> = Mutex::new(Arc::new(Default::default()));
+    static ref CONFIG_DIR: Mutex<PathBuf> = Mutex::new(PathBuf::new());
 }
 
 #[derive(Default, Serialize, Deserialize, Clone)]
@@ -132,12 +133,15 @@ pub struct Network {
     pub enabled_regions: Vec<String>,
     #[serde(with = "humantime_serde")]
     pub device_session_ttl: Duration,
+    pub device_session_persistence: bool,
+    pub f_cnt_rollback_threshold: u32,
     #[serde(with = "humantime_serde")]
     pub deduplication_delay: Duration,
     #[serde(with = "humantime_serde")]
     pub get_downlink_data_delay: Duration,
     pub mac_commands_disabled: bool,
     pub adr_plugins: Vec<String>,
+    pub adr_plugin_runtime: AdrPluginRuntime,
     pub scheduler: Scheduler,
 }
 
@@ -148,15 +152,37 @@ impl Default for Network {
             dev_addr_prefixes: vec![],
             enabled_regions: vec![],
             device_session_ttl: Duration::from_secs(60 * 60 * 24 * 31),
+            device_session_persistence: false,
+            f_cnt_rollback_threshold: 100,
             deduplication_delay: Duration::from_millis(200),
             get_downlink_data_delay: Duration::from_millis(100),
             mac_commands_disabled: false,
             adr_plugins: vec![],
+            adr_plugin_runtime: Default::default(),
             scheduler: Default::default(),
         }
     }
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AdrPluginRuntime {
+    pub pool_size: usize,
+    #[serde(with = "humantime_serde")]
+    pub max_execution_time: Duration,
+    pub max_memory: usize,
+}
+
+impl Default for AdrPluginRuntime {
+    fn default() -> Self {
+        AdrPluginRuntime {
+            pool_size: 4,
+            max_execution_time: Duration::from_millis(100),
+            max_memory: 16 * 1024 * 1024,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct Scheduler {
@@ -233,6 +259,35 @@ pub struct Integration {
     pub postgresql: PostgresqlIntegration,
     pub amqp: AmqpIntegration,
     pub kafka: KafkaIntegration,
+    pub delivery: IntegrationDelivery,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct IntegrationDelivery {
+    pub max_attempts: usize,
+    #[serde(with = "humantime_serde")]
+    pub min_backoff: Duration,
+    #[serde(with = "humantime_serde")]
+    pub max_backoff: Duration,
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    pub batch_size: usize,
+    #[serde(with = "humantime_serde")]
+    pub dead_letter_ttl: Duration,
+}
+
+impl Default for IntegrationDelivery {
+    fn default() -> Self {
+        IntegrationDelivery {
+            max_attempts: 10,
+            min_backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(60 * 60),
+            interval: Duration::from_secs(5),
+            batch_size: 100,
+            dead_letter_ttl: Duration::from_secs(60 * 60 * 24 * 7),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -320,6 +375,10 @@ pub struct AmqpIntegration {
     pub url: String,
     pub json: bool,
     pub event_routing_key: String,
+    pub command_queue: String,
+    pub command_routing_key: String,
+    pub command_max_attempts: usize,
+    pub command_dead_letter_queue: String,
 }
 
 impl Default for AmqpIntegration {
@@ -329,6 +388,11 @@ impl Default for AmqpIntegration {
             json: true,
             event_routing_key: "application.{{application_id}}.device.{{dev_eui}}.event.{{event}}"
                 .to_string(),
+            command_queue: "".to_string(),
+            command_routing_key:
+                "application.{{application_id}}.device.{{dev_eui}}.command.{{command}}".to_string(),
+            command_max_attempts: 5,
+            command_dead_letter_queue: "".to_string(),
         }
     }
 }
@@ -344,6 +408,11 @@ pub struct KafkaIntegration {
     pub password: String,
     pub mechanism: String,
     pub json: bool,
+    pub command_topic: String,
+    pub command_key: String,
+    pub command_max_attempts: usize,
+    pub command_dead_letter_topic: String,
+    pub consumer_group: String,
 }
 
 impl Default for KafkaIntegration {
@@ -358,6 +427,12 @@ impl Default for KafkaIntegration {
             password: "".to_string(),
             mechanism: "PLAIN".to_string(),
             json: true,
+            command_topic: "".to_string(),
+            command_key: "application.{{application_id}}.device.{{dev_eui}}.command.{{command}}"
+                .to_string(),
+            command_max_attempts: 5,
+            command_dead_letter_topic: "".to_string(),
+            consumer_group: "chirpstack".to_string(),
         }
     }
 }
@@ -407,6 +482,8 @@ pub struct OpenIdConnect {
 #[serde(default)]
 pub struct JoinServer {
     pub servers: Vec<JoinServerServer>,
+    pub network_servers: Vec<JoinServerNetworkServer>,
+    pub application_servers: Vec<JoinServerApplicationServer>,
 }
 
 #[derive(Serialize, Deserialize, Default, Clone)]
@@ -421,6 +498,35 @@ pub struct JoinServerServer {
     pub tls_key: String,
 }
 
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct JoinServerNetworkServer {
+    pub net_id: NetID,
+    pub server: String,
+    #[serde(with = "humantime_serde")]
+    pub async_timeout: Duration,
+    pub ca_cert: String,
+    pub tls_cert: String,
+    pub tls_key: String,
+    pub authorization_header: String,
+    pub kek_label: String,
+    pub app_s_key_kek_label: String,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct JoinServerApplicationServer {
+    pub net_id: NetID,
+    pub server: String,
+    #[serde(with = "humantime_serde")]
+    pub async_timeout: Duration,
+    pub ca_cert: String,
+    pub tls_cert: String,
+    pub tls_key: String,
+    pub authorization_header: String,
+    pub app_s_key_kek_label: String,
+}
+
 #[derive(Serialize, Deserialize, Default, Clone)]
 #[serde(default)]
 pub struct Roaming {
@@ -489,6 +595,7 @@ pub struct Region {
     pub user_info: String,
     pub network: RegionNetwork,
     pub gateway: RegionGateway,
+    pub custom: region::custom::Definition,
 }
 
 impl Default for Region {
@@ -500,6 +607,7 @@ impl Default for Region {
             user_info: "".into(),
             network: RegionNetwork::default(),
             gateway: RegionGateway::default(),
+            custom: Default::default(),
         }
     }
 }
@@ -507,6 +615,7 @@ impl Default for Region {
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct RegionNetwork {
+    pub dev_addr_prefixes: Vec<DevAddrPrefix>,
     pub installation_margin: f32,
     pub rx_window: u8,
     pub rx1_delay: u8,
@@ -534,6 +643,7 @@ pub struct RegionNetwork {
 impl Default for RegionNetwork {
     fn default() -> Self {
         RegionNetwork {
+            dev_addr_prefixes: vec![],
             installation_margin: 10.0,
             rx_window: 0,
             rx1_delay: 1,
@@ -596,6 +706,8 @@ pub struct RegionGateway {
 pub struct GatewayBackend {
     pub enabled: String,
     pub mqtt: GatewayBackendMqtt,
+    pub semtech_udp: GatewayBackendSemtechUdp,
+    pub basic_station: GatewayBackendBasicStation,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -639,6 +751,46 @@ impl Default for GatewayBackendMqtt {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct GatewayBackendSemtechUdp {
+    pub bind: String,
+}
+
+impl Default for GatewayBackendSemtechUdp {
+    fn default() -> Self {
+        GatewayBackendSemtechUdp {
+            bind: "0.0.0.0:1700".into(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct GatewayBackendBasicStation {
+    pub bind: String,
+    pub tls_cert: String,
+    pub tls_key: String,
+    pub ca_cert: String,
+    #[serde(with = "humantime_serde")]
+    pub stats_interval: Duration,
+    #[serde(with = "humantime_serde")]
+    pub ping_interval: Duration,
+}
+
+impl Default for GatewayBackendBasicStation {
+    fn default() -> Self {
+        GatewayBackendBasicStation {
+            bind: "0.0.0.0:3001".into(),
+            tls_cert: "".into(),
+            tls_key: "".into(),
+            ca_cert: "".into(),
+            stats_interval: Duration::from_secs(30),
+            ping_interval: Duration::from_secs(60),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Hash)]
 #[allow(non_camel_case_types)]
 #[allow(clippy::upper_case_acronyms)]
@@ -670,6 +822,24 @@ impl Default for GatewayChannel {
 }
 
 pub fn load(config_dir: &Path) -> Result<()> {
+    let conf = read(config_dir)?;
+    set(conf);
+
+    let mut config_dir_mutex = CONFIG_DIR.lock().unwrap();
+    *config_dir_mutex = config_dir.to_path_buf();
+
+    Ok(())
+}
+
+/// Returns the configuration directory from which the configuration was loaded.
+pub fn get_config_dir() -> PathBuf {
+    let config_dir = CONFIG_DIR.lock().unwrap();
+    config_dir.clone()
+}
+
+/// Reads and parses the configuration files within the given directory,
+/// without activating the configuration.
+pub fn read(config_dir: &Path) -> Result<Configuration> {
     let mut content: String = String::new();
 
     let paths = fs::read_dir(config_dir)?;
@@ -692,9 +862,7 @@ pub fn load(config_dir: &Path) -> Result<()> {
     }
 
     let conf: Configuration = toml::from_str(&content)?;
-    set(conf);
-
-    Ok(())
+    Ok(conf)
 }
 
 pub fn set(c: Configuration) {
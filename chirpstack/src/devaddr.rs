@@ -1,8 +1,71 @@
+use std::collections::HashMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
 use rand::seq::SliceRandom;
 use rand::RngCore;
+use serde::Serialize;
+use tokio::task;
+use tracing::{info, warn};
 
 use crate::config;
-use lrwn::DevAddr;
+use crate::monitoring::prometheus;
+use crate::storage::{device, get_redis_conn, redis_key, tenant};
+use lrwn::{DevAddr, DevAddrPrefix, EUI64};
+
+// Max. number of random DevAddrs to try before accepting a collision.
+const MAX_ALLOCATION_ATTEMPTS: usize = 8;
+
+// Time (ms) a newly allocated DevAddr is reserved for the device, such that concurrent joins
+// do not get the same DevAddr before it has been assigned to the device.
+const RESERVATION_TTL: usize = 60_000;
+
+// Duration after which the cached prefix utilization is refreshed from the database.
+const UTILIZATION_CACHE_TTL: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    static ref PREFIX_ALLOCATED: Family<PrefixLabels, Gauge> = {
+        let gauge = Family::<PrefixLabels, Gauge>::default();
+        prometheus::register(
+            "dev_addr_prefix_allocated",
+            "Number of allocated DevAddrs by DevAddr prefix",
+            gauge.clone(),
+        );
+        gauge
+    };
+    static ref PREFIX_UTILIZATION: Family<PrefixLabels, Gauge<f64, AtomicU64>> = {
+        let gauge = Family::<PrefixLabels, Gauge<f64, AtomicU64>>::default();
+        prometheus::register(
+            "dev_addr_prefix_utilization",
+            "Utilization (0.0 - 1.0) of the DevAddr prefix",
+            gauge.clone(),
+        );
+        gauge
+    };
+    static ref UTILIZATION_CACHE: RwLock<HashMap<String, (Instant, PrefixUtilization)>> =
+        RwLock::new(HashMap::new());
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+struct PrefixLabels {
+    prefix: String,
+}
+
+/// DevAddr prefix utilization.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct PrefixUtilization {
+    pub prefix: String,
+    // Number of DevAddrs assigned to devices.
+    pub allocated: u64,
+    // Number of DevAddrs within the prefix.
+    pub size: u64,
+    pub utilization: f64,
+}
 
 pub fn get_random_dev_addr() -> DevAddr {
     let conf = config::get();
@@ -19,15 +82,319 @@ pub fn get_random_dev_addr() -> DevAddr {
     let prefix = *prefixes.choose(&mut rng).unwrap();
 
     // Generate random DevAddr.
-    let mut dev_addr: [u8; 4] = [0; 4];
-    rng.fill_bytes(&mut dev_addr);
-    #[cfg(test)]
-    {
-        dev_addr = [1, 2, 3, 4];
-    }
-    let mut dev_addr = DevAddr::from_be_bytes(dev_addr);
+    let mut dev_addr = DevAddr::from_be_bytes(get_random_bytes());
 
     // Set DevAddr prefix.
     dev_addr.set_dev_addr_prefix(prefix);
     dev_addr
 }
+
+/// Returns the DevAddr prefixes for the given region configuration. The region prefixes
+/// take precedence over the network prefixes. If none are configured, the NetID prefix
+/// is returned.
+pub fn get_dev_addr_prefixes(region_config_id: Option<&str>) -> Result<Vec<DevAddrPrefix>> {
+    if let Some(region_config_id) = region_config_id {
+        let region_network = config::get_region_network(region_config_id)?;
+        if !region_network.dev_addr_prefixes.is_empty() {
+            return Ok(region_network.dev_addr_prefixes);
+        }
+    }
+
+    let conf = config::get();
+    Ok(if conf.network.dev_addr_prefixes.is_empty() {
+        vec![conf.network.net_id.dev_addr_prefix()]
+    } else {
+        conf.network.dev_addr_prefixes.clone()
+    })
+}
+
+/// Returns the utilization of the given prefix and updates the prefix metrics.
+pub async fn get_prefix_utilization(prefix: DevAddrPrefix) -> Result<PrefixUtilization> {
+    let (start, end) = get_prefix_range(prefix);
+    let allocated = device::get_dev_addr_count(start, end).await? as u64;
+    let size = 1_u64 << (32 - prefix.size());
+    let utilization = allocated as f64 / size as f64;
+
+    let labels = PrefixLabels {
+        prefix: prefix.to_string(),
+    };
+    PREFIX_ALLOCATED
+        .get_or_create(&labels)
+        .set(allocated as i64);
+    PREFIX_UTILIZATION.get_or_create(&labels).set(utilization);
+
+    let u = PrefixUtilization {
+        prefix: prefix.to_string(),
+        allocated,
+        size,
+        utilization,
+    };
+
+    let mut cache = UTILIZATION_CACHE.write().unwrap();
+    cache.insert(u.prefix.clone(), (Instant::now(), u.clone()));
+
+    Ok(u)
+}
+
+// Returns the cached utilization of the given prefix. The utilization is refreshed when
+// the cached value is older than UTILIZATION_CACHE_TTL.
+async fn get_cached_prefix_utilization(prefix: DevAddrPrefix) -> Result<PrefixUtilization> {
+    {
+        let cache = UTILIZATION_CACHE.read().unwrap();
+        if let Some((updated_at, u)) = cache.get(&prefix.to_string()) {
+            if updated_at.elapsed() < UTILIZATION_CACHE_TTL {
+                return Ok(u.clone());
+            }
+        }
+    }
+
+    get_prefix_utilization(prefix).await
+}
+
+// Increments the cached number of allocated DevAddrs of the given prefix, such that
+// allocations are spread over the prefixes in between cache refreshes.
+fn incr_cached_prefix_allocated(prefix: DevAddrPrefix) {
+    let mut cache = UTILIZATION_CACHE.write().unwrap();
+    if let Some((_, u)) = cache.get_mut(&prefix.to_string()) {
+        u.allocated += 1;
+        u.utilization = u.allocated as f64 / u.size as f64;
+    }
+}
+
+/// Allocates a DevAddr for the given device.
+///
+/// The DevAddr is allocated within the least utilized prefix of the tenant prefixes, or
+/// if the tenant has no prefixes configured, of the region (or network) prefixes.
+/// DevAddrs which are assigned to other devices (or reserved by a concurrent allocation)
+/// are skipped. In case no free DevAddr was found after a number of attempts, a DevAddr
+/// colliding with other devices is returned.
+pub async fn allocate_dev_addr(
+    tenant: Option<&tenant::Tenant>,
+    region_config_id: Option<&str>,
+    dev_eui: EUI64,
+) -> Result<DevAddr> {
+    let mut prefixes = match tenant {
+        Some(t) => t.get_dev_addr_prefixes()?,
+        None => Vec::new(),
+    };
+    if prefixes.is_empty() {
+        prefixes = get_dev_addr_prefixes(region_config_id)?;
+    }
+
+    let prefix = if prefixes.len() == 1 {
+        prefixes[0]
+    } else {
+        let mut candidates: Vec<DevAddrPrefix> = Vec::new();
+        let mut min_utilization = f64::MAX;
+        for prefix in &prefixes {
+            let u = get_cached_prefix_utilization(*prefix).await?;
+            if u.utilization < min_utilization {
+                min_utilization = u.utilization;
+                candidates.clear();
+            }
+            if u.utilization == min_utilization {
+                candidates.push(*prefix);
+            }
+        }
+
+        *candidates
+            .choose(&mut rand::thread_rng())
+            .ok_or_else(|| anyhow!("No DevAddr prefixes configured"))?
+    };
+
+    let dev_addr = allocate_in_prefix(prefix, dev_eui, get_random_bytes).await?;
+    incr_cached_prefix_allocated(prefix);
+    Ok(dev_addr)
+}
+
+async fn allocate_in_prefix<F>(prefix: DevAddrPrefix, dev_eui: EUI64, mut gen: F) -> Result<DevAddr>
+where
+    F: FnMut() -> [u8; 4],
+{
+    let mut attempt = 1;
+
+    loop {
+        let mut dev_addr = DevAddr::from_be_bytes(gen());
+        dev_addr.set_dev_addr_prefix(prefix);
+
+        if !device::is_dev_addr_in_use(dev_addr, dev_eui).await?
+            && reserve_dev_addr(dev_addr, dev_eui).await?
+        {
+            info!(dev_eui = %dev_eui, dev_addr = %dev_addr, prefix = %prefix, "DevAddr allocated");
+            return Ok(dev_addr);
+        }
+
+        if attempt == MAX_ALLOCATION_ATTEMPTS {
+            warn!(dev_eui = %dev_eui, dev_addr = %dev_addr, prefix = %prefix, "Unable to allocate a free DevAddr, DevAddr will collide with other devices");
+            return Ok(dev_addr);
+        }
+
+        attempt += 1;
+    }
+}
+
+// This reserves the DevAddr for the given device. It returns false in case the DevAddr has
+// already been reserved by a different device.
+async fn reserve_dev_addr(dev_addr: DevAddr, dev_eui: EUI64) -> Result<bool> {
+    task::spawn_blocking(move || -> Result<bool> {
+        let key = redis_key(format!("devaddr:{{{}}}:reservation", dev_addr));
+        let mut c = get_redis_conn()?;
+
+        let set: bool = redis::cmd("SET")
+            .arg(&key)
+            .arg(dev_eui.to_string())
+            .arg("PX")
+            .arg(RESERVATION_TTL)
+            .arg("NX")
+            .query(&mut *c)?;
+        if set {
+            return Ok(true);
+        }
+
+        let reserved_by: Option<String> = redis::cmd("GET").arg(&key).query(&mut *c)?;
+        Ok(reserved_by == Some(dev_eui.to_string()))
+    })
+    .await?
+}
+
+// Returns the first and last DevAddr of the given prefix.
+fn get_prefix_range(prefix: DevAddrPrefix) -> (DevAddr, DevAddr) {
+    let mut start = DevAddr::from_be_bytes([0x00; 4]);
+    start.set_dev_addr_prefix(prefix);
+    let mut end = DevAddr::from_be_bytes([0xff; 4]);
+    end.set_dev_addr_prefix(prefix);
+    (start, end)
+}
+
+fn get_random_bytes() -> [u8; 4] {
+    let mut b: [u8; 4] = [0; 4];
+    rand::thread_rng().fill_bytes(&mut b);
+    #[cfg(test)]
+    {
+        b = [1, 2, 3, 4];
+    }
+    b
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::storage;
+    use crate::test;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_get_prefix_range() {
+        let prefix = DevAddrPrefix::from_str("01000000/8").unwrap();
+        assert_eq!(
+            (
+                DevAddr::from_be_bytes([0x01, 0x00, 0x00, 0x00]),
+                DevAddr::from_be_bytes([0x01, 0xff, 0xff, 0xff])
+            ),
+            get_prefix_range(prefix)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_allocate_dev_addr() {
+        let _guard = test::prepare().await;
+
+        let prefix = DevAddrPrefix::from_str("01000000/8").unwrap();
+        let mut conf = (*config::get()).clone();
+        conf.network.dev_addr_prefixes = vec![prefix];
+        config::set(conf);
+
+        let dp = storage::device_profile::test::create_device_profile(None).await;
+        let d1 = storage::device::test::create_device(
+            EUI64::from_be_bytes([1, 1, 1, 1, 1, 1, 1, 1]),
+            dp.id,
+            None,
+        )
+        .await;
+        let d2 = storage::device::test::create_device(
+            EUI64::from_be_bytes([2, 2, 2, 2, 2, 2, 2, 2]),
+            dp.id,
+            None,
+        )
+        .await;
+
+        // Allocate and assign.
+        let dev_addr = allocate_dev_addr(None, None, d1.dev_eui).await.unwrap();
+        assert_eq!(DevAddr::from_be_bytes([1, 2, 3, 4]), dev_addr);
+        device::set_dev_addr(d1.dev_eui, dev_addr).await.unwrap();
+
+        let u = get_prefix_utilization(prefix).await.unwrap();
+        assert_eq!(1, u.allocated);
+        assert_eq!(1 << 24, u.size);
+
+        // The DevAddr of d1 is skipped for d2.
+        let mut addrs = vec![[1, 2, 3, 4], [1, 2, 3, 5]].into_iter();
+        let dev_addr = allocate_in_prefix(prefix, d2.dev_eui, || addrs.next().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(DevAddr::from_be_bytes([1, 2, 3, 5]), dev_addr);
+
+        // The DevAddr reserved for d2 is skipped for d1.
+        let mut addrs = vec![[1, 2, 3, 5], [1, 2, 3, 6]].into_iter();
+        let dev_addr = allocate_in_prefix(prefix, d1.dev_eui, || addrs.next().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(DevAddr::from_be_bytes([1, 2, 3, 6]), dev_addr);
+
+        // Unable to allocate a free DevAddr.
+        let dev_addr = allocate_in_prefix(prefix, d2.dev_eui, || [1, 2, 3, 4])
+            .await
+            .unwrap();
+        assert_eq!(DevAddr::from_be_bytes([1, 2, 3, 4]), dev_addr);
+
+        // The tenant prefixes take precedence.
+        let t = tenant::Tenant {
+            dev_addr_prefixes: vec![Some("02000000/8".into())],
+            ..Default::default()
+        };
+        let dev_addr = allocate_dev_addr(Some(&t), None, d2.dev_eui).await.unwrap();
+        assert_eq!(DevAddr::from_be_bytes([2, 2, 3, 4]), dev_addr);
+    }
+
+    #[tokio::test]
+    async fn test_prefix_utilization_cache() {
+        let _guard = test::prepare().await;
+        UTILIZATION_CACHE.write().unwrap().clear();
+
+        let prefix = DevAddrPrefix::from_str("03000000/8").unwrap();
+        let dp = storage::device_profile::test::create_device_profile(None).await;
+        let d = storage::device::test::create_device(
+            EUI64::from_be_bytes([1, 1, 1, 1, 1, 1, 1, 1]),
+            dp.id,
+            None,
+        )
+        .await;
+
+        // Not cached.
+        let u = get_cached_prefix_utilization(prefix).await.unwrap();
+        assert_eq!(0, u.allocated);
+
+        // Cached, the assigned DevAddr is not yet reflected.
+        device::set_dev_addr(d.dev_eui, DevAddr::from_be_bytes([3, 2, 3, 4]))
+            .await
+            .unwrap();
+        let u = get_cached_prefix_utilization(prefix).await.unwrap();
+        assert_eq!(0, u.allocated);
+
+        // Allocations increment the cached value.
+        incr_cached_prefix_allocated(prefix);
+        let u = get_cached_prefix_utilization(prefix).await.unwrap();
+        assert_eq!(1, u.allocated);
+
+        // Expired.
+        UTILIZATION_CACHE
+            .write()
+            .unwrap()
+            .get_mut(&prefix.to_string())
+            .unwrap()
+            .0 = Instant::now() - UTILIZATION_CACHE_TTL;
+        let u = get_cached_prefix_utilization(prefix).await.unwrap();
+        assert_eq!(1, u.allocated);
+        assert_eq!(1.0 / (1 << 24) as f64, u.utilization);
+    }
+}
@@ -16,13 +16,13 @@ use crate::storage;
 use crate::storage::{
     application,
     device::{self, DeviceClass},
-    device_gateway, device_profile, device_queue, device_session, downlink_frame, mac_command,
-    relay, tenant,
+    device_gateway, device_profile, device_queue, device_session, downlink_frame, gateway_load,
+    mac_command, relay, tenant,
 };
 use crate::uplink::{RelayContext, UplinkFrameSet};
 use crate::{adr, config, gateway, integration, maccommand, region, sensitivity};
 use chirpstack_api::{gw, integration as integration_pb, internal};
-use lrwn::{keys, AES128Key, DevAddr, NetID};
+use lrwn::{keys, AES128Key, DevAddr, NetID, EUI64};
 
 struct DownlinkFrameItem {
     downlink_frame_item: gw::DownlinkFrameItem,
@@ -1065,10 +1065,45 @@ impl Data {
         Ok(())
     }
 
+    // Returns the gateways that received the last uplink (from the device-gateway
+    // rx-info), including their uplink channel utilization (if known).
+    async fn _get_adr_gateways(&self) -> Result<Vec<adr::Gateway>> {
+        let mut gateways: Vec<adr::Gateway> = Vec::new();
+
+        if let Some(dev_gw) = &self.device_gateway_rx_info {
+            for item in &dev_gw.items {
+                gateways.push(adr::Gateway {
+                    gateway_id: EUI64::from_slice(&item.gateway_id)?,
+                    rssi: item.rssi,
+                    snr: item.lora_snr,
+                    utilization: None,
+                });
+            }
+        }
+
+        let load = gateway_load::get_for_gateway_ids(
+            &gateways
+                .iter()
+                .map(|gw| gw.gateway_id)
+                .collect::<Vec<EUI64>>(),
+        )
+        .await?;
+        for gw in &mut gateways {
+            gw.utilization = load.get(&gw.gateway_id).cloned();
+        }
+
+        Ok(gateways)
+    }
+
     async fn _request_adr_change(&mut self) -> Result<()> {
         trace!("Requesting ADR change");
 
-        if self.network_conf.adr_disabled {
+        // The link parameters pinned for the device are also applied when ADR
+        // is disabled.
+        if self.network_conf.adr_disabled
+            && self.device.adr_pinned_dr.is_none()
+            && self.device.adr_pinned_tx_power_index.is_none()
+        {
             return Ok(());
         }
 
@@ -1088,16 +1123,23 @@ impl Data {
             dr: self.uplink_frame_set.as_ref().unwrap().dr,
             tx_power_index: self.device_session.tx_power_index as u8,
             nb_trans: self.device_session.nb_trans as u8,
-            max_tx_power_index: if self.device_session.max_supported_tx_power_index != 0 {
-                self.device_session.max_supported_tx_power_index as u8
-            } else {
-                let mut max_tx_power_index: u8 = 0;
-                for n in 0..16 {
-                    if self.region_conf.get_tx_power_offset(n).is_ok() {
-                        max_tx_power_index = n as u8;
+            max_tx_power_index: {
+                let max_tx_power_index = if self.device_session.max_supported_tx_power_index != 0 {
+                    self.device_session.max_supported_tx_power_index as u8
+                } else {
+                    let mut max_tx_power_index: u8 = 0;
+                    for n in 0..16 {
+                        if self.region_conf.get_tx_power_offset(n).is_ok() {
+                            max_tx_power_index = n as u8;
+                        }
                     }
+                    max_tx_power_index
+                };
+
+                match self.device.adr_max_tx_power_index {
+                    Some(v) => max_tx_power_index.min(v as u8),
+                    None => max_tx_power_index,
                 }
-                max_tx_power_index
             },
             required_snr_for_dr: match dr {
                 lrwn::region::DataRateModulation::Lora(params) => {
@@ -1105,13 +1147,35 @@ impl Data {
                 }
                 _ => 0.0,
             },
-            installation_margin: self.network_conf.installation_margin,
-            min_dr: self.network_conf.min_dr,
-            max_dr: self.network_conf.max_dr,
+            installation_margin: self
+                .device
+                .adr_installation_margin
+                .unwrap_or(self.network_conf.installation_margin),
+            min_dr: self
+                .device
+                .adr_min_dr
+                .map(|v| v as u8)
+                .unwrap_or(self.network_conf.min_dr),
+            max_dr: self
+                .device
+                .adr_max_dr
+                .map(|v| v as u8)
+                .unwrap_or(self.network_conf.max_dr),
             uplink_history: self.device_session.uplink_adr_history.clone(),
+            gateways: self._get_adr_gateways().await?,
+        };
+
+        let mut resp = if self.network_conf.adr_disabled {
+            adr::Response {
+                dr: req.dr,
+                tx_power_index: req.tx_power_index,
+                nb_trans: req.nb_trans,
+            }
+        } else {
+            adr::handle(&self.device_profile.adr_algorithm_id, &req).await
         };
 
-        let resp = adr::handle(&self.device_profile.adr_algorithm_id, &req).await;
+        apply_device_link_parameters(&self.device, &**self.region_conf, &req, &mut resp);
 
         // The response values are different than the request values, thus we must
         // send a LinkADRReq to the device.
@@ -1304,10 +1368,10 @@ impl Data {
     async fn _set_tx_parameters(&mut self) -> Result<()> {
         trace!("Setting tx parameters");
 
-        if !self
-            .region_conf
-            .implements_tx_param_setup(self.device_session.mac_version().from_proto())
-        {
+        if !self.region_conf.implements_tx_param_setup(
+            self.device_session.mac_version().from_proto(),
+            self.device_profile.reg_params_revision,
+        ) {
             return Ok(());
         }
 
@@ -2355,6 +2419,43 @@ impl Data {
     }
 }
 
+// Applies the ADR overrides and the pinned link parameters (SetLinkParameters API) of the
+// device to the ADR response. The pinned parameters take precedence. Pinned data-rates which
+// are not enabled uplink data-rates of the region are ignored and all values are clamped to
+// the bounds of the request.
+fn apply_device_link_parameters(
+    d: &device::Device,
+    region_conf: &(dyn lrwn::region::Region + Sync + Send),
+    req: &adr::Request,
+    resp: &mut adr::Response,
+) {
+    // The ADR algorithm does not necessarily take the min. data-rate into account.
+    if d.adr_min_dr.is_some() || d.adr_max_dr.is_some() {
+        resp.dr = resp.dr.max(req.min_dr).min(req.max_dr);
+    }
+    if d.adr_max_tx_power_index.is_some() {
+        resp.tx_power_index = resp.tx_power_index.min(req.max_tx_power_index);
+    }
+    if let Some(v) = d.adr_nb_trans {
+        resp.nb_trans = v.clamp(1, 15) as u8;
+    }
+
+    if let Some(v) = d.adr_pinned_dr {
+        if (0..=u8::MAX as i16).contains(&v)
+            && region_conf
+                .get_enabled_uplink_data_rates()
+                .contains(&(v as u8))
+        {
+            resp.dr = v as u8;
+        } else {
+            warn!(dev_eui = %d.dev_eui, dr = v, "Ignoring pinned data-rate, data-rate is not enabled for region");
+        }
+    }
+    if let Some(v) = d.adr_pinned_tx_power_index {
+        resp.tx_power_index = v.clamp(0, req.max_tx_power_index as i16) as u8;
+    }
+}
+
 fn filter_mac_commands(
     device_session: &internal::DeviceSession,
     mac_commands: &[lrwn::MACCommandSet],
@@ -2586,6 +2687,114 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_apply_device_link_parameters() {
+        let region_conf = lrwn::region::eu868::Configuration::new(false);
+        let req = adr::Request {
+            region_config_id: "eu868".into(),
+            region_common_name: lrwn::region::CommonName::EU868,
+            dev_eui: EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
+            mac_version: lrwn::region::MacVersion::LORAWAN_1_0_4,
+            reg_params_revision: lrwn::region::Revision::RP002_1_0_3,
+            adr: true,
+            dr: 0,
+            tx_power_index: 0,
+            nb_trans: 1,
+            max_tx_power_index: 5,
+            required_snr_for_dr: 0.0,
+            installation_margin: 0.0,
+            min_dr: 2,
+            max_dr: 4,
+            uplink_history: vec![],
+            gateways: vec![],
+        };
+
+        struct Test {
+            name: String,
+            device: device::Device,
+            resp: adr::Response,
+            expected_resp: adr::Response,
+        }
+
+        let tests = vec![
+            Test {
+                name: "no overrides".into(),
+                device: device::Device::default(),
+                resp: adr::Response {
+                    dr: 5,
+                    tx_power_index: 7,
+                    nb_trans: 3,
+                },
+                expected_resp: adr::Response {
+                    dr: 5,
+                    tx_power_index: 7,
+                    nb_trans: 3,
+                },
+            },
+            Test {
+                name: "overrides are clamped".into(),
+                device: device::Device {
+                    adr_max_dr: Some(4),
+                    adr_max_tx_power_index: Some(5),
+                    adr_nb_trans: Some(0),
+                    ..Default::default()
+                },
+                resp: adr::Response {
+                    dr: 5,
+                    tx_power_index: 7,
+                    nb_trans: 3,
+                },
+                expected_resp: adr::Response {
+                    dr: 4,
+                    tx_power_index: 5,
+                    nb_trans: 1,
+                },
+            },
+            Test {
+                name: "pinned parameters take precedence".into(),
+                device: device::Device {
+                    adr_max_dr: Some(4),
+                    adr_pinned_dr: Some(5),
+                    adr_pinned_tx_power_index: Some(1),
+                    ..Default::default()
+                },
+                resp: adr::Response {
+                    dr: 2,
+                    tx_power_index: 3,
+                    nb_trans: 1,
+                },
+                expected_resp: adr::Response {
+                    dr: 5,
+                    tx_power_index: 1,
+                    nb_trans: 1,
+                },
+            },
+            Test {
+                name: "invalid pinned parameters".into(),
+                device: device::Device {
+                    adr_pinned_dr: Some(11),
+                    adr_pinned_tx_power_index: Some(9),
+                    ..Default::default()
+                },
+                resp: adr::Response {
+                    dr: 2,
+                    tx_power_index: 3,
+                    nb_trans: 1,
+                },
+                expected_resp: adr::Response {
+                    dr: 2,
+                    tx_power_index: 5,
+                    nb_trans: 1,
+                },
+            },
+        ];
+
+        for tst in tests {
+            let mut resp = tst.resp;
+            apply_device_link_parameters(&tst.device, &region_conf, &req, &mut resp);
+            assert_eq!(tst.expected_resp, resp, "{}", tst.name);
+        }
+    }
     #[tokio::test]
     async fn test_update_uplink_list() {
         struct Test {
@@ -0,0 +1,466 @@
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use tracing::{info, span, warn, Instrument, Level};
+
+use crate::downlink;
+use crate::gpstime::{ToDateTime, ToGpsTime};
+use crate::storage::fields::FuotaDeploymentStep;
+use crate::storage::{device_keys, device_profile, device_queue, fuota, multicast};
+use lrwn::applayer::{fragmentation, multicastsetup};
+use lrwn::region::MacVersion;
+use lrwn::{AES128Key, EUI64};
+
+// The id of the multicast-group (within the device) used for the deployment.
+const MC_GROUP_ID: u8 = 0;
+
+pub struct Flow {
+    fuota_deployment: fuota::FuotaDeployment,
+    device_profile: device_profile::DeviceProfile,
+}
+
+impl Flow {
+    pub async fn handle_deployment(fd: fuota::FuotaDeployment) -> Result<()> {
+        let span = span!(Level::INFO, "fuota", fuota_deployment_id = %fd.id, step = %fd.next_step);
+
+        Flow::_handle_deployment(fd).instrument(span).await
+    }
+
+    async fn _handle_deployment(fd: fuota::FuotaDeployment) -> Result<()> {
+        let dp = device_profile::get(&fd.device_profile_id).await?;
+
+        let mut ctx = Flow {
+            fuota_deployment: fd,
+            device_profile: dp,
+        };
+
+        match ctx.fuota_deployment.next_step {
+            FuotaDeploymentStep::MC_GROUP_SETUP => ctx.multicast_group_setup().await?,
+            FuotaDeploymentStep::FRAG_SESSION_SETUP => ctx.fragmentation_session_setup().await?,
+            FuotaDeploymentStep::MC_SESSION => ctx.multicast_session_setup().await?,
+            FuotaDeploymentStep::ENQUEUE => ctx.enqueue().await?,
+            FuotaDeploymentStep::FRAG_STATUS => ctx.fragmentation_status().await?,
+            FuotaDeploymentStep::COMPLETE => ctx.complete().await?,
+        }
+
+        fuota::update(ctx.fuota_deployment).await?;
+
+        Ok(())
+    }
+
+    async fn multicast_group_setup(&mut self) -> Result<()> {
+        if self.fuota_deployment.multicast_group_id.is_none() {
+            self.create_multicast_group().await?;
+        }
+
+        let devices = self
+            .get_pending_devices(|d| d.mc_group_setup_completed_at.is_none())
+            .await?;
+
+        if devices.is_empty() || self.max_retry_count_exceeded() {
+            self.set_timeout_error(devices, "McGroupSetupReq").await?;
+            self.set_next_step(FuotaDeploymentStep::FRAG_SESSION_SETUP);
+            return Ok(());
+        }
+
+        info!(
+            device_count = devices.len(),
+            "Enqueueing McGroupSetupReq to devices"
+        );
+
+        for d in &devices {
+            let dk = device_keys::get(&d.dev_eui).await?;
+
+            // LoRaWAN 1.0.x devices derive the McRootKey from the GenAppKey. Devices
+            // without GenAppKey would not be able to decrypt the McKey.
+            let mc_root_key = match self.device_profile.mac_version {
+                MacVersion::LORAWAN_1_0_0
+                | MacVersion::LORAWAN_1_0_1
+                | MacVersion::LORAWAN_1_0_2
+                | MacVersion::LORAWAN_1_0_3
+                | MacVersion::LORAWAN_1_0_4 => {
+                    if dk.gen_app_key == AES128Key::null() {
+                        warn!(dev_eui = %d.dev_eui, "Device has no GenAppKey, skipping McGroupSetupReq");
+                        let mut d = d.clone();
+                        d.error_msg = "GenAppKey is not set".into();
+                        fuota::update_device(d).await?;
+                        continue;
+                    }
+
+                    multicastsetup::get_mc_root_key_for_gen_app_key(&dk.gen_app_key)?
+                }
+                MacVersion::LORAWAN_1_1_0 | MacVersion::Latest => {
+                    multicastsetup::get_mc_root_key_for_app_key(&dk.app_key)?
+                }
+            };
+            let mc_ke_key = multicastsetup::get_mc_ke_key(&mc_root_key)?;
+            let mc_key_encrypted =
+                multicastsetup::encrypt_mc_key(&mc_ke_key, &self.fuota_deployment.multicast_key)?;
+
+            let pl =
+                multicastsetup::Payload::McGroupSetupReq(multicastsetup::McGroupSetupReqPayload {
+                    mc_group_id: MC_GROUP_ID,
+                    mc_addr: self.fuota_deployment.multicast_addr,
+                    mc_key_encrypted,
+                    min_mc_f_count: 0,
+                    max_mc_f_count: u32::MAX,
+                });
+
+            enqueue_unicast(
+                d.dev_eui,
+                lrwn::applayer::FPORT_MULTICAST_SETUP,
+                pl.to_vec()?,
+            )
+            .await?;
+        }
+
+        self.set_retry();
+        Ok(())
+    }
+
+    async fn fragmentation_session_setup(&mut self) -> Result<()> {
+        let devices = self
+            .get_pending_devices(|d| {
+                d.mc_group_setup_completed_at.is_some()
+                    && d.frag_session_setup_completed_at.is_none()
+            })
+            .await?;
+
+        if devices.is_empty() || self.max_retry_count_exceeded() {
+            self.set_timeout_error(devices, "FragSessionSetupReq")
+                .await?;
+            self.set_next_step(FuotaDeploymentStep::MC_SESSION);
+            return Ok(());
+        }
+
+        info!(
+            device_count = devices.len(),
+            "Enqueueing FragSessionSetupReq to devices"
+        );
+
+        let fragment_size = self.fuota_deployment.fragmentation_fragment_size as usize;
+        let padding = self.get_padding();
+        let nb_frag = (self.fuota_deployment.payload.len() + padding) / fragment_size;
+
+        let mut descriptor = [0; 4];
+        descriptor.copy_from_slice(&self.fuota_deployment.fragmentation_descriptor);
+
+        let pl = fragmentation::Payload::FragSessionSetupReq(
+            fragmentation::FragSessionSetupReqPayload {
+                frag_index: self.fuota_deployment.fragmentation_session_index as u8,
+                // Only the multicast-group with MC_GROUP_ID is used.
+                mc_group_bit_mask: [true, false, false, false],
+                nb_frag: nb_frag as u16,
+                frag_size: fragment_size as u8,
+                fragmentation_matrix: self.fuota_deployment.fragmentation_matrix as u8,
+                block_ack_delay: self.fuota_deployment.fragmentation_block_ack_delay as u8,
+                padding: padding as u8,
+                descriptor,
+            },
+        )
+        .to_vec()?;
+
+        for d in &devices {
+            enqueue_unicast(d.dev_eui, lrwn::applayer::FPORT_FRAGMENTATION, pl.clone()).await?;
+        }
+
+        self.set_retry();
+        Ok(())
+    }
+
+    async fn multicast_session_setup(&mut self) -> Result<()> {
+        let session_start = match self.fuota_deployment.multicast_session_start {
+            Some(v) => v,
+            None => {
+                // The session must start after all the (unicast) retries.
+                let mut session_start = Utc::now()
+                    + Duration::seconds(
+                        self.fuota_deployment.unicast_timeout as i64
+                            * (self.fuota_deployment.unicast_max_retry_count as i64 + 1),
+                    );
+
+                // Class-B sessions start at a beacon boundary.
+                if self.fuota_deployment.multicast_group_type == "B" {
+                    let gps_secs = session_start.to_gps_time().num_seconds();
+                    let gps_secs = gps_secs - (gps_secs % 128) + 128;
+                    session_start = Duration::seconds(gps_secs).to_date_time();
+                }
+
+                self.fuota_deployment.multicast_session_start = Some(session_start);
+                session_start
+            }
+        };
+
+        let devices = self
+            .get_pending_devices(|d| {
+                d.frag_session_setup_completed_at.is_some() && d.mc_session_completed_at.is_none()
+            })
+            .await?;
+
+        if devices.is_empty() || self.max_retry_count_exceeded() {
+            self.set_timeout_error(devices, "McClassB/CSessionReq")
+                .await?;
+            self.fuota_deployment.next_step = FuotaDeploymentStep::ENQUEUE;
+            self.fuota_deployment.retry_count = 0;
+            self.fuota_deployment.scheduler_run_after = session_start;
+            return Ok(());
+        }
+
+        info!(
+            device_count = devices.len(),
+            session_start = %session_start,
+            "Enqueueing McClassB/CSessionReq to devices"
+        );
+
+        // Session time is the number of seconds since GPS epoch (modulo 2^32).
+        let session_time = (session_start.to_gps_time().num_seconds() % (1 << 32)) as u32;
+
+        let pl = match self.fuota_deployment.multicast_group_type.as_ref() {
+            "B" => multicastsetup::Payload::McClassBSessionReq(
+                multicastsetup::McClassBSessionReqPayload {
+                    mc_group_id: MC_GROUP_ID,
+                    session_time,
+                    time_out: self.fuota_deployment.multicast_timeout as u8,
+                    periodicity: (self.fuota_deployment.multicast_class_b_ping_slot_period / 32)
+                        .max(1)
+                        .ilog2() as u8,
+                    dl_frequency: self.fuota_deployment.multicast_frequency as u32,
+                    dr: self.fuota_deployment.multicast_dr as u8,
+                },
+            ),
+            "C" => multicastsetup::Payload::McClassCSessionReq(
+                multicastsetup::McClassCSessionReqPayload {
+                    mc_group_id: MC_GROUP_ID,
+                    session_time,
+                    session_time_out: self.fuota_deployment.multicast_timeout as u8,
+                    dl_frequency: self.fuota_deployment.multicast_frequency as u32,
+                    dr: self.fuota_deployment.multicast_dr as u8,
+                },
+            ),
+            _ => {
+                return Err(anyhow!(
+                    "Unexpected multicast-group type: {}",
+                    self.fuota_deployment.multicast_group_type
+                ));
+            }
+        }
+        .to_vec()?;
+
+        for d in &devices {
+            enqueue_unicast(d.dev_eui, lrwn::applayer::FPORT_MULTICAST_SETUP, pl.clone()).await?;
+        }
+
+        self.set_retry();
+        Ok(())
+    }
+
+    async fn enqueue(&mut self) -> Result<()> {
+        let devices = self
+            .get_pending_devices(|d| d.mc_session_completed_at.is_some())
+            .await?;
+
+        if devices.is_empty() {
+            info!("No devices left to send the fragments to, completing deployment");
+            self.set_next_step(FuotaDeploymentStep::COMPLETE);
+            return Ok(());
+        }
+
+        let multicast_group_id = self
+            .fuota_deployment
+            .multicast_group_id
+            .ok_or_else(|| anyhow!("Multicast-group is not set"))?;
+
+        let fragment_size = self.fuota_deployment.fragmentation_fragment_size as usize;
+        let mut payload = self.fuota_deployment.payload.clone();
+        payload.extend_from_slice(&vec![0; self.get_padding()]);
+
+        let nb_frag = payload.len() / fragment_size;
+        let redundancy =
+            nb_frag * self.fuota_deployment.fragmentation_redundancy_percentage as usize / 100;
+        let fragments = fragmentation::encode(&payload, fragment_size, redundancy)?;
+
+        info!(
+            fragment_count = fragments.len(),
+            redundancy = redundancy,
+            "Enqueueing fragments to multicast-group"
+        );
+
+        for (i, fragment) in fragments.into_iter().enumerate() {
+            let pl = fragmentation::Payload::DataFragment(fragmentation::DataFragmentPayload {
+                frag_index: self.fuota_deployment.fragmentation_session_index as u8,
+                n: (i + 1) as u16,
+                payload: fragment,
+            });
+
+            downlink::multicast::enqueue(multicast::MulticastGroupQueueItem {
+                multicast_group_id,
+                f_port: lrwn::applayer::FPORT_FRAGMENTATION as i16,
+                data: pl.to_vec()?,
+                ..Default::default()
+            })
+            .await?;
+        }
+
+        // Wait until the end of the multicast-session before requesting the
+        // fragmentation status.
+        let session_duration = match self.fuota_deployment.multicast_group_type.as_ref() {
+            // Class-B: 2^timeout beacon periods.
+            "B" => Duration::seconds((1 << self.fuota_deployment.multicast_timeout) * 128),
+            // Class-C: 2^timeout seconds.
+            _ => Duration::seconds(1 << self.fuota_deployment.multicast_timeout),
+        };
+
+        self.fuota_deployment.next_step = FuotaDeploymentStep::FRAG_STATUS;
+        self.fuota_deployment.retry_count = 0;
+        self.fuota_deployment.scheduler_run_after = self
+            .fuota_deployment
+            .multicast_session_start
+            .unwrap_or_else(Utc::now)
+            + session_duration;
+
+        Ok(())
+    }
+
+    async fn fragmentation_status(&mut self) -> Result<()> {
+        let devices = self
+            .get_pending_devices(|d| {
+                d.mc_session_completed_at.is_some() && d.frag_status_completed_at.is_none()
+            })
+            .await?;
+
+        if devices.is_empty() || self.max_retry_count_exceeded() {
+            self.set_timeout_error(devices, "FragSessionStatusReq")
+                .await?;
+            self.set_next_step(FuotaDeploymentStep::COMPLETE);
+            return Ok(());
+        }
+
+        info!(
+            device_count = devices.len(),
+            "Enqueueing FragSessionStatusReq to devices"
+        );
+
+        let pl = fragmentation::Payload::FragSessionStatusReq(
+            fragmentation::FragSessionStatusReqPayload {
+                participants: true,
+                frag_index: self.fuota_deployment.fragmentation_session_index as u8,
+            },
+        )
+        .to_vec()?;
+
+        for d in &devices {
+            enqueue_unicast(d.dev_eui, lrwn::applayer::FPORT_FRAGMENTATION, pl.clone()).await?;
+        }
+
+        self.set_retry();
+        Ok(())
+    }
+
+    async fn complete(&mut self) -> Result<()> {
+        let devices = self
+            .get_pending_devices(|d| d.frag_status_completed_at.is_none())
+            .await?;
+        for mut d in devices {
+            d.error_msg = "Deployment did not complete".into();
+            fuota::update_device(d).await?;
+        }
+
+        if let Some(multicast_group_id) = self.fuota_deployment.multicast_group_id.take() {
+            multicast::delete(&multicast_group_id).await?;
+        }
+
+        info!("FUOTA deployment completed");
+        self.fuota_deployment.completed_at = Some(Utc::now());
+
+        Ok(())
+    }
+
+    async fn create_multicast_group(&mut self) -> Result<()> {
+        let mc_key = &self.fuota_deployment.multicast_key;
+        let mc_addr = &self.fuota_deployment.multicast_addr;
+
+        let mg = multicast::create(multicast::MulticastGroup {
+            application_id: self.fuota_deployment.application_id,
+            name: format!("fuota-{}", self.fuota_deployment.id),
+            region: self.device_profile.region,
+            mc_addr: *mc_addr,
+            mc_nwk_s_key: multicastsetup::get_mc_net_s_key(mc_key, mc_addr)?,
+            mc_app_s_key: multicastsetup::get_mc_app_s_key(mc_key, mc_addr)?,
+            group_type: self.fuota_deployment.multicast_group_type.clone(),
+            dr: self.fuota_deployment.multicast_dr,
+            frequency: self.fuota_deployment.multicast_frequency,
+            class_b_ping_slot_period: self.fuota_deployment.multicast_class_b_ping_slot_period,
+            class_c_scheduling_type: self.fuota_deployment.multicast_class_c_scheduling_type,
+            ..Default::default()
+        })
+        .await?;
+
+        for d in fuota::get_devices(&self.fuota_deployment.id).await? {
+            multicast::add_device(&mg.id, &d.dev_eui).await?;
+        }
+
+        info!(multicast_group_id = %mg.id, "Multicast-group created for FUOTA deployment");
+        self.fuota_deployment.multicast_group_id = Some(mg.id);
+
+        // Persist the multicast-group id, such that it is not created twice
+        // in case one of the next actions fails.
+        self.fuota_deployment = fuota::update(self.fuota_deployment.clone()).await?;
+
+        Ok(())
+    }
+
+    // Returns the devices without error, for which the given filter returns true.
+    async fn get_pending_devices<F>(&self, f: F) -> Result<Vec<fuota::FuotaDeploymentDevice>>
+    where
+        F: Fn(&fuota::FuotaDeploymentDevice) -> bool,
+    {
+        Ok(fuota::get_devices(&self.fuota_deployment.id)
+            .await?
+            .into_iter()
+            .filter(|d| d.error_msg.is_empty() && f(d))
+            .collect())
+    }
+
+    async fn set_timeout_error(
+        &self,
+        devices: Vec<fuota::FuotaDeploymentDevice>,
+        command: &str,
+    ) -> Result<()> {
+        for mut d in devices {
+            d.error_msg = format!("{} timeout", command);
+            fuota::update_device(d).await?;
+        }
+        Ok(())
+    }
+
+    fn max_retry_count_exceeded(&self) -> bool {
+        self.fuota_deployment.retry_count > self.fuota_deployment.unicast_max_retry_count
+    }
+
+    fn set_retry(&mut self) {
+        self.fuota_deployment.retry_count += 1;
+        self.fuota_deployment.scheduler_run_after =
+            Utc::now() + Duration::seconds(self.fuota_deployment.unicast_timeout as i64);
+    }
+
+    fn set_next_step(&mut self, step: FuotaDeploymentStep) {
+        self.fuota_deployment.next_step = step;
+        self.fuota_deployment.retry_count = 0;
+        self.fuota_deployment.scheduler_run_after = Utc::now();
+    }
+
+    fn get_padding(&self) -> usize {
+        let fragment_size = self.fuota_deployment.fragmentation_fragment_size as usize;
+        (fragment_size - (self.fuota_deployment.payload.len() % fragment_size)) % fragment_size
+    }
+}
+
+async fn enqueue_unicast(dev_eui: EUI64, f_port: u8, data: Vec<u8>) -> Result<()> {
+    device_queue::enqueue_item(device_queue::DeviceQueueItem {
+        dev_eui,
+        f_port: f_port as i16,
+        data,
+        ..Default::default()
+    })
+    .await?;
+    Ok(())
+}
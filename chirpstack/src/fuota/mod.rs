@@ -0,0 +1,11 @@
+use tracing::info;
+
+pub mod flow;
+pub mod scheduler;
+
+pub async fn setup() {
+    info!("Setting up FUOTA scheduler loop");
+    tokio::spawn(async move {
+        scheduler::fuota_scheduler_loop().await;
+    });
+}
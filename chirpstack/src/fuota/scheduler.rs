@@ -0,0 +1,47 @@
+use anyhow::Result;
+use tokio::time::sleep;
+use tracing::{error, trace};
+
+use super::flow;
+use crate::config;
+use crate::storage::fuota;
+
+pub async fn fuota_scheduler_loop() {
+    let conf = config::get();
+
+    loop {
+        trace!("Starting FUOTA scheduler loop run");
+
+        if let Err(err) = schedule_fuota_deployments_batch(conf.network.scheduler.batch_size).await
+        {
+            error!(error = %err, "Scheduling FUOTA deployments batch failed");
+        } else {
+            trace!("FUOTA scheduler loop run completed successfully");
+        }
+
+        sleep(conf.network.scheduler.interval).await;
+    }
+}
+
+pub async fn schedule_fuota_deployments_batch(size: usize) -> Result<()> {
+    trace!("Getting schedulable FUOTA deployments");
+    let deployments = fuota::get_schedulable_deployments(size).await?;
+    trace!(
+        count = deployments.len(),
+        "Got this number of FUOTA deployments"
+    );
+
+    let mut handles = vec![];
+
+    for fd in deployments {
+        let handle = tokio::spawn(async move {
+            if let Err(e) = flow::Flow::handle_deployment(fd).await {
+                error!(error = %e, "Handle FUOTA deployment step failed");
+            }
+        });
+        handles.push(handle);
+    }
+
+    futures::future::join_all(handles).await;
+    Ok(())
+}
@@ -0,0 +1,755 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use futures::{SinkExt, StreamExt};
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, RwLock};
+use tokio_rustls::rustls;
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, error, info, trace, warn};
+use warp::http::StatusCode;
+use warp::ws::{Message, WebSocket, Ws};
+use warp::{Filter, Reply};
+
+use super::GatewayBackend;
+use crate::config::{self, GatewayBackendBasicStation, GatewayChannel};
+use crate::gpstime::ToGpsTime;
+use crate::monitoring::prometheus;
+use crate::storage::downlink_frame;
+use crate::{certificate, downlink, region, uplink};
+use chirpstack_api::gw;
+use lrwn::region::CommonName;
+use lrwn::EUI64;
+
+mod structs;
+
+use structs::{
+    DownlinkMessage, RouterConfig, RouterId, RouterInfoRequest, RouterInfoResponse,
+    TimeSyncResponse, UpstreamMessage,
+};
+
+// Pending downlinks for which no dntxed has been received within this duration are removed.
+const PENDING_DOWNLINK_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+struct EventLabels {
+    event: String,
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+struct CommandLabels {
+    command: String,
+}
+
+lazy_static! {
+    static ref EVENT_COUNTER: Family<EventLabels, Counter> = {
+        let counter = Family::<EventLabels, Counter>::default();
+        prometheus::register(
+            "gateway_backend_basic_station_events",
+            "Number of events received",
+            counter.clone(),
+        );
+        counter
+    };
+    static ref COMMAND_COUNTER: Family<CommandLabels, Counter> = {
+        let counter = Family::<CommandLabels, Counter>::default();
+        prometheus::register(
+            "gateway_backend_basic_station_commands",
+            "Number of commands sent",
+            counter.clone(),
+        );
+        counter
+    };
+}
+
+// Basics Station does not send gateway stats, these counters are used to generate them.
+#[derive(Default)]
+struct Counters {
+    rx_packets_received: u32,
+    rx_packets_received_ok: u32,
+    tx_packets_received: u32,
+    tx_packets_emitted: u32,
+}
+
+struct Gateway {
+    sender: mpsc::UnboundedSender<Message>,
+    counters: Arc<Mutex<Counters>>,
+}
+
+struct PendingDownlink {
+    downlink_frame: gw::DownlinkFrame,
+    created_at: Instant,
+}
+
+#[derive(Default)]
+struct State {
+    gateways: HashMap<EUI64, Gateway>,
+    downlinks: HashMap<(EUI64, u32), PendingDownlink>,
+}
+
+// Common-name of the client-certificate of the TLS connection.
+#[derive(Clone)]
+struct ClientCommonName(String);
+
+struct Context {
+    region_config_id: String,
+    region_common_name: CommonName,
+    router_config: String,
+    stats_interval: Duration,
+    ping_interval: Duration,
+    state: RwLock<State>,
+}
+
+pub struct BasicStationBackend {
+    ctx: Arc<Context>,
+}
+
+impl BasicStationBackend {
+    pub async fn new(
+        region_config_id: &str,
+        region_common_name: CommonName,
+        channels: &[GatewayChannel],
+        conf: &GatewayBackendBasicStation,
+    ) -> Result<BasicStationBackend> {
+        info!(region_config_id = %region_config_id, bind = %conf.bind, "Starting Basics Station backend");
+
+        let addr: SocketAddr = conf.bind.parse().context("Parse bind address")?;
+        let region_conf = region::get(region_config_id)?;
+        let router_config = serde_json::to_string(&RouterConfig::new(&**region_conf, channels)?)?;
+
+        let ctx = Arc::new(Context {
+            region_config_id: region_config_id.to_string(),
+            region_common_name,
+            router_config,
+            stats_interval: conf.stats_interval,
+            ping_interval: conf.ping_interval,
+            state: RwLock::new(State::default()),
+        });
+
+        // When no CA certificate is configured, we fallback to the gateway CA certificate
+        // such that the client-certificates generated for gateways can be used.
+        let ca_cert = if conf.ca_cert.is_empty() {
+            config::get().gateway.ca_cert.clone()
+        } else {
+            conf.ca_cert.clone()
+        };
+        if conf.tls_cert.is_empty() != conf.tls_key.is_empty() {
+            return Err(anyhow!(
+                "Both tls_cert and tls_key must be configured to enable TLS"
+            ));
+        }
+        let tls = !conf.tls_cert.is_empty();
+        let client_auth = tls && !ca_cert.is_empty();
+
+        let router_info = warp::path!("router-info")
+            .and(warp::ws())
+            .and(warp::header::optional::<String>("host"))
+            .map({
+                let bind = conf.bind.clone();
+                move |ws: Ws, host: Option<String>| {
+                    let host = host.unwrap_or_else(|| bind.clone());
+                    ws.on_upgrade(move |socket| handle_router_info(socket, host, tls))
+                }
+            });
+
+        let gateway = warp::path!("gateway" / String)
+            .and(warp::ws())
+            .and(warp::ext::optional::<ClientCommonName>())
+            .map({
+                let ctx = ctx.clone();
+                move |gateway_id: String, ws: Ws, cn: Option<ClientCommonName>| -> Box<dyn Reply> {
+                    // When client-certificates are required, the gateway can only connect
+                    // using the certificate that has been generated for its gateway ID.
+                    if client_auth {
+                        if let Err(e) = validate_client_cn(&gateway_id, cn.as_ref()) {
+                            warn!(gateway_id = %gateway_id, error = %e, "Client-certificate validation failed");
+                            return Box::new(StatusCode::FORBIDDEN);
+                        }
+                    }
+
+                    let ctx = ctx.clone();
+                    Box::new(ws.on_upgrade(move |socket| handle_gateway(ctx, socket, gateway_id)))
+                }
+            });
+
+        let routes = router_info.or(gateway);
+
+        if tls {
+            let acceptor = get_tls_acceptor(&conf.tls_cert, &conf.tls_key, &ca_cert)?;
+            let listener = TcpListener::bind(addr).await.context("Bind TCP listener")?;
+            let svc = warp::service(routes);
+
+            // The TLS connections are accepted by us (rather than by warp), such that the
+            // common-name of the client-certificate can be passed to the request handlers.
+            tokio::spawn(async move {
+                loop {
+                    let (stream, remote_addr) = match listener.accept().await {
+                        Ok(v) => v,
+                        Err(e) => {
+                            error!(error = %e, "Accept TCP connection error");
+                            continue;
+                        }
+                    };
+
+                    let acceptor = acceptor.clone();
+                    let svc = svc.clone();
+
+                    tokio::spawn(async move {
+                        let stream = match acceptor.accept(stream).await {
+                            Ok(v) => v,
+                            Err(e) => {
+                                warn!(remote_addr = %remote_addr, error = %e, "TLS handshake error");
+                                return;
+                            }
+                        };
+
+                        let cn = match stream
+                            .get_ref()
+                            .1
+                            .peer_certificates()
+                            .and_then(|v| v.first())
+                            .map(|v| certificate::common_name_from_der(&v.0))
+                            .transpose()
+                        {
+                            Ok(v) => v.map(ClientCommonName),
+                            Err(e) => {
+                                warn!(remote_addr = %remote_addr, error = %e, "Read client-certificate common-name error");
+                                return;
+                            }
+                        };
+
+                        let svc = hyper::service::service_fn(
+                            move |mut req: hyper::Request<hyper::Body>| {
+                                if let Some(cn) = &cn {
+                                    req.extensions_mut().insert(cn.clone());
+                                }
+                                let mut svc = svc.clone();
+                                hyper::service::Service::call(&mut svc, req)
+                            },
+                        );
+
+                        if let Err(e) = hyper::server::conn::Http::new()
+                            .serve_connection(stream, svc)
+                            .with_upgrades()
+                            .await
+                        {
+                            debug!(remote_addr = %remote_addr, error = %e, "Serve connection error");
+                        }
+                    });
+                }
+            });
+        } else {
+            let (_, srv) = warp::serve(routes)
+                .try_bind_ephemeral(addr)
+                .context("Bind TCP listener")?;
+            tokio::spawn(srv);
+        }
+
+        Ok(BasicStationBackend { ctx })
+    }
+}
+
+#[async_trait]
+impl GatewayBackend for BasicStationBackend {
+    async fn send_downlink(&self, df: &gw::DownlinkFrame) -> Result<()> {
+        COMMAND_COUNTER
+            .get_or_create(&CommandLabels {
+                command: "dnmsg".to_string(),
+            })
+            .inc();
+
+        let gateway_id: EUI64 = df.gateway_id.parse()?;
+        let region_conf = region::get(&self.ctx.region_config_id)?;
+
+        // The DevEUI is not part of the downlink frame. It is read from the downlink-frame
+        // context, which is stored before the downlink is sent.
+        let dev_eui = match downlink_frame::get(df.downlink_id).await {
+            Ok(v) if !v.dev_eui.is_empty() => Some(EUI64::from_slice(&v.dev_eui)?),
+            Ok(_) => None,
+            Err(e) => {
+                warn!(downlink_id = df.downlink_id, error = %e, "Get downlink-frame context error");
+                None
+            }
+        };
+
+        let dm = DownlinkMessage::from_downlink_frame(&**region_conf, df, dev_eui.as_ref())?;
+        let msg = Message::text(serde_json::to_string(&dm)?);
+
+        let mut state = self.ctx.state.write().await;
+        let gw = state
+            .gateways
+            .get(&gateway_id)
+            .ok_or_else(|| anyhow!("Gateway {} is not connected", gateway_id))?;
+
+        gw.counters.lock().unwrap().tx_packets_received += 1;
+
+        info!(gateway_id = %gateway_id, downlink_id = df.downlink_id, "Sending downlink frame");
+        gw.sender
+            .send(msg)
+            .map_err(|_| anyhow!("Gateway {} connection is closed", gateway_id))?;
+
+        state
+            .downlinks
+            .retain(|_, v| v.created_at.elapsed() < PENDING_DOWNLINK_TTL);
+        state.downlinks.insert(
+            (gateway_id, df.downlink_id),
+            PendingDownlink {
+                downlink_frame: df.clone(),
+                created_at: Instant::now(),
+            },
+        );
+
+        Ok(())
+    }
+
+    async fn send_configuration(&self, gw_conf: &gw::GatewayConfiguration) -> Result<()> {
+        Err(anyhow!(
+            "Gateway {} configuration update is not supported by the Basics Station backend, the router_config is sent on connect",
+            gw_conf.gateway_id
+        ))
+    }
+}
+
+async fn handle_router_info(socket: WebSocket, host: String, tls: bool) {
+    let (mut ws_tx, mut ws_rx) = socket.split();
+
+    // The router-info connection is closed after the response has been sent.
+    if let Some(msg) = ws_rx.next().await {
+        let msg = match msg {
+            Ok(v) => v,
+            Err(e) => {
+                error!(error = %e, "Read router-info message error");
+                return;
+            }
+        };
+
+        let text = match msg.to_str() {
+            Ok(v) => v,
+            Err(_) => {
+                warn!("Expected router-info request as text message");
+                return;
+            }
+        };
+
+        let resp = match serde_json::from_str::<RouterInfoRequest>(text)
+            .context("Parse router-info request")
+            .and_then(|v| v.router.to_eui64())
+        {
+            Ok(gateway_id) => {
+                info!(gateway_id = %gateway_id, "Router-info request received");
+                RouterInfoResponse {
+                    router: structs::format_id6(&gateway_id),
+                    muxs: "::0".into(),
+                    uri: format!(
+                        "{}://{}/gateway/{}",
+                        if tls { "wss" } else { "ws" },
+                        host,
+                        gateway_id
+                    ),
+                    error: "".into(),
+                }
+            }
+            Err(e) => {
+                warn!(error = %e, "Invalid router-info request");
+                RouterInfoResponse {
+                    router: "".into(),
+                    muxs: "".into(),
+                    uri: "".into(),
+                    error: e.to_string(),
+                }
+            }
+        };
+
+        let resp = match serde_json::to_string(&resp) {
+            Ok(v) => v,
+            Err(e) => {
+                error!(error = %e, "Encode router-info response error");
+                return;
+            }
+        };
+
+        if let Err(e) = ws_tx.send(Message::text(resp)).await {
+            error!(error = %e, "Send router-info response error");
+        }
+    }
+
+    let _ = ws_tx.close().await;
+}
+
+async fn handle_gateway(ctx: Arc<Context>, socket: WebSocket, gateway_id: String) {
+    let gateway_id = match RouterId::Str(gateway_id.clone()).to_eui64() {
+        Ok(v) => v,
+        Err(e) => {
+            warn!(gateway_id = %gateway_id, error = %e, "Invalid gateway ID");
+            return;
+        }
+    };
+
+    info!(region_config_id = %ctx.region_config_id, gateway_id = %gateway_id, "Gateway connected");
+
+    let (mut ws_tx, mut ws_rx) = socket.split();
+    let (sender, mut receiver) = mpsc::unbounded_channel::<Message>();
+    let counters: Arc<Mutex<Counters>> = Arc::new(Mutex::new(Counters::default()));
+
+    {
+        let mut state = ctx.state.write().await;
+        state.gateways.insert(
+            gateway_id,
+            Gateway {
+                sender: sender.clone(),
+                counters: counters.clone(),
+            },
+        );
+    }
+
+    // Writer loop.
+    let writer = tokio::spawn(async move {
+        while let Some(msg) = receiver.recv().await {
+            if let Err(e) = ws_tx.send(msg).await {
+                error!(gateway_id = %gateway_id, error = %e, "Send websocket message error");
+                break;
+            }
+        }
+        let _ = ws_tx.close().await;
+    });
+
+    // Ping and stats loop.
+    let ticker = tokio::spawn({
+        let ctx = ctx.clone();
+        let sender = sender.clone();
+        let counters = counters.clone();
+
+        async move {
+            let mut ping_interval = tokio::time::interval(ctx.ping_interval);
+            let mut stats_interval = tokio::time::interval(ctx.stats_interval);
+
+            loop {
+                tokio::select! {
+                    _ = ping_interval.tick() => {
+                        if sender.send(Message::ping(vec![])).is_err() {
+                            return;
+                        }
+                    }
+                    _ = stats_interval.tick() => {
+                        send_stats(&ctx, &gateway_id, &counters);
+                    }
+                }
+            }
+        }
+    });
+
+    while let Some(msg) = ws_rx.next().await {
+        let msg = match msg {
+            Ok(v) => v,
+            Err(e) => {
+                error!(gateway_id = %gateway_id, error = %e, "Read websocket message error");
+                break;
+            }
+        };
+
+        if msg.is_close() {
+            break;
+        }
+
+        let text = match msg.to_str() {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+
+        if let Err(e) = handle_message(&ctx, &gateway_id, &sender, &counters, text).await {
+            error!(region_config_id = %ctx.region_config_id, gateway_id = %gateway_id, error = %e, "Handle websocket message error");
+        }
+    }
+
+    info!(region_config_id = %ctx.region_config_id, gateway_id = %gateway_id, "Gateway disconnected");
+
+    // The gateway might have re-connected in the meantime, in which case the state must not
+    // be removed.
+    {
+        let mut state = ctx.state.write().await;
+        if state
+            .gateways
+            .get(&gateway_id)
+            .map(|v| v.sender.same_channel(&sender))
+            .unwrap_or_default()
+        {
+            state.gateways.remove(&gateway_id);
+        }
+    }
+
+    ticker.abort();
+    drop(sender);
+    let _ = writer.await;
+}
+
+async fn handle_message(
+    ctx: &Context,
+    gateway_id: &EUI64,
+    sender: &mpsc::UnboundedSender<Message>,
+    counters: &Mutex<Counters>,
+    text: &str,
+) -> Result<()> {
+    trace!(gateway_id = %gateway_id, message = %text, "Websocket message received");
+
+    let msg: UpstreamMessage = serde_json::from_str(text).context("Parse message")?;
+    let region_conf = region::get(&ctx.region_config_id)?;
+
+    let uplink_frame = match msg {
+        UpstreamMessage::Version(v) => {
+            inc_event("version");
+            info!(gateway_id = %gateway_id, station = %v.station, model = %v.model, protocol = v.protocol, "Version message received, sending router_config");
+
+            sender
+                .send(Message::text(ctx.router_config.clone()))
+                .map_err(|_| anyhow!("Send router_config error"))?;
+            return Ok(());
+        }
+        UpstreamMessage::UplinkDataFrame(v) => {
+            inc_event("updf");
+            v.to_uplink_frame(&**region_conf, gateway_id)
+        }
+        UpstreamMessage::JoinRequest(v) => {
+            inc_event("jreq");
+            v.to_uplink_frame(&**region_conf, gateway_id)
+        }
+        UpstreamMessage::ProprietaryDataFrame(v) => {
+            inc_event("propdf");
+            v.to_uplink_frame(&**region_conf, gateway_id)
+        }
+        UpstreamMessage::DownlinkTransmitted(v) => {
+            inc_event("dntxed");
+            counters.lock().unwrap().tx_packets_emitted += 1;
+            return handle_dntxed(ctx, gateway_id, v).await;
+        }
+        UpstreamMessage::TimeSync(v) => {
+            inc_event("timesync");
+            let resp = TimeSyncResponse::new(v.txtime, Utc::now().to_gps_time());
+            sender
+                .send(Message::text(serde_json::to_string(&resp)?))
+                .map_err(|_| anyhow!("Send timesync response error"))?;
+            return Ok(());
+        }
+        UpstreamMessage::Unknown => {
+            debug!(gateway_id = %gateway_id, "Ignoring unknown message-type");
+            return Ok(());
+        }
+    };
+
+    {
+        let mut counters = counters.lock().unwrap();
+        counters.rx_packets_received += 1;
+        if uplink_frame.is_ok() {
+            counters.rx_packets_received_ok += 1;
+        }
+    }
+
+    let mut event = uplink_frame?;
+    if let Some(rx_info) = &mut event.rx_info {
+        rx_info
+            .metadata
+            .insert("region_config_id".to_string(), ctx.region_config_id.clone());
+        rx_info.metadata.insert(
+            "region_common_name".to_string(),
+            ctx.region_common_name.to_string(),
+        );
+    }
+
+    info!(region_config_id = %ctx.region_config_id, gateway_id = %gateway_id, "Uplink frame received from gateway");
+    tokio::spawn(uplink::deduplicate_uplink(event));
+
+    Ok(())
+}
+
+async fn handle_dntxed(
+    ctx: &Context,
+    gateway_id: &EUI64,
+    dntxed: structs::DownlinkTransmitted,
+) -> Result<()> {
+    let pending = match ctx
+        .state
+        .write()
+        .await
+        .downlinks
+        .remove(&(*gateway_id, dntxed.diid))
+    {
+        Some(v) => v,
+        None => {
+            warn!(gateway_id = %gateway_id, diid = dntxed.diid, "Received dntxed for unknown downlink");
+            return Ok(());
+        }
+    };
+
+    // The Station only reports the emitted frequency, we use it to find the emitted item.
+    // The remaining items are marked as ignored.
+    let items = &pending.downlink_frame.items;
+    let emitted = match items.iter().position(|v| {
+        v.tx_info
+            .as_ref()
+            .map(|v| v.frequency == dntxed.frequency)
+            .unwrap_or_default()
+    }) {
+        Some(v) => v,
+        None => {
+            warn!(gateway_id = %gateway_id, diid = dntxed.diid, frequency = dntxed.frequency, "Received dntxed with frequency not matching any downlink item");
+            return Ok(());
+        }
+    };
+
+    let acks = items
+        .iter()
+        .enumerate()
+        .map(|(i, _)| gw::DownlinkTxAckItem {
+            status: if i == emitted {
+                gw::TxAckStatus::Ok
+            } else {
+                gw::TxAckStatus::Ignored
+            }
+            .into(),
+        })
+        .collect();
+
+    tokio::spawn(downlink::tx_ack::TxAck::handle(gw::DownlinkTxAck {
+        gateway_id: pending.downlink_frame.gateway_id.clone(),
+        downlink_id: pending.downlink_frame.downlink_id,
+        items: acks,
+        ..Default::default()
+    }));
+
+    Ok(())
+}
+
+// Validates that the common-name of the client-certificate matches the gateway ID.
+fn validate_client_cn(gateway_id: &str, cn: Option<&ClientCommonName>) -> Result<()> {
+    let cn = cn.ok_or_else(|| anyhow!("No client-certificate"))?;
+    let gateway_id = RouterId::Str(gateway_id.to_string()).to_eui64()?;
+    let cn_gateway_id: EUI64 =
+        cn.0.parse()
+            .with_context(|| format!("Parse common-name {}", cn.0))?;
+
+    if gateway_id != cn_gateway_id {
+        return Err(anyhow!(
+            "Common-name {} does not match gateway ID {}",
+            cn_gateway_id,
+            gateway_id
+        ));
+    }
+
+    Ok(())
+}
+
+fn get_tls_acceptor(tls_cert: &str, tls_key: &str, ca_cert: &str) -> Result<TlsAcceptor> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(
+        File::open(tls_cert).context("Open tls_cert")?,
+    ))
+    .context("Read tls_cert")?
+    .into_iter()
+    .map(rustls::Certificate)
+    .collect();
+
+    let key = rustls_pemfile::read_all(&mut BufReader::new(
+        File::open(tls_key).context("Open tls_key")?,
+    ))
+    .context("Read tls_key")?
+    .into_iter()
+    .find_map(|v| match v {
+        rustls_pemfile::Item::RSAKey(v)
+        | rustls_pemfile::Item::PKCS8Key(v)
+        | rustls_pemfile::Item::ECKey(v) => Some(rustls::PrivateKey(v)),
+        _ => None,
+    })
+    .ok_or_else(|| anyhow!("No private-key found in tls_key"))?;
+
+    let builder = rustls::ServerConfig::builder().with_safe_defaults();
+    let conf = if ca_cert.is_empty() {
+        builder.with_no_client_auth().with_single_cert(certs, key)?
+    } else {
+        let mut roots = rustls::RootCertStore::empty();
+        for cert in rustls_pemfile::certs(&mut BufReader::new(
+            File::open(ca_cert).context("Open ca_cert")?,
+        ))
+        .context("Read ca_cert")?
+        {
+            roots
+                .add(&rustls::Certificate(cert))
+                .map_err(|e| anyhow!("Add ca_cert error: {:?}", e))?;
+        }
+
+        builder
+            .with_client_cert_verifier(rustls::server::AllowAnyAuthenticatedClient::new(roots))
+            .with_single_cert(certs, key)?
+    };
+
+    Ok(TlsAcceptor::from(Arc::new(conf)))
+}
+
+fn send_stats(ctx: &Context, gateway_id: &EUI64, counters: &Mutex<Counters>) {
+    let counters = std::mem::take(&mut *counters.lock().unwrap());
+
+    let mut event = gw::GatewayStats {
+        gateway_id: gateway_id.to_string(),
+        time: Some(Utc::now().into()),
+        rx_packets_received: counters.rx_packets_received,
+        rx_packets_received_ok: counters.rx_packets_received_ok,
+        tx_packets_received: counters.tx_packets_received,
+        tx_packets_emitted: counters.tx_packets_emitted,
+        ..Default::default()
+    };
+    event
+        .metadata
+        .insert("region_config_id".to_string(), ctx.region_config_id.clone());
+    event.metadata.insert(
+        "region_common_name".to_string(),
+        ctx.region_common_name.to_string(),
+    );
+
+    inc_event("stats");
+    tokio::spawn(uplink::stats::Stats::handle(event));
+}
+
+fn inc_event(event: &str) {
+    EVENT_COUNTER
+        .get_or_create(&EventLabels {
+            event: event.to_string(),
+        })
+        .inc();
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_client_cn() {
+        // no client-certificate
+        assert!(validate_client_cn("0102030405060708", None).is_err());
+
+        // matching gateway ID
+        assert!(validate_client_cn(
+            "0102030405060708",
+            Some(&ClientCommonName("0102030405060708".into()))
+        )
+        .is_ok());
+        assert!(validate_client_cn(
+            "01-02-03-04-05-06-07-08",
+            Some(&ClientCommonName("0102030405060708".into()))
+        )
+        .is_ok());
+
+        // other gateway ID
+        assert!(validate_client_cn(
+            "0807060504030201",
+            Some(&ClientCommonName("0102030405060708".into()))
+        )
+        .is_err());
+    }
+}
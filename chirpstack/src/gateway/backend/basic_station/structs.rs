@@ -0,0 +1,1076 @@
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use chirpstack_api::gw;
+use lrwn::region::{CommonName, DataRateModulation, Region};
+use lrwn::EUI64;
+
+// Max bandwidth which can be covered by a single SX1255 / SX1257 radio.
+const RADIO_BANDWIDTH: u32 = 925_000;
+
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[serde(tag = "msgtype")]
+pub enum UpstreamMessage {
+    #[serde(rename = "version")]
+    Version(Version),
+    #[serde(rename = "updf")]
+    UplinkDataFrame(UplinkDataFrame),
+    #[serde(rename = "jreq")]
+    JoinRequest(JoinRequest),
+    #[serde(rename = "propdf")]
+    ProprietaryDataFrame(ProprietaryDataFrame),
+    #[serde(rename = "dntxed")]
+    DownlinkTransmitted(DownlinkTransmitted),
+    #[serde(rename = "timesync")]
+    TimeSync(TimeSyncRequest),
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct Version {
+    pub station: String,
+    pub firmware: Option<String>,
+    pub package: Option<String>,
+    pub model: String,
+    pub protocol: u32,
+    pub features: Option<String>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct UplinkInfo {
+    pub rctx: i64,
+    pub xtime: i64,
+    pub gpstime: i64,
+    pub rssi: f32,
+    pub snr: f32,
+    pub rxtime: f64,
+}
+
+impl UplinkInfo {
+    fn to_rx_info(&self, gateway_id: &EUI64) -> gw::UplinkRxInfo {
+        let mut context = self.xtime.to_be_bytes().to_vec();
+        context.extend_from_slice(&self.rctx.to_be_bytes());
+
+        gw::UplinkRxInfo {
+            gateway_id: gateway_id.to_string(),
+            uplink_id: rand::thread_rng().gen(),
+            time: NaiveDateTime::from_timestamp_opt(
+                self.rxtime.trunc() as i64,
+                (self.rxtime.fract() * 1_000_000_000.0) as u32,
+            )
+            .map(|v| DateTime::<Utc>::from_utc(v, Utc).into()),
+            time_since_gps_epoch: if self.gpstime > 0 {
+                Some(pbjson_types::Duration {
+                    seconds: self.gpstime / 1_000_000,
+                    nanos: ((self.gpstime % 1_000_000) * 1_000) as i32,
+                })
+            } else {
+                None
+            },
+            rssi: self.rssi as i32,
+            snr: self.snr,
+            context,
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct RadioMetaData {
+    #[serde(rename = "DR")]
+    pub dr: u8,
+    #[serde(rename = "Freq")]
+    pub frequency: u32,
+    pub upinfo: UplinkInfo,
+}
+
+impl RadioMetaData {
+    fn to_uplink_frame(
+        &self,
+        region: &dyn Region,
+        gateway_id: &EUI64,
+        phy_payload: Vec<u8>,
+    ) -> Result<gw::UplinkFrame> {
+        Ok(gw::UplinkFrame {
+            phy_payload,
+            tx_info: Some(gw::UplinkTxInfo {
+                frequency: self.frequency,
+                modulation: Some(data_rate_to_modulation(
+                    region.get_data_rate(self.dr)?,
+                    false,
+                )?),
+            }),
+            rx_info: Some(self.upinfo.to_rx_info(gateway_id)),
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct UplinkDataFrame {
+    #[serde(rename = "MHdr")]
+    pub mhdr: u8,
+    #[serde(rename = "DevAddr")]
+    pub dev_addr: i32,
+    #[serde(rename = "FCtrl")]
+    pub f_ctrl: u8,
+    #[serde(rename = "FCnt")]
+    pub f_cnt: u16,
+    #[serde(rename = "FOpts")]
+    pub f_opts: String,
+    #[serde(rename = "FPort")]
+    pub f_port: i16,
+    #[serde(rename = "FRMPayload")]
+    pub frm_payload: String,
+    #[serde(rename = "MIC")]
+    pub mic: i32,
+    #[serde(flatten)]
+    pub radio_meta_data: RadioMetaData,
+}
+
+impl UplinkDataFrame {
+    pub fn to_uplink_frame(
+        &self,
+        region: &dyn Region,
+        gateway_id: &EUI64,
+    ) -> Result<gw::UplinkFrame> {
+        let mut b = vec![self.mhdr];
+        b.extend_from_slice(&self.dev_addr.to_le_bytes());
+        b.push(self.f_ctrl);
+        b.extend_from_slice(&self.f_cnt.to_le_bytes());
+        b.extend_from_slice(&hex::decode(&self.f_opts).context("Decode FOpts")?);
+        if self.f_port >= 0 {
+            b.push(self.f_port as u8);
+        }
+        b.extend_from_slice(&hex::decode(&self.frm_payload).context("Decode FRMPayload")?);
+        b.extend_from_slice(&self.mic.to_le_bytes());
+
+        self.radio_meta_data.to_uplink_frame(region, gateway_id, b)
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct JoinRequest {
+    #[serde(rename = "MHdr")]
+    pub mhdr: u8,
+    #[serde(rename = "JoinEui", alias = "JoinEUI")]
+    pub join_eui: String,
+    #[serde(rename = "DevEui", alias = "DevEUI")]
+    pub dev_eui: String,
+    #[serde(rename = "DevNonce")]
+    pub dev_nonce: u16,
+    #[serde(rename = "MIC")]
+    pub mic: i32,
+    #[serde(flatten)]
+    pub radio_meta_data: RadioMetaData,
+}
+
+impl JoinRequest {
+    pub fn to_uplink_frame(
+        &self,
+        region: &dyn Region,
+        gateway_id: &EUI64,
+    ) -> Result<gw::UplinkFrame> {
+        let join_eui = parse_eui(&self.join_eui)?;
+        let dev_eui = parse_eui(&self.dev_eui)?;
+
+        let mut b = vec![self.mhdr];
+        b.extend(join_eui.to_be_bytes().iter().rev());
+        b.extend(dev_eui.to_be_bytes().iter().rev());
+        b.extend_from_slice(&self.dev_nonce.to_le_bytes());
+        b.extend_from_slice(&self.mic.to_le_bytes());
+
+        self.radio_meta_data.to_uplink_frame(region, gateway_id, b)
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct ProprietaryDataFrame {
+    #[serde(rename = "FRMPayload")]
+    pub frm_payload: String,
+    #[serde(flatten)]
+    pub radio_meta_data: RadioMetaData,
+}
+
+impl ProprietaryDataFrame {
+    pub fn to_uplink_frame(
+        &self,
+        region: &dyn Region,
+        gateway_id: &EUI64,
+    ) -> Result<gw::UplinkFrame> {
+        let b = hex::decode(&self.frm_payload).context("Decode FRMPayload")?;
+        self.radio_meta_data.to_uplink_frame(region, gateway_id, b)
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct DownlinkTransmitted {
+    pub diid: u32,
+    #[serde(rename = "DR")]
+    pub dr: u8,
+    #[serde(rename = "Freq")]
+    pub frequency: u32,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct TimeSyncRequest {
+    pub txtime: f64,
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct TimeSyncResponse {
+    pub msgtype: String,
+    pub txtime: f64,
+    pub gpstime: i64,
+}
+
+impl TimeSyncResponse {
+    pub fn new(txtime: f64, time_since_gps_epoch: chrono::Duration) -> Self {
+        TimeSyncResponse {
+            msgtype: "timesync".into(),
+            txtime,
+            gpstime: time_since_gps_epoch.num_microseconds().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+pub struct RouterInfoRequest {
+    pub router: RouterId,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[serde(untagged)]
+pub enum RouterId {
+    Int(u64),
+    Str(String),
+}
+
+impl RouterId {
+    /// Returns the router ID as EUI64. The router ID can be given as integer, as plain hex
+    /// string, in the EUI (dash separated) format or in the ID6 format.
+    pub fn to_eui64(&self) -> Result<EUI64> {
+        match self {
+            RouterId::Int(v) => Ok(EUI64::from_be_bytes(v.to_be_bytes())),
+            RouterId::Str(v) => {
+                if v.contains(':') {
+                    return parse_id6(v);
+                }
+                parse_eui(v)
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct RouterInfoResponse {
+    pub router: String,
+    pub muxs: String,
+    pub uri: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub error: String,
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct RouterConfig {
+    pub msgtype: String,
+    #[serde(rename = "NetID")]
+    pub net_id: Option<Vec<u32>>,
+    #[serde(rename = "JoinEui")]
+    pub join_eui: Option<Vec<[u64; 2]>>,
+    pub region: String,
+    pub hwspec: String,
+    pub freq_range: [u32; 2],
+    #[serde(rename = "DRs")]
+    pub drs: Vec<[i32; 3]>,
+    pub sx1301_conf: Vec<Sx1301Conf>,
+    pub nocca: bool,
+    pub nodc: bool,
+    pub nodwell: bool,
+}
+
+impl RouterConfig {
+    pub fn new(region: &dyn Region, channels: &[config::GatewayChannel]) -> Result<Self> {
+        let common_name = region.get_name();
+
+        let mut drs = Vec::new();
+        for dr in 0..16 {
+            drs.push(match region.get_data_rate(dr) {
+                Ok(DataRateModulation::Lora(v)) => {
+                    let dn_only = region
+                        .get_data_rate_index(true, &DataRateModulation::Lora(v.clone()))
+                        .map(|i| i != dr)
+                        .unwrap_or(true);
+                    [
+                        v.spreading_factor.into(),
+                        (v.bandwidth / 1000) as i32,
+                        dn_only.into(),
+                    ]
+                }
+                Ok(DataRateModulation::Fsk(_)) => [0, 0, 0],
+                _ => [-1, 0, 0],
+            });
+        }
+
+        Ok(RouterConfig {
+            msgtype: "router_config".into(),
+            net_id: None,
+            join_eui: None,
+            region: get_region_name(common_name)?.into(),
+            hwspec: "sx1301/1".into(),
+            freq_range: get_frequency_range(common_name)?,
+            drs,
+            sx1301_conf: vec![Sx1301Conf::new(channels)?],
+            nocca: false,
+            nodc: false,
+            nodwell: false,
+        })
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone, Default)]
+pub struct Sx1301Conf {
+    pub radio_0: RadioConf,
+    pub radio_1: RadioConf,
+    #[serde(rename = "chan_FSK")]
+    pub chan_fsk: ChannelConf,
+    #[serde(rename = "chan_Lora_std")]
+    pub chan_lora_std: ChannelConf,
+    #[serde(rename = "chan_multiSF_0")]
+    pub chan_multi_sf_0: ChannelConf,
+    #[serde(rename = "chan_multiSF_1")]
+    pub chan_multi_sf_1: ChannelConf,
+    #[serde(rename = "chan_multiSF_2")]
+    pub chan_multi_sf_2: ChannelConf,
+    #[serde(rename = "chan_multiSF_3")]
+    pub chan_multi_sf_3: ChannelConf,
+    #[serde(rename = "chan_multiSF_4")]
+    pub chan_multi_sf_4: ChannelConf,
+    #[serde(rename = "chan_multiSF_5")]
+    pub chan_multi_sf_5: ChannelConf,
+    #[serde(rename = "chan_multiSF_6")]
+    pub chan_multi_sf_6: ChannelConf,
+    #[serde(rename = "chan_multiSF_7")]
+    pub chan_multi_sf_7: ChannelConf,
+}
+
+impl Sx1301Conf {
+    /// Returns the SX1301 configuration for the given channels. The radio center frequencies
+    /// are calculated such that all multi-SF and FSK channels fit within the bandwidth of the
+    /// two radios. The single-SF LoRa channel is assigned to the closest radio.
+    pub fn new(channels: &[config::GatewayChannel]) -> Result<Self> {
+        let mut channels: Vec<&config::GatewayChannel> = channels.iter().collect();
+        channels.sort_by_key(|c| c.frequency);
+
+        let is_lora_std = |c: &config::GatewayChannel| {
+            matches!(c.modulation, config::GatewayChannelModulation::LORA)
+                && c.spreading_factors.len() == 1
+        };
+
+        // (min, max) frequency per radio.
+        let mut radios: Vec<(u32, u32)> = Vec::new();
+
+        for c in channels.iter().filter(|c| !is_lora_std(c)) {
+            let min = c.frequency - c.bandwidth / 2;
+            let max = c.frequency + c.bandwidth / 2;
+
+            match radios
+                .iter()
+                .position(|(r_min, r_max)| max.max(*r_max) - min.min(*r_min) <= RADIO_BANDWIDTH)
+            {
+                Some(i) => {
+                    radios[i] = (min.min(radios[i].0), max.max(radios[i].1));
+                }
+                None => {
+                    if radios.len() == 2 {
+                        return Err(anyhow!(
+                            "Channel {} does not fit within the bandwidth of the radios",
+                            c.frequency
+                        ));
+                    }
+                    radios.push((min, max));
+                }
+            }
+        }
+
+        let mut radio_freqs: Vec<u32> = radios
+            .iter()
+            .map(|(min, max)| min + (max - min) / 2)
+            .collect();
+        if radio_freqs.is_empty() {
+            if let Some(c) = channels.first() {
+                radio_freqs.push(c.frequency);
+            }
+        }
+
+        let mut conf = Sx1301Conf::default();
+        for (i, freq) in radio_freqs.iter().enumerate() {
+            let radio = RadioConf {
+                enable: true,
+                freq: *freq,
+            };
+            match i {
+                0 => conf.radio_0 = radio,
+                _ => conf.radio_1 = radio,
+            }
+        }
+
+        let mut multi_sf: Vec<ChannelConf> = Vec::new();
+
+        for c in &channels {
+            // Select the radio closest to the channel frequency.
+            let (radio, radio_freq) = radio_freqs
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, f)| (c.frequency as i64 - **f as i64).abs())
+                .ok_or_else(|| anyhow!("No radio available"))?;
+
+            let ch_conf = ChannelConf {
+                enable: true,
+                radio: radio as u32,
+                if_freq: c.frequency as i32 - *radio_freq as i32,
+                ..Default::default()
+            };
+
+            match c.modulation {
+                config::GatewayChannelModulation::FSK => {
+                    conf.chan_fsk = ChannelConf {
+                        bandwidth: Some(c.bandwidth),
+                        datarate: Some(c.datarate),
+                        ..ch_conf
+                    };
+                }
+                config::GatewayChannelModulation::LORA => {
+                    if is_lora_std(c) {
+                        conf.chan_lora_std = ChannelConf {
+                            bandwidth: Some(c.bandwidth),
+                            spread_factor: Some(c.spreading_factors[0]),
+                            ..ch_conf
+                        };
+                    } else {
+                        multi_sf.push(ch_conf);
+                    }
+                }
+            }
+        }
+
+        if multi_sf.len() > 8 {
+            return Err(anyhow!("Max. 8 multi-SF channels are supported"));
+        }
+        multi_sf.resize(8, ChannelConf::default());
+
+        conf.chan_multi_sf_0 = multi_sf[0].clone();
+        conf.chan_multi_sf_1 = multi_sf[1].clone();
+        conf.chan_multi_sf_2 = multi_sf[2].clone();
+        conf.chan_multi_sf_3 = multi_sf[3].clone();
+        conf.chan_multi_sf_4 = multi_sf[4].clone();
+        conf.chan_multi_sf_5 = multi_sf[5].clone();
+        conf.chan_multi_sf_6 = multi_sf[6].clone();
+        conf.chan_multi_sf_7 = multi_sf[7].clone();
+
+        Ok(conf)
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone, Default)]
+pub struct RadioConf {
+    pub enable: bool,
+    pub freq: u32,
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone, Default)]
+pub struct ChannelConf {
+    pub enable: bool,
+    pub radio: u32,
+    #[serde(rename = "if")]
+    pub if_freq: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bandwidth: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spread_factor: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub datarate: Option<u32>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone, Default)]
+pub struct DownlinkMessage {
+    pub msgtype: String,
+    #[serde(rename = "DevEui")]
+    pub dev_eui: String,
+    #[serde(rename = "dC")]
+    pub device_class: u8,
+    pub diid: u32,
+    pub pdu: String,
+    pub priority: u8,
+    #[serde(rename = "RxDelay", skip_serializing_if = "Option::is_none")]
+    pub rx_delay: Option<u64>,
+    #[serde(rename = "RX1DR", skip_serializing_if = "Option::is_none")]
+    pub rx1_dr: Option<u8>,
+    #[serde(rename = "RX1Freq", skip_serializing_if = "Option::is_none")]
+    pub rx1_freq: Option<u32>,
+    #[serde(rename = "RX2DR", skip_serializing_if = "Option::is_none")]
+    pub rx2_dr: Option<u8>,
+    #[serde(rename = "RX2Freq", skip_serializing_if = "Option::is_none")]
+    pub rx2_freq: Option<u32>,
+    #[serde(rename = "DR", skip_serializing_if = "Option::is_none")]
+    pub dr: Option<u8>,
+    #[serde(rename = "Freq", skip_serializing_if = "Option::is_none")]
+    pub freq: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xtime: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rctx: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gpstime: Option<i64>,
+}
+
+impl DownlinkMessage {
+    /// Returns the dnmsg for the given downlink frame. The first item determines the device
+    /// class: delay timing maps to Class-A (with the second item as RX2), GPS epoch timing
+    /// to Class-B and immediately to Class-C. The DevEUI is not known for multicast and
+    /// proprietary downlinks, in which case it is set to zero.
+    pub fn from_downlink_frame(
+        region: &dyn Region,
+        df: &gw::DownlinkFrame,
+        dev_eui: Option<&EUI64>,
+    ) -> Result<Self> {
+        let item = df
+            .items
+            .first()
+            .ok_or_else(|| anyhow!("Downlink frame does not contain any items"))?;
+        let tx_info = item
+            .tx_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("tx_info must not be None"))?;
+
+        let mut dm = DownlinkMessage {
+            msgtype: "dnmsg".into(),
+            dev_eui: format_eui(dev_eui.unwrap_or(&EUI64::from_be_bytes([0; 8]))),
+            diid: df.downlink_id,
+            pdu: hex::encode(&item.phy_payload),
+            ..Default::default()
+        };
+
+        match tx_info
+            .timing
+            .as_ref()
+            .and_then(|v| v.parameters.as_ref())
+            .ok_or_else(|| anyhow!("timing parameters must not be None"))?
+        {
+            gw::timing::Parameters::Delay(v) => {
+                if tx_info.context.len() != 16 {
+                    return Err(anyhow!("context must be exactly 16 bytes"));
+                }
+
+                let mut xtime = [0; 8];
+                let mut rctx = [0; 8];
+                xtime.copy_from_slice(&tx_info.context[0..8]);
+                rctx.copy_from_slice(&tx_info.context[8..16]);
+
+                dm.device_class = 0;
+                dm.xtime = Some(i64::from_be_bytes(xtime));
+                dm.rctx = Some(i64::from_be_bytes(rctx));
+                dm.rx_delay = Some(
+                    v.delay
+                        .as_ref()
+                        .map(|v| v.seconds as u64)
+                        .unwrap_or_default(),
+                );
+                dm.rx1_dr = Some(get_downlink_dr(region, tx_info)?);
+                dm.rx1_freq = Some(tx_info.frequency);
+
+                if let Some(rx2_tx_info) = df.items.get(1).and_then(|v| v.tx_info.as_ref()) {
+                    dm.rx2_dr = Some(get_downlink_dr(region, rx2_tx_info)?);
+                    dm.rx2_freq = Some(rx2_tx_info.frequency);
+                }
+            }
+            gw::timing::Parameters::GpsEpoch(v) => {
+                let gps_time = v
+                    .time_since_gps_epoch
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("time_since_gps_epoch must not be None"))?;
+
+                dm.device_class = 1;
+                dm.gpstime = Some(gps_time.seconds * 1_000_000 + (gps_time.nanos / 1_000) as i64);
+                dm.dr = Some(get_downlink_dr(region, tx_info)?);
+                dm.freq = Some(tx_info.frequency);
+            }
+            gw::timing::Parameters::Immediately(_) => {
+                dm.device_class = 2;
+                dm.rx2_dr = Some(get_downlink_dr(region, tx_info)?);
+                dm.rx2_freq = Some(tx_info.frequency);
+            }
+        }
+
+        Ok(dm)
+    }
+}
+
+fn get_downlink_dr(region: &dyn Region, tx_info: &gw::DownlinkTxInfo) -> Result<u8> {
+    let modulation = match tx_info
+        .modulation
+        .as_ref()
+        .and_then(|v| v.parameters.as_ref())
+        .ok_or_else(|| anyhow!("modulation parameters must not be None"))?
+    {
+        gw::modulation::Parameters::Lora(v) => {
+            DataRateModulation::Lora(lrwn::region::LoraDataRate {
+                spreading_factor: v.spreading_factor as u8,
+                bandwidth: v.bandwidth,
+                coding_rate: v.code_rate().into(),
+            })
+        }
+        gw::modulation::Parameters::Fsk(v) => DataRateModulation::Fsk(lrwn::region::FskDataRate {
+            bitrate: v.datarate,
+        }),
+        gw::modulation::Parameters::LrFhss(_) => {
+            return Err(anyhow!("LR-FHSS downlinks are not supported"));
+        }
+    };
+
+    region.get_data_rate_index(false, &modulation)
+}
+
+fn data_rate_to_modulation(
+    dr: DataRateModulation,
+    polarization_inversion: bool,
+) -> Result<gw::Modulation> {
+    Ok(gw::Modulation {
+        parameters: Some(match dr {
+            DataRateModulation::Lora(v) => {
+                gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: v.bandwidth,
+                    spreading_factor: v.spreading_factor as u32,
+                    code_rate: gw::CodeRate::from_str(&v.coding_rate)
+                        .map_err(|e| anyhow!("{}", e))?
+                        .into(),
+                    code_rate_legacy: "".into(),
+                    polarization_inversion,
+                })
+            }
+            DataRateModulation::Fsk(v) => gw::modulation::Parameters::Fsk(gw::FskModulationInfo {
+                datarate: v.bitrate,
+                ..Default::default()
+            }),
+            DataRateModulation::LrFhss(v) => {
+                gw::modulation::Parameters::LrFhss(gw::LrFhssModulationInfo {
+                    operating_channel_width: v.occupied_channel_width,
+                    code_rate: gw::CodeRate::from_str(&v.coding_rate)
+                        .map_err(|e| anyhow!("{}", e))?
+                        .into(),
+                    ..Default::default()
+                })
+            }
+        }),
+    })
+}
+
+/// Parses an EUI in hex or dash separated format (e.g. 01-02-03-04-05-06-07-08).
+pub fn parse_eui(s: &str) -> Result<EUI64> {
+    Ok(EUI64::from_str(&s.replace('-', ""))?)
+}
+
+/// Returns the EUI in dash separated format (e.g. 01-02-03-04-05-06-07-08).
+pub fn format_eui(eui: &EUI64) -> String {
+    eui.to_be_bytes()
+        .iter()
+        .map(|v| format!("{:02X}", v))
+        .collect::<Vec<String>>()
+        .join("-")
+}
+
+/// Parses an EUI in the ID6 format (e.g. 1:2:3:4 or ::1).
+pub fn parse_id6(s: &str) -> Result<EUI64> {
+    let (head, tail) = match s.split_once("::") {
+        Some((head, tail)) => (head, tail),
+        None => (s, ""),
+    };
+
+    let parse_groups = |s: &str| -> Result<Vec<u16>> {
+        if s.is_empty() {
+            return Ok(vec![]);
+        }
+
+        s.split(':')
+            .map(|v| u16::from_str_radix(v, 16).context("Parse ID6 group"))
+            .collect()
+    };
+
+    let head = parse_groups(head)?;
+    let tail = parse_groups(tail)?;
+
+    if head.len() + tail.len() > 4 || (!s.contains("::") && head.len() != 4) {
+        return Err(anyhow!("Invalid ID6: {}", s));
+    }
+
+    let mut groups = head;
+    groups.resize(4 - tail.len(), 0);
+    groups.extend(tail);
+
+    let mut b = [0; 8];
+    for (i, g) in groups.iter().enumerate() {
+        b[i * 2..i * 2 + 2].copy_from_slice(&g.to_be_bytes());
+    }
+
+    Ok(EUI64::from_be_bytes(b))
+}
+
+/// Returns the EUI formatted as ID6 (e.g. 0102:0304:0506:0708).
+pub fn format_id6(eui: &EUI64) -> String {
+    eui.to_be_bytes()
+        .chunks(2)
+        .map(|v| format!("{:x}", u16::from_be_bytes([v[0], v[1]])))
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+fn get_region_name(common_name: CommonName) -> Result<&'static str> {
+    Ok(match common_name {
+        CommonName::EU868 => "EU863",
+        CommonName::US915 => "US902",
+        CommonName::CN779 => "CN779",
+        CommonName::EU433 => "EU433",
+        CommonName::AU915 => "AU915",
+        CommonName::CN470 => "CN470",
+        CommonName::AS923 => "AS923-1",
+        CommonName::AS923_2 => "AS923-2",
+        CommonName::AS923_3 => "AS923-3",
+        CommonName::AS923_4 => "AS923-4",
+        CommonName::KR920 => "KR920",
+        CommonName::IN865 => "IN865",
+        CommonName::RU864 => "RU864",
+        CommonName::ISM2400 => {
+            return Err(anyhow!("ISM2400 is not supported by Basics Station"));
+        }
+        CommonName::CUSTOM => {
+            return Err(anyhow!("CUSTOM is not supported by Basics Station"));
+        }
+    })
+}
+
+fn get_frequency_range(common_name: CommonName) -> Result<[u32; 2]> {
+    Ok(match common_name {
+        CommonName::EU868 => [863000000, 870000000],
+        CommonName::US915 => [902000000, 928000000],
+        CommonName::CN779 => [779000000, 787000000],
+        CommonName::EU433 => [433175000, 434665000],
+        CommonName::AU915 => [915000000, 928000000],
+        CommonName::CN470 => [470000000, 510000000],
+        CommonName::AS923 | CommonName::AS923_2 | CommonName::AS923_3 | CommonName::AS923_4 => {
+            [915000000, 928000000]
+        }
+        CommonName::KR920 => [920900000, 923300000],
+        CommonName::IN865 => [865000000, 867000000],
+        CommonName::RU864 => [864000000, 870000000],
+        CommonName::ISM2400 => {
+            return Err(anyhow!("ISM2400 is not supported by Basics Station"));
+        }
+        CommonName::CUSTOM => {
+            return Err(anyhow!("CUSTOM is not supported by Basics Station"));
+        }
+    })
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn test_router_id() {
+        let tests = vec![
+            (RouterId::Int(1), "0000000000000001"),
+            (RouterId::Str("0102030405060708".into()), "0102030405060708"),
+            (
+                RouterId::Str("01-02-03-04-05-06-07-08".into()),
+                "0102030405060708",
+            ),
+            (RouterId::Str("102:304:506:708".into()), "0102030405060708"),
+            (RouterId::Str("::1".into()), "0000000000000001"),
+            (RouterId::Str("1::".into()), "0001000000000000"),
+            (RouterId::Str("1::2".into()), "0001000000000002"),
+        ];
+
+        for (id, expected) in tests {
+            assert_eq!(expected, id.to_eui64().unwrap().to_string());
+        }
+
+        assert_eq!(
+            "102:304:506:708",
+            format_id6(&EUI64::from_str("0102030405060708").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_uplink_data_frame() {
+        let region = lrwn::region::get(CommonName::EU868, false, false);
+        let gateway_id = EUI64::from_str("0102030405060708").unwrap();
+
+        let msg: UpstreamMessage = serde_json::from_str(
+            r#"{"msgtype":"updf","MHdr":64,"DevAddr":16909060,"FCtrl":128,"FCnt":10,"FOpts":"","FPort":1,"FRMPayload":"0102","MIC":-1,"DR":5,"Freq":868100000,"upinfo":{"rctx":1,"xtime":2,"gpstime":1000000,"rssi":-50,"snr":7.5,"rxtime":1680000000.5}}"#,
+        )
+        .unwrap();
+
+        let frame = match msg {
+            UpstreamMessage::UplinkDataFrame(v) => {
+                v.to_uplink_frame(&*region, &gateway_id).unwrap()
+            }
+            _ => panic!("Unexpected message"),
+        };
+
+        assert_eq!(
+            vec![
+                0x40, 0x04, 0x03, 0x02, 0x01, 0x80, 0x0a, 0x00, 0x01, 0x01, 0x02, 0xff, 0xff, 0xff,
+                0xff
+            ],
+            frame.phy_payload
+        );
+
+        let tx_info = frame.tx_info.as_ref().unwrap();
+        assert_eq!(868100000, tx_info.frequency);
+        assert_eq!(
+            Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 7,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    code_rate_legacy: "".into(),
+                    polarization_inversion: false,
+                })),
+            }),
+            tx_info.modulation
+        );
+
+        let rx_info = frame.rx_info.as_ref().unwrap();
+        assert_eq!("0102030405060708", rx_info.gateway_id);
+        assert_eq!(-50, rx_info.rssi);
+        assert_eq!(7.5, rx_info.snr);
+        assert_eq!(
+            vec![0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 1],
+            rx_info.context
+        );
+        assert_eq!(
+            Some(pbjson_types::Duration {
+                seconds: 1,
+                nanos: 0,
+            }),
+            rx_info.time_since_gps_epoch
+        );
+    }
+
+    #[test]
+    fn test_join_request() {
+        let region = lrwn::region::get(CommonName::EU868, false, false);
+        let gateway_id = EUI64::from_str("0102030405060708").unwrap();
+
+        let msg: UpstreamMessage = serde_json::from_str(
+            r#"{"msgtype":"jreq","MHdr":0,"JoinEui":"01-02-03-04-05-06-07-08","DevEui":"08-07-06-05-04-03-02-01","DevNonce":258,"MIC":16909060,"DR":0,"Freq":868100000,"upinfo":{}}"#,
+        )
+        .unwrap();
+
+        let frame = match msg {
+            UpstreamMessage::JoinRequest(v) => v.to_uplink_frame(&*region, &gateway_id).unwrap(),
+            _ => panic!("Unexpected message"),
+        };
+
+        assert_eq!(
+            vec![
+                0x00, 0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01, 0x01, 0x02, 0x03, 0x04, 0x05,
+                0x06, 0x07, 0x08, 0x02, 0x01, 0x04, 0x03, 0x02, 0x01
+            ],
+            frame.phy_payload
+        );
+    }
+
+    #[test]
+    fn test_unknown_message() {
+        let msg: UpstreamMessage =
+            serde_json::from_str(r#"{"msgtype":"rmtsh","rmtsh":[]}"#).unwrap();
+        assert_eq!(UpstreamMessage::Unknown, msg);
+    }
+
+    #[test]
+    fn test_downlink_message_class_a() {
+        let region = lrwn::region::get(CommonName::EU868, false, false);
+        let lora = |sf: u32| gw::Modulation {
+            parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                bandwidth: 125000,
+                spreading_factor: sf,
+                code_rate: gw::CodeRate::Cr45.into(),
+                polarization_inversion: true,
+                ..Default::default()
+            })),
+        };
+        let mut context = 2i64.to_be_bytes().to_vec();
+        context.extend_from_slice(&1i64.to_be_bytes());
+
+        let df = gw::DownlinkFrame {
+            downlink_id: 123,
+            gateway_id: "0102030405060708".into(),
+            items: vec![
+                gw::DownlinkFrameItem {
+                    phy_payload: vec![1, 2, 3],
+                    tx_info: Some(gw::DownlinkTxInfo {
+                        frequency: 868100000,
+                        modulation: Some(lora(7)),
+                        timing: Some(gw::Timing {
+                            parameters: Some(gw::timing::Parameters::Delay(gw::DelayTimingInfo {
+                                delay: Some(pbjson_types::Duration {
+                                    seconds: 1,
+                                    nanos: 0,
+                                }),
+                            })),
+                        }),
+                        context: context.clone(),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                gw::DownlinkFrameItem {
+                    phy_payload: vec![1, 2, 3],
+                    tx_info: Some(gw::DownlinkTxInfo {
+                        frequency: 869525000,
+                        modulation: Some(lora(12)),
+                        timing: Some(gw::Timing {
+                            parameters: Some(gw::timing::Parameters::Delay(gw::DelayTimingInfo {
+                                delay: Some(pbjson_types::Duration {
+                                    seconds: 2,
+                                    nanos: 0,
+                                }),
+                            })),
+                        }),
+                        context,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let dm = DownlinkMessage::from_downlink_frame(
+            &*region,
+            &df,
+            Some(&EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8])),
+        )
+        .unwrap();
+        assert_eq!(
+            DownlinkMessage {
+                msgtype: "dnmsg".into(),
+                dev_eui: "01-02-03-04-05-06-07-08".into(),
+                device_class: 0,
+                diid: 123,
+                pdu: "010203".into(),
+                priority: 0,
+                rx_delay: Some(1),
+                rx1_dr: Some(5),
+                rx1_freq: Some(868100000),
+                rx2_dr: Some(0),
+                rx2_freq: Some(869525000),
+                dr: None,
+                freq: None,
+                xtime: Some(2),
+                rctx: Some(1),
+                gpstime: None,
+            },
+            dm
+        );
+    }
+
+    #[test]
+    fn test_router_config() {
+        let region = lrwn::region::get(CommonName::US915, false, false);
+        let channels: Vec<config::GatewayChannel> = (0..8)
+            .map(|i| config::GatewayChannel {
+                frequency: 902300000 + i * 200000,
+                bandwidth: 125000,
+                modulation: config::GatewayChannelModulation::LORA,
+                spreading_factors: vec![7, 8, 9, 10],
+                datarate: 0,
+            })
+            .chain(std::iter::once(config::GatewayChannel {
+                frequency: 903000000,
+                bandwidth: 500000,
+                modulation: config::GatewayChannelModulation::LORA,
+                spreading_factors: vec![8],
+                datarate: 0,
+            }))
+            .collect();
+
+        let rc = RouterConfig::new(&*region, &channels).unwrap();
+        assert_eq!("US902", rc.region);
+        assert_eq!([902000000, 928000000], rc.freq_range);
+        assert_eq!([10, 125, 0], rc.drs[0]);
+        assert_eq!([8, 500, 0], rc.drs[4]);
+        assert_eq!([12, 500, 1], rc.drs[8]);
+        assert_eq!([-1, 0, 0], rc.drs[7]);
+
+        let conf = &rc.sx1301_conf[0];
+        assert_eq!(
+            RadioConf {
+                enable: true,
+                freq: 902700000,
+            },
+            conf.radio_0
+        );
+        assert_eq!(
+            RadioConf {
+                enable: true,
+                freq: 903500000,
+            },
+            conf.radio_1
+        );
+        assert_eq!(
+            ChannelConf {
+                enable: true,
+                radio: 0,
+                if_freq: -400000,
+                ..Default::default()
+            },
+            conf.chan_multi_sf_0
+        );
+        assert_eq!(
+            ChannelConf {
+                enable: true,
+                radio: 0,
+                if_freq: 400000,
+                ..Default::default()
+            },
+            conf.chan_multi_sf_4
+        );
+        assert_eq!(
+            ChannelConf {
+                enable: true,
+                radio: 1,
+                if_freq: 200000,
+                ..Default::default()
+            },
+            conf.chan_multi_sf_7
+        );
+        assert_eq!(
+            ChannelConf {
+                enable: true,
+                radio: 0,
+                if_freq: 300000,
+                bandwidth: Some(500000),
+                spread_factor: Some(8),
+                datarate: None,
+            },
+            conf.chan_lora_std
+        );
+    }
+}
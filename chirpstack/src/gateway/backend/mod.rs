@@ -7,9 +7,11 @@ use tracing::{info, warn};
 
 use crate::config;
 
+mod basic_station;
 #[cfg(test)]
 pub mod mock;
 mod mqtt;
+mod semtech_udp;
 
 lazy_static! {
     static ref BACKENDS: RwLock<HashMap<String, Box<dyn GatewayBackend + Sync + Send>>> =
@@ -41,12 +43,45 @@ pub async fn setup() -> Result<()> {
             "Setting up gateway backend for region"
         );
 
-        let backend =
-            mqtt::MqttBackend::new(&region.id, region.common_name, &region.gateway.backend.mqtt)
-                .await
-                .context("New MQTT gateway backend error")?;
-
-        set_backend(&region.id, Box::new(backend)).await;
+        let backend: Box<dyn GatewayBackend + Sync + Send> =
+            match region.gateway.backend.enabled.as_str() {
+                "mqtt" | "" => Box::new(
+                    mqtt::MqttBackend::new(
+                        &region.id,
+                        region.common_name,
+                        &region.gateway.backend.mqtt,
+                    )
+                    .await
+                    .context("New MQTT gateway backend error")?,
+                ),
+                "semtech_udp" => Box::new(
+                    semtech_udp::SemtechUdpBackend::new(
+                        &region.id,
+                        region.common_name,
+                        &region.gateway.backend.semtech_udp,
+                    )
+                    .await
+                    .context("New Semtech UDP gateway backend error")?,
+                ),
+                "basic_station" => Box::new(
+                    basic_station::BasicStationBackend::new(
+                        &region.id,
+                        region.common_name,
+                        &region.gateway.channels,
+                        &region.gateway.backend.basic_station,
+                    )
+                    .await
+                    .context("New Basics Station gateway backend error")?,
+                ),
+                _ => {
+                    return Err(anyhow!(
+                        "Unexpected gateway backend: {}",
+                        region.gateway.backend.enabled
+                    ));
+                }
+            };
+
+        set_backend(&region.id, backend).await;
     }
 
     Ok(())
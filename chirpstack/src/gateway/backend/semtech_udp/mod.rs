@@ -0,0 +1,420 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use tokio::net::UdpSocket;
+use tokio::sync::RwLock;
+use tracing::{debug, error, info, trace, warn};
+
+use super::GatewayBackend;
+use crate::config::GatewayBackendSemtechUdp;
+use crate::monitoring::prometheus;
+use crate::{downlink, uplink};
+use chirpstack_api::gw;
+use lrwn::region::CommonName;
+use lrwn::EUI64;
+
+mod structs;
+
+use structs::{GatewayHeader, PacketType, PushData, TxAck};
+
+// Pending downlinks for which no TX_ACK has been received within this duration are removed.
+const PENDING_DOWNLINK_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+struct EventLabels {
+    event: String,
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+struct CommandLabels {
+    command: String,
+}
+
+lazy_static! {
+    static ref EVENT_COUNTER: Family<EventLabels, Counter> = {
+        let counter = Family::<EventLabels, Counter>::default();
+        prometheus::register(
+            "gateway_backend_semtech_udp_events",
+            "Number of events received",
+            counter.clone(),
+        );
+        counter
+    };
+    static ref COMMAND_COUNTER: Family<CommandLabels, Counter> = {
+        let counter = Family::<CommandLabels, Counter>::default();
+        prometheus::register(
+            "gateway_backend_semtech_udp_commands",
+            "Number of commands sent",
+            counter.clone(),
+        );
+        counter
+    };
+}
+
+struct Gateway {
+    addr: SocketAddr,
+    protocol_version: u8,
+}
+
+struct PendingDownlink {
+    downlink_frame: gw::DownlinkFrame,
+    acks: Vec<gw::DownlinkTxAckItem>,
+    created_at: Instant,
+}
+
+#[derive(Default)]
+struct State {
+    gateways: HashMap<EUI64, Gateway>,
+    downlinks: HashMap<(EUI64, u16), PendingDownlink>,
+}
+
+pub struct SemtechUdpBackend {
+    socket: Arc<UdpSocket>,
+    state: Arc<RwLock<State>>,
+}
+
+impl SemtechUdpBackend {
+    pub async fn new(
+        region_config_id: &str,
+        region_common_name: CommonName,
+        conf: &GatewayBackendSemtechUdp,
+    ) -> Result<SemtechUdpBackend> {
+        info!(region_config_id = %region_config_id, bind = %conf.bind, "Starting Semtech UDP packet-forwarder backend");
+
+        let socket = Arc::new(
+            UdpSocket::bind(&conf.bind)
+                .await
+                .context("Bind UDP socket")?,
+        );
+        let state: Arc<RwLock<State>> = Arc::new(RwLock::new(State::default()));
+
+        // Packet reader loop.
+        tokio::spawn({
+            let region_config_id = region_config_id.to_string();
+            let socket = socket.clone();
+            let state = state.clone();
+
+            async move {
+                // Max. UDP payload size.
+                let mut buf = [0; 65507];
+
+                loop {
+                    let (size, addr) = match socket.recv_from(&mut buf).await {
+                        Ok(v) => v,
+                        Err(e) => {
+                            error!(region_config_id = %region_config_id, error = %e, "Read from UDP socket error");
+                            continue;
+                        }
+                    };
+
+                    if let Err(e) = handle_packet(
+                        &region_config_id,
+                        region_common_name,
+                        &socket,
+                        &state,
+                        addr,
+                        &buf[..size],
+                    )
+                    .await
+                    {
+                        error!(region_config_id = %region_config_id, addr = %addr, error = %e, "Processing UDP packet error");
+                    }
+                }
+            }
+        });
+
+        Ok(SemtechUdpBackend { socket, state })
+    }
+}
+
+#[async_trait]
+impl GatewayBackend for SemtechUdpBackend {
+    async fn send_downlink(&self, df: &gw::DownlinkFrame) -> Result<()> {
+        COMMAND_COUNTER
+            .get_or_create(&CommandLabels {
+                command: "down".to_string(),
+            })
+            .inc();
+
+        let gateway_id: EUI64 = df.gateway_id.parse()?;
+        let token = df.downlink_id as u16;
+        let item = df
+            .items
+            .first()
+            .ok_or_else(|| anyhow!("Downlink frame does not contain any items"))?;
+
+        let (addr, protocol_version) = {
+            let mut state = self.state.write().await;
+            let (addr, protocol_version) = state
+                .gateways
+                .get(&gateway_id)
+                .map(|v| (v.addr, v.protocol_version))
+                .ok_or_else(|| anyhow!("Gateway {} has not sent a PULL_DATA packet", gateway_id))?;
+
+            // The pending downlink must be stored before sending the PULL_RESP as the TX_ACK
+            // could be received before this function returns.
+            if protocol_version != structs::PROTOCOL_VERSION_1 {
+                state
+                    .downlinks
+                    .retain(|_, v| v.created_at.elapsed() < PENDING_DOWNLINK_TTL);
+                state.downlinks.insert(
+                    (gateway_id, token),
+                    PendingDownlink {
+                        downlink_frame: df.clone(),
+                        acks: Vec::new(),
+                        created_at: Instant::now(),
+                    },
+                );
+            }
+
+            (addr, protocol_version)
+        };
+
+        info!(gateway_id = %gateway_id, downlink_id = df.downlink_id, "Sending downlink frame");
+        send_item(&self.socket, addr, protocol_version, token, item).await?;
+
+        // Protocol version 1 does not implement TX_ACK, we assume that the first item was
+        // emitted.
+        if protocol_version == structs::PROTOCOL_VERSION_1 {
+            let mut acks = vec![
+                gw::DownlinkTxAckItem {
+                    status: gw::TxAckStatus::Ignored.into(),
+                };
+                df.items.len()
+            ];
+            acks[0].status = gw::TxAckStatus::Ok.into();
+
+            tokio::spawn(downlink::tx_ack::TxAck::handle(gw::DownlinkTxAck {
+                gateway_id: df.gateway_id.clone(),
+                downlink_id: df.downlink_id,
+                items: acks,
+                ..Default::default()
+            }));
+        }
+
+        Ok(())
+    }
+
+    async fn send_configuration(&self, gw_conf: &gw::GatewayConfiguration) -> Result<()> {
+        Err(anyhow!(
+            "Gateway {} configuration update is not supported by the Semtech UDP backend",
+            gw_conf.gateway_id
+        ))
+    }
+}
+
+async fn handle_packet(
+    region_config_id: &str,
+    region_common_name: CommonName,
+    socket: &UdpSocket,
+    state: &RwLock<State>,
+    addr: SocketAddr,
+    b: &[u8],
+) -> Result<()> {
+    let packet_type = structs::get_packet_type(b)?;
+    trace!(region_config_id = region_config_id, addr = %addr, packet_type = ?packet_type, "UDP packet received");
+
+    match packet_type {
+        PacketType::PushData => {
+            handle_push_data(region_config_id, region_common_name, socket, addr, b).await
+        }
+        PacketType::PullData => handle_pull_data(socket, state, addr, b).await,
+        PacketType::TxAck => handle_tx_ack(socket, state, b).await,
+        _ => Err(anyhow!(
+            "Unexpected packet-type {:?} received from gateway",
+            packet_type
+        )),
+    }
+}
+
+async fn handle_push_data(
+    region_config_id: &str,
+    region_common_name: CommonName,
+    socket: &UdpSocket,
+    addr: SocketAddr,
+    b: &[u8],
+) -> Result<()> {
+    let header = GatewayHeader::from_slice(b)?;
+    socket
+        .send_to(&structs::ack_bytes(&header, PacketType::PushAck), addr)
+        .await
+        .context("Send PUSH_ACK")?;
+
+    let pd = PushData::from_slice(b)?;
+
+    for mut event in pd.to_uplink_frames()? {
+        EVENT_COUNTER
+            .get_or_create(&EventLabels {
+                event: "up".to_string(),
+            })
+            .inc();
+
+        if let Some(rx_info) = &mut event.rx_info {
+            rx_info
+                .metadata
+                .insert("region_config_id".to_string(), region_config_id.to_string());
+            rx_info.metadata.insert(
+                "region_common_name".to_string(),
+                region_common_name.to_string(),
+            );
+        }
+
+        info!(region_config_id = region_config_id, gateway_id = %header.gateway_id, "Uplink frame received from gateway");
+        tokio::spawn(uplink::deduplicate_uplink(event));
+    }
+
+    if let Some(mut event) = pd.to_gateway_stats()? {
+        EVENT_COUNTER
+            .get_or_create(&EventLabels {
+                event: "stats".to_string(),
+            })
+            .inc();
+
+        event
+            .metadata
+            .insert("region_config_id".to_string(), region_config_id.to_string());
+        event.metadata.insert(
+            "region_common_name".to_string(),
+            region_common_name.to_string(),
+        );
+
+        info!(region_config_id = region_config_id, gateway_id = %header.gateway_id, "Gateway stats received from gateway");
+        tokio::spawn(uplink::stats::Stats::handle(event));
+    }
+
+    Ok(())
+}
+
+async fn handle_pull_data(
+    socket: &UdpSocket,
+    state: &RwLock<State>,
+    addr: SocketAddr,
+    b: &[u8],
+) -> Result<()> {
+    let header = GatewayHeader::from_slice(b)?;
+    socket
+        .send_to(&structs::ack_bytes(&header, PacketType::PullAck), addr)
+        .await
+        .context("Send PULL_ACK")?;
+
+    let mut state = state.write().await;
+    if let Some(gw) = state.gateways.get(&header.gateway_id) {
+        if gw.addr != addr {
+            info!(gateway_id = %header.gateway_id, addr = %addr, "Gateway address changed");
+        }
+    } else {
+        info!(gateway_id = %header.gateway_id, addr = %addr, protocol_version = header.protocol_version, "Gateway connected");
+    }
+
+    state.gateways.insert(
+        header.gateway_id,
+        Gateway {
+            addr,
+            protocol_version: header.protocol_version,
+        },
+    );
+
+    Ok(())
+}
+
+async fn handle_tx_ack(socket: &UdpSocket, state: &RwLock<State>, b: &[u8]) -> Result<()> {
+    EVENT_COUNTER
+        .get_or_create(&EventLabels {
+            event: "ack".to_string(),
+        })
+        .inc();
+
+    let ack = TxAck::from_slice(b)?;
+    let status = ack.get_status();
+    let key = (ack.header.gateway_id, ack.header.random_token);
+
+    let mut state_w = state.write().await;
+    let mut pending = match state_w.downlinks.remove(&key) {
+        Some(v) => v,
+        None => {
+            warn!(gateway_id = %ack.header.gateway_id, token = ack.header.random_token, "Received TX_ACK for unknown downlink");
+            return Ok(());
+        }
+    };
+
+    pending.acks.push(gw::DownlinkTxAckItem {
+        status: status.into(),
+    });
+
+    // In case of an error, try the next downlink frame item (e.g. RX2 after RX1 failed).
+    if status != gw::TxAckStatus::Ok {
+        if let Some(item) = pending
+            .downlink_frame
+            .items
+            .get(pending.acks.len())
+            .cloned()
+        {
+            let (addr, protocol_version) = state_w
+                .gateways
+                .get(&ack.header.gateway_id)
+                .map(|v| (v.addr, v.protocol_version))
+                .ok_or_else(|| anyhow!("Unknown gateway {}", ack.header.gateway_id))?;
+
+            debug!(gateway_id = %ack.header.gateway_id, downlink_id = pending.downlink_frame.downlink_id, status = ?status, "Downlink frame item rejected, trying next item");
+
+            // The pending downlink must be stored before sending the PULL_RESP as the TX_ACK
+            // could be received before this function returns. The lock is released before
+            // sending, such that other packets can be handled in the meantime.
+            state_w.downlinks.insert(key, pending);
+            drop(state_w);
+
+            if let Err(e) = send_item(
+                socket,
+                addr,
+                protocol_version,
+                ack.header.random_token,
+                &item,
+            )
+            .await
+            {
+                state.write().await.downlinks.remove(&key);
+                return Err(e);
+            }
+
+            return Ok(());
+        }
+    }
+    drop(state_w);
+
+    // Items that were not attempted are marked as ignored.
+    while pending.acks.len() < pending.downlink_frame.items.len() {
+        pending.acks.push(gw::DownlinkTxAckItem {
+            status: gw::TxAckStatus::Ignored.into(),
+        });
+    }
+
+    tokio::spawn(downlink::tx_ack::TxAck::handle(gw::DownlinkTxAck {
+        gateway_id: pending.downlink_frame.gateway_id.clone(),
+        downlink_id: pending.downlink_frame.downlink_id,
+        items: pending.acks,
+        ..Default::default()
+    }));
+
+    Ok(())
+}
+
+async fn send_item(
+    socket: &UdpSocket,
+    addr: SocketAddr,
+    protocol_version: u8,
+    token: u16,
+    item: &gw::DownlinkFrameItem,
+) -> Result<()> {
+    let b = structs::pull_resp_bytes(protocol_version, token, item)?;
+    socket.send_to(&b, addr).await.context("Send PULL_RESP")?;
+    trace!(addr = %addr, "PULL_RESP sent");
+
+    Ok(())
+}
@@ -0,0 +1,861 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use chirpstack_api::{common, gw};
+use lrwn::EUI64;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PacketType {
+    PushData,
+    PushAck,
+    PullData,
+    PullResp,
+    PullAck,
+    TxAck,
+}
+
+impl PacketType {
+    pub fn from_byte(b: u8) -> Result<Self> {
+        Ok(match b {
+            0x00 => PacketType::PushData,
+            0x01 => PacketType::PushAck,
+            0x02 => PacketType::PullData,
+            0x03 => PacketType::PullResp,
+            0x04 => PacketType::PullAck,
+            0x05 => PacketType::TxAck,
+            _ => {
+                return Err(anyhow!("Unknown packet type: {}", b));
+            }
+        })
+    }
+
+    pub fn to_byte(self) -> u8 {
+        match self {
+            PacketType::PushData => 0x00,
+            PacketType::PushAck => 0x01,
+            PacketType::PullData => 0x02,
+            PacketType::PullResp => 0x03,
+            PacketType::PullAck => 0x04,
+            PacketType::TxAck => 0x05,
+        }
+    }
+}
+
+pub const PROTOCOL_VERSION_1: u8 = 0x01;
+pub const PROTOCOL_VERSION_2: u8 = 0x02;
+
+/// Returns the packet-type of the given UDP packet.
+pub fn get_packet_type(b: &[u8]) -> Result<PacketType> {
+    if b.len() < 4 {
+        return Err(anyhow!("At least 4 bytes of data are expected"));
+    }
+
+    if b[0] != PROTOCOL_VERSION_1 && b[0] != PROTOCOL_VERSION_2 {
+        return Err(anyhow!("Unsupported protocol version: {}", b[0]));
+    }
+
+    PacketType::from_byte(b[3])
+}
+
+/// Header shared by all gateway to server packets (PUSH_DATA, PULL_DATA and TX_ACK).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GatewayHeader {
+    pub protocol_version: u8,
+    pub random_token: u16,
+    pub gateway_id: EUI64,
+}
+
+impl GatewayHeader {
+    pub fn from_slice(b: &[u8]) -> Result<Self> {
+        if b.len() < 12 {
+            return Err(anyhow!("At least 12 bytes of data are expected"));
+        }
+
+        Ok(GatewayHeader {
+            protocol_version: b[0],
+            random_token: u16::from_be_bytes([b[1], b[2]]),
+            gateway_id: EUI64::from_slice(&b[4..12])?,
+        })
+    }
+}
+
+/// Returns the PUSH_ACK or PULL_ACK for the given header.
+pub fn ack_bytes(header: &GatewayHeader, packet_type: PacketType) -> Vec<u8> {
+    let token = header.random_token.to_be_bytes();
+    vec![
+        header.protocol_version,
+        token[0],
+        token[1],
+        packet_type.to_byte(),
+    ]
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PushData {
+    pub header: GatewayHeader,
+    pub payload: PushDataPayload,
+}
+
+impl PushData {
+    pub fn from_slice(b: &[u8]) -> Result<Self> {
+        let header = GatewayHeader::from_slice(b)?;
+        let payload: PushDataPayload =
+            serde_json::from_slice(&b[12..]).context("Decode PUSH_DATA payload")?;
+
+        Ok(PushData { header, payload })
+    }
+
+    /// Returns the uplink frames contained by the PUSH_DATA packet. In case the rxpk object
+    /// contains multiple rsig elements (multiple antennas), an uplink frame is returned for each.
+    pub fn to_uplink_frames(&self) -> Result<Vec<gw::UplinkFrame>> {
+        let mut out = Vec::new();
+
+        for rxpk in &self.payload.rxpk {
+            out.extend(rxpk.to_uplink_frames(&self.header.gateway_id)?);
+        }
+
+        Ok(out)
+    }
+
+    /// Returns the gateway stats in case the PUSH_DATA packet contains a stat object.
+    pub fn to_gateway_stats(&self) -> Result<Option<gw::GatewayStats>> {
+        match &self.payload.stat {
+            Some(v) => Ok(Some(v.to_gateway_stats(&self.header.gateway_id)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct PushDataPayload {
+    pub rxpk: Vec<RxPk>,
+    pub stat: Option<Stat>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(untagged)]
+pub enum DataRate {
+    Lora(String),
+    Fsk(u32),
+}
+
+impl Default for DataRate {
+    fn default() -> Self {
+        DataRate::Lora("".into())
+    }
+}
+
+impl DataRate {
+    fn from_modulation(modulation: &gw::Modulation) -> Result<(String, DataRate, Option<String>)> {
+        match modulation
+            .parameters
+            .as_ref()
+            .ok_or_else(|| anyhow!("modulation parameters must not be None"))?
+        {
+            gw::modulation::Parameters::Lora(v) => {
+                let code_rate: String = v.code_rate().into();
+                Ok((
+                    "LORA".into(),
+                    DataRate::Lora(format!("SF{}BW{}", v.spreading_factor, v.bandwidth / 1000)),
+                    Some(code_rate),
+                ))
+            }
+            gw::modulation::Parameters::Fsk(v) => {
+                Ok(("FSK".into(), DataRate::Fsk(v.datarate), None))
+            }
+            gw::modulation::Parameters::LrFhss(_) => Err(anyhow!(
+                "LR-FHSS modulation is not supported by the Semtech UDP protocol"
+            )),
+        }
+    }
+
+    fn to_modulation(
+        &self,
+        modu: &str,
+        codr: &Option<String>,
+        polarization_inversion: bool,
+    ) -> Result<gw::Modulation> {
+        Ok(gw::Modulation {
+            parameters: Some(match modu {
+                "LORA" => {
+                    let datr = match self {
+                        DataRate::Lora(v) => v,
+                        DataRate::Fsk(_) => {
+                            return Err(anyhow!("Expected string data-rate for LoRa modulation"));
+                        }
+                    };
+                    let (sf, bw) = parse_lora_data_rate(datr)?;
+
+                    gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                        bandwidth: bw,
+                        spreading_factor: sf,
+                        code_rate: match codr {
+                            Some(v) => gw::CodeRate::from_str(v)
+                                .map_err(|e| anyhow!("{}", e))?
+                                .into(),
+                            None => gw::CodeRate::CrUndefined.into(),
+                        },
+                        code_rate_legacy: "".into(),
+                        polarization_inversion,
+                    })
+                }
+                "FSK" => {
+                    let datr = match self {
+                        DataRate::Fsk(v) => *v,
+                        DataRate::Lora(_) => {
+                            return Err(anyhow!("Expected numeric data-rate for FSK modulation"));
+                        }
+                    };
+
+                    gw::modulation::Parameters::Fsk(gw::FskModulationInfo {
+                        datarate: datr,
+                        ..Default::default()
+                    })
+                }
+                _ => {
+                    return Err(anyhow!("Unsupported modulation: {}", modu));
+                }
+            }),
+        })
+    }
+}
+
+/// Parses the LoRa data-rate identifier (e.g. SF7BW125) and returns the spreading-factor and
+/// the bandwidth in Hz.
+fn parse_lora_data_rate(s: &str) -> Result<(u32, u32)> {
+    let s = s
+        .strip_prefix("SF")
+        .ok_or_else(|| anyhow!("Invalid LoRa data-rate: {}", s))?;
+    let (sf, bw) = s
+        .split_once("BW")
+        .ok_or_else(|| anyhow!("Invalid LoRa data-rate: {}", s))?;
+
+    let sf: u32 = sf.parse().context("Parse spreading-factor")?;
+    let bw: u32 = bw.parse().context("Parse bandwidth")?;
+
+    Ok((sf, bw * 1000))
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct RxPk {
+    /// UTC time of pkt RX, us precision, ISO 8601 'compact' format.
+    pub time: Option<String>,
+    /// GPS time of pkt RX, number of milliseconds since 06.Jan.1980.
+    pub tmms: Option<u64>,
+    /// Internal timestamp of "RX finished" event (32b unsigned).
+    pub tmst: u32,
+    /// RX central frequency in MHz.
+    pub freq: f64,
+    /// Concentrator "IF" channel used for RX.
+    pub chan: u32,
+    /// Concentrator "RF chain" used for RX.
+    pub rfch: u32,
+    /// Concentrator board used for RX.
+    pub brd: u32,
+    /// CRC status: 1 = OK, -1 = fail, 0 = no CRC.
+    pub stat: i8,
+    /// Modulation identifier "LORA" or "FSK".
+    pub modu: String,
+    /// LoRa datarate identifier (eg. SF12BW500) or FSK datarate (unsigned, in bits per second).
+    pub datr: DataRate,
+    /// LoRa ECC coding rate identifier.
+    pub codr: Option<String>,
+    /// RSSI in dBm (signed integer, 1 dB precision).
+    pub rssi: i32,
+    /// LoRa SNR ratio in dB (signed float, 0.1 dB precision).
+    pub lsnr: Option<f32>,
+    /// RF packet payload size in bytes.
+    pub size: u16,
+    /// Base64 encoded RF packet payload, padded.
+    pub data: String,
+    /// Signal information per antenna (v2 packet-forwarder).
+    pub rsig: Vec<RSig>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct RSig {
+    /// Antenna number on which signal has been received.
+    pub ant: u32,
+    /// Concentrator "IF" channel used for RX.
+    pub chan: u32,
+    /// RSSI in dBm of the channel.
+    pub rssic: i32,
+    /// LoRa SNR ratio in dB.
+    pub lsnr: Option<f32>,
+}
+
+impl RxPk {
+    fn to_uplink_frames(&self, gateway_id: &EUI64) -> Result<Vec<gw::UplinkFrame>> {
+        let phy_payload = general_purpose::STANDARD
+            .decode(&self.data)
+            .context("Decode rxpk data")?;
+
+        let tx_info = gw::UplinkTxInfo {
+            frequency: (self.freq * 1_000_000.0).round() as u32,
+            modulation: Some(self.datr.to_modulation(&self.modu, &self.codr, false)?),
+        };
+
+        let time: Option<pbjson_types::Timestamp> = match &self.time {
+            Some(v) => Some(
+                DateTime::parse_from_rfc3339(v)
+                    .context("Parse rxpk time")?
+                    .with_timezone(&Utc)
+                    .into(),
+            ),
+            None => None,
+        };
+
+        let time_since_gps_epoch = self.tmms.map(|v| {
+            let d = Duration::from_millis(v);
+            pbjson_types::Duration {
+                seconds: d.as_secs() as i64,
+                nanos: d.subsec_nanos() as i32,
+            }
+        });
+
+        let rx_info = gw::UplinkRxInfo {
+            gateway_id: gateway_id.to_string(),
+            uplink_id: 0,
+            time,
+            time_since_gps_epoch,
+            rssi: self.rssi,
+            snr: self.lsnr.unwrap_or_default(),
+            channel: self.chan,
+            rf_chain: self.rfch,
+            board: self.brd,
+            context: self.tmst.to_be_bytes().to_vec(),
+            crc_status: match self.stat {
+                1 => gw::CrcStatus::CrcOk,
+                -1 => gw::CrcStatus::BadCrc,
+                _ => gw::CrcStatus::NoCrc,
+            }
+            .into(),
+            ..Default::default()
+        };
+
+        let rx_infos: Vec<gw::UplinkRxInfo> = if self.rsig.is_empty() {
+            vec![rx_info]
+        } else {
+            self.rsig
+                .iter()
+                .map(|rsig| gw::UplinkRxInfo {
+                    antenna: rsig.ant,
+                    channel: rsig.chan,
+                    rssi: rsig.rssic,
+                    snr: rsig.lsnr.unwrap_or_default(),
+                    ..rx_info.clone()
+                })
+                .collect()
+        };
+
+        let mut rng = rand::thread_rng();
+
+        Ok(rx_infos
+            .into_iter()
+            .map(|mut rx_info| {
+                rx_info.uplink_id = rng.gen();
+
+                gw::UplinkFrame {
+                    phy_payload: phy_payload.clone(),
+                    tx_info: Some(tx_info.clone()),
+                    rx_info: Some(rx_info),
+                    ..Default::default()
+                }
+            })
+            .collect())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct Stat {
+    /// UTC 'system' time of the gateway, ISO 8601 'expanded' format.
+    pub time: String,
+    /// GPS latitude of the gateway in degree.
+    pub lati: Option<f64>,
+    /// GPS longitude of the gateway in degree.
+    pub long: Option<f64>,
+    /// GPS altitude of the gateway in meter RX.
+    pub alti: Option<i32>,
+    /// Number of radio packets received.
+    pub rxnb: u32,
+    /// Number of radio packets received with a valid PHY CRC.
+    pub rxok: u32,
+    /// Number of radio packets forwarded.
+    pub rxfw: u32,
+    /// Percentage of upstream datagrams that were acknowledged.
+    pub ackr: f32,
+    /// Number of downlink datagrams received.
+    pub dwnb: u32,
+    /// Number of packets emitted.
+    pub txnb: u32,
+}
+
+impl Stat {
+    fn to_gateway_stats(&self, gateway_id: &EUI64) -> Result<gw::GatewayStats> {
+        let time = NaiveDateTime::parse_from_str(&self.time, "%Y-%m-%d %H:%M:%S GMT")
+            .context("Parse stat time")?;
+        let time = DateTime::<Utc>::from_utc(time, Utc);
+
+        let location = match (self.lati, self.long) {
+            (Some(lati), Some(long)) => Some(common::Location {
+                latitude: lati,
+                longitude: long,
+                altitude: self.alti.unwrap_or_default().into(),
+                source: common::LocationSource::Gps.into(),
+                ..Default::default()
+            }),
+            _ => None,
+        };
+
+        Ok(gw::GatewayStats {
+            gateway_id: gateway_id.to_string(),
+            time: Some(time.into()),
+            location,
+            rx_packets_received: self.rxnb,
+            rx_packets_received_ok: self.rxok,
+            tx_packets_received: self.dwnb,
+            tx_packets_emitted: self.txnb,
+            metadata: [
+                ("rx_packets_forwarded".to_string(), self.rxfw.to_string()),
+                ("upstream_ack_ratio".to_string(), self.ackr.to_string()),
+            ]
+            .iter()
+            .cloned()
+            .collect(),
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PullRespPayload {
+    pub txpk: TxPk,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct TxPk {
+    /// Send packet immediately (will ignore tmst & time).
+    pub imme: bool,
+    /// Send packet on a certain timestamp value (will ignore time).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tmst: Option<u32>,
+    /// Send packet at a certain GPS time (GPS synchronization required).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tmms: Option<u64>,
+    /// TX central frequency in MHz.
+    pub freq: f64,
+    /// Concentrator "RF chain" used for TX.
+    pub rfch: u32,
+    /// TX output power in dBm.
+    pub powe: i32,
+    /// Modulation identifier "LORA" or "FSK".
+    pub modu: String,
+    /// LoRa datarate identifier (eg. SF12BW500) or FSK datarate (unsigned, in bits per second).
+    pub datr: DataRate,
+    /// LoRa ECC coding rate identifier.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub codr: Option<String>,
+    /// FSK frequency deviation (unsigned integer, in Hz).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fdev: Option<u32>,
+    /// Lora modulation polarization inversion.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipol: Option<bool>,
+    /// RF packet payload size in bytes.
+    pub size: u16,
+    /// Base64 encoded RF packet payload, padding optional.
+    pub data: String,
+    /// Concentrator board used for TX.
+    pub brd: u32,
+    /// Antenna used for TX.
+    pub ant: u32,
+}
+
+impl TxPk {
+    pub fn from_downlink_frame_item(item: &gw::DownlinkFrameItem) -> Result<Self> {
+        let tx_info = item
+            .tx_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("tx_info must not be None"))?;
+        let (modu, datr, codr) = DataRate::from_modulation(
+            tx_info
+                .modulation
+                .as_ref()
+                .ok_or_else(|| anyhow!("modulation must not be None"))?,
+        )?;
+
+        let mut txpk = TxPk {
+            freq: tx_info.frequency as f64 / 1_000_000.0,
+            powe: tx_info.power,
+            size: item.phy_payload.len() as u16,
+            data: general_purpose::STANDARD.encode(&item.phy_payload),
+            brd: tx_info.board,
+            ant: tx_info.antenna,
+            codr,
+            datr,
+            modu,
+            ..Default::default()
+        };
+
+        if let Some(gw::modulation::Parameters::Lora(v)) = tx_info
+            .modulation
+            .as_ref()
+            .and_then(|v| v.parameters.as_ref())
+        {
+            txpk.ipol = Some(v.polarization_inversion);
+        }
+
+        if let Some(gw::modulation::Parameters::Fsk(v)) = tx_info
+            .modulation
+            .as_ref()
+            .and_then(|v| v.parameters.as_ref())
+        {
+            txpk.fdev = Some(v.frequency_deviation);
+        }
+
+        match tx_info
+            .timing
+            .as_ref()
+            .and_then(|v| v.parameters.as_ref())
+            .ok_or_else(|| anyhow!("timing parameters must not be None"))?
+        {
+            gw::timing::Parameters::Immediately(_) => {
+                txpk.imme = true;
+            }
+            gw::timing::Parameters::Delay(v) => {
+                if tx_info.context.len() != 4 {
+                    return Err(anyhow!("context must be exactly 4 bytes"));
+                }
+
+                let tmst = u32::from_be_bytes([
+                    tx_info.context[0],
+                    tx_info.context[1],
+                    tx_info.context[2],
+                    tx_info.context[3],
+                ]);
+                let delay = v
+                    .delay
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("delay must not be None"))?;
+                let delay_us = (delay.seconds as u64 * 1_000_000) + (delay.nanos as u64 / 1_000);
+
+                txpk.tmst = Some(tmst.wrapping_add(delay_us as u32));
+            }
+            gw::timing::Parameters::GpsEpoch(v) => {
+                let gps_time = v
+                    .time_since_gps_epoch
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("time_since_gps_epoch must not be None"))?;
+
+                txpk.tmms =
+                    Some((gps_time.seconds as u64 * 1_000) + (gps_time.nanos as u64 / 1_000_000));
+            }
+        }
+
+        Ok(txpk)
+    }
+}
+
+/// Returns the PULL_RESP packet for the given downlink frame item.
+pub fn pull_resp_bytes(
+    protocol_version: u8,
+    token: u16,
+    item: &gw::DownlinkFrameItem,
+) -> Result<Vec<u8>> {
+    let payload = PullRespPayload {
+        txpk: TxPk::from_downlink_frame_item(item)?,
+    };
+
+    let token = token.to_be_bytes();
+    let mut b = vec![
+        protocol_version,
+        token[0],
+        token[1],
+        PacketType::PullResp.to_byte(),
+    ];
+    b.extend_from_slice(&serde_json::to_vec(&payload)?);
+
+    Ok(b)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TxAck {
+    pub header: GatewayHeader,
+    pub payload: Option<TxAckPayload>,
+}
+
+impl TxAck {
+    pub fn from_slice(b: &[u8]) -> Result<Self> {
+        let header = GatewayHeader::from_slice(b)?;
+
+        // Some packet-forwarders append a NULL terminator to the JSON object.
+        let json = b[12..]
+            .iter()
+            .cloned()
+            .filter(|b| *b != 0x00)
+            .collect::<Vec<u8>>();
+
+        let payload = if json.is_empty() {
+            None
+        } else {
+            Some(serde_json::from_slice(&json).context("Decode TX_ACK payload")?)
+        };
+
+        Ok(TxAck { header, payload })
+    }
+
+    /// Returns the ack status. A missing payload or error field means no error.
+    pub fn get_status(&self) -> gw::TxAckStatus {
+        let error = match &self.payload {
+            Some(v) => v.txpk_ack.error.as_str(),
+            None => "",
+        };
+
+        match error {
+            "" | "NONE" => gw::TxAckStatus::Ok,
+            "TOO_LATE" => gw::TxAckStatus::TooLate,
+            "TOO_EARLY" => gw::TxAckStatus::TooEarly,
+            "COLLISION_PACKET" => gw::TxAckStatus::CollisionPacket,
+            "COLLISION_BEACON" => gw::TxAckStatus::CollisionBeacon,
+            "TX_FREQ" => gw::TxAckStatus::TxFreq,
+            "TX_POWER" => gw::TxAckStatus::TxPower,
+            "GPS_UNLOCKED" => gw::TxAckStatus::GpsUnlocked,
+            _ => gw::TxAckStatus::InternalError,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct TxAckPayload {
+    pub txpk_ack: TxPkAck,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct TxPkAck {
+    pub error: String,
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_packet_type() {
+        assert!(get_packet_type(&[0x02, 0x01]).is_err());
+        assert!(get_packet_type(&[0x03, 0x01, 0x02, 0x00]).is_err());
+        assert_eq!(
+            PacketType::PullData,
+            get_packet_type(&[0x02, 0x01, 0x02, 0x02]).unwrap()
+        );
+        assert_eq!(
+            PacketType::TxAck,
+            get_packet_type(&[0x01, 0x01, 0x02, 0x05]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_ack_bytes() {
+        let header = GatewayHeader::from_slice(&[
+            0x02, 0x01, 0x02, 0x02, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+        ])
+        .unwrap();
+        assert_eq!(
+            GatewayHeader {
+                protocol_version: 2,
+                random_token: 258,
+                gateway_id: EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
+            },
+            header
+        );
+        assert_eq!(
+            vec![0x02, 0x01, 0x02, 0x04],
+            ack_bytes(&header, PacketType::PullAck)
+        );
+    }
+
+    #[test]
+    fn test_push_data_rxpk() {
+        let mut b = vec![
+            0x02, 0x01, 0x02, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+        ];
+        b.extend_from_slice(
+            r#"{"rxpk":[{"time":"2023-03-31T16:21:17.528002Z","tmms":1364487695528,"tmst":3512348611,"chan":2,"rfch":0,"freq":866.349812,"stat":1,"modu":"LORA","datr":"SF7BW125","codr":"4/5","rssi":-35,"lsnr":5.1,"size":3,"data":"AQID"}]}"#.as_bytes(),
+        );
+
+        let pd = PushData::from_slice(&b).unwrap();
+        let frames = pd.to_uplink_frames().unwrap();
+        assert_eq!(1, frames.len());
+
+        let f = &frames[0];
+        assert_eq!(vec![1, 2, 3], f.phy_payload);
+
+        let tx_info = f.tx_info.as_ref().unwrap();
+        assert_eq!(866349812, tx_info.frequency);
+        assert_eq!(
+            Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 7,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    code_rate_legacy: "".into(),
+                    polarization_inversion: false,
+                })),
+            }),
+            tx_info.modulation
+        );
+
+        let rx_info = f.rx_info.as_ref().unwrap();
+        assert_eq!("0102030405060708", rx_info.gateway_id);
+        assert_eq!(-35, rx_info.rssi);
+        assert_eq!(5.1, rx_info.snr);
+        assert_eq!(2, rx_info.channel);
+        assert_eq!(3512348611u32.to_be_bytes().to_vec(), rx_info.context);
+        assert_eq!(gw::CrcStatus::CrcOk, rx_info.crc_status());
+        assert_eq!(
+            Some(pbjson_types::Duration {
+                seconds: 1364487695,
+                nanos: 528000000,
+            }),
+            rx_info.time_since_gps_epoch
+        );
+    }
+
+    #[test]
+    fn test_push_data_rxpk_rsig() {
+        let mut b = vec![
+            0x02, 0x01, 0x02, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+        ];
+        b.extend_from_slice(
+            r#"{"rxpk":[{"tmst":1,"freq":868.1,"stat":1,"modu":"LORA","datr":"SF12BW125","codr":"4/5","size":3,"data":"AQID","rsig":[{"ant":0,"chan":1,"rssic":-60,"lsnr":3.5},{"ant":1,"chan":1,"rssic":-70,"lsnr":-2.0}]}]}"#.as_bytes(),
+        );
+
+        let pd = PushData::from_slice(&b).unwrap();
+        let frames = pd.to_uplink_frames().unwrap();
+        assert_eq!(2, frames.len());
+        assert_eq!(0, frames[0].rx_info.as_ref().unwrap().antenna);
+        assert_eq!(-60, frames[0].rx_info.as_ref().unwrap().rssi);
+        assert_eq!(1, frames[1].rx_info.as_ref().unwrap().antenna);
+        assert_eq!(-2.0, frames[1].rx_info.as_ref().unwrap().snr);
+    }
+
+    #[test]
+    fn test_push_data_stat() {
+        let mut b = vec![
+            0x02, 0x01, 0x02, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+        ];
+        b.extend_from_slice(
+            r#"{"stat":{"time":"2014-01-12 08:59:28 GMT","lati":46.24000,"long":3.25230,"alti":145,"rxnb":2,"rxok":2,"rxfw":2,"ackr":100.0,"dwnb":2,"txnb":2}}"#.as_bytes(),
+        );
+
+        let pd = PushData::from_slice(&b).unwrap();
+        assert!(pd.to_uplink_frames().unwrap().is_empty());
+
+        let stats = pd.to_gateway_stats().unwrap().unwrap();
+        assert_eq!("0102030405060708", stats.gateway_id);
+        assert_eq!(2, stats.rx_packets_received);
+        assert_eq!(2, stats.tx_packets_emitted);
+        assert_eq!(
+            Some(&"2".to_string()),
+            stats.metadata.get("rx_packets_forwarded")
+        );
+        assert_eq!(
+            Some(&"100".to_string()),
+            stats.metadata.get("upstream_ack_ratio")
+        );
+        assert_eq!(
+            Some(common::Location {
+                latitude: 46.24,
+                longitude: 3.2523,
+                altitude: 145.0,
+                source: common::LocationSource::Gps.into(),
+                ..Default::default()
+            }),
+            stats.location
+        );
+    }
+
+    #[test]
+    fn test_pull_resp() {
+        let item = gw::DownlinkFrameItem {
+            phy_payload: vec![1, 2, 3],
+            tx_info: Some(gw::DownlinkTxInfo {
+                frequency: 868100000,
+                power: 14,
+                modulation: Some(gw::Modulation {
+                    parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                        bandwidth: 125000,
+                        spreading_factor: 7,
+                        code_rate: gw::CodeRate::Cr45.into(),
+                        polarization_inversion: true,
+                        ..Default::default()
+                    })),
+                }),
+                timing: Some(gw::Timing {
+                    parameters: Some(gw::timing::Parameters::Delay(gw::DelayTimingInfo {
+                        delay: Some(pbjson_types::Duration {
+                            seconds: 1,
+                            nanos: 0,
+                        }),
+                    })),
+                }),
+                context: vec![0xff, 0xff, 0xff, 0xff],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let txpk = TxPk::from_downlink_frame_item(&item).unwrap();
+        assert_eq!(
+            TxPk {
+                imme: false,
+                tmst: Some(999999),
+                tmms: None,
+                freq: 868.1,
+                rfch: 0,
+                powe: 14,
+                modu: "LORA".into(),
+                datr: DataRate::Lora("SF7BW125".into()),
+                codr: Some("4/5".into()),
+                fdev: None,
+                ipol: Some(true),
+                size: 3,
+                data: "AQID".into(),
+                brd: 0,
+                ant: 0,
+            },
+            txpk
+        );
+
+        let b = pull_resp_bytes(2, 258, &item).unwrap();
+        assert_eq!(vec![0x02, 0x01, 0x02, 0x03], b[0..4].to_vec());
+        let pl: PullRespPayload = serde_json::from_slice(&b[4..]).unwrap();
+        assert_eq!(txpk, pl.txpk);
+    }
+
+    #[test]
+    fn test_tx_ack() {
+        let mut b = vec![
+            0x02, 0x01, 0x02, 0x05, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+        ];
+        let ack = TxAck::from_slice(&b).unwrap();
+        assert_eq!(gw::TxAckStatus::Ok, ack.get_status());
+
+        b.extend_from_slice(r#"{"txpk_ack":{"error":"TOO_LATE"}}"#.as_bytes());
+        b.push(0x00);
+        let ack = TxAck::from_slice(&b).unwrap();
+        assert_eq!(258, ack.header.random_token);
+        assert_eq!(gw::TxAckStatus::TooLate, ack.get_status());
+    }
+}
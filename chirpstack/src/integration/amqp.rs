@@ -1,16 +1,28 @@
 use std::collections::HashMap;
 
+use std::time::Duration;
+
 use anyhow::Result;
 use async_trait::async_trait;
+use futures::stream::StreamExt;
 use handlebars::Handlebars;
 use lapin::{
-    options::BasicPublishOptions, BasicProperties, Channel, Connection, ConnectionProperties,
+    options::{
+        BasicAckOptions, BasicConsumeOptions, BasicPublishOptions, BasicQosOptions,
+        QueueBindOptions, QueueDeclareOptions,
+    },
+    types::{AMQPValue, FieldTable},
+    BasicProperties, Channel, Connection, ConnectionProperties,
 };
 use prost::Message;
+use regex::Regex;
 use serde::Serialize;
 use tokio::sync::RwLock;
-use tracing::{error, info};
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tracing::{error, info, warn};
 
+use super::command;
 use super::Integration as IntegrationTrait;
 use crate::config::AmqpIntegration as Config;
 use chirpstack_api::integration;
@@ -28,6 +40,7 @@ pub struct Integration<'a> {
     templates: Handlebars<'a>,
     json: bool,
     url: String,
+    command_task: Option<JoinHandle<()>>,
 }
 
 #[derive(Serialize)]
@@ -46,13 +59,25 @@ impl<'a> Integration<'a> {
         templates.register_escape_fn(handlebars::no_escape);
         templates.register_template_string("event_routing_key", &conf.event_routing_key)?;
 
-        let i = Integration {
+        let mut i = Integration {
             templates,
             url: conf.url.clone(),
             json: conf.json,
+            command_task: None,
         };
         i.connect().await?;
 
+        if !conf.command_queue.is_empty() {
+            let binding_key = command::render_wildcard(&conf.command_routing_key, "*")?;
+            let command_regex = command::get_regex(&conf.command_routing_key)?;
+
+            i.command_task = Some(tokio::spawn(command_loop(
+                conf.clone(),
+                binding_key,
+                command_regex,
+            )));
+        }
+
         Ok(i)
     }
 
@@ -127,6 +152,128 @@ impl<'a> Integration<'a> {
     }
 }
 
+// On reload, the integration is replaced by a new instance. This stops the command
+// consumer, un-acknowledged commands are re-delivered to the new consumer.
+impl Drop for Integration<'_> {
+    fn drop(&mut self) {
+        if let Some(t) = &self.command_task {
+            t.abort();
+        }
+    }
+}
+
+// Consumes the commands from the command queue, re-connecting on error.
+async fn command_loop(conf: Config, binding_key: String, command_regex: Regex) {
+    loop {
+        if let Err(e) = consume_commands(&conf, &binding_key, &command_regex).await {
+            error!(queue = %conf.command_queue, error = %e, "Consuming AMQP commands error");
+        }
+
+        sleep(Duration::from_secs(1)).await;
+    }
+}
+
+async fn consume_commands(conf: &Config, binding_key: &str, command_regex: &Regex) -> Result<()> {
+    let queue = conf.command_queue.as_str();
+    info!(queue = %queue, binding_key = %binding_key, "Starting AMQP command consumer");
+
+    let options = ConnectionProperties::default()
+        .with_executor(tokio_executor_trait::Tokio::current())
+        .with_reactor(tokio_reactor_trait::Tokio);
+    let conn = Connection::connect(&conf.url, options).await?;
+    let chan = conn.create_channel().await?;
+
+    chan.queue_declare(
+        queue,
+        QueueDeclareOptions {
+            durable: true,
+            ..Default::default()
+        },
+        FieldTable::default(),
+    )
+    .await?;
+    if !conf.command_dead_letter_queue.is_empty() {
+        chan.queue_declare(
+            &conf.command_dead_letter_queue,
+            QueueDeclareOptions {
+                durable: true,
+                ..Default::default()
+            },
+            FieldTable::default(),
+        )
+        .await?;
+    }
+    chan.queue_bind(
+        queue,
+        "amq.topic",
+        binding_key,
+        QueueBindOptions::default(),
+        FieldTable::default(),
+    )
+    .await?;
+    chan.basic_qos(10, BasicQosOptions::default()).await?;
+
+    let mut consumer = chan
+        .basic_consume(
+            queue,
+            "",
+            BasicConsumeOptions::default(),
+            FieldTable::default(),
+        )
+        .await?;
+
+    while let Some(delivery) = consumer.next().await {
+        let delivery = delivery?;
+        let routing_key = delivery.routing_key.as_str();
+
+        let key = match command::parse_key(command_regex, routing_key) {
+            Some(v) => v,
+            None => {
+                warn!(routing_key = %routing_key, "Error parsing command routing-key, ignoring command");
+                delivery.ack(BasicAckOptions::default()).await?;
+                continue;
+            }
+        };
+
+        // Commands are only acknowledged once these have been handled, such that on
+        // a restart these are re-delivered. Commands which still fail after the max.
+        // number of attempts are published to the dead-letter queue (if configured).
+        if let Err(e) =
+            command::handle_with_retry(&key, conf.json, &delivery.data, conf.command_max_attempts)
+                .await
+        {
+            if conf.command_dead_letter_queue.is_empty() {
+                error!(routing_key = %routing_key, error = %e, "Handling command failed, dropping command");
+            } else {
+                error!(routing_key = %routing_key, queue = %conf.command_dead_letter_queue, error = %e, "Handling command failed, publishing command to dead-letter queue");
+
+                let mut headers = FieldTable::default();
+                headers.insert("error".into(), AMQPValue::LongString(e.to_string().into()));
+                headers.insert(
+                    "routing_key".into(),
+                    AMQPValue::LongString(routing_key.to_string().into()),
+                );
+
+                // Publish to the default exchange, which routes the message to the
+                // queue with the same name as the routing-key.
+                chan.basic_publish(
+                    "",
+                    &conf.command_dead_letter_queue,
+                    BasicPublishOptions::default(),
+                    &delivery.data,
+                    delivery.properties.clone().with_headers(headers),
+                )
+                .await?
+                .await?;
+            }
+        }
+
+        delivery.ack(BasicAckOptions::default()).await?;
+    }
+
+    Ok(())
+}
+
 #[async_trait]
 impl<'a> IntegrationTrait for Integration<'a> {
     async fn uplink_event(
@@ -266,6 +413,7 @@ pub mod test {
             json: true,
             event_routing_key: "application.{{application_id}}.device.{{dev_eui}}.event.{{event}}"
                 .to_string(),
+            ..Default::default()
         };
 
         let conn = loop {
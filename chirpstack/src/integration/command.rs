@@ -0,0 +1,348 @@
+use std::future::Future;
+use std::io::Cursor;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::Utc;
+use handlebars::Handlebars;
+use prost::Message;
+use regex::Regex;
+use serde::Serialize;
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use super::enqueue_down_command;
+use crate::api::helpers::ToProto;
+use crate::storage::error::Error as StorageError;
+use crate::storage::{application, device, device_profile, tenant};
+use chirpstack_api::integration;
+use lrwn::EUI64;
+
+// Interval between retries of a command which failed because of a transient error.
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Serialize)]
+struct CommandKeyContext {
+    pub application_id: String,
+    pub dev_eui: String,
+    pub command: String,
+}
+
+/// Command parsed from the routing-key / message-key of a received command.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CommandKey {
+    pub application_id: String,
+    pub dev_eui: String,
+    pub command: String,
+}
+
+/// Error returned when handling a command failed because of a transient error
+/// (e.g. the database is not available). In this case the command must be
+/// re-delivered.
+#[derive(Debug)]
+pub struct TransientError(pub anyhow::Error);
+
+impl std::fmt::Display for TransientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Renders the given command-key template, using the given wildcard for all
+/// variables (e.g. to subscribe to all commands).
+pub fn render_wildcard(template: &str, wildcard: &str) -> Result<String> {
+    let mut templates = Handlebars::new();
+    templates.register_escape_fn(handlebars::no_escape);
+    templates.register_template_string("command_key", template)?;
+
+    Ok(templates.render(
+        "command_key",
+        &CommandKeyContext {
+            application_id: wildcard.to_string(),
+            dev_eui: wildcard.to_string(),
+            command: wildcard.to_string(),
+        },
+    )?)
+}
+
+/// Returns the regular expression to parse the given command-key template.
+pub fn get_regex(template: &str) -> Result<Regex> {
+    let mut templates = Handlebars::new();
+    templates.register_escape_fn(handlebars::no_escape);
+    templates.register_template_string("command_key", template)?;
+
+    Ok(Regex::new(&format!(
+        "^{}$",
+        templates.render(
+            "command_key",
+            &CommandKeyContext {
+                application_id: r#"(?P<application_id>[\w-]+)"#.to_string(),
+                dev_eui: r#"(?P<dev_eui>[\w]+)"#.to_string(),
+                command: r#"(?P<command>[\w]+)"#.to_string(),
+            },
+        )?
+    ))?)
+}
+
+/// Parses the routing-key / message-key of a command.
+pub fn parse_key(re: &Regex, key: &str) -> Option<CommandKey> {
+    let caps = re.captures(key)?;
+
+    Some(CommandKey {
+        application_id: caps.name("application_id")?.as_str().to_string(),
+        dev_eui: caps.name("dev_eui")?.as_str().to_string(),
+        command: caps.name("command")?.as_str().to_string(),
+    })
+}
+
+/// Handles the received command.
+///
+/// Errors caused by the command itself (e.g. an invalid payload or unknown device)
+/// are logged and reported as log event to the integrations of the application,
+/// as re-delivering the command will not resolve these. A TransientError is
+/// returned when the command must be re-delivered.
+pub async fn handle(key: &CommandKey, json: bool, b: &[u8]) -> Result<(), TransientError> {
+    info!(application_id = %key.application_id, dev_eui = %key.dev_eui, command = %key.command, "Command received for device");
+
+    let dev = match get_device(key).await {
+        Ok(v) => v,
+        Err(e) => {
+            if is_transient(&e) {
+                return Err(TransientError(e));
+            }
+
+            error!(application_id = %key.application_id, dev_eui = %key.dev_eui, command = %key.command, error = %e, "Processing command error");
+            return Ok(());
+        }
+    };
+
+    let res = async {
+        match key.command.as_ref() {
+            "down" => {
+                let cmd: integration::DownlinkCommand = match json {
+                    true => serde_json::from_slice(b)?,
+                    false => integration::DownlinkCommand::decode(&mut Cursor::new(b))?,
+                };
+                if !cmd.dev_eui.eq_ignore_ascii_case(&key.dev_eui) {
+                    return Err(anyhow!(
+                        "Payload dev_eui {} does not match key dev_eui {}",
+                        cmd.dev_eui,
+                        key.dev_eui
+                    ));
+                }
+
+                match enqueue_down_command(&dev, &cmd).await {
+                    Ok(id) => {
+                        info!(dev_eui = %dev.dev_eui, id = %id, "Downlink command enqueued");
+                        Ok(())
+                    }
+                    // Commands might be delivered more than once, in which case the queue-item
+                    // with the same ID has already been enqueued.
+                    Err(e) if matches!(e.downcast_ref::<StorageError>(), Some(StorageError::AlreadyExists(_))) => {
+                        warn!(dev_eui = %dev.dev_eui, id = %cmd.id, "Downlink command has already been enqueued");
+                        Ok(())
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            _ => Err(anyhow!("Unknown command type: {}", key.command)),
+        }
+    }
+    .await;
+
+    if let Err(e) = res {
+        if is_transient(&e) {
+            return Err(TransientError(e));
+        }
+
+        error!(dev_eui = %dev.dev_eui, command = %key.command, error = %e, "Processing command error");
+        if let Err(e) = send_error_event(&dev, &key.command, &e).await {
+            error!(dev_eui = %dev.dev_eui, error = %e, "Sending command error event failed");
+        }
+    }
+
+    Ok(())
+}
+
+/// Handles the received command, retrying it on transient errors. The error of the
+/// last attempt is returned when the command could not be handled within the
+/// given max. number of attempts, in which case the command must be dead-lettered
+/// or dropped by the caller.
+pub async fn handle_with_retry(
+    key: &CommandKey,
+    json: bool,
+    b: &[u8],
+    max_attempts: usize,
+) -> Result<(), TransientError> {
+    retry(max_attempts, RETRY_INTERVAL, || handle(key, json, b)).await
+}
+
+async fn retry<F, Fut>(
+    max_attempts: usize,
+    interval: Duration,
+    mut f: F,
+) -> Result<(), TransientError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<(), TransientError>>,
+{
+    let mut attempts: usize = 0;
+
+    loop {
+        attempts += 1;
+
+        match f().await {
+            Ok(_) => return Ok(()),
+            Err(e) => {
+                if attempts >= max_attempts {
+                    return Err(e);
+                }
+
+                warn!(attempts = attempts, error = %e, "Handling command failed, retrying");
+                sleep(interval).await;
+            }
+        }
+    }
+}
+
+// Returns the device and validates that it belongs to the application from the key.
+async fn get_device(key: &CommandKey) -> Result<device::Device> {
+    let dev_eui = EUI64::from_str(&key.dev_eui)?;
+    let app_id = Uuid::from_str(&key.application_id)?;
+
+    let dev = device::get(&dev_eui).await?;
+    if dev.application_id != app_id {
+        return Err(anyhow!(
+            "Application ID from key does not match application ID from device"
+        ));
+    }
+
+    Ok(dev)
+}
+
+// Returns true for storage errors which are not caused by the command itself.
+fn is_transient(e: &anyhow::Error) -> bool {
+    match e.downcast_ref::<StorageError>() {
+        Some(e) => matches!(
+            e,
+            StorageError::Diesel(_)
+                | StorageError::Redis(_)
+                | StorageError::TokioJoin(_)
+                | StorageError::Anyhow(_)
+        ),
+        None => false,
+    }
+}
+
+async fn send_error_event(dev: &device::Device, command: &str, err: &anyhow::Error) -> Result<()> {
+    let app = application::get(&dev.application_id).await?;
+    let t = tenant::get(&app.tenant_id).await?;
+    let dp = device_profile::get(&dev.device_profile_id).await?;
+
+    let pl = integration::LogEvent {
+        time: Some(Utc::now().into()),
+        device_info: Some(integration::DeviceInfo {
+            tenant_id: t.id.to_string(),
+            tenant_name: t.name.clone(),
+            application_id: app.id.to_string(),
+            application_name: app.name.to_string(),
+            device_profile_id: dp.id.to_string(),
+            device_profile_name: dp.name.clone(),
+            device_name: dev.name.clone(),
+            device_class_enabled: dev.enabled_class.to_proto().into(),
+            dev_eui: dev.dev_eui.to_string(),
+            tags: {
+                let mut tags = (*dp.tags).clone();
+                tags.extend((*dev.tags).clone());
+                tags
+            },
+        }),
+        level: integration::LogLevel::Error.into(),
+        code: integration::LogCode::DownlinkCommand.into(),
+        description: err.to_string(),
+        context: [("command".to_string(), command.to_string())]
+            .iter()
+            .cloned()
+            .collect(),
+    };
+
+    super::log_event(app.id, &dev.variables, &pl).await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_retry() {
+        // Transient errors are retried.
+        let mut calls = 0;
+        let res = retry(3, Duration::ZERO, || {
+            calls += 1;
+            let calls = calls;
+            async move {
+                if calls < 3 {
+                    Err(TransientError(anyhow!("database unavailable")))
+                } else {
+                    Ok(())
+                }
+            }
+        })
+        .await;
+        assert!(res.is_ok());
+        assert_eq!(3, calls);
+
+        // The error is returned after max. attempts.
+        let mut calls = 0;
+        let res = retry(3, Duration::ZERO, || {
+            calls += 1;
+            async { Err(TransientError(anyhow!("database unavailable"))) }
+        })
+        .await;
+        assert!(res.is_err());
+        assert_eq!(3, calls);
+    }
+
+    #[tokio::test]
+    async fn test_handle_permanent_error() {
+        // Errors caused by the command itself are not retried, the command is
+        // dropped.
+        let key = CommandKey {
+            application_id: "0c5bd8d9-2c39-4e58-a0ad-5a3d8e82b4c8".into(),
+            dev_eui: "invalid".into(),
+            command: "down".into(),
+        };
+        assert!(handle_with_retry(&key, true, b"{}", 3).await.is_ok());
+    }
+
+    #[test]
+    fn test_parse_key() {
+        let template = "application.{{application_id}}.device.{{dev_eui}}.command.{{command}}";
+
+        assert_eq!(
+            "application.*.device.*.command.*",
+            render_wildcard(template, "*").unwrap()
+        );
+
+        let re = get_regex(template).unwrap();
+        assert_eq!(
+            Some(CommandKey {
+                application_id: "0c5bd8d9-2c39-4e58-a0ad-5a3d8e82b4c8".into(),
+                dev_eui: "0102030405060708".into(),
+                command: "down".into(),
+            }),
+            parse_key(
+                &re,
+                "application.0c5bd8d9-2c39-4e58-a0ad-5a3d8e82b4c8.device.0102030405060708.command.down"
+            )
+        );
+        assert_eq!(
+            None,
+            parse_key(&re, "application.foo.device.0102030405060708.event.up")
+        );
+    }
+}
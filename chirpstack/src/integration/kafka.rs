@@ -6,11 +6,16 @@ use async_trait::async_trait;
 use handlebars::Handlebars;
 use prost::Message;
 use rdkafka::config::ClientConfig;
-use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
+use rdkafka::message::{Header, Message as KafkaMessage, OwnedHeaders};
 use rdkafka::producer::{FutureProducer, FutureRecord};
+use regex::Regex;
 use serde::Serialize;
-use tracing::{error, info};
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tracing::{error, info, warn};
 
+use super::command;
 use super::Integration as IntegrationTrait;
 use crate::config::KafkaIntegration as Config;
 use chirpstack_api::integration;
@@ -20,6 +25,7 @@ pub struct Integration<'a> {
     topic: String,
     json: bool,
     producer: FutureProducer,
+    command_task: Option<JoinHandle<()>>,
 }
 
 #[derive(Serialize)]
@@ -38,32 +44,35 @@ impl<'a> Integration<'a> {
         templates.register_escape_fn(handlebars::no_escape);
         templates.register_template_string("event_key", &conf.event_key)?;
 
-        let producer: FutureProducer = ClientConfig::new()
-            .set("bootstrap.servers", &conf.brokers.join(","))
+        let producer: FutureProducer = client_config(conf)?
             .set("message.timeout.ms", "5000")
             .set("allow.auto.create.topics", "true")
-            .set(
-                "sasl.mechanism",
-                match conf.mechanism.as_ref() {
-                    "PLAIN" => "PLAIN",
-                    "SCRAM-SHA-256" => "SCRAM-SHA-256",
-                    "SCRAM-SHA-512" => "SCRAM-SHA-512",
-                    _ => {
-                        return Err(anyhow!(
-                            "mechanism must be PLAIN, SCRAM-SHA-256 or SCRAM-SHA-512"
-                        ));
-                    }
-                },
-            )
-            .set("sasl.username", &conf.username)
-            .set("sasl.password", &conf.password)
             .create()?;
 
+        let command_task = if !conf.command_topic.is_empty() {
+            let consumer: StreamConsumer = client_config(conf)?
+                .set("group.id", &conf.consumer_group)
+                .set("enable.auto.commit", "false")
+                .create()?;
+            consumer.subscribe(&[&conf.command_topic])?;
+
+            let command_regex = command::get_regex(&conf.command_key)?;
+            Some(tokio::spawn(command_loop(
+                consumer,
+                producer.clone(),
+                command_regex,
+                conf.clone(),
+            )))
+        } else {
+            None
+        };
+
         let i = Integration {
             templates,
             producer,
             json: conf.json,
             topic: conf.topic.clone(),
+            command_task,
         };
 
         Ok(i)
@@ -106,6 +115,121 @@ impl<'a> Integration<'a> {
     }
 }
 
+// On reload, the integration is replaced by a new instance. This stops the command
+// consumer, uncommitted commands are re-delivered to the new consumer.
+impl Drop for Integration<'_> {
+    fn drop(&mut self) {
+        if let Some(t) = &self.command_task {
+            t.abort();
+        }
+    }
+}
+
+// Returns the client config shared by the producer and the command consumer.
+fn client_config(conf: &Config) -> Result<ClientConfig> {
+    let mut c = ClientConfig::new();
+    c.set("bootstrap.servers", &conf.brokers.join(","))
+        .set(
+            "sasl.mechanism",
+            match conf.mechanism.as_ref() {
+                "PLAIN" => "PLAIN",
+                "SCRAM-SHA-256" => "SCRAM-SHA-256",
+                "SCRAM-SHA-512" => "SCRAM-SHA-512",
+                _ => {
+                    return Err(anyhow!(
+                        "mechanism must be PLAIN, SCRAM-SHA-256 or SCRAM-SHA-512"
+                    ));
+                }
+            },
+        )
+        .set("sasl.username", &conf.username)
+        .set("sasl.password", &conf.password);
+    Ok(c)
+}
+
+// Consumes the commands from the command topic. The offset of a command is only
+// committed once it has been handled, such that on a restart the command is
+// re-delivered. Commands which still fail after the max. number of attempts are
+// published to the dead-letter topic (if configured) and skipped, such that these
+// do not block the partition.
+async fn command_loop(
+    consumer: StreamConsumer,
+    producer: FutureProducer,
+    command_regex: Regex,
+    conf: Config,
+) {
+    info!("Starting Kafka command consumer");
+
+    loop {
+        let msg = match consumer.recv().await {
+            Ok(v) => v,
+            Err(e) => {
+                error!(error = %e, "Receiving Kafka command error");
+                sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+
+        let key = String::from_utf8_lossy(msg.key().unwrap_or_default()).to_string();
+        match command::parse_key(&command_regex, &key) {
+            Some(k) => {
+                let b = msg.payload().unwrap_or_default();
+                if let Err(e) =
+                    command::handle_with_retry(&k, conf.json, b, conf.command_max_attempts).await
+                {
+                    if conf.command_dead_letter_topic.is_empty() {
+                        error!(key = %key, error = %e, "Handling command failed, dropping command");
+                    } else {
+                        error!(key = %key, topic = %conf.command_dead_letter_topic, error = %e, "Handling command failed, publishing command to dead-letter topic");
+                        if let Err(e) = publish_dead_letter(
+                            &producer,
+                            &conf.command_dead_letter_topic,
+                            &key,
+                            b,
+                            &e.to_string(),
+                        )
+                        .await
+                        {
+                            error!(key = %key, error = %e, "Publishing command to dead-letter topic failed, dropping command");
+                        }
+                    }
+                }
+            }
+            None => {
+                warn!(key = %key, "Error parsing command key, ignoring command");
+            }
+        }
+
+        if let Err(e) = consumer.commit_message(&msg, CommitMode::Async) {
+            error!(key = %key, error = %e, "Committing Kafka command offset error");
+        }
+    }
+}
+
+async fn publish_dead_letter(
+    producer: &FutureProducer,
+    topic: &str,
+    key: &str,
+    b: &[u8],
+    error: &str,
+) -> Result<()> {
+    producer
+        .send(
+            FutureRecord::to(topic)
+                .key(key)
+                .headers(OwnedHeaders::new().insert(Header {
+                    key: "error",
+                    value: Some(error),
+                }))
+                .payload(b),
+            Duration::from_secs(0),
+        )
+        .await
+        .map_err(|(e, _)| anyhow!("{:?}", e))?;
+
+    Ok(())
+}
+
 #[async_trait]
 impl<'a> IntegrationTrait for Integration<'a> {
     async fn uplink_event(
@@ -232,6 +356,7 @@ pub mod test {
     use rdkafka::message::Headers;
     use rdkafka::Message;
     use std::time::Duration;
+    use tokio::task::JoinHandle;
     use tokio::time::sleep;
     use tracing::trace;
 
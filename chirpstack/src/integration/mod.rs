@@ -1,14 +1,16 @@
 use std::collections::HashMap;
+use std::io::Cursor;
 use std::str::FromStr;
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use futures::future::join_all;
+use prost::Message;
 use tokio::sync::RwLock;
-use tracing::{error, info};
+use tracing::{error, info, trace, warn};
 use uuid::Uuid;
 
-use crate::storage::{application, device, device_profile, device_queue};
+use crate::storage::{application, device, device_profile, device_queue, integration_delivery};
 use crate::{codec, config};
 use chirpstack_api::integration;
 use lrwn::EUI64;
@@ -16,6 +18,7 @@ use lrwn::EUI64;
 mod amqp;
 mod aws_sns;
 mod azure_service_bus;
+mod command;
 mod gcp_pub_sub;
 mod http;
 mod ifttt;
@@ -26,53 +29,79 @@ mod loracloud;
 pub mod mock;
 mod mqtt;
 mod mydevices;
+pub mod outbox;
 mod pilot_things;
 mod postgresql;
 mod redis;
+pub mod routing;
 mod thingsboard;
 
 lazy_static! {
-    static ref GLOBAL_INTEGRATIONS: RwLock<Vec<Box<dyn Integration + Sync + Send>>> =
-        RwLock::new(Vec::new());
+    static ref GLOBAL_INTEGRATIONS: RwLock<Integrations> = RwLock::new(Vec::new());
     static ref MOCK_INTEGRATION: RwLock<bool> = RwLock::new(false);
 }
 
+// Global integrations by name.
+type Integrations = Vec<(String, Box<dyn Integration + Sync + Send>)>;
+type AppIntegrations = Vec<(
+    application::IntegrationKind,
+    Box<dyn Integration + Sync + Send>,
+)>;
+
 pub async fn setup() -> Result<()> {
     info!("Setting up global integrations");
     let conf = config::get();
-    let mut integrations = GLOBAL_INTEGRATIONS.write().await;
+    let integrations = build(&conf).await?;
+    replace(integrations).await;
+
+    Ok(())
+}
 
-    integrations.push(Box::new(redis::Integration::new()));
+/// Builds the global integrations for the given configuration, without
+/// activating these.
+pub async fn build(conf: &config::Configuration) -> Result<Integrations> {
+    let mut integrations: Integrations = Vec::new();
+
+    integrations.push(("redis".to_string(), Box::new(redis::Integration::new())));
 
     for name in &conf.integration.enabled {
-        match name.as_ref() {
-            "mqtt" => {
-                integrations.push(Box::new(
-                    mqtt::Integration::new(&conf.integration.mqtt)
-                        .await
-                        .context("Setup MQTT integration")?,
-                ));
-            }
-            "postgresql" => integrations.push(Box::new(
+        let i: Box<dyn Integration + Sync + Send> = match name.as_ref() {
+            "mqtt" => Box::new(
+                mqtt::Integration::new(&conf.integration.mqtt)
+                    .await
+                    .context("Setup MQTT integration")?,
+            ),
+            "postgresql" => Box::new(
                 postgresql::Integration::new(&conf.integration.postgresql)
                     .context("Setup PostgreSQL integration")?,
-            )),
-            "amqp" => integrations.push(Box::new(
+            ),
+            "amqp" => Box::new(
                 amqp::Integration::new(&conf.integration.amqp)
                     .await
                     .context("Setup AMQP integration")?,
-            )),
-            "kafka" => integrations.push(Box::new(
+            ),
+            "kafka" => Box::new(
                 kafka::Integration::new(&conf.integration.kafka)
                     .context("Setup Kafka integration")?,
-            )),
+            ),
             _ => {
                 return Err(anyhow!("Unexpected integration: {}", name));
             }
-        }
+        };
+        integrations.push((name.clone(), i));
     }
 
-    Ok(())
+    Ok(integrations)
+}
+
+/// Replaces all the global integrations. The replaced integrations are dropped,
+/// which stops their background tasks (e.g. the command consumers).
+pub async fn replace(integrations: Integrations) {
+    let old = {
+        let mut integrations_w = GLOBAL_INTEGRATIONS.write().await;
+        std::mem::replace(&mut *integrations_w, integrations)
+    };
+    drop(old);
 }
 
 #[cfg(test)]
@@ -132,142 +161,196 @@ pub trait Integration {
     ) -> Result<()>;
 }
 
-// Returns a Vec of integrations for the given Application ID.
-async fn for_application_id(id: Uuid) -> Result<Vec<Box<dyn Integration + Sync + Send>>> {
-    #[cfg(test)]
-    {
-        let m = MOCK_INTEGRATION.read().await;
-        if *m {
-            return Ok(vec![Box::new(mock::Integration {})]);
+/// Integration event.
+#[derive(Clone, Debug)]
+pub enum Event {
+    Up(integration::UplinkEvent),
+    Join(integration::JoinEvent),
+    Ack(integration::AckEvent),
+    TxAck(integration::TxAckEvent),
+    Log(integration::LogEvent),
+    Status(integration::StatusEvent),
+    Location(integration::LocationEvent),
+    Integration(integration::IntegrationEvent),
+}
+
+impl Event {
+    /// Returns the event type.
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            Event::Up(_) => "up",
+            Event::Join(_) => "join",
+            Event::Ack(_) => "ack",
+            Event::TxAck(_) => "txack",
+            Event::Log(_) => "log",
+            Event::Status(_) => "status",
+            Event::Location(_) => "location",
+            Event::Integration(_) => "integration",
         }
     }
 
-    let mut out: Vec<Box<dyn Integration + Sync + Send>> = Vec::new();
-    let integrations = application::get_integrations_for_application(&id).await?;
+    /// Returns the device information of the event.
+    pub fn device_info(&self) -> Option<&integration::DeviceInfo> {
+        match self {
+            Event::Up(pl) => pl.device_info.as_ref(),
+            Event::Join(pl) => pl.device_info.as_ref(),
+            Event::Ack(pl) => pl.device_info.as_ref(),
+            Event::TxAck(pl) => pl.device_info.as_ref(),
+            Event::Log(pl) => pl.device_info.as_ref(),
+            Event::Status(pl) => pl.device_info.as_ref(),
+            Event::Location(pl) => pl.device_info.as_ref(),
+            Event::Integration(pl) => pl.device_info.as_ref(),
+        }
+    }
 
-    for app_i in &integrations {
-        out.push(match &app_i.configuration {
-            application::IntegrationConfiguration::AwsSns(conf) => {
-                Box::new(aws_sns::Integration::new(conf).await?)
-            }
-            application::IntegrationConfiguration::AzureServiceBus(conf) => {
-                Box::new(azure_service_bus::Integration::new(conf)?)
-            }
-            application::IntegrationConfiguration::GcpPubSub(conf) => {
-                Box::new(gcp_pub_sub::Integration::new(conf).await?)
-            }
-            application::IntegrationConfiguration::Http(conf) => {
-                Box::new(http::Integration::new(conf))
-            }
-            application::IntegrationConfiguration::InfluxDb(conf) => {
-                Box::new(influxdb::Integration::new(conf)?)
-            }
-            application::IntegrationConfiguration::LoraCloud(conf) => {
-                Box::new(loracloud::Integration::new(conf))
-            }
-            application::IntegrationConfiguration::MyDevices(conf) => {
-                Box::new(mydevices::Integration::new(conf))
-            }
-            application::IntegrationConfiguration::PilotThings(conf) => {
-                Box::new(pilot_things::Integration::new(conf))
-            }
-            application::IntegrationConfiguration::ThingsBoard(conf) => {
-                Box::new(thingsboard::Integration::new(conf))
-            }
-            application::IntegrationConfiguration::Ifttt(conf) => {
-                Box::new(ifttt::Integration::new(conf))
+    /// Returns the Protobuf encoded event.
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Event::Up(pl) => pl.encode_to_vec(),
+            Event::Join(pl) => pl.encode_to_vec(),
+            Event::Ack(pl) => pl.encode_to_vec(),
+            Event::TxAck(pl) => pl.encode_to_vec(),
+            Event::Log(pl) => pl.encode_to_vec(),
+            Event::Status(pl) => pl.encode_to_vec(),
+            Event::Location(pl) => pl.encode_to_vec(),
+            Event::Integration(pl) => pl.encode_to_vec(),
+        }
+    }
+
+    /// Decodes the Protobuf encoded event of the given event type.
+    pub fn decode(event_type: &str, b: &[u8]) -> Result<Self> {
+        Ok(match event_type {
+            "up" => Event::Up(integration::UplinkEvent::decode(&mut Cursor::new(b))?),
+            "join" => Event::Join(integration::JoinEvent::decode(&mut Cursor::new(b))?),
+            "ack" => Event::Ack(integration::AckEvent::decode(&mut Cursor::new(b))?),
+            "txack" => Event::TxAck(integration::TxAckEvent::decode(&mut Cursor::new(b))?),
+            "log" => Event::Log(integration::LogEvent::decode(&mut Cursor::new(b))?),
+            "status" => Event::Status(integration::StatusEvent::decode(&mut Cursor::new(b))?),
+            "location" => Event::Location(integration::LocationEvent::decode(&mut Cursor::new(b))?),
+            "integration" => {
+                Event::Integration(integration::IntegrationEvent::decode(&mut Cursor::new(b))?)
             }
             _ => {
-                continue;
+                return Err(anyhow!("Unexpected event type: {}", event_type));
             }
         })
     }
 
-    Ok(out)
+    /// Sends the event to the given integration.
+    pub async fn send(
+        &self,
+        i: &(dyn Integration + Sync + Send),
+        vars: &HashMap<String, String>,
+    ) -> Result<()> {
+        match self {
+            Event::Up(pl) => i.uplink_event(vars, pl).await,
+            Event::Join(pl) => i.join_event(vars, pl).await,
+            Event::Ack(pl) => i.ack_event(vars, pl).await,
+            Event::TxAck(pl) => i.txack_event(vars, pl).await,
+            Event::Log(pl) => i.log_event(vars, pl).await,
+            Event::Status(pl) => i.status_event(vars, pl).await,
+            Event::Location(pl) => i.location_event(vars, pl).await,
+            Event::Integration(pl) => i.integration_event(vars, pl).await,
+        }
+    }
 }
 
-pub async fn uplink_event(
+/// Returns the integration for the given application integration configuration.
+/// None is returned for configurations which are not handled as application
+/// integration (e.g. the MQTT integration).
+async fn new_integration(
     application_id: Uuid,
-    vars: &HashMap<String, String>,
-    pl: &integration::UplinkEvent,
-) {
-    tokio::spawn({
-        let vars = vars.clone();
-        let pl = pl.clone();
-
-        async move {
-            if let Err(err) = _uplink_event(application_id, &vars, &pl).await {
-                error!(application_id = %application_id, error = %err, "Uplink event error");
-            }
+    conf: &application::IntegrationConfiguration,
+) -> Result<Option<Box<dyn Integration + Sync + Send>>> {
+    Ok(Some(match conf {
+        application::IntegrationConfiguration::AwsSns(conf) => {
+            Box::new(aws_sns::Integration::new(conf).await?)
         }
-    });
+        application::IntegrationConfiguration::AzureServiceBus(conf) => {
+            Box::new(azure_service_bus::Integration::new(conf)?)
+        }
+        application::IntegrationConfiguration::GcpPubSub(conf) => {
+            Box::new(gcp_pub_sub::Integration::new(conf).await?)
+        }
+        application::IntegrationConfiguration::Http(conf) => {
+            Box::new(http::Integration::new(application_id, conf))
+        }
+        application::IntegrationConfiguration::InfluxDb(conf) => {
+            Box::new(influxdb::Integration::new(conf)?)
+        }
+        application::IntegrationConfiguration::LoraCloud(conf) => {
+            Box::new(loracloud::Integration::new(conf))
+        }
+        application::IntegrationConfiguration::MyDevices(conf) => {
+            Box::new(mydevices::Integration::new(conf))
+        }
+        application::IntegrationConfiguration::PilotThings(conf) => {
+            Box::new(pilot_things::Integration::new(conf))
+        }
+        application::IntegrationConfiguration::ThingsBoard(conf) => {
+            Box::new(thingsboard::Integration::new(conf))
+        }
+        application::IntegrationConfiguration::Ifttt(conf) => {
+            Box::new(ifttt::Integration::new(conf))
+        }
+        _ => {
+            return Ok(None);
+        }
+    }))
 }
 
-async fn _uplink_event(
-    application_id: Uuid,
-    vars: &HashMap<String, String>,
-    pl: &integration::UplinkEvent,
-) -> Result<()> {
-    let app_ints = for_application_id(application_id)
-        .await
-        .context("Get integrations for application")?;
-    let global_ints = GLOBAL_INTEGRATIONS.read().await;
-    let mut futures = Vec::new();
-
-    for (i, _) in app_ints.iter().enumerate() {
-        futures.push(app_ints[i].uplink_event(vars, pl));
-    }
-    for (i, _) in global_ints.iter().enumerate() {
-        futures.push(global_ints[i].uplink_event(vars, pl));
+// Returns a Vec of integrations for the given Application ID, for which the
+// routing rules match the given event.
+async fn for_application_id(id: Uuid, event: &Event) -> Result<AppIntegrations> {
+    #[cfg(test)]
+    {
+        let m = MOCK_INTEGRATION.read().await;
+        if *m {
+            return Ok(vec![(
+                application::IntegrationKind::Http,
+                Box::new(mock::Integration {}),
+            )]);
+        }
     }
 
-    for e in join_all(futures).await {
-        e?;
+    let mut out: AppIntegrations = Vec::new();
+    let integrations = application::get_integrations_for_application(&id).await?;
+
+    for app_i in &integrations {
+        match routing::matches(&app_i.routing, event) {
+            Ok(true) => {}
+            Ok(false) => {
+                trace!(application_id = %id, integration_kind = %app_i.kind, event_type = event.event_type(), "Event does not match integration routing rules");
+                continue;
+            }
+            Err(e) => {
+                warn!(application_id = %id, integration_kind = %app_i.kind, error = %e, "Evaluating integration routing rules failed");
+                continue;
+            }
+        }
+
+        if let Some(i) = new_integration(id, &app_i.configuration).await? {
+            out.push((app_i.kind, i));
+        }
     }
 
-    Ok(())
+    Ok(out)
 }
 
-pub async fn join_event(
+pub async fn uplink_event(
     application_id: Uuid,
     vars: &HashMap<String, String>,
-    pl: &integration::JoinEvent,
+    pl: &integration::UplinkEvent,
 ) {
-    tokio::spawn({
-        let vars = vars.clone();
-        let pl = pl.clone();
-
-        async move {
-            if let Err(err) = _join_event(application_id, &vars, &pl).await {
-                error!(application_id = %application_id, error = %err, "Join event error");
-            }
-        }
-    });
+    handle_event(application_id, vars, Event::Up(pl.clone())).await;
 }
 
-async fn _join_event(
+pub async fn join_event(
     application_id: Uuid,
     vars: &HashMap<String, String>,
     pl: &integration::JoinEvent,
-) -> Result<()> {
-    let app_ints = for_application_id(application_id)
-        .await
-        .context("Get integrations for application")?;
-    let global_ints = GLOBAL_INTEGRATIONS.read().await;
-    let mut futures = Vec::new();
-
-    for (i, _) in app_ints.iter().enumerate() {
-        futures.push(app_ints[i].join_event(vars, pl));
-    }
-    for (i, _) in global_ints.iter().enumerate() {
-        futures.push(global_ints[i].join_event(vars, pl));
-    }
-
-    for e in join_all(futures).await {
-        e?;
-    }
-
-    Ok(())
+) {
+    handle_event(application_id, vars, Event::Join(pl.clone())).await;
 }
 
 pub async fn ack_event(
@@ -275,41 +358,7 @@ pub async fn ack_event(
     vars: &HashMap<String, String>,
     pl: &integration::AckEvent,
 ) {
-    tokio::spawn({
-        let vars = vars.clone();
-        let pl = pl.clone();
-
-        async move {
-            if let Err(err) = _ack_event(application_id, &vars, &pl).await {
-                error!(application_id = %application_id, error = %err, "Ack event error");
-            }
-        }
-    });
-}
-
-async fn _ack_event(
-    application_id: Uuid,
-    vars: &HashMap<String, String>,
-    pl: &integration::AckEvent,
-) -> Result<()> {
-    let app_ints = for_application_id(application_id)
-        .await
-        .context("Get integrations for application")?;
-    let global_ints = GLOBAL_INTEGRATIONS.read().await;
-    let mut futures = Vec::new();
-
-    for (i, _) in app_ints.iter().enumerate() {
-        futures.push(app_ints[i].ack_event(vars, pl));
-    }
-    for (i, _) in global_ints.iter().enumerate() {
-        futures.push(global_ints[i].ack_event(vars, pl));
-    }
-
-    for e in join_all(futures).await {
-        e?;
-    }
-
-    Ok(())
+    handle_event(application_id, vars, Event::Ack(pl.clone())).await;
 }
 
 pub async fn txack_event(
@@ -317,41 +366,7 @@ pub async fn txack_event(
     vars: &HashMap<String, String>,
     pl: &integration::TxAckEvent,
 ) {
-    tokio::spawn({
-        let vars = vars.clone();
-        let pl = pl.clone();
-
-        async move {
-            if let Err(err) = _txack_event(application_id, &vars, &pl).await {
-                error!(application_id = %application_id, error = %err, "Txack event error");
-            }
-        }
-    });
-}
-
-async fn _txack_event(
-    application_id: Uuid,
-    vars: &HashMap<String, String>,
-    pl: &integration::TxAckEvent,
-) -> Result<()> {
-    let app_ints = for_application_id(application_id)
-        .await
-        .context("Get integrations for application")?;
-    let global_ints = GLOBAL_INTEGRATIONS.read().await;
-    let mut futures = Vec::new();
-
-    for (i, _) in app_ints.iter().enumerate() {
-        futures.push(app_ints[i].txack_event(vars, pl));
-    }
-    for (i, _) in global_ints.iter().enumerate() {
-        futures.push(global_ints[i].txack_event(vars, pl));
-    }
-
-    for e in join_all(futures).await {
-        e?;
-    }
-
-    Ok(())
+    handle_event(application_id, vars, Event::TxAck(pl.clone())).await;
 }
 
 pub async fn log_event(
@@ -359,41 +374,7 @@ pub async fn log_event(
     vars: &HashMap<String, String>,
     pl: &integration::LogEvent,
 ) {
-    tokio::spawn({
-        let vars = vars.clone();
-        let pl = pl.clone();
-
-        async move {
-            if let Err(err) = _log_event(application_id, &vars, &pl).await {
-                error!(application_id = %application_id, error = %err, "Log event error");
-            }
-        }
-    });
-}
-
-async fn _log_event(
-    application_id: Uuid,
-    vars: &HashMap<String, String>,
-    pl: &integration::LogEvent,
-) -> Result<()> {
-    let app_ints = for_application_id(application_id)
-        .await
-        .context("Get integrations for application")?;
-    let global_ints = GLOBAL_INTEGRATIONS.read().await;
-    let mut futures = Vec::new();
-
-    for (i, _) in app_ints.iter().enumerate() {
-        futures.push(app_ints[i].log_event(vars, pl));
-    }
-    for (i, _) in global_ints.iter().enumerate() {
-        futures.push(global_ints[i].log_event(vars, pl));
-    }
-
-    for e in join_all(futures).await {
-        e?;
-    }
-
-    Ok(())
+    handle_event(application_id, vars, Event::Log(pl.clone())).await;
 }
 
 pub async fn status_event(
@@ -401,41 +382,7 @@ pub async fn status_event(
     vars: &HashMap<String, String>,
     pl: &integration::StatusEvent,
 ) {
-    tokio::spawn({
-        let vars = vars.clone();
-        let pl = pl.clone();
-
-        async move {
-            if let Err(err) = _status_event(application_id, &vars, &pl).await {
-                error!(application_id = %application_id, error = %err, "Status event error");
-            }
-        }
-    });
-}
-
-async fn _status_event(
-    application_id: Uuid,
-    vars: &HashMap<String, String>,
-    pl: &integration::StatusEvent,
-) -> Result<()> {
-    let app_ints = for_application_id(application_id)
-        .await
-        .context("Get integrations for application")?;
-    let global_ints = GLOBAL_INTEGRATIONS.read().await;
-    let mut futures = Vec::new();
-
-    for (i, _) in app_ints.iter().enumerate() {
-        futures.push(app_ints[i].status_event(vars, pl));
-    }
-    for (i, _) in global_ints.iter().enumerate() {
-        futures.push(global_ints[i].status_event(vars, pl));
-    }
-
-    for e in join_all(futures).await {
-        e?;
-    }
-
-    Ok(())
+    handle_event(application_id, vars, Event::Status(pl.clone())).await;
 }
 
 pub async fn location_event(
@@ -443,41 +390,7 @@ pub async fn location_event(
     vars: &HashMap<String, String>,
     pl: &integration::LocationEvent,
 ) {
-    tokio::spawn({
-        let vars = vars.clone();
-        let pl = pl.clone();
-
-        async move {
-            if let Err(err) = _location_event(application_id, &vars, &pl).await {
-                error!(application_id = %application_id, error = %err, "Location event error");
-            }
-        }
-    });
-}
-
-async fn _location_event(
-    application_id: Uuid,
-    vars: &HashMap<String, String>,
-    pl: &integration::LocationEvent,
-) -> Result<()> {
-    let app_ints = for_application_id(application_id)
-        .await
-        .context("Get integrations for application")?;
-    let global_ints = GLOBAL_INTEGRATIONS.read().await;
-    let mut futures = Vec::new();
-
-    for (i, _) in app_ints.iter().enumerate() {
-        futures.push(app_ints[i].location_event(vars, pl));
-    }
-    for (i, _) in global_ints.iter().enumerate() {
-        futures.push(global_ints[i].location_event(vars, pl));
-    }
-
-    for e in join_all(futures).await {
-        e?;
-    }
-
-    Ok(())
+    handle_event(application_id, vars, Event::Location(pl.clone())).await;
 }
 
 pub async fn integration_event(
@@ -485,38 +398,90 @@ pub async fn integration_event(
     vars: &HashMap<String, String>,
     pl: &integration::IntegrationEvent,
 ) {
+    handle_event(application_id, vars, Event::Integration(pl.clone())).await;
+}
+
+// Stores a delivery of the event for each application and global integration,
+// before dispatching the event in the background. Stored deliveries are removed
+// once delivered, else these are retried by the outbox. This makes sure that
+// events are not lost when ChirpStack is stopped before these were delivered.
+async fn handle_event(application_id: Uuid, vars: &HashMap<String, String>, event: Event) {
+    let app_ints = match for_application_id(application_id, &event).await {
+        Ok(v) => v,
+        Err(err) => {
+            error!(application_id = %application_id, event_type = event.event_type(), error = %err, "Get integrations for application error");
+            return;
+        }
+    };
+    let app_kinds: Vec<application::IntegrationKind> = app_ints.iter().map(|(k, _)| *k).collect();
+    let global_names: Vec<String> = GLOBAL_INTEGRATIONS
+        .read()
+        .await
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    let deliveries =
+        outbox::new_deliveries(application_id, &app_kinds, &global_names, vars, &event);
+    let (deliveries, stored) = match integration_delivery::create_many(deliveries.clone()).await {
+        Ok(v) => (v, true),
+        Err(err) => {
+            error!(application_id = %application_id, event_type = event.event_type(), error = %err, "Storing integration deliveries failed, sending event without storing");
+            (deliveries, false)
+        }
+    };
+
     tokio::spawn({
         let vars = vars.clone();
-        let pl = pl.clone();
 
         async move {
-            if let Err(err) = _integration_event(application_id, &vars, &pl).await {
-                error!(application_id = %application_id, error = %err, "Location event error");
+            if let Err(err) = send_event(&vars, &event, &app_ints, deliveries, stored).await {
+                error!(application_id = %application_id, event_type = event.event_type(), error = %err, "Sending event error");
             }
         }
     });
 }
 
-async fn _integration_event(
-    application_id: Uuid,
+// Sends the event to the integrations of the given deliveries and completes each
+// delivery with the result.
+async fn send_event(
     vars: &HashMap<String, String>,
-    pl: &integration::IntegrationEvent,
+    event: &Event,
+    app_ints: &AppIntegrations,
+    deliveries: Vec<integration_delivery::IntegrationDelivery>,
+    stored: bool,
 ) -> Result<()> {
-    let app_ints = for_application_id(application_id)
-        .await
-        .context("Get integrations for application")?;
     let global_ints = GLOBAL_INTEGRATIONS.read().await;
-    let mut futures = Vec::new();
 
-    for (i, _) in app_ints.iter().enumerate() {
-        futures.push(app_ints[i].integration_event(vars, pl));
-    }
-    for (i, _) in global_ints.iter().enumerate() {
-        futures.push(global_ints[i].integration_event(vars, pl));
-    }
+    let futures: Vec<_> = deliveries
+        .into_iter()
+        .map(|d| {
+            let i = match d.integration_kind {
+                Some(kind) => app_ints
+                    .iter()
+                    .find(|(k, _)| *k == kind)
+                    .map(|(_, i)| i.as_ref()),
+                None => global_ints
+                    .iter()
+                    .find(|(name, _)| *name == d.global_integration)
+                    .map(|(_, i)| i.as_ref()),
+            };
+
+            async move {
+                let res = match i {
+                    Some(i) => event.send(i, vars).await,
+                    None => Err(anyhow!(
+                        "Integration {} is not configured",
+                        d.integration_name()
+                    )),
+                };
+                outbox::complete(d, stored, res).await
+            }
+        })
+        .collect();
 
-    for e in join_all(futures).await {
-        e?;
+    for res in join_all(futures).await {
+        res?;
     }
 
     Ok(())
@@ -537,33 +502,7 @@ async fn handle_down_command(application_id: String, pl: integration::DownlinkCo
             ));
         }
 
-        let mut data = pl.data.clone();
-        if let Some(obj) = &pl.object {
-            let dp = device_profile::get(&dev.device_profile_id).await?;
-
-            data = codec::struct_to_binary(
-                dp.payload_codec_runtime,
-                pl.f_port as u8,
-                &dev.variables,
-                &dp.payload_codec_script,
-                &codec::convert::pb_json_to_prost(obj),
-            )
-            .await?;
-        }
-
-        let qi = device_queue::DeviceQueueItem {
-            id: match pl.id.is_empty() {
-                true => Uuid::new_v4(),
-                false => Uuid::from_str(&pl.id)?,
-            },
-            f_port: pl.f_port as i16,
-            confirmed: pl.confirmed,
-            data,
-            dev_eui,
-            ..Default::default()
-        };
-
-        device_queue::enqueue_item(qi).await?;
+        enqueue_down_command(&dev, &pl).await?;
 
         Ok(())
     }
@@ -574,3 +513,40 @@ async fn handle_down_command(application_id: String, pl: integration::DownlinkCo
         error!(dev_eui = %pl.dev_eui, error = %err.as_ref().unwrap(), "Handling downlink command error");
     }
 }
+
+/// Enqueues the downlink command for the given device. In case an object is given, it is
+/// encoded using the payload codec of the device-profile. It returns the ID of the queue-item.
+pub async fn enqueue_down_command(
+    dev: &device::Device,
+    pl: &integration::DownlinkCommand,
+) -> Result<Uuid> {
+    let mut data = pl.data.clone();
+    if let Some(obj) = &pl.object {
+        let dp = device_profile::get(&dev.device_profile_id).await?;
+
+        data = codec::struct_to_binary(
+            dp.payload_codec_runtime,
+            pl.f_port as u8,
+            &dev.variables,
+            &dp.payload_codec_script,
+            &codec::convert::pb_json_to_prost(obj),
+        )
+        .await?;
+    }
+
+    let qi = device_queue::DeviceQueueItem {
+        id: match pl.id.is_empty() {
+            true => Uuid::new_v4(),
+            false => Uuid::from_str(&pl.id)?,
+        },
+        f_port: pl.f_port as i16,
+        confirmed: pl.confirmed,
+        data,
+        dev_eui: dev.dev_eui,
+        ..Default::default()
+    };
+
+    let qi = device_queue::enqueue_item(qi).await?;
+
+    Ok(qi.id)
+}
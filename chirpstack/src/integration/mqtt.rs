@@ -13,6 +13,7 @@ use rand::Rng;
 use regex::Regex;
 use serde::Serialize;
 use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
 use tracing::{error, info};
 
 use super::Integration as IntegrationTrait;
@@ -25,6 +26,7 @@ pub struct Integration<'a> {
     json: bool,
     qos: usize,
     command_regex: Regex,
+    tasks: Vec<JoinHandle<()>>,
 }
 
 #[derive(Serialize)]
@@ -137,7 +139,7 @@ impl<'a> Integration<'a> {
         // get message stream
         let mut stream = client.get_stream(25);
 
-        let i = Integration {
+        let mut i = Integration {
             command_regex: Regex::new(&templates.render(
                 "command_topic",
                 &CommandTopicContext {
@@ -150,6 +152,7 @@ impl<'a> Integration<'a> {
             json: conf.json,
             client,
             templates,
+            tasks: vec![],
         };
 
         // connect
@@ -160,8 +163,9 @@ impl<'a> Integration<'a> {
             .context("Connect to MQTT broker")?;
 
         // Command consume loop.
-        tokio::spawn({
+        i.tasks.push(tokio::spawn({
             let command_regex = i.command_regex.clone();
+            let json = i.json;
 
             async move {
                 info!("Starting MQTT consumer loop");
@@ -183,17 +187,17 @@ impl<'a> Integration<'a> {
                             caps.get(1).map_or("", |m| m.as_str()).to_string(),
                             caps.get(2).map_or("", |m| m.as_str()).to_string(),
                             caps.get(3).map_or("", |m| m.as_str()).to_string(),
-                            i.json,
+                            json,
                             msg,
                         )
                         .await;
                     }
                 }
             }
-        });
+        }));
 
         // (Re)subscribe loop.
-        tokio::spawn({
+        i.tasks.push(tokio::spawn({
             let client = i.client.clone();
             let qos = conf.qos as i32;
 
@@ -205,7 +209,7 @@ impl<'a> Integration<'a> {
                     }
                 }
             }
-        });
+        }));
 
         // Return integration.
         Ok(i)
@@ -230,6 +234,17 @@ impl<'a> Integration<'a> {
     }
 }
 
+// On reload, the integration is replaced by a new instance. This stops the consume and
+// (re)subscribe loops and disconnects the client, such that commands are not handled twice.
+impl Drop for Integration<'_> {
+    fn drop(&mut self) {
+        for t in &self.tasks {
+            t.abort();
+        }
+        let _ = self.client.disconnect(None);
+    }
+}
+
 #[async_trait]
 impl IntegrationTrait for Integration<'_> {
     async fn uplink_event(
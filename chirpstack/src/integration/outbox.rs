@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use chrono::{Duration, Utc};
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use tokio::time::sleep;
+use tracing::{error, info, trace, warn};
+use uuid::Uuid;
+
+use super::http::EndpointsError;
+use super::{new_integration, Event, GLOBAL_INTEGRATIONS};
+use crate::config;
+use crate::monitoring::prometheus;
+use crate::storage::error::Error;
+use crate::storage::{application, fields, integration_delivery};
+
+// Duration for which pending deliveries are locked while being delivered.
+const LOCK_DURATION_SECS: i64 = 60;
+
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+struct DeliveryLabels {
+    kind: String,
+    status: String,
+}
+
+lazy_static! {
+    static ref DELIVERY_COUNTER: Family<DeliveryLabels, Counter> = {
+        let counter = Family::<DeliveryLabels, Counter>::default();
+        prometheus::register(
+            "integration_delivery",
+            "Number of failed integration deliveries by status",
+            counter.clone(),
+        );
+        counter
+    };
+}
+
+pub async fn setup() {
+    info!("Setting up integration delivery retry loop");
+    tokio::spawn(async move {
+        delivery_loop().await;
+    });
+}
+
+/// Returns a delivery of the event for each of the given application and global
+/// integrations. These must be stored before the event is sent, such that the
+/// event is retried when ChirpStack is stopped before it has been delivered.
+pub fn new_deliveries(
+    application_id: Uuid,
+    app_kinds: &[application::IntegrationKind],
+    global_names: &[String],
+    vars: &HashMap<String, String>,
+    event: &Event,
+) -> Vec<integration_delivery::IntegrationDelivery> {
+    let d = integration_delivery::IntegrationDelivery {
+        application_id,
+        event_type: event.event_type().to_string(),
+        vars: fields::KeyValue::new(vars.clone()),
+        payload: event.encode(),
+        // Deliveries which are not completed within the lock duration are picked
+        // up by the delivery loop.
+        next_attempt_at: Utc::now() + Duration::seconds(LOCK_DURATION_SECS),
+        ..Default::default()
+    };
+
+    app_kinds
+        .iter()
+        .map(|kind| integration_delivery::IntegrationDelivery {
+            id: Uuid::new_v4(),
+            integration_kind: Some(*kind),
+            ..d.clone()
+        })
+        .chain(
+            global_names
+                .iter()
+                .map(|name| integration_delivery::IntegrationDelivery {
+                    id: Uuid::new_v4(),
+                    integration_kind: None,
+                    global_integration: name.clone(),
+                    ..d.clone()
+                }),
+        )
+        .collect()
+}
+
+/// Completes the delivery with the result of the first delivery attempt. The
+/// delivery is removed when delivered, else it is scheduled for retry. When the
+/// delivery has not been stored (stored is false), it is only stored on error.
+pub async fn complete(
+    d: integration_delivery::IntegrationDelivery,
+    stored: bool,
+    res: Result<()>,
+) -> Result<()> {
+    match res {
+        Ok(_) => {
+            if stored {
+                integration_delivery::delete(&d.id).await?;
+            }
+        }
+        Err(err) => {
+            warn!(application_id = %d.application_id, integration = %d.integration_name(), event_type = %d.event_type, error = %err, "Delivering event to integration failed, scheduling retry");
+
+            let d = match stored {
+                true => d,
+                false => integration_delivery::create(d).await?,
+            };
+            record_failure(d, &err).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn delivery_loop() {
+    loop {
+        trace!("Starting integration delivery loop run");
+
+        // The configuration is read on every run, such that configuration reloads
+        // are applied.
+        let conf = config::get();
+
+        if let Err(err) = deliver_batch(conf.integration.delivery.batch_size).await {
+            error!(error = %err, "Delivering integration deliveries batch failed");
+        }
+
+        if let Err(err) = integration_delivery::delete_dead_letters_before(
+            Utc::now()
+                - Duration::from_std(conf.integration.delivery.dead_letter_ttl)
+                    .unwrap_or_else(|_| Duration::days(7)),
+        )
+        .await
+        {
+            error!(error = %err, "Deleting expired integration dead-letters failed");
+        }
+
+        sleep(conf.integration.delivery.interval).await;
+    }
+}
+
+async fn deliver_batch(size: usize) -> Result<()> {
+    let deliveries =
+        integration_delivery::get_pending(size, Duration::seconds(LOCK_DURATION_SECS)).await?;
+    trace!(
+        count = deliveries.len(),
+        "Got this number of pending integration deliveries"
+    );
+
+    let mut handles = vec![];
+
+    for d in deliveries {
+        let handle = tokio::spawn(async move {
+            if let Err(e) = handle_delivery(d).await {
+                error!(error = %e, "Handle integration delivery failed");
+            }
+        });
+        handles.push(handle);
+    }
+
+    futures::future::join_all(handles).await;
+    Ok(())
+}
+
+async fn handle_delivery(d: integration_delivery::IntegrationDelivery) -> Result<()> {
+    let event = Event::decode(&d.event_type, &d.payload)?;
+    let vars = d.vars.into_hashmap();
+
+    let res = match d.integration_kind {
+        Some(kind) => {
+            let app_int = match application::get_integration(&d.application_id, kind).await {
+                Ok(v) => v,
+                Err(Error::NotFound(_)) => {
+                    // The integration has been removed in the meantime.
+                    integration_delivery::delete(&d.id).await?;
+                    return Ok(());
+                }
+                Err(e) => {
+                    return Err(e).context("Get application integration");
+                }
+            };
+
+            let mut configuration = app_int.configuration;
+            if !set_endpoint(&mut configuration, &d.endpoint) {
+                // The endpoint has been removed from the integration in the meantime.
+                integration_delivery::delete(&d.id).await?;
+                return Ok(());
+            }
+
+            match new_integration(d.application_id, &configuration).await {
+                Ok(Some(i)) => event.send(i.as_ref(), &vars).await,
+                Ok(None) => {
+                    integration_delivery::delete(&d.id).await?;
+                    return Ok(());
+                }
+                Err(e) => Err(e),
+            }
+        }
+        None => {
+            let global_ints = GLOBAL_INTEGRATIONS.read().await;
+            match global_ints
+                .iter()
+                .find(|(name, _)| *name == d.global_integration)
+            {
+                Some((_, i)) => event.send(i.as_ref(), &vars).await,
+                None => {
+                    // The integration has been disabled in the meantime.
+                    integration_delivery::delete(&d.id).await?;
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    match res {
+        Ok(_) => {
+            integration_delivery::delete(&d.id).await?;
+            inc_counter(&d, "delivered");
+        }
+        Err(err) => {
+            record_failure(d, &err).await?;
+        }
+    }
+
+    Ok(())
+}
+
+// Records the failed delivery attempt. When the integration reports which of its
+// endpoints failed, the delivery is stored for each failed endpoint, such that the
+// endpoints which did receive the event are not retried.
+async fn record_failure(
+    mut d: integration_delivery::IntegrationDelivery,
+    err: &anyhow::Error,
+) -> Result<()> {
+    let conf = config::get();
+
+    d.attempts += 1;
+    d.last_error = err.to_string();
+
+    if d.attempts as usize >= conf.integration.delivery.max_attempts {
+        warn!(id = %d.id, application_id = %d.application_id, integration = %d.integration_name(), attempts = d.attempts, error = %err, "Integration delivery exceeded max. attempts, moving to dead-letters");
+        d.dead_letter = true;
+        inc_counter(&d, "dead_letter");
+    } else {
+        d.next_attempt_at = Utc::now()
+            + get_backoff(
+                d.attempts,
+                conf.integration.delivery.min_backoff,
+                conf.integration.delivery.max_backoff,
+            );
+        inc_counter(&d, if d.attempts == 1 { "queued" } else { "retry" });
+    }
+
+    if let Some(e) = err.downcast_ref::<EndpointsError>() {
+        if let Some((first, others)) = e.endpoints.split_first() {
+            integration_delivery::create_many(
+                others
+                    .iter()
+                    .map(|endpoint| integration_delivery::IntegrationDelivery {
+                        id: Uuid::new_v4(),
+                        endpoint: endpoint.clone(),
+                        ..d.clone()
+                    })
+                    .collect(),
+            )
+            .await?;
+            d.endpoint = first.clone();
+        }
+    }
+
+    integration_delivery::update(d).await?;
+
+    Ok(())
+}
+
+// Limits the integration configuration to the given endpoint, such that the
+// event is not re-delivered to the endpoints which already received it. This
+// returns false when the endpoint is no longer configured.
+fn set_endpoint(conf: &mut application::IntegrationConfiguration, endpoint: &str) -> bool {
+    if endpoint.is_empty() {
+        return true;
+    }
+
+    match conf {
+        application::IntegrationConfiguration::Http(conf) => {
+            if !conf
+                .event_endpoint_url
+                .split(',')
+                .any(|s| s.trim() == endpoint)
+            {
+                return false;
+            }
+
+            conf.event_endpoint_url = endpoint.to_string();
+            true
+        }
+        _ => true,
+    }
+}
+
+// Returns the backoff after the given number of attempts. The backoff doubles
+// for every attempt, starting at the min. backoff and capped at the max. backoff.
+fn get_backoff(
+    attempts: i16,
+    min_backoff: std::time::Duration,
+    max_backoff: std::time::Duration,
+) -> Duration {
+    let exp = (attempts.max(1) - 1).min(31) as u32;
+    let backoff = min_backoff
+        .checked_mul(2_u32.pow(exp))
+        .unwrap_or(max_backoff)
+        .min(max_backoff);
+
+    Duration::from_std(backoff).unwrap_or_else(|_| Duration::hours(1))
+}
+
+fn inc_counter(d: &integration_delivery::IntegrationDelivery, status: &str) {
+    DELIVERY_COUNTER
+        .get_or_create(&DeliveryLabels {
+            kind: d.integration_name(),
+            status: status.to_string(),
+        })
+        .inc();
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn test_new_deliveries() {
+        let app_id = Uuid::new_v4();
+        let ds = new_deliveries(
+            app_id,
+            &[application::IntegrationKind::Http],
+            &["mqtt".to_string()],
+            &HashMap::new(),
+            &Event::Up(Default::default()),
+        );
+
+        assert_eq!(2, ds.len());
+        assert_eq!(
+            Some(application::IntegrationKind::Http),
+            ds[0].integration_kind
+        );
+        assert_eq!("", ds[0].global_integration);
+        assert_eq!(None, ds[1].integration_kind);
+        assert_eq!("mqtt", ds[1].global_integration);
+        assert_ne!(ds[0].id, ds[1].id);
+
+        for d in &ds {
+            assert_eq!(app_id, d.application_id);
+            assert_eq!("up", d.event_type);
+            assert_eq!(0, d.attempts);
+            assert!(d.next_attempt_at > Utc::now());
+        }
+    }
+
+    #[test]
+    fn test_set_endpoint() {
+        let mut conf =
+            application::IntegrationConfiguration::Http(application::HttpConfiguration {
+                event_endpoint_url: "http://a.com, http://b.com".into(),
+                ..Default::default()
+            });
+
+        // No endpoint, all endpoints are used.
+        assert!(set_endpoint(&mut conf, ""));
+        assert_eq!(
+            application::IntegrationConfiguration::Http(application::HttpConfiguration {
+                event_endpoint_url: "http://a.com, http://b.com".into(),
+                ..Default::default()
+            }),
+            conf
+        );
+
+        // Endpoint has been removed.
+        assert!(!set_endpoint(&mut conf.clone(), "http://c.com"));
+
+        // Only the given endpoint is used.
+        assert!(set_endpoint(&mut conf, "http://b.com"));
+        assert_eq!(
+            application::IntegrationConfiguration::Http(application::HttpConfiguration {
+                event_endpoint_url: "http://b.com".into(),
+                ..Default::default()
+            }),
+            conf
+        );
+    }
+
+    #[test]
+    fn test_get_backoff() {
+        let min_backoff = std::time::Duration::from_secs(10);
+        let max_backoff = std::time::Duration::from_secs(60);
+
+        assert_eq!(
+            Duration::seconds(10),
+            get_backoff(0, min_backoff, max_backoff)
+        );
+        assert_eq!(
+            Duration::seconds(10),
+            get_backoff(1, min_backoff, max_backoff)
+        );
+        assert_eq!(
+            Duration::seconds(20),
+            get_backoff(2, min_backoff, max_backoff)
+        );
+        assert_eq!(
+            Duration::seconds(40),
+            get_backoff(3, min_backoff, max_backoff)
+        );
+        assert_eq!(
+            Duration::seconds(60),
+            get_backoff(4, min_backoff, max_backoff)
+        );
+        assert_eq!(
+            Duration::seconds(60),
+            get_backoff(100, min_backoff, max_backoff)
+        );
+    }
+}
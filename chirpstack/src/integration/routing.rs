@@ -0,0 +1,507 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use super::Event;
+use crate::storage::application::IntegrationRouting;
+
+// Max. number of cached object expressions. The cache is cleared when exceeded.
+const MAX_CACHED_EXPRESSIONS: usize = 1024;
+
+lazy_static! {
+    // Parsed object expressions, such that these are not parsed for every event.
+    static ref EXPRESSIONS: RwLock<HashMap<String, Arc<Expression>>> = RwLock::new(HashMap::new());
+}
+
+/// Returns true when the event matches the routing rules of the integration.
+pub fn matches(routing: &IntegrationRouting, event: &Event) -> Result<bool> {
+    if !routing.event_types.is_empty()
+        && !routing
+            .event_types
+            .iter()
+            .any(|t| t.as_str() == event.event_type())
+    {
+        return Ok(false);
+    }
+
+    if !routing.device_tags.is_empty() || !routing.device_profile_ids.is_empty() {
+        let di = match event.device_info() {
+            Some(v) => v,
+            None => return Ok(false),
+        };
+
+        for (k, v) in &routing.device_tags {
+            if di.tags.get(k) != Some(v) {
+                return Ok(false);
+            }
+        }
+
+        if !routing.device_profile_ids.is_empty() {
+            let dp_id = Uuid::from_str(&di.device_profile_id).unwrap_or_else(|_| Uuid::nil());
+            if !routing.device_profile_ids.contains(&dp_id) {
+                return Ok(false);
+            }
+        }
+    }
+
+    if !routing.f_ports.is_empty() {
+        if let Event::Up(pl) = event {
+            if !routing.f_ports.contains(&pl.f_port) {
+                return Ok(false);
+            }
+        }
+    }
+
+    if !routing.object_expression.is_empty() {
+        let object = match event {
+            Event::Up(pl) => Some(&pl.object),
+            Event::Integration(pl) => Some(&pl.object),
+            _ => None,
+        };
+
+        if let Some(object) = object {
+            let expr = get_expression(&routing.object_expression)?;
+            match object {
+                Some(v) => {
+                    if !expr.eval(v) {
+                        return Ok(false);
+                    }
+                }
+                None => return Ok(false),
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+// Returns the parsed expression, from the cache when the expression has been
+// parsed before. The expression is validated when the routing rules are saved.
+fn get_expression(s: &str) -> Result<Arc<Expression>> {
+    if let Some(expr) = EXPRESSIONS.read().unwrap().get(s) {
+        return Ok(expr.clone());
+    }
+
+    let expr = Arc::new(Expression::from_str(s)?);
+    let mut expressions = EXPRESSIONS.write().unwrap();
+    if expressions.len() >= MAX_CACHED_EXPRESSIONS {
+        expressions.clear();
+    }
+    expressions.insert(s.to_string(), expr.clone());
+
+    Ok(expr)
+}
+
+/// Expression over the decoded object.
+///
+/// An expression consists of one or multiple conditions, combined using `&&`
+/// and `||` (`&&` takes precedence). A condition either compares a field with
+/// a number, string or boolean value (using `==`, `!=`, `>`, `>=`, `<` or `<=`),
+/// or consists of only a field, in which case the field must be set and must
+/// not be `false`, `0`, an empty string or null. Nested fields are separated
+/// by a dot.
+///
+/// Example: `temperature > 20.5 && sensor.type == "door" || alarm`
+#[derive(Debug, PartialEq)]
+pub struct Expression {
+    // Conditions in disjunctive normal form.
+    or: Vec<Vec<Condition>>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Condition {
+    path: Vec<String>,
+    comparison: Option<(Operator, Literal)>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Operator {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+#[derive(Debug, PartialEq)]
+enum Literal {
+    Number(f64),
+    String(String),
+    Bool(bool),
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Path(String),
+    Literal(Literal),
+    Operator(Operator),
+    And,
+    Or,
+}
+
+impl FromStr for Expression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut or: Vec<Vec<Condition>> = vec![vec![]];
+        let mut tokens = tokens.into_iter().peekable();
+
+        loop {
+            let path = match tokens.next() {
+                Some(Token::Path(v)) => v,
+                Some(t) => return Err(anyhow!("Expected field, got: {:?}", t)),
+                None => return Err(anyhow!("Unexpected end of expression")),
+            };
+
+            let comparison = if let Some(Token::Operator(op)) = tokens.peek() {
+                let op = *op;
+                tokens.next();
+                match tokens.next() {
+                    Some(Token::Literal(v)) => Some((op, v)),
+                    Some(t) => return Err(anyhow!("Expected value, got: {:?}", t)),
+                    None => return Err(anyhow!("Unexpected end of expression")),
+                }
+            } else {
+                None
+            };
+
+            or.last_mut().unwrap().push(Condition {
+                path: path.split('.').map(|p| p.to_string()).collect(),
+                comparison,
+            });
+
+            match tokens.next() {
+                Some(Token::And) => {}
+                Some(Token::Or) => or.push(vec![]),
+                Some(t) => return Err(anyhow!("Expected && or ||, got: {:?}", t)),
+                None => break,
+            }
+        }
+
+        Ok(Expression { or })
+    }
+}
+
+impl Expression {
+    /// Evaluates the expression against the given object.
+    pub fn eval(&self, obj: &pbjson_types::Struct) -> bool {
+        self.or.iter().any(|and| and.iter().all(|c| c.eval(obj)))
+    }
+}
+
+impl Condition {
+    fn eval(&self, obj: &pbjson_types::Struct) -> bool {
+        use pbjson_types::value::Kind;
+
+        let mut fields = &obj.fields;
+        let mut value: Option<&Kind> = None;
+
+        for (i, key) in self.path.iter().enumerate() {
+            value = fields.get(key).and_then(|v| v.kind.as_ref());
+            if i < self.path.len() - 1 {
+                match value {
+                    Some(Kind::StructValue(v)) => fields = &v.fields,
+                    _ => return false,
+                }
+            }
+        }
+
+        let value = match value {
+            Some(v) => v,
+            None => return false,
+        };
+
+        match &self.comparison {
+            None => match value {
+                Kind::NullValue(_) => false,
+                Kind::BoolValue(v) => *v,
+                Kind::NumberValue(v) => *v != 0.0,
+                Kind::StringValue(v) => !v.is_empty(),
+                Kind::StructValue(_) | Kind::ListValue(_) => true,
+            },
+            Some((op, lit)) => {
+                let ord = match (value, lit) {
+                    (Kind::NumberValue(a), Literal::Number(b)) => a.partial_cmp(b),
+                    (Kind::StringValue(a), Literal::String(b)) => Some(a.as_str().cmp(b)),
+                    (Kind::BoolValue(a), Literal::Bool(b)) => Some(a.cmp(b)),
+                    _ => None,
+                };
+
+                match ord {
+                    Some(ord) => match op {
+                        Operator::Eq => ord.is_eq(),
+                        Operator::Ne => ord.is_ne(),
+                        Operator::Gt => ord.is_gt(),
+                        Operator::Gte => ord.is_ge(),
+                        Operator::Lt => ord.is_lt(),
+                        Operator::Lte => ord.is_le(),
+                    },
+                    // Values of different types are never equal.
+                    None => *op == Operator::Ne,
+                }
+            }
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut out = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let next = chars.get(i + 1).copied();
+
+        match (c, next) {
+            ('&', Some('&')) => {
+                out.push(Token::And);
+                i += 2;
+            }
+            ('|', Some('|')) => {
+                out.push(Token::Or);
+                i += 2;
+            }
+            ('=', Some('=')) => {
+                out.push(Token::Operator(Operator::Eq));
+                i += 2;
+            }
+            ('!', Some('=')) => {
+                out.push(Token::Operator(Operator::Ne));
+                i += 2;
+            }
+            ('>', Some('=')) => {
+                out.push(Token::Operator(Operator::Gte));
+                i += 2;
+            }
+            ('<', Some('=')) => {
+                out.push(Token::Operator(Operator::Lte));
+                i += 2;
+            }
+            ('>', _) => {
+                out.push(Token::Operator(Operator::Gt));
+                i += 1;
+            }
+            ('<', _) => {
+                out.push(Token::Operator(Operator::Lt));
+                i += 1;
+            }
+            ('"', _) | ('\'', _) => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|v| *v == c)
+                    .ok_or_else(|| anyhow!("Unterminated string at position {}", i))?;
+                out.push(Token::Literal(Literal::String(
+                    chars[i + 1..i + 1 + end].iter().collect(),
+                )));
+                i += end + 2;
+            }
+            _ if c.is_ascii_digit() || c == '-' => {
+                let len = chars[i + 1..]
+                    .iter()
+                    .take_while(|v| v.is_ascii_digit() || **v == '.')
+                    .count();
+                let s: String = chars[i..i + 1 + len].iter().collect();
+                out.push(Token::Literal(Literal::Number(
+                    s.parse().map_err(|_| anyhow!("Invalid number: {}", s))?,
+                )));
+                i += len + 1;
+            }
+            _ if c.is_alphabetic() || c == '_' => {
+                let len = chars[i + 1..]
+                    .iter()
+                    .take_while(|v| v.is_alphanumeric() || **v == '_' || **v == '.')
+                    .count();
+                let s: String = chars[i..i + 1 + len].iter().collect();
+                out.push(match s.as_str() {
+                    "true" => Token::Literal(Literal::Bool(true)),
+                    "false" => Token::Literal(Literal::Bool(false)),
+                    _ => Token::Path(s),
+                });
+                i += len + 1;
+            }
+            _ => {
+                return Err(anyhow!("Unexpected character '{}' at position {}", c, i));
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use chirpstack_api::integration;
+    use std::collections::HashMap;
+
+    fn get_object() -> pbjson_types::Struct {
+        serde_json::from_str(
+            r#"{"temperature": 21.5, "alarm": false, "sensor": {"type": "door", "open": true}}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_expression() {
+        struct Test {
+            expr: &'static str,
+            expected: bool,
+        }
+
+        let obj = get_object();
+        let tests = vec![
+            Test {
+                expr: "temperature > 20",
+                expected: true,
+            },
+            Test {
+                expr: "temperature <= 21.5",
+                expected: true,
+            },
+            Test {
+                expr: "temperature < -1",
+                expected: false,
+            },
+            Test {
+                expr: "sensor.type == \"door\"",
+                expected: true,
+            },
+            Test {
+                expr: "sensor.type != 'door'",
+                expected: false,
+            },
+            Test {
+                expr: "sensor.open && temperature > 30",
+                expected: false,
+            },
+            Test {
+                expr: "alarm || sensor.open == true",
+                expected: true,
+            },
+            Test {
+                expr: "alarm",
+                expected: false,
+            },
+            Test {
+                expr: "humidity > 10",
+                expected: false,
+            },
+            Test {
+                expr: "temperature == \"21.5\"",
+                expected: false,
+            },
+        ];
+
+        for tst in &tests {
+            let expr = Expression::from_str(tst.expr).unwrap();
+            assert_eq!(tst.expected, expr.eval(&obj), "{}", tst.expr);
+        }
+
+        assert!(Expression::from_str("temperature >").is_err());
+        assert!(Expression::from_str("temperature > 10 &&").is_err());
+        assert!(Expression::from_str("sensor.type == \"door").is_err());
+        assert!(Expression::from_str("10 > temperature").is_err());
+    }
+
+    #[test]
+    fn test_get_expression() {
+        let a = get_expression("counter > 1").unwrap();
+        let b = get_expression("counter > 1").unwrap();
+        assert!(Arc::ptr_eq(&a, &b));
+
+        assert!(get_expression("counter >").is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let dp_id = Uuid::new_v4();
+        let up = Event::Up(integration::UplinkEvent {
+            device_info: Some(integration::DeviceInfo {
+                device_profile_id: dp_id.to_string(),
+                tags: [("floor".to_string(), "2".to_string())]
+                    .iter()
+                    .cloned()
+                    .collect(),
+                ..Default::default()
+            }),
+            f_port: 10,
+            object: Some(get_object()),
+            ..Default::default()
+        });
+        let status = Event::Status(integration::StatusEvent {
+            device_info: Some(integration::DeviceInfo {
+                device_profile_id: dp_id.to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+
+        // No rules.
+        let routing = IntegrationRouting::default();
+        assert!(matches(&routing, &up).unwrap());
+        assert!(matches(&routing, &status).unwrap());
+
+        // Event types.
+        let routing = IntegrationRouting {
+            event_types: vec!["up".into()],
+            ..Default::default()
+        };
+        assert!(matches(&routing, &up).unwrap());
+        assert!(!matches(&routing, &status).unwrap());
+
+        // Device tags.
+        let mut tags = HashMap::new();
+        tags.insert("floor".to_string(), "2".to_string());
+        let routing = IntegrationRouting {
+            device_tags: tags,
+            ..Default::default()
+        };
+        assert!(matches(&routing, &up).unwrap());
+        assert!(!matches(&routing, &status).unwrap());
+
+        // Device-profile.
+        let routing = IntegrationRouting {
+            device_profile_ids: vec![Uuid::new_v4()],
+            ..Default::default()
+        };
+        assert!(!matches(&routing, &up).unwrap());
+        let routing = IntegrationRouting {
+            device_profile_ids: vec![dp_id],
+            ..Default::default()
+        };
+        assert!(matches(&routing, &up).unwrap());
+
+        // fPort, does not apply to non-uplink events.
+        let routing = IntegrationRouting {
+            f_ports: vec![20],
+            ..Default::default()
+        };
+        assert!(!matches(&routing, &up).unwrap());
+        assert!(matches(&routing, &status).unwrap());
+
+        // Object expression.
+        let routing = IntegrationRouting {
+            object_expression: "temperature > 25".into(),
+            ..Default::default()
+        };
+        assert!(!matches(&routing, &up).unwrap());
+        assert!(matches(&routing, &status).unwrap());
+        let routing = IntegrationRouting {
+            object_expression: "temperature > 20".into(),
+            ..Default::default()
+        };
+        assert!(matches(&routing, &up).unwrap());
+    }
+}
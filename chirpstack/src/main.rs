@@ -19,14 +19,17 @@ use std::str::FromStr;
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
-use tracing::Level;
-use tracing_subscriber::{filter, prelude::*};
+use tracing_subscriber::prelude::*;
+
+use uuid::Uuid;
 
 use lrwn::EUI64;
 
 mod adr;
 mod api;
+mod applayer;
 mod backend;
+mod bulk;
 mod certificate;
 mod cmd;
 mod codec;
@@ -35,6 +38,7 @@ mod devaddr;
 mod downlink;
 mod eventlog;
 mod framelog;
+mod fuota;
 mod gateway;
 mod gpstime;
 mod integration;
@@ -42,6 +46,7 @@ mod maccommand;
 mod metalog;
 mod monitoring;
 mod region;
+mod reload;
 mod requestlog;
 mod sensitivity;
 mod storage;
@@ -65,6 +70,9 @@ enum Commands {
     /// Print the configuration template
     Configfile {},
 
+    /// Validate the configuration
+    ValidateConfig {},
+
     /// Print the device-session for debugging
     PrintDs {
         /// Device EUI
@@ -72,29 +80,161 @@ enum Commands {
         dev_eui: String,
     },
 
+    /// Replay the uplink history of a device through an ADR algorithm
+    SimulateAdr {
+        /// Device EUI
+        #[arg(long, value_name = "DEV_EUI")]
+        dev_eui: String,
+
+        /// ADR algorithm ID (defaults to the device-profile algorithm).
+        #[arg(long, value_name = "ID")]
+        algorithm: Option<String>,
+
+        /// Installation margin in dB (defaults to the region configuration).
+        #[arg(long, value_name = "DB")]
+        installation_margin: Option<f32>,
+
+        /// JSON file containing the uplink history to replay (defaults to the
+        /// device-session uplink history).
+        #[arg(long, value_name = "FILE")]
+        history: Option<String>,
+    },
+
     /// Import legacy lorawan-devices repository.
     ImportLegacyLorawanDevicesRepository {
         /// Path to repository root.
         #[arg(short, long, value_name = "DIR")]
         dir: String,
     },
+
+    /// Import lorawan-devices repository (current format).
+    ImportLorawanDevicesRepository {
+        /// Path to repository root.
+        #[arg(short, long, value_name = "DIR")]
+        dir: String,
+    },
+
+    /// Import devices into an application from a CSV or JSON file.
+    ImportDevices {
+        /// Application ID.
+        #[arg(long, value_name = "APPLICATION_ID")]
+        application_id: String,
+
+        /// Path to CSV or JSON file.
+        #[arg(short, long, value_name = "FILE")]
+        file: String,
+
+        /// Only validate the file, do not write to the database.
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Export the devices of an application to a CSV or JSON file.
+    ExportDevices {
+        /// Application ID.
+        #[arg(long, value_name = "APPLICATION_ID")]
+        application_id: String,
+
+        /// Path to CSV or JSON file.
+        #[arg(short, long, value_name = "FILE")]
+        file: String,
+    },
+
+    /// Import gateways into a tenant from a CSV or JSON file.
+    ImportGateways {
+        /// Tenant ID.
+        #[arg(long, value_name = "TENANT_ID")]
+        tenant_id: String,
+
+        /// Path to CSV or JSON file.
+        #[arg(short, long, value_name = "FILE")]
+        file: String,
+
+        /// Only validate the file, do not write to the database.
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Export the gateways of a tenant to a CSV or JSON file.
+    ExportGateways {
+        /// Tenant ID.
+        #[arg(long, value_name = "TENANT_ID")]
+        tenant_id: String,
+
+        /// Path to CSV or JSON file.
+        #[arg(short, long, value_name = "FILE")]
+        file: String,
+    },
+
+    /// Import device-sessions from a JSON file (exported using
+    /// export-device-sessions). The devices must already exist.
+    ImportDeviceSessions {
+        /// Path to JSON file.
+        #[arg(short, long, value_name = "FILE")]
+        file: String,
+
+        /// Only validate the file, do not write to the database.
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Export the device-sessions of the given applications and / or devices
+    /// to a JSON file.
+    ExportDeviceSessions {
+        /// Application ID (can be repeated).
+        #[arg(long, value_name = "APPLICATION_ID")]
+        application_id: Vec<String>,
+
+        /// Device EUI (can be repeated).
+        #[arg(long, value_name = "DEV_EUI")]
+        dev_eui: Vec<String>,
+
+        /// Label of the KEK used to wrap the session keys.
+        #[arg(long, value_name = "LABEL")]
+        kek_label: String,
+
+        /// Path to JSON file.
+        #[arg(short, long, value_name = "FILE")]
+        file: String,
+    },
+
+    /// Migrate the device-sessions from the given Redis server(s) and key
+    /// prefix to the configured storage.
+    MigrateDeviceSessions {
+        /// Redis URL to migrate from (can be repeated, in case of a Redis
+        /// Cluster, the URL of every master node must be given).
+        #[arg(long, value_name = "URL", required = true)]
+        from_redis_url: Vec<String>,
+
+        /// Redis key prefix to migrate from.
+        #[arg(long, value_name = "PREFIX", default_value = "")]
+        from_key_prefix: String,
+
+        /// Only list the device-sessions, do not write to the database.
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
+
+    if let Some(Commands::ValidateConfig {}) = &cli.command {
+        let ok = cmd::validate_config::run(Path::new(&cli.config))?;
+        process::exit(if ok { 0 } else { 1 });
+    }
+
     config::load(Path::new(&cli.config))?;
 
     let conf = config::get();
-    let filter = filter::Targets::new().with_targets(vec![
-        ("chirpstack", Level::from_str(&conf.logging.level).unwrap()),
-        ("backend", Level::from_str(&conf.logging.level).unwrap()),
-        ("lrwn", Level::from_str(&conf.logging.level).unwrap()),
-    ]);
+    let (filter, filter_handle) =
+        tracing_subscriber::reload::Layer::new(reload::get_log_filter(&conf.logging.level)?);
+    reload::set_log_filter_handle(filter_handle);
 
     tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer())
         .with(filter)
+        .with(tracing_subscriber::fmt::layer())
         .init();
 
     if let Some(Commands::Configfile {}) = &cli.command {
@@ -108,6 +248,28 @@ async fn main() -> Result<()> {
         process::exit(0);
     }
 
+    if let Some(Commands::SimulateAdr {
+        dev_eui,
+        algorithm,
+        installation_margin,
+        history,
+    }) = &cli.command
+    {
+        let dev_eui = EUI64::from_str(dev_eui).unwrap();
+        if let Err(e) = cmd::simulate_adr::run(
+            &dev_eui,
+            algorithm.as_deref(),
+            *installation_margin,
+            history.as_deref().map(Path::new),
+        )
+        .await
+        {
+            eprintln!("{:#}", e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
     if let Some(Commands::ImportLegacyLorawanDevicesRepository { dir }) = &cli.command {
         cmd::import_legacy_lorawan_devices_repository::run(Path::new(&dir))
             .await
@@ -115,6 +277,114 @@ async fn main() -> Result<()> {
         process::exit(0);
     }
 
+    if let Some(Commands::ImportLorawanDevicesRepository { dir }) = &cli.command {
+        cmd::import_lorawan_devices_repository::run(Path::new(&dir))
+            .await
+            .unwrap();
+        process::exit(0);
+    }
+
+    if let Some(Commands::ImportDevices {
+        application_id,
+        file,
+        dry_run,
+    }) = &cli.command
+    {
+        let application_id = Uuid::from_str(application_id).unwrap();
+        if let Err(e) = cmd::bulk::import_devices(&application_id, Path::new(&file), *dry_run).await
+        {
+            eprintln!("{:#}", e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
+    if let Some(Commands::ExportDevices {
+        application_id,
+        file,
+    }) = &cli.command
+    {
+        let application_id = Uuid::from_str(application_id).unwrap();
+        cmd::bulk::export_devices(&application_id, Path::new(&file))
+            .await
+            .unwrap();
+        process::exit(0);
+    }
+
+    if let Some(Commands::ImportGateways {
+        tenant_id,
+        file,
+        dry_run,
+    }) = &cli.command
+    {
+        let tenant_id = Uuid::from_str(tenant_id).unwrap();
+        if let Err(e) = cmd::bulk::import_gateways(&tenant_id, Path::new(&file), *dry_run).await {
+            eprintln!("{:#}", e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
+    if let Some(Commands::ExportGateways { tenant_id, file }) = &cli.command {
+        let tenant_id = Uuid::from_str(tenant_id).unwrap();
+        cmd::bulk::export_gateways(&tenant_id, Path::new(&file))
+            .await
+            .unwrap();
+        process::exit(0);
+    }
+
+    if let Some(Commands::ImportDeviceSessions { file, dry_run }) = &cli.command {
+        if let Err(e) = cmd::bulk::import_device_sessions(Path::new(&file), *dry_run).await {
+            eprintln!("{:#}", e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
+    if let Some(Commands::ExportDeviceSessions {
+        application_id,
+        dev_eui,
+        kek_label,
+        file,
+    }) = &cli.command
+    {
+        let application_ids: Vec<Uuid> = application_id
+            .iter()
+            .map(|v| Uuid::from_str(v).unwrap())
+            .collect();
+        let dev_euis: Vec<EUI64> = dev_eui
+            .iter()
+            .map(|v| EUI64::from_str(v).unwrap())
+            .collect();
+        if let Err(e) = cmd::bulk::export_device_sessions(
+            &application_ids,
+            &dev_euis,
+            kek_label,
+            Path::new(&file),
+        )
+        .await
+        {
+            eprintln!("{:#}", e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
+    if let Some(Commands::MigrateDeviceSessions {
+        from_redis_url,
+        from_key_prefix,
+        dry_run,
+    }) = &cli.command
+    {
+        if let Err(e) =
+            cmd::migrate_device_sessions::run(from_redis_url, from_key_prefix, *dry_run).await
+        {
+            eprintln!("{:#}", e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
     cmd::root::run().await?;
 
     Ok(())
@@ -12,11 +12,22 @@ lazy_static! {
         RwLock::new(HashMap::new());
 }
 
+type Regions = HashMap<String, Arc<Box<dyn region::Region + Sync + Send>>>;
+
 pub fn setup() -> Result<()> {
     info!("Setting up regions");
     let conf = config::get();
+    let regions = build(&conf)?;
+    replace(regions);
+
+    Ok(())
+}
 
-    reset();
+/// Builds the regions for the given configuration, without activating these.
+/// This makes it possible to validate a (new) configuration before it is
+/// applied using replace.
+pub fn build(conf: &config::Configuration) -> Result<Regions> {
+    let mut regions: Regions = HashMap::new();
 
     for r in &conf.regions {
         let span = span!(Level::INFO, "setup", common_name = %r.common_name, region_id = %r.id);
@@ -29,11 +40,7 @@ pub fn setup() -> Result<()> {
 
         info!("Configuring region");
 
-        let mut region_conf = region::get(
-            r.common_name,
-            r.network.repeater_compatible,
-            r.network.dwell_time_400ms,
-        );
+        let mut region_conf = new_region_conf(r)?;
 
         for ec in &r.network.extra_channels {
             trace!(
@@ -59,15 +66,32 @@ pub fn setup() -> Result<()> {
             }
         }
 
-        set(&r.id, region_conf);
+        regions.insert(r.id.clone(), Arc::new(region_conf));
     }
 
-    Ok(())
+    Ok(regions)
+}
+
+/// Returns the region implementation for the given region configuration.
+/// In case of a custom region, this is built from the custom region definition.
+pub fn new_region_conf(r: &config::Region) -> Result<Box<dyn region::Region + Sync + Send>> {
+    Ok(match r.common_name {
+        region::CommonName::CUSTOM => Box::new(
+            region::custom::Configuration::new(&r.custom, r.network.dwell_time_400ms)
+                .context("Custom region definition")?,
+        ),
+        _ => region::get(
+            r.common_name,
+            r.network.repeater_compatible,
+            r.network.dwell_time_400ms,
+        ),
+    })
 }
 
-fn reset() {
+/// Replaces all the configured regions.
+pub fn replace(regions: Regions) {
     let mut regions_w = REGIONS.write().unwrap();
-    regions_w.clear();
+    *regions_w = regions;
 }
 
 pub fn set(region_config_id: &str, r: Box<dyn region::Region + Sync + Send>) {
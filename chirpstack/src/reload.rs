@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::{error, info, warn, Level};
+use tracing_subscriber::{filter, reload as tracing_reload, Registry};
+
+use crate::cmd::validate_config;
+use crate::{adr, backend, config, integration, region};
+
+type LogFilterHandle = tracing_reload::Handle<filter::Targets, Registry>;
+
+lazy_static! {
+    static ref LOG_FILTER_HANDLE: Mutex<Option<LogFilterHandle>> = Mutex::new(None);
+    static ref RELOAD_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::new(());
+}
+
+/// Returns the log filter for the given log level.
+pub fn get_log_filter(level: &str) -> Result<filter::Targets> {
+    let level = Level::from_str(level).context("Parse log level")?;
+
+    Ok(filter::Targets::new().with_targets(vec![
+        ("chirpstack", level),
+        ("backend", level),
+        ("lrwn", level),
+    ]))
+}
+
+/// Sets the handle which is used to update the log filter on reload.
+pub fn set_log_filter_handle(handle: LogFilterHandle) {
+    let mut handle_w = LOG_FILTER_HANDLE.lock().unwrap();
+    *handle_w = Some(handle);
+}
+
+/// Reloads the configuration on SIGHUP.
+pub fn setup() -> Result<()> {
+    info!("Setting up SIGHUP configuration reload handler");
+    let mut stream = signal(SignalKind::hangup()).context("Setup SIGHUP handler")?;
+
+    tokio::spawn(async move {
+        while stream.recv().await.is_some() {
+            info!("SIGHUP received");
+            if let Err(e) = reload().await {
+                error!(error = %e, "Reloading configuration failed");
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Re-reads the configuration directory and swaps the regions, ADR algorithms,
+/// global integrations, Join Server / roaming clients and log level.
+///
+/// The new configuration is first validated and all components are built
+/// using the new configuration. Only when this succeeds, the components are
+/// replaced, else the running configuration is left untouched. Replaced
+/// global integrations stop their background tasks (e.g. command consumers)
+/// when dropped. The gateway backends, the storage pools and the API
+/// servers are not affected by a reload.
+pub async fn reload() -> Result<()> {
+    let _guard = RELOAD_LOCK.lock().await;
+
+    let config_dir = config::get_config_dir();
+    info!(config_dir = %config_dir.display(), "Reloading configuration");
+
+    let conf = config::read(&config_dir).context("Read configuration")?;
+    validate_config::check(&config_dir, &conf)?;
+    let log_filter = get_log_filter(&conf.logging.level)?;
+    let regions = region::build(&conf).context("Setup regions")?;
+    let js_clients = backend::joinserver::build(&conf).context("Setup Join Server clients")?;
+    let ns_clients =
+        backend::networkserver::build(&conf).context("Setup Network Server clients")?;
+    let as_clients =
+        backend::applicationserver::build(&conf).context("Setup Application Server clients")?;
+    let roaming_clients = backend::roaming::build(&conf).context("Setup roaming clients")?;
+    let adr_algos = adr::build(&conf).context("Setup ADR algorithms")?;
+    let integrations = integration::build(&conf)
+        .await
+        .context("Setup global integrations")?;
+
+    for section in get_restart_required(&config::get(), &conf)? {
+        warn!(
+            section = %section,
+            "Configuration changed, but this requires a restart to be applied"
+        );
+    }
+
+    config::set(conf);
+    region::replace(regions);
+    backend::joinserver::replace(js_clients);
+    backend::networkserver::replace(ns_clients);
+    backend::applicationserver::replace(as_clients);
+    backend::roaming::replace(roaming_clients);
+    adr::replace(adr_algos).await;
+    integration::replace(integrations).await;
+
+    if let Some(handle) = LOG_FILTER_HANDLE.lock().unwrap().as_ref() {
+        handle.reload(log_filter).context("Reload log filter")?;
+    }
+
+    info!("Configuration reloaded");
+
+    Ok(())
+}
+
+// Returns the configuration sections which have been changed, but which can
+// not be applied without a restart.
+fn get_restart_required(
+    old: &config::Configuration,
+    new: &config::Configuration,
+) -> Result<Vec<String>> {
+    let mut out: Vec<String> = Vec::new();
+
+    for (section, old_v, new_v) in [
+        (
+            "postgresql",
+            serde_json::to_value(&old.postgresql)?,
+            serde_json::to_value(&new.postgresql)?,
+        ),
+        (
+            "redis",
+            serde_json::to_value(&old.redis)?,
+            serde_json::to_value(&new.redis)?,
+        ),
+        (
+            "api",
+            serde_json::to_value(&old.api)?,
+            serde_json::to_value(&new.api)?,
+        ),
+        (
+            "monitoring",
+            serde_json::to_value(&old.monitoring)?,
+            serde_json::to_value(&new.monitoring)?,
+        ),
+        (
+            "backend_interfaces",
+            serde_json::to_value(&old.backend_interfaces)?,
+            serde_json::to_value(&new.backend_interfaces)?,
+        ),
+    ] {
+        if old_v != new_v {
+            out.push(section.to_string());
+        }
+    }
+
+    // The gateway backends are setup once per enabled region.
+    let old_backends = get_gateway_backends(old)?;
+    for (region_id, new_v) in get_gateway_backends(new)? {
+        if old_backends.get(&region_id) != Some(&new_v) {
+            out.push(format!("regions.{}.gateway.backend", region_id));
+        }
+    }
+
+    // The Basics Station backend builds the router_config from the channels
+    // once, on setup.
+    let old_channels = get_basic_station_channels(old)?;
+    for (region_id, new_v) in get_basic_station_channels(new)? {
+        if old_channels.get(&region_id) != Some(&new_v) {
+            out.push(format!("regions.{}.gateway.channels", region_id));
+        }
+    }
+
+    Ok(out)
+}
+
+fn get_gateway_backends(
+    conf: &config::Configuration,
+) -> Result<HashMap<String, serde_json::Value>> {
+    let mut out: HashMap<String, serde_json::Value> = HashMap::new();
+
+    for r in &conf.regions {
+        if conf.network.enabled_regions.contains(&r.id) {
+            out.insert(r.id.clone(), serde_json::to_value(&r.gateway.backend)?);
+        }
+    }
+
+    Ok(out)
+}
+
+fn get_basic_station_channels(
+    conf: &config::Configuration,
+) -> Result<HashMap<String, serde_json::Value>> {
+    let mut out: HashMap<String, serde_json::Value> = HashMap::new();
+
+    for r in &conf.regions {
+        if conf.network.enabled_regions.contains(&r.id)
+            && r.gateway.backend.enabled == "basic_station"
+        {
+            out.insert(r.id.clone(), serde_json::to_value(&r.gateway.channels)?);
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_restart_required() {
+        let old = config::Configuration {
+            network: config::Network {
+                enabled_regions: vec!["eu868".into()],
+                ..Default::default()
+            },
+            regions: vec![config::Region {
+                id: "eu868".into(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        // Nothing changed.
+        let new = old.clone();
+        assert!(get_restart_required(&old, &new).unwrap().is_empty());
+
+        // Roaming and log-level changes can be reloaded.
+        let mut new = old.clone();
+        new.logging.level = "debug".into();
+        new.roaming.resolve_net_id_domain_suffix = ".example.com".into();
+        assert!(get_restart_required(&old, &new).unwrap().is_empty());
+
+        // Storage and gateway backend changes require a restart.
+        let mut new = old.clone();
+        new.postgresql.dsn = "postgres://localhost/other".into();
+        new.regions.push(config::Region {
+            id: "us915_0".into(),
+            ..Default::default()
+        });
+        new.network.enabled_regions.push("us915_0".into());
+        assert_eq!(
+            vec![
+                "postgresql".to_string(),
+                "regions.us915_0.gateway.backend".to_string()
+            ],
+            get_restart_required(&old, &new).unwrap()
+        );
+
+        // Channel changes only require a restart when used by the Basics Station
+        // backend.
+        let mut new = old.clone();
+        new.regions[0]
+            .gateway
+            .channels
+            .push(config::GatewayChannel {
+                frequency: 868100000,
+                ..Default::default()
+            });
+        assert!(get_restart_required(&old, &new).unwrap().is_empty());
+
+        let mut old = old.clone();
+        old.regions[0].gateway.backend.enabled = "basic_station".into();
+        new.regions[0].gateway.backend.enabled = "basic_station".into();
+        assert_eq!(
+            vec!["regions.eu868.gateway.channels".to_string()],
+            get_restart_required(&old, &new).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_get_log_filter() {
+        assert!(get_log_filter("debug").is_ok());
+        assert!(get_log_filter("foo").is_err());
+    }
+}
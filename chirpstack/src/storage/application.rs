@@ -18,6 +18,7 @@ use uuid::Uuid;
 use super::error::Error;
 use super::get_db_conn;
 use super::schema::{application, application_integration};
+use crate::integration::routing;
 
 #[derive(Clone, Queryable, Insertable, PartialEq, Eq, Debug)]
 #[diesel(table_name = application)]
@@ -164,11 +165,81 @@ impl serialize::ToSql<Jsonb, Pg> for IntegrationConfiguration {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Routing rules of an integration. Only events matching all the configured
+/// rules are sent to the integration. Rules which are left empty match all events.
+#[derive(
+    Default, Debug, Clone, PartialEq, Eq, AsExpression, FromSqlRow, Serialize, Deserialize,
+)]
+#[diesel(sql_type = Jsonb)]
+#[serde(default)]
+pub struct IntegrationRouting {
+    // Event types (e.g. up, join, status, ...).
+    pub event_types: Vec<String>,
+    // Device tags, all given key / values must be set on the device.
+    pub device_tags: HashMap<String, String>,
+    pub device_profile_ids: Vec<Uuid>,
+    // Uplink fPorts. This rule does not apply to other event types.
+    pub f_ports: Vec<u32>,
+    // Expression over the decoded object (e.g. temperature > 20.5). This rule
+    // only applies to event types with a decoded object.
+    pub object_expression: String,
+}
+
+impl IntegrationRouting {
+    pub fn validate(&self) -> Result<(), Error> {
+        for event_type in &self.event_types {
+            if !matches!(
+                event_type.as_str(),
+                "up" | "join" | "ack" | "txack" | "log" | "status" | "location" | "integration"
+            ) {
+                return Err(Error::Validation(format!(
+                    "invalid event type: {}",
+                    event_type
+                )));
+            }
+        }
+
+        if !self.object_expression.is_empty() {
+            routing::Expression::from_str(&self.object_expression)
+                .map_err(|e| Error::Validation(format!("invalid object_expression: {}", e)))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl deserialize::FromSql<Jsonb, Pg> for IntegrationRouting {
+    fn from_sql(value: <Pg as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        let value = <serde_json::Value as deserialize::FromSql<Jsonb, Pg>>::from_sql(value)?;
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+impl serialize::ToSql<Jsonb, Pg> for IntegrationRouting {
+    fn to_sql(&self, out: &mut serialize::Output<'_, '_, Pg>) -> serialize::Result {
+        let value = serde_json::to_value(self)?;
+        <serde_json::Value as serialize::ToSql<Jsonb, Pg>>::to_sql(&value, &mut out.reborrow())
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct HttpConfiguration {
     pub headers: HashMap<String, String>,
     pub json: bool,
     pub event_endpoint_url: String,
+    pub signing_secret: String,
+    pub oauth2_token_url: String,
+    pub oauth2_client_id: String,
+    pub oauth2_client_secret: String,
+    pub oauth2_scopes: Vec<String>,
+    pub tls_client_cert: String,
+    pub tls_client_key: String,
+    pub ca_cert: String,
+    // Request timeout (seconds), 0 = default.
+    pub timeout: u32,
+    // Enqueue the downlink command from the uplink response body.
+    pub uplink_response_downlink: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -269,6 +340,7 @@ pub struct Integration {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub configuration: IntegrationConfiguration,
+    pub routing: IntegrationRouting,
 }
 
 impl Default for Integration {
@@ -281,6 +353,7 @@ impl Default for Integration {
             created_at: now,
             updated_at: now,
             configuration: IntegrationConfiguration::None,
+            routing: IntegrationRouting::default(),
         }
     }
 }
@@ -449,6 +522,7 @@ pub async fn list(
 }
 
 pub async fn create_integration(i: Integration) -> Result<Integration, Error> {
+    i.routing.validate()?;
     task::spawn_blocking({
         move || -> Result<Integration, Error> {
             let mut c = get_db_conn()?;
@@ -525,6 +599,38 @@ pub async fn update_integration(i: Integration) -> Result<Integration, Error> {
     .await?
 }
 
+pub async fn update_integration_routing(
+    application_id: &Uuid,
+    kind: IntegrationKind,
+    routing: IntegrationRouting,
+) -> Result<Integration, Error> {
+    routing.validate()?;
+    task::spawn_blocking({
+        let application_id = *application_id;
+        move || -> Result<Integration, Error> {
+            let mut c = get_db_conn()?;
+            let i: Integration = diesel::update(
+                application_integration::dsl::application_integration.filter(
+                    application_integration::dsl::application_id
+                        .eq(&application_id)
+                        .and(application_integration::dsl::kind.eq(&kind)),
+                ),
+            )
+            .set((
+                application_integration::updated_at.eq(Utc::now()),
+                application_integration::routing.eq(&routing),
+            ))
+            .get_result(&mut c)
+            .map_err(|e| Error::from_diesel(e, application_id.to_string()))?;
+
+            info!(application_id = %i.application_id, kind = %i.kind, "Integration routing updated");
+
+            Ok(i)
+        }
+    })
+    .await?
+}
+
 pub async fn delete_integration(application_id: &Uuid, kind: IntegrationKind) -> Result<(), Error> {
     task::spawn_blocking({
         let application_id = *application_id;
@@ -95,6 +95,13 @@ pub struct Device {
     pub tags: fields::KeyValue,
     pub variables: fields::KeyValue,
     pub join_eui: EUI64,
+    pub adr_min_dr: Option<i16>,
+    pub adr_max_dr: Option<i16>,
+    pub adr_max_tx_power_index: Option<i16>,
+    pub adr_nb_trans: Option<i16>,
+    pub adr_installation_margin: Option<f32>,
+    pub adr_pinned_dr: Option<i16>,
+    pub adr_pinned_tx_power_index: Option<i16>,
 }
 
 impl Device {
@@ -102,6 +109,20 @@ impl Device {
         if self.name.is_empty() {
             return Err(Error::Validation("name is not set".into()));
         }
+        if let (Some(min_dr), Some(max_dr)) = (self.adr_min_dr, self.adr_max_dr) {
+            if min_dr > max_dr {
+                return Err(Error::Validation(
+                    "adr_min_dr must be less than or equal to adr_max_dr".into(),
+                ));
+            }
+        }
+        if let Some(nb_trans) = self.adr_nb_trans {
+            if !(1..=15).contains(&nb_trans) {
+                return Err(Error::Validation(
+                    "adr_nb_trans must be between 1 and 15".into(),
+                ));
+            }
+        }
         Ok(())
     }
 }
@@ -134,6 +155,13 @@ impl Default for Device {
             tags: fields::KeyValue::new(HashMap::new()),
             variables: fields::KeyValue::new(HashMap::new()),
             join_eui: EUI64::default(),
+            adr_min_dr: None,
+            adr_max_dr: None,
+            adr_max_tx_power_index: None,
+            adr_nb_trans: None,
+            adr_installation_margin: None,
+            adr_pinned_dr: None,
+            adr_pinned_tx_power_index: None,
         }
     }
 }
@@ -195,6 +223,7 @@ pub async fn create(d: Device) -> Result<Device, Error> {
                         tenant::dsl::max_gateway_count,
                         tenant::dsl::private_gateways_up,
                         tenant::dsl::private_gateways_down,
+                        tenant::dsl::dev_addr_prefixes,
                     ))
                     .inner_join(application::table)
                     .filter(application::dsl::id.eq(&d.application_id))
@@ -256,6 +285,11 @@ pub async fn update(d: Device) -> Result<Device, Error> {
                     device::tags.eq(&d.tags),
                     device::variables.eq(&d.variables),
                     device::join_eui.eq(&d.join_eui),
+                    device::adr_min_dr.eq(&d.adr_min_dr),
+                    device::adr_max_dr.eq(&d.adr_max_dr),
+                    device::adr_max_tx_power_index.eq(&d.adr_max_tx_power_index),
+                    device::adr_nb_trans.eq(&d.adr_nb_trans),
+                    device::adr_installation_margin.eq(&d.adr_installation_margin),
                 ))
                 .get_result(&mut c)
                 .map_err(|e| Error::from_diesel(e, d.dev_eui.to_string()))
@@ -266,6 +300,32 @@ pub async fn update(d: Device) -> Result<Device, Error> {
     Ok(d)
 }
 
+/// Pins (or un-pins when None) the data-rate and TX Power index of the device.
+/// The pinned values take precedence over the ADR algorithm.
+pub async fn set_link_parameters(
+    dev_eui: &EUI64,
+    dr: Option<i16>,
+    tx_power_index: Option<i16>,
+) -> Result<Device, Error> {
+    let d = task::spawn_blocking({
+        let dev_eui = *dev_eui;
+
+        move || -> Result<Device, Error> {
+            let mut c = get_db_conn()?;
+            diesel::update(device::dsl::device.find(&dev_eui))
+                .set((
+                    device::adr_pinned_dr.eq(&dr),
+                    device::adr_pinned_tx_power_index.eq(&tx_power_index),
+                ))
+                .get_result(&mut c)
+                .map_err(|e| Error::from_diesel(e, dev_eui.to_string()))
+        }
+    })
+    .await??;
+    info!(dev_eui = %dev_eui, dr = ?dr, tx_power_index = ?tx_power_index, "Link parameters updated");
+    Ok(d)
+}
+
 pub async fn set_enabled_class(dev_eui: &EUI64, mode: DeviceClass) -> Result<Device, Error> {
     let d = task::spawn_blocking({
         let dev_eui = *dev_eui;
@@ -313,6 +373,40 @@ pub async fn set_dev_addr(dev_eui: EUI64, dev_addr: DevAddr) -> Result<Device, E
     Ok(d)
 }
 
+// This returns true in case the given DevAddr is assigned to a device other than the given
+// device.
+pub async fn is_dev_addr_in_use(dev_addr: DevAddr, dev_eui: EUI64) -> Result<bool, Error> {
+    task::spawn_blocking({
+        move || -> Result<bool, Error> {
+            let mut c = get_db_conn()?;
+            let count: i64 = device::dsl::device
+                .select(dsl::count_star())
+                .filter(device::dsl::dev_addr.eq(&dev_addr))
+                .filter(device::dsl::dev_eui.ne(&dev_eui))
+                .first(&mut c)?;
+            Ok(count != 0)
+        }
+    })
+    .await?
+}
+
+// This returns the number of distinct DevAddrs assigned to devices within the given (inclusive)
+// DevAddr range.
+pub async fn get_dev_addr_count(start: DevAddr, end: DevAddr) -> Result<i64, Error> {
+    task::spawn_blocking({
+        move || -> Result<i64, Error> {
+            let mut c = get_db_conn()?;
+            let count: i64 = device::dsl::device
+                .select(dsl::count_distinct(device::dsl::dev_addr))
+                .filter(device::dsl::dev_addr.ge(&start))
+                .filter(device::dsl::dev_addr.le(&end))
+                .first(&mut c)?;
+            Ok(count)
+        }
+    })
+    .await?
+}
+
 // In case the current_ts has been updated during the last device get and calling this update
 // function, this will return a NotFound error. The purpose of this error is to catch concurrent
 // scheduling, e.g. Class-A downlink and Class-B/C downlink. In such case we want to terminate one
@@ -476,6 +570,48 @@ pub async fn list(
     .await?
 }
 
+pub async fn get_tag_keys(application_id: &Uuid) -> Result<Vec<String>, Error> {
+    get_json_keys(application_id, "tags").await
+}
+
+pub async fn get_variable_keys(application_id: &Uuid) -> Result<Vec<String>, Error> {
+    get_json_keys(application_id, "variables").await
+}
+
+// Returns the distinct keys of the given JSON column of the devices within the application.
+async fn get_json_keys(application_id: &Uuid, column: &'static str) -> Result<Vec<String>, Error> {
+    #[derive(QueryableByName)]
+    struct Key {
+        #[diesel(sql_type = diesel::sql_types::Text)]
+        pub key: String,
+    }
+
+    task::spawn_blocking({
+        let application_id = *application_id;
+        move || -> Result<Vec<String>, Error> {
+            let mut c = get_db_conn()?;
+            let keys: Vec<Key> = diesel::sql_query(format!(
+                r#"
+                select
+                    distinct jsonb_object_keys({}) as key
+                from
+                    device
+                where
+                    application_id = $1
+                order by
+                    key
+                "#,
+                column
+            ))
+            .bind::<diesel::sql_types::Uuid, _>(application_id)
+            .load(&mut c)
+            .map_err(|e| Error::from_diesel(e, application_id.to_string()))?;
+            Ok(keys.into_iter().map(|k| k.key).collect())
+        }
+    })
+    .await?
+}
+
 pub async fn get_active_inactive(tenant_id: &Option<Uuid>) -> Result<DevicesActiveInactive, Error> {
     task::spawn_blocking({
         let tenant_id = *tenant_id;
@@ -634,6 +770,64 @@ pub async fn get_lock(dev_eui: &EUI64, ttl: Duration) -> Result<(), Error> {
     .await?
 }
 
+// This arms a one-shot frame-counter reset window. Within the given TTL, the next uplink with a
+// frame-counter reset to a value <= max_f_cnt will be accepted. A zero TTL disarms the window.
+pub async fn set_f_cnt_reset_window(dev_eui: &EUI64, max_f_cnt: u32, ttl: Duration) -> Result<()> {
+    task::spawn_blocking({
+        let dev_eui = *dev_eui;
+        move || -> Result<()> {
+            let key = redis_key(format!("device:{{{}}}:fcnt-reset", dev_eui));
+            let mut c = get_redis_conn()?;
+
+            if ttl.num_milliseconds() <= 0 {
+                info!(dev_eui = %dev_eui, "Disarming frame-counter reset window");
+                redis::cmd("DEL").arg(key).query(&mut *c)?;
+                return Ok(());
+            }
+
+            info!(dev_eui = %dev_eui, max_f_cnt = max_f_cnt, "Arming frame-counter reset window");
+            redis::cmd("PSETEX")
+                .arg(key)
+                .arg(ttl.num_milliseconds())
+                .arg(max_f_cnt)
+                .query(&mut *c)?;
+
+            Ok(())
+        }
+    })
+    .await?
+}
+
+// This returns the max. frame-counter of the armed frame-counter reset window or None in case no
+// window is armed.
+pub async fn get_f_cnt_reset_window(dev_eui: &EUI64) -> Result<Option<u32>> {
+    task::spawn_blocking({
+        let dev_eui = *dev_eui;
+        move || -> Result<Option<u32>> {
+            let key = redis_key(format!("device:{{{}}}:fcnt-reset", dev_eui));
+            let mut c = get_redis_conn()?;
+            let v: Option<u32> = redis::cmd("GET").arg(key).query(&mut *c)?;
+            Ok(v)
+        }
+    })
+    .await?
+}
+
+// This deletes the frame-counter reset window. It returns true in case a window was armed, this
+// makes sure that the window can only be used once in case of concurrent uplinks.
+pub async fn delete_f_cnt_reset_window(dev_eui: &EUI64) -> Result<bool> {
+    task::spawn_blocking({
+        let dev_eui = *dev_eui;
+        move || -> Result<bool> {
+            let key = redis_key(format!("device:{{{}}}:fcnt-reset", dev_eui));
+            let mut c = get_redis_conn()?;
+            let deleted: usize = redis::cmd("DEL").arg(key).query(&mut *c)?;
+            Ok(deleted > 0)
+        }
+    })
+    .await?
+}
+
 #[cfg(test)]
 pub mod test {
     use super::*;
@@ -693,10 +887,27 @@ pub mod test {
 
         // update
         d.name = "updated".into();
+        d.adr_min_dr = Some(1);
+        d.adr_max_dr = Some(3);
+        d.adr_installation_margin = Some(15.0);
         d = update(d).await.unwrap();
         let d_get = get(&d.dev_eui).await.unwrap();
         assert_eq!(d, d_get);
 
+        // invalid ADR overrides
+        let mut d_invalid = d.clone();
+        d_invalid.adr_min_dr = Some(4);
+        assert!(update(d_invalid).await.is_err());
+
+        // set link parameters
+        d = set_link_parameters(&d.dev_eui, Some(2), Some(1))
+            .await
+            .unwrap();
+        assert_eq!(Some(2), d.adr_pinned_dr);
+        assert_eq!(Some(1), d.adr_pinned_tx_power_index);
+        let d_get = get(&d.dev_eui).await.unwrap();
+        assert_eq!(d, d_get);
+
         // get count and list
         let tests = vec![
             FilterTest {
@@ -871,4 +1082,33 @@ pub mod test {
         .await;
         assert!(res.is_err());
     }
+
+    #[tokio::test]
+    async fn test_f_cnt_reset_window() {
+        let _guard = test::prepare().await;
+        let dev_eui = EUI64::from_be_bytes([1, 1, 1, 1, 1, 1, 1, 1]);
+
+        // Not armed.
+        assert_eq!(None, get_f_cnt_reset_window(&dev_eui).await.unwrap());
+        assert!(!delete_f_cnt_reset_window(&dev_eui).await.unwrap());
+
+        // Arm.
+        set_f_cnt_reset_window(&dev_eui, 10, Duration::seconds(10))
+            .await
+            .unwrap();
+        assert_eq!(Some(10), get_f_cnt_reset_window(&dev_eui).await.unwrap());
+
+        // The window can only be used once.
+        assert!(delete_f_cnt_reset_window(&dev_eui).await.unwrap());
+        assert!(!delete_f_cnt_reset_window(&dev_eui).await.unwrap());
+
+        // Disarm.
+        set_f_cnt_reset_window(&dev_eui, 10, Duration::seconds(10))
+            .await
+            .unwrap();
+        set_f_cnt_reset_window(&dev_eui, 0, Duration::zero())
+            .await
+            .unwrap();
+        assert_eq!(None, get_f_cnt_reset_window(&dev_eui).await.unwrap());
+    }
 }
@@ -20,6 +20,8 @@ pub struct DeviceKeys {
     pub app_key: AES128Key,
     pub dev_nonces: Vec<Option<i32>>,
     pub join_nonce: i32,
+    // GenAppKey of LoRaWAN 1.0.x devices, used for deriving the McRootKey.
+    pub gen_app_key: AES128Key,
 }
 
 impl Default for DeviceKeys {
@@ -40,6 +42,10 @@ impl Default for DeviceKeys {
             ]),
             dev_nonces: Vec::new(),
             join_nonce: 0,
+            gen_app_key: AES128Key::from_bytes([
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00,
+            ]),
         }
     }
 }
@@ -2,12 +2,16 @@ use std::collections::HashSet;
 use std::io::Cursor;
 
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use diesel::{dsl, prelude::*};
 use prost::Message;
 use tokio::task;
 use tracing::{error, info, trace, warn};
+use uuid::Uuid;
 
 use super::error::Error;
-use super::{get_redis_conn, redis_key};
+use super::schema::{device, device_session};
+use super::{get_db_conn, get_redis_conn, redis_key};
 use crate::api::helpers::FromProto;
 use crate::config;
 use chirpstack_api::internal;
@@ -19,10 +23,46 @@ pub enum ValidationStatus {
     Reset(u32, internal::DeviceSession),
 }
 
+#[derive(Queryable, PartialEq, Debug)]
+pub struct DeviceSessionListItem {
+    pub dev_eui: EUI64,
+    pub dev_addr: DevAddr,
+    pub region_config_id: String,
+    pub f_cnt_up: i64,
+    pub n_f_cnt_down: i64,
+    pub a_f_cnt_down: i64,
+    pub dr: i16,
+    pub tx_power_index: i16,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Default, Clone)]
+pub struct Filters {
+    pub application_id: Option<Uuid>,
+    pub dev_addr: Option<DevAddr>,
+    pub region_config_id: Option<String>,
+    pub dr: Option<u8>,
+    pub min_f_cnt_up: Option<u32>,
+}
+
 pub async fn save(ds: &internal::DeviceSession) -> Result<()> {
     let eui = EUI64::from_slice(&ds.dev_eui)?;
     let addr = DevAddr::from_slice(&ds.dev_addr)?;
 
+    let conf = config::get();
+    if conf.network.device_session_persistence {
+        save_db(ds).await?;
+    }
+    save_cache(ds).await?;
+
+    info!(dev_eui = %eui, dev_addr = %addr, "Device-session saved");
+    Ok(())
+}
+
+async fn save_cache(ds: &internal::DeviceSession) -> Result<()> {
+    let eui = EUI64::from_slice(&ds.dev_eui)?;
+    let addr = DevAddr::from_slice(&ds.dev_addr)?;
+
     task::spawn_blocking({
         let ds = ds.clone();
         move || -> Result<()> {
@@ -74,13 +114,78 @@ pub async fn save(ds: &internal::DeviceSession) -> Result<()> {
             Ok(())
         }
     })
-    .await??;
+    .await?
+}
 
-    info!(dev_eui = %eui, dev_addr = %addr, "Device-session saved");
-    Ok(())
+async fn save_db(ds: &internal::DeviceSession) -> Result<()> {
+    let dev_eui = EUI64::from_slice(&ds.dev_eui)?;
+    let dev_addr = DevAddr::from_slice(&ds.dev_addr)?;
+    let pending_rejoin_dev_addr = match &ds.pending_rejoin_device_session {
+        Some(v) => Some(DevAddr::from_slice(&v.dev_addr)?),
+        None => None,
+    };
+
+    task::spawn_blocking({
+        let ds = ds.clone();
+        move || -> Result<()> {
+            let mut c = get_db_conn()?;
+            let now = Utc::now();
+
+            diesel::insert_into(device_session::table)
+                .values((
+                    device_session::dev_eui.eq(&dev_eui),
+                    device_session::created_at.eq(now),
+                    device_session::updated_at.eq(now),
+                    device_session::dev_addr.eq(&dev_addr),
+                    device_session::pending_rejoin_dev_addr.eq(&pending_rejoin_dev_addr),
+                    device_session::region_config_id.eq(&ds.region_config_id),
+                    device_session::f_cnt_up.eq(ds.f_cnt_up as i64),
+                    device_session::n_f_cnt_down.eq(ds.n_f_cnt_down as i64),
+                    device_session::a_f_cnt_down.eq(ds.a_f_cnt_down as i64),
+                    device_session::dr.eq(ds.dr as i16),
+                    device_session::tx_power_index.eq(ds.tx_power_index as i16),
+                    device_session::device_session.eq(ds.encode_to_vec()),
+                ))
+                .on_conflict(device_session::dev_eui)
+                .do_update()
+                .set((
+                    device_session::updated_at.eq(now),
+                    device_session::dev_addr.eq(&dev_addr),
+                    device_session::pending_rejoin_dev_addr.eq(&pending_rejoin_dev_addr),
+                    device_session::region_config_id.eq(&ds.region_config_id),
+                    device_session::f_cnt_up.eq(ds.f_cnt_up as i64),
+                    device_session::n_f_cnt_down.eq(ds.n_f_cnt_down as i64),
+                    device_session::a_f_cnt_down.eq(ds.a_f_cnt_down as i64),
+                    device_session::dr.eq(ds.dr as i16),
+                    device_session::tx_power_index.eq(ds.tx_power_index as i16),
+                    device_session::device_session.eq(ds.encode_to_vec()),
+                ))
+                .execute(&mut c)
+                .map_err(|e| Error::from_diesel(e, dev_eui.to_string()))?;
+
+            Ok(())
+        }
+    })
+    .await?
 }
 
 pub async fn get(dev_eui: &EUI64) -> Result<chirpstack_api::internal::DeviceSession, Error> {
+    let conf = config::get();
+
+    match get_cache(dev_eui).await {
+        Err(Error::NotFound(_)) if conf.network.device_session_persistence => {
+            // Restore the device-session cache from the database.
+            let ds = get_db(dev_eui).await?;
+            save_cache(&ds).await?;
+
+            info!(dev_eui = %dev_eui, "Device-session restored from database");
+            Ok(ds)
+        }
+        v => v,
+    }
+}
+
+async fn get_cache(dev_eui: &EUI64) -> Result<chirpstack_api::internal::DeviceSession, Error> {
     task::spawn_blocking({
         let dev_eui = *dev_eui;
         move || -> Result<chirpstack_api::internal::DeviceSession, Error> {
@@ -101,6 +206,24 @@ pub async fn get(dev_eui: &EUI64) -> Result<chirpstack_api::internal::DeviceSess
     .await?
 }
 
+async fn get_db(dev_eui: &EUI64) -> Result<chirpstack_api::internal::DeviceSession, Error> {
+    task::spawn_blocking({
+        let dev_eui = *dev_eui;
+        move || -> Result<chirpstack_api::internal::DeviceSession, Error> {
+            let mut c = get_db_conn()?;
+            let v: Vec<u8> = device_session::dsl::device_session
+                .select(device_session::device_session)
+                .find(&dev_eui)
+                .first(&mut c)
+                .map_err(|e| Error::from_diesel(e, dev_eui.to_string()))?;
+            let ds = chirpstack_api::internal::DeviceSession::decode(&mut Cursor::new(v))
+                .context("Decode device-session")?;
+            Ok(ds)
+        }
+    })
+    .await?
+}
+
 pub async fn delete(dev_eui: &EUI64) -> Result<()> {
     task::spawn_blocking({
         let dev_eui = *dev_eui;
@@ -109,6 +232,11 @@ pub async fn delete(dev_eui: &EUI64) -> Result<()> {
             let mut c = get_redis_conn()?;
             redis::cmd("DEL").arg(&key).query(&mut *c)?;
 
+            let mut c = get_db_conn()?;
+            diesel::delete(device_session::dsl::device_session.find(&dev_eui))
+                .execute(&mut c)
+                .map_err(|e| Error::from_diesel(e, dev_eui.to_string()))?;
+
             Ok(())
         }
     })
@@ -117,6 +245,100 @@ pub async fn delete(dev_eui: &EUI64) -> Result<()> {
     Ok(())
 }
 
+/// Returns the number of persisted device-sessions matching the given filters.
+/// This requires the device-session persistence to be enabled.
+pub async fn get_count(filters: &Filters) -> Result<i64, Error> {
+    task::spawn_blocking({
+        let filters = filters.clone();
+        move || -> Result<i64, Error> {
+            let mut c = get_db_conn()?;
+            let mut q = device_session::dsl::device_session
+                .inner_join(device::table)
+                .select(dsl::count_star())
+                .into_boxed();
+
+            if let Some(application_id) = &filters.application_id {
+                q = q.filter(device::dsl::application_id.eq(application_id));
+            }
+
+            if let Some(dev_addr) = &filters.dev_addr {
+                q = q.filter(device_session::dsl::dev_addr.eq(dev_addr));
+            }
+
+            if let Some(region_config_id) = &filters.region_config_id {
+                q = q.filter(device_session::dsl::region_config_id.eq(region_config_id));
+            }
+
+            if let Some(dr) = filters.dr {
+                q = q.filter(device_session::dsl::dr.eq(dr as i16));
+            }
+
+            if let Some(min_f_cnt_up) = filters.min_f_cnt_up {
+                q = q.filter(device_session::dsl::f_cnt_up.ge(min_f_cnt_up as i64));
+            }
+
+            Ok(q.first(&mut c)?)
+        }
+    })
+    .await?
+}
+
+/// Returns the persisted device-sessions matching the given filters. This
+/// requires the device-session persistence to be enabled.
+pub async fn list(
+    limit: i64,
+    offset: i64,
+    filters: &Filters,
+) -> Result<Vec<DeviceSessionListItem>, Error> {
+    task::spawn_blocking({
+        let filters = filters.clone();
+        move || -> Result<Vec<DeviceSessionListItem>, Error> {
+            let mut c = get_db_conn()?;
+            let mut q = device_session::dsl::device_session
+                .inner_join(device::table)
+                .select((
+                    device_session::dev_eui,
+                    device_session::dev_addr,
+                    device_session::region_config_id,
+                    device_session::f_cnt_up,
+                    device_session::n_f_cnt_down,
+                    device_session::a_f_cnt_down,
+                    device_session::dr,
+                    device_session::tx_power_index,
+                    device_session::updated_at,
+                ))
+                .into_boxed();
+
+            if let Some(application_id) = &filters.application_id {
+                q = q.filter(device::dsl::application_id.eq(application_id));
+            }
+
+            if let Some(dev_addr) = &filters.dev_addr {
+                q = q.filter(device_session::dsl::dev_addr.eq(dev_addr));
+            }
+
+            if let Some(region_config_id) = &filters.region_config_id {
+                q = q.filter(device_session::dsl::region_config_id.eq(region_config_id));
+            }
+
+            if let Some(dr) = filters.dr {
+                q = q.filter(device_session::dsl::dr.eq(dr as i16));
+            }
+
+            if let Some(min_f_cnt_up) = filters.min_f_cnt_up {
+                q = q.filter(device_session::dsl::f_cnt_up.ge(min_f_cnt_up as i64));
+            }
+
+            q.order_by(device_session::dsl::dev_eui)
+                .limit(limit)
+                .offset(offset)
+                .load(&mut c)
+                .map_err(|e| Error::from_diesel(e, "".into()))
+        }
+    })
+    .await?
+}
+
 // Return the device-session matching the given PhyPayload. This will fetch all device-session
 // associated with the used DevAddr and based on f_cont and mic, decides which one to use.
 // This function will increment the uplink frame-counter and will immediately update the
@@ -310,16 +532,38 @@ pub async fn get_for_phypayload(
 }
 
 async fn get_dev_euis_for_dev_addr(dev_addr: DevAddr) -> Result<Vec<EUI64>> {
+    let conf = config::get();
+
     task::spawn_blocking({
         let dev_addr = dev_addr;
         move || -> Result<Vec<EUI64>> {
             let key = redis_key(format!("devaddr:{{{}}}", dev_addr));
             let mut c = get_redis_conn()?;
-            let dev_euis: HashSet<Vec<u8>> = redis::cmd("SMEMBERS")
+            let mut dev_euis: HashSet<Vec<u8>> = redis::cmd("SMEMBERS")
                 .arg(key)
                 .query(&mut *c)
                 .context("Get DevEUIs for DevAddr")?;
 
+            // The DevAddr -> DevEUI set in Redis might be incomplete, e.g. after
+            // a Redis flush, while other devices using the same DevAddr have
+            // already re-populated the set.
+            if conf.network.device_session_persistence {
+                let mut c = get_db_conn()?;
+                let db_dev_euis: Vec<EUI64> = device_session::dsl::device_session
+                    .select(device_session::dev_eui)
+                    .filter(
+                        device_session::dsl::dev_addr
+                            .eq(&dev_addr)
+                            .or(device_session::dsl::pending_rejoin_dev_addr.eq(&dev_addr)),
+                    )
+                    .load(&mut c)
+                    .context("Get DevEUIs for DevAddr from database")?;
+
+                for dev_eui in db_dev_euis {
+                    dev_euis.insert(dev_eui.to_vec());
+                }
+            }
+
             let mut out = Vec::new();
             for dev_eui in &dev_euis {
                 out.push(EUI64::from_slice(dev_eui)?);
@@ -417,7 +661,7 @@ fn get_full_f_cnt_up(next_expected_full_fcnt: u32, truncated_f_cnt: u32) -> u32
 #[cfg(test)]
 pub mod test {
     use super::*;
-    use crate::test;
+    use crate::{storage, test};
 
     #[test]
     fn test_get_full_f_cnt_up() {
@@ -793,4 +1037,80 @@ pub mod test {
         assert_eq!(1, dev_euis.len());
         assert_eq!(dev_eui_1, dev_euis[0]);
     }
+
+    #[tokio::test]
+    async fn test_device_session_persistence() {
+        let _guard = test::prepare().await;
+
+        let mut conf = (*config::get()).clone();
+        conf.network.device_session_persistence = true;
+        config::set(conf);
+
+        let dp = storage::device_profile::test::create_device_profile(None).await;
+        let dev_eui_1 = EUI64::from_be_bytes([1, 1, 1, 1, 1, 1, 1, 1]);
+        let dev_eui_2 = EUI64::from_be_bytes([2, 2, 2, 2, 2, 2, 2, 2]);
+        let dev_addr = DevAddr::from_be_bytes([1, 2, 3, 4]);
+        let d = storage::device::test::create_device(dev_eui_1, dp.id, None).await;
+        storage::device::test::create_device(dev_eui_2, dp.id, Some(d.application_id)).await;
+
+        let ds_1 = internal::DeviceSession {
+            dev_addr: dev_addr.to_vec(),
+            dev_eui: dev_eui_1.to_vec(),
+            region_config_id: "eu868".into(),
+            f_cnt_up: 10,
+            dr: 0,
+            ..Default::default()
+        };
+        let ds_2 = internal::DeviceSession {
+            dev_addr: dev_addr.to_vec(),
+            dev_eui: dev_eui_2.to_vec(),
+            region_config_id: "eu868".into(),
+            f_cnt_up: 200,
+            dr: 5,
+            ..Default::default()
+        };
+
+        save(&ds_1).await.unwrap();
+        save(&ds_2).await.unwrap();
+
+        // Flush the Redis cache.
+        storage::reset_redis().await.unwrap();
+
+        // Restored from the database.
+        assert_eq!(ds_1, get(&dev_eui_1).await.unwrap());
+
+        // The DevAddr -> DevEUI set only contains the restored device-session,
+        // the other device-session is resolved from the database.
+        let dss = get_for_dev_addr(dev_addr).await.unwrap();
+        assert_eq!(2, dss.len());
+
+        // Filters.
+        let filters = Filters {
+            application_id: Some(d.application_id),
+            ..Default::default()
+        };
+        assert_eq!(2, get_count(&filters).await.unwrap());
+
+        let filters = Filters {
+            min_f_cnt_up: Some(100),
+            ..Default::default()
+        };
+        assert_eq!(1, get_count(&filters).await.unwrap());
+        let items = list(10, 0, &filters).await.unwrap();
+        assert_eq!(dev_eui_2, items[0].dev_eui);
+
+        let filters = Filters {
+            dr: Some(0),
+            ..Default::default()
+        };
+        let items = list(10, 0, &filters).await.unwrap();
+        assert_eq!(1, items.len());
+        assert_eq!(dev_eui_1, items[0].dev_eui);
+        assert_eq!(dev_addr, items[0].dev_addr);
+
+        // Delete.
+        delete(&dev_eui_1).await.unwrap();
+        assert!(matches!(get(&dev_eui_1).await, Err(Error::NotFound(_))));
+        assert_eq!(1, get_count(&Filters::default()).await.unwrap());
+    }
 }
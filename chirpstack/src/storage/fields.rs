@@ -175,3 +175,72 @@ impl FromStr for MulticastGroupSchedulingType {
         })
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, AsExpression, FromSqlRow)]
+#[allow(clippy::upper_case_acronyms)]
+#[allow(non_camel_case_types)]
+#[diesel(sql_type = diesel::sql_types::Text)]
+pub enum FuotaDeploymentStep {
+    // Send the McGroupSetupReq to the devices.
+    MC_GROUP_SETUP,
+    // Send the FragSessionSetupReq to the devices.
+    FRAG_SESSION_SETUP,
+    // Send the McClassB/CSessionReq to the devices.
+    MC_SESSION,
+    // Enqueue the fragments to the multicast-group.
+    ENQUEUE,
+    // Send the FragSessionStatusReq to the devices.
+    FRAG_STATUS,
+    // Complete the deployment.
+    COMPLETE,
+}
+
+impl fmt::Display for FuotaDeploymentStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl<DB> deserialize::FromSql<Text, DB> for FuotaDeploymentStep
+where
+    DB: Backend,
+    *const str: deserialize::FromSql<Text, DB>,
+{
+    fn from_sql(value: <DB as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        let string = String::from_sql(value)?;
+        Ok(FuotaDeploymentStep::from_str(&string)?)
+    }
+}
+
+impl serialize::ToSql<Text, diesel::pg::Pg> for FuotaDeploymentStep
+where
+    str: serialize::ToSql<Text, diesel::pg::Pg>,
+{
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut serialize::Output<'b, '_, diesel::pg::Pg>,
+    ) -> serialize::Result {
+        <str as serialize::ToSql<Text, diesel::pg::Pg>>::to_sql(
+            &self.to_string(),
+            &mut out.reborrow(),
+        )
+    }
+}
+
+impl FromStr for FuotaDeploymentStep {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "MC_GROUP_SETUP" => FuotaDeploymentStep::MC_GROUP_SETUP,
+            "FRAG_SESSION_SETUP" => FuotaDeploymentStep::FRAG_SESSION_SETUP,
+            "MC_SESSION" => FuotaDeploymentStep::MC_SESSION,
+            "ENQUEUE" => FuotaDeploymentStep::ENQUEUE,
+            "FRAG_STATUS" => FuotaDeploymentStep::FRAG_STATUS,
+            "COMPLETE" => FuotaDeploymentStep::COMPLETE,
+            _ => {
+                return Err(anyhow!("Unexpected FuotaDeploymentStep: {}", s));
+            }
+        })
+    }
+}
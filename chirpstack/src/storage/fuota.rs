@@ -0,0 +1,605 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use diesel::dsl;
+use diesel::prelude::*;
+use tokio::task;
+use tracing::info;
+use uuid::Uuid;
+
+use lrwn::{AES128Key, DevAddr, EUI64};
+
+use super::error::Error;
+use super::schema::{device, fuota_deployment, fuota_deployment_device};
+use super::{fields, get_db_conn};
+use crate::config;
+
+#[derive(Clone, Queryable, Insertable, Debug, PartialEq, Eq)]
+#[diesel(table_name = fuota_deployment)]
+pub struct FuotaDeployment {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub name: String,
+    pub application_id: Uuid,
+    pub device_profile_id: Uuid,
+    pub multicast_group_id: Option<Uuid>,
+    pub multicast_group_type: String,
+    pub multicast_class_c_scheduling_type: fields::MulticastGroupSchedulingType,
+    pub multicast_dr: i16,
+    pub multicast_class_b_ping_slot_period: i32,
+    pub multicast_frequency: i64,
+    pub multicast_timeout: i16,
+    pub multicast_addr: DevAddr,
+    pub multicast_key: AES128Key,
+    pub unicast_timeout: i32,
+    pub unicast_max_retry_count: i16,
+    pub fragmentation_fragment_size: i16,
+    pub fragmentation_redundancy_percentage: i16,
+    pub fragmentation_session_index: i16,
+    pub fragmentation_matrix: i16,
+    pub fragmentation_block_ack_delay: i16,
+    pub fragmentation_descriptor: Vec<u8>,
+    pub payload: Vec<u8>,
+    pub next_step: fields::FuotaDeploymentStep,
+    pub retry_count: i16,
+    pub scheduler_run_after: DateTime<Utc>,
+    pub multicast_session_start: Option<DateTime<Utc>>,
+}
+
+impl FuotaDeployment {
+    fn validate(&self) -> Result<(), Error> {
+        if self.name.is_empty() {
+            return Err(Error::Validation("name is not set".into()));
+        }
+        if self.multicast_group_type != "B" && self.multicast_group_type != "C" {
+            return Err(Error::Validation(
+                "multicast_group_type must be B or C".into(),
+            ));
+        }
+        if self.multicast_timeout < 0 || self.multicast_timeout > 15 {
+            return Err(Error::Validation(
+                "multicast_timeout must be between 0 - 15".into(),
+            ));
+        }
+        if self.unicast_timeout <= 0 {
+            return Err(Error::Validation("unicast_timeout must be > 0".into()));
+        }
+        if self.fragmentation_fragment_size <= 0 || self.fragmentation_fragment_size > 255 {
+            return Err(Error::Validation(
+                "fragmentation_fragment_size must be between 1 - 255".into(),
+            ));
+        }
+        if self.fragmentation_session_index < 0 || self.fragmentation_session_index > 3 {
+            return Err(Error::Validation(
+                "fragmentation_session_index must be between 0 - 3".into(),
+            ));
+        }
+        if self.fragmentation_descriptor.len() != 4 {
+            return Err(Error::Validation(
+                "fragmentation_descriptor must be exactly 4 bytes".into(),
+            ));
+        }
+        if self.payload.is_empty() {
+            return Err(Error::Validation("payload is empty".into()));
+        }
+        Ok(())
+    }
+}
+
+impl Default for FuotaDeployment {
+    fn default() -> Self {
+        let now = Utc::now();
+
+        FuotaDeployment {
+            id: Uuid::new_v4(),
+            created_at: now,
+            updated_at: now,
+            started_at: None,
+            completed_at: None,
+            name: "".into(),
+            application_id: Uuid::nil(),
+            device_profile_id: Uuid::nil(),
+            multicast_group_id: None,
+            multicast_group_type: "C".into(),
+            multicast_class_c_scheduling_type: fields::MulticastGroupSchedulingType::DELAY,
+            multicast_dr: 0,
+            multicast_class_b_ping_slot_period: 0,
+            multicast_frequency: 0,
+            multicast_timeout: 0,
+            multicast_addr: DevAddr::default(),
+            multicast_key: AES128Key::default(),
+            unicast_timeout: 60,
+            unicast_max_retry_count: 0,
+            fragmentation_fragment_size: 0,
+            fragmentation_redundancy_percentage: 0,
+            fragmentation_session_index: 0,
+            fragmentation_matrix: 0,
+            fragmentation_block_ack_delay: 0,
+            fragmentation_descriptor: vec![0, 0, 0, 0],
+            payload: vec![],
+            next_step: fields::FuotaDeploymentStep::MC_GROUP_SETUP,
+            retry_count: 0,
+            scheduler_run_after: now,
+            multicast_session_start: None,
+        }
+    }
+}
+
+#[derive(Queryable, PartialEq, Eq, Debug)]
+pub struct FuotaDeploymentListItem {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub name: String,
+}
+
+#[derive(Default, Clone)]
+pub struct Filters {
+    pub application_id: Option<Uuid>,
+}
+
+#[derive(Clone, Queryable, Insertable, Debug, PartialEq, Eq)]
+#[diesel(table_name = fuota_deployment_device)]
+pub struct FuotaDeploymentDevice {
+    pub fuota_deployment_id: Uuid,
+    pub dev_eui: EUI64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub mc_group_setup_completed_at: Option<DateTime<Utc>>,
+    pub mc_session_completed_at: Option<DateTime<Utc>>,
+    pub frag_session_setup_completed_at: Option<DateTime<Utc>>,
+    pub frag_status_completed_at: Option<DateTime<Utc>>,
+    pub error_msg: String,
+}
+
+impl Default for FuotaDeploymentDevice {
+    fn default() -> Self {
+        let now = Utc::now();
+
+        FuotaDeploymentDevice {
+            fuota_deployment_id: Uuid::nil(),
+            dev_eui: EUI64::default(),
+            created_at: now,
+            updated_at: now,
+            mc_group_setup_completed_at: None,
+            mc_session_completed_at: None,
+            frag_session_setup_completed_at: None,
+            frag_status_completed_at: None,
+            error_msg: "".into(),
+        }
+    }
+}
+
+pub async fn create(fd: FuotaDeployment) -> Result<FuotaDeployment, Error> {
+    fd.validate()?;
+    let fd = task::spawn_blocking({
+        move || -> Result<FuotaDeployment, Error> {
+            let mut c = get_db_conn()?;
+            diesel::insert_into(fuota_deployment::table)
+                .values(&fd)
+                .get_result(&mut c)
+                .map_err(|e| Error::from_diesel(e, fd.id.to_string()))
+        }
+    })
+    .await??;
+    info!(id = %fd.id, "FUOTA deployment created");
+    Ok(fd)
+}
+
+pub async fn get(id: &Uuid) -> Result<FuotaDeployment, Error> {
+    task::spawn_blocking({
+        let id = *id;
+        move || -> Result<FuotaDeployment, Error> {
+            let mut c = get_db_conn()?;
+            fuota_deployment::dsl::fuota_deployment
+                .find(&id)
+                .first(&mut c)
+                .map_err(|e| Error::from_diesel(e, id.to_string()))
+        }
+    })
+    .await?
+}
+
+pub async fn update(fd: FuotaDeployment) -> Result<FuotaDeployment, Error> {
+    fd.validate()?;
+    let fd = task::spawn_blocking({
+        move || -> Result<FuotaDeployment, Error> {
+            let mut c = get_db_conn()?;
+
+            diesel::update(fuota_deployment::dsl::fuota_deployment.find(&fd.id))
+                .set((
+                    fuota_deployment::updated_at.eq(Utc::now()),
+                    fuota_deployment::started_at.eq(&fd.started_at),
+                    fuota_deployment::completed_at.eq(&fd.completed_at),
+                    fuota_deployment::name.eq(&fd.name),
+                    fuota_deployment::multicast_group_id.eq(&fd.multicast_group_id),
+                    fuota_deployment::multicast_addr.eq(&fd.multicast_addr),
+                    fuota_deployment::multicast_key.eq(&fd.multicast_key),
+                    fuota_deployment::next_step.eq(&fd.next_step),
+                    fuota_deployment::retry_count.eq(&fd.retry_count),
+                    fuota_deployment::scheduler_run_after.eq(&fd.scheduler_run_after),
+                    fuota_deployment::multicast_session_start.eq(&fd.multicast_session_start),
+                ))
+                .get_result(&mut c)
+                .map_err(|e| Error::from_diesel(e, fd.id.to_string()))
+        }
+    })
+    .await??;
+    info!(id = %fd.id, next_step = %fd.next_step, "FUOTA deployment updated");
+    Ok(fd)
+}
+
+pub async fn delete(id: &Uuid) -> Result<(), Error> {
+    task::spawn_blocking({
+        let id = *id;
+        move || -> Result<(), Error> {
+            let mut c = get_db_conn()?;
+            let ra = diesel::delete(fuota_deployment::dsl::fuota_deployment.find(&id))
+                .execute(&mut c)?;
+            if ra == 0 {
+                return Err(Error::NotFound(id.to_string()));
+            }
+            Ok(())
+        }
+    })
+    .await??;
+    info!(id = %id, "FUOTA deployment deleted");
+    Ok(())
+}
+
+pub async fn get_count(filters: &Filters) -> Result<i64, Error> {
+    task::spawn_blocking({
+        let filters = filters.clone();
+        move || -> Result<i64, Error> {
+            let mut c = get_db_conn()?;
+            let mut q = fuota_deployment::dsl::fuota_deployment
+                .select(dsl::count_star())
+                .into_boxed();
+
+            if let Some(application_id) = &filters.application_id {
+                q = q.filter(fuota_deployment::dsl::application_id.eq(application_id));
+            }
+
+            q.first(&mut c)
+                .map_err(|e| Error::from_diesel(e, "".into()))
+        }
+    })
+    .await?
+}
+
+pub async fn list(
+    limit: i64,
+    offset: i64,
+    filters: &Filters,
+) -> Result<Vec<FuotaDeploymentListItem>, Error> {
+    task::spawn_blocking({
+        let filters = filters.clone();
+        move || -> Result<Vec<FuotaDeploymentListItem>, Error> {
+            let mut c = get_db_conn()?;
+            let mut q = fuota_deployment::dsl::fuota_deployment
+                .select((
+                    fuota_deployment::id,
+                    fuota_deployment::created_at,
+                    fuota_deployment::updated_at,
+                    fuota_deployment::started_at,
+                    fuota_deployment::completed_at,
+                    fuota_deployment::name,
+                ))
+                .into_boxed();
+
+            if let Some(application_id) = &filters.application_id {
+                q = q.filter(fuota_deployment::dsl::application_id.eq(application_id));
+            }
+
+            q.order_by(fuota_deployment::dsl::created_at.desc())
+                .limit(limit)
+                .offset(offset)
+                .load(&mut c)
+                .map_err(|e| Error::from_diesel(e, "".into()))
+        }
+    })
+    .await?
+}
+
+pub async fn add_devices(fuota_deployment_id: &Uuid, dev_euis: &[EUI64]) -> Result<(), Error> {
+    task::spawn_blocking({
+        let fuota_deployment_id = *fuota_deployment_id;
+        let dev_euis = dev_euis.to_vec();
+        move || -> Result<(), Error> {
+            let mut c = get_db_conn()?;
+            c.transaction::<(), Error, _>(|c| {
+                let fd: FuotaDeployment = fuota_deployment::dsl::fuota_deployment
+                    .find(&fuota_deployment_id)
+                    .for_update()
+                    .get_result(c)
+                    .map_err(|e| Error::from_diesel(e, fuota_deployment_id.to_string()))?;
+
+                if fd.started_at.is_some() {
+                    return Err(Error::NotAllowed(
+                        "Devices can not be added to a started deployment".into(),
+                    ));
+                }
+
+                for dev_eui in &dev_euis {
+                    let d: super::device::Device = device::dsl::device
+                        .find(&dev_eui)
+                        .get_result(c)
+                        .map_err(|e| Error::from_diesel(e, dev_eui.to_string()))?;
+
+                    if d.application_id != fd.application_id
+                        || d.device_profile_id != fd.device_profile_id
+                    {
+                        // Device not found within the same application and device-profile.
+                        return Err(Error::NotFound(dev_eui.to_string()));
+                    }
+
+                    let _ = diesel::insert_into(fuota_deployment_device::table)
+                        .values(&FuotaDeploymentDevice {
+                            fuota_deployment_id,
+                            dev_eui: *dev_eui,
+                            ..Default::default()
+                        })
+                        .execute(c)
+                        .map_err(|e| Error::from_diesel(e, dev_eui.to_string()))?;
+                }
+
+                Ok(())
+            })
+        }
+    })
+    .await??;
+    info!(fuota_deployment_id = %fuota_deployment_id, count = dev_euis.len(), "Devices added to FUOTA deployment");
+    Ok(())
+}
+
+pub async fn get_device_count(fuota_deployment_id: &Uuid) -> Result<i64, Error> {
+    task::spawn_blocking({
+        let fuota_deployment_id = *fuota_deployment_id;
+        move || -> Result<i64, Error> {
+            let mut c = get_db_conn()?;
+            fuota_deployment_device::dsl::fuota_deployment_device
+                .select(dsl::count_star())
+                .filter(fuota_deployment_device::dsl::fuota_deployment_id.eq(&fuota_deployment_id))
+                .first(&mut c)
+                .map_err(|e| Error::from_diesel(e, "".into()))
+        }
+    })
+    .await?
+}
+
+pub async fn list_devices(
+    fuota_deployment_id: &Uuid,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<FuotaDeploymentDevice>, Error> {
+    task::spawn_blocking({
+        let fuota_deployment_id = *fuota_deployment_id;
+        move || -> Result<Vec<FuotaDeploymentDevice>, Error> {
+            let mut c = get_db_conn()?;
+            fuota_deployment_device::dsl::fuota_deployment_device
+                .filter(fuota_deployment_device::dsl::fuota_deployment_id.eq(&fuota_deployment_id))
+                .order_by(fuota_deployment_device::dsl::dev_eui)
+                .limit(limit)
+                .offset(offset)
+                .load(&mut c)
+                .map_err(|e| Error::from_diesel(e, "".into()))
+        }
+    })
+    .await?
+}
+
+pub async fn get_devices(fuota_deployment_id: &Uuid) -> Result<Vec<FuotaDeploymentDevice>, Error> {
+    list_devices(fuota_deployment_id, i64::MAX, 0).await
+}
+
+pub async fn get_device(
+    fuota_deployment_id: &Uuid,
+    dev_eui: &EUI64,
+) -> Result<FuotaDeploymentDevice, Error> {
+    task::spawn_blocking({
+        let fuota_deployment_id = *fuota_deployment_id;
+        let dev_eui = *dev_eui;
+        move || -> Result<FuotaDeploymentDevice, Error> {
+            let mut c = get_db_conn()?;
+            fuota_deployment_device::dsl::fuota_deployment_device
+                .find((&fuota_deployment_id, &dev_eui))
+                .first(&mut c)
+                .map_err(|e| Error::from_diesel(e, dev_eui.to_string()))
+        }
+    })
+    .await?
+}
+
+pub async fn update_device(d: FuotaDeploymentDevice) -> Result<FuotaDeploymentDevice, Error> {
+    let d = task::spawn_blocking({
+        move || -> Result<FuotaDeploymentDevice, Error> {
+            let mut c = get_db_conn()?;
+            diesel::update(
+                fuota_deployment_device::dsl::fuota_deployment_device
+                    .find((&d.fuota_deployment_id, &d.dev_eui)),
+            )
+            .set((
+                fuota_deployment_device::updated_at.eq(Utc::now()),
+                fuota_deployment_device::mc_group_setup_completed_at
+                    .eq(&d.mc_group_setup_completed_at),
+                fuota_deployment_device::mc_session_completed_at.eq(&d.mc_session_completed_at),
+                fuota_deployment_device::frag_session_setup_completed_at
+                    .eq(&d.frag_session_setup_completed_at),
+                fuota_deployment_device::frag_status_completed_at.eq(&d.frag_status_completed_at),
+                fuota_deployment_device::error_msg.eq(&d.error_msg),
+            ))
+            .get_result(&mut c)
+            .map_err(|e| Error::from_diesel(e, d.dev_eui.to_string()))
+        }
+    })
+    .await??;
+    info!(fuota_deployment_id = %d.fuota_deployment_id, dev_eui = %d.dev_eui, "FUOTA deployment device updated");
+    Ok(d)
+}
+
+// Returns the started, but not yet completed deployment for the given device.
+pub async fn get_active_deployment_for_dev_eui(dev_eui: &EUI64) -> Result<FuotaDeployment, Error> {
+    task::spawn_blocking({
+        let dev_eui = *dev_eui;
+        move || -> Result<FuotaDeployment, Error> {
+            let mut c = get_db_conn()?;
+            fuota_deployment::dsl::fuota_deployment
+                .select(fuota_deployment::all_columns)
+                .inner_join(fuota_deployment_device::table)
+                .filter(fuota_deployment_device::dsl::dev_eui.eq(&dev_eui))
+                .filter(fuota_deployment::dsl::started_at.is_not_null())
+                .filter(fuota_deployment::dsl::completed_at.is_null())
+                .order_by(fuota_deployment::dsl::started_at.desc())
+                .first(&mut c)
+                .map_err(|e| Error::from_diesel(e, dev_eui.to_string()))
+        }
+    })
+    .await?
+}
+
+// Returns the deployments that must be handled by the scheduler. The
+// scheduler_run_after of the returned deployments is incremented, to avoid
+// that these are picked up again by a concurrent scheduler run.
+pub async fn get_schedulable_deployments(limit: usize) -> Result<Vec<FuotaDeployment>> {
+    task::spawn_blocking({
+        move || -> Result<Vec<FuotaDeployment>> {
+            let mut c = get_db_conn()?;
+            c.transaction::<Vec<FuotaDeployment>, Error, _>(|c| {
+                let conf = config::get();
+                let now = Utc::now();
+
+                let ids: Vec<Uuid> = fuota_deployment::dsl::fuota_deployment
+                    .select(fuota_deployment::dsl::id)
+                    .filter(fuota_deployment::dsl::started_at.is_not_null())
+                    .filter(fuota_deployment::dsl::completed_at.is_null())
+                    .filter(fuota_deployment::dsl::scheduler_run_after.le(now))
+                    .order_by(fuota_deployment::dsl::scheduler_run_after)
+                    .limit(limit as i64)
+                    .for_update()
+                    .skip_locked()
+                    .load(c)?;
+
+                diesel::update(
+                    fuota_deployment::dsl::fuota_deployment
+                        .filter(fuota_deployment::dsl::id.eq_any(ids)),
+                )
+                .set(
+                    fuota_deployment::dsl::scheduler_run_after
+                        .eq(now + Duration::from_std(2 * conf.network.scheduler.interval).unwrap()),
+                )
+                .get_results(c)
+                .map_err(|e| Error::from_diesel(e, "".into()))
+            })
+            .context("Get schedulable FUOTA deployments")
+        }
+    })
+    .await?
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::storage::{application, device, device_profile, tenant};
+    use crate::test;
+
+    #[tokio::test]
+    async fn test_fuota_deployment() {
+        let _guard = test::prepare().await;
+
+        let t = tenant::create(tenant::Tenant {
+            name: "test-tenant".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let app = application::create(application::Application {
+            name: "test-app".into(),
+            tenant_id: t.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let dp = device_profile::create(device_profile::DeviceProfile {
+            tenant_id: t.id,
+            name: "test-dp".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let d = device::create(device::Device {
+            application_id: app.id,
+            device_profile_id: dp.id,
+            name: "test-device".into(),
+            dev_eui: EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // create
+        let mut fd = create(FuotaDeployment {
+            application_id: app.id,
+            device_profile_id: dp.id,
+            name: "test-fuota".into(),
+            multicast_addr: DevAddr::from_be_bytes([1, 2, 3, 4]),
+            fragmentation_fragment_size: 50,
+            payload: vec![1, 2, 3, 4],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // get
+        let fd_get = get(&fd.id).await.unwrap();
+        assert_eq!(fd, fd_get);
+
+        // count and list
+        let filters = Filters {
+            application_id: Some(app.id),
+        };
+        assert_eq!(1, get_count(&filters).await.unwrap());
+        let items = list(10, 0, &filters).await.unwrap();
+        assert_eq!(1, items.len());
+        assert_eq!(fd.id, items[0].id);
+
+        // add devices
+        add_devices(&fd.id, &[d.dev_eui]).await.unwrap();
+        assert_eq!(1, get_device_count(&fd.id).await.unwrap());
+
+        // not yet started
+        assert!(get_active_deployment_for_dev_eui(&d.dev_eui).await.is_err());
+        assert!(get_schedulable_deployments(10).await.unwrap().is_empty());
+
+        // start
+        fd.started_at = Some(Utc::now());
+        let fd = update(fd).await.unwrap();
+        let fd_active = get_active_deployment_for_dev_eui(&d.dev_eui).await.unwrap();
+        assert_eq!(fd.id, fd_active.id);
+
+        // adding devices to a started deployment is not allowed
+        assert!(add_devices(&fd.id, &[d.dev_eui]).await.is_err());
+
+        // schedulable
+        let items = get_schedulable_deployments(10).await.unwrap();
+        assert_eq!(1, items.len());
+        assert!(get_schedulable_deployments(10).await.unwrap().is_empty());
+
+        // update device
+        let mut fdd = get_device(&fd.id, &d.dev_eui).await.unwrap();
+        fdd.mc_group_setup_completed_at = Some(Utc::now());
+        let fdd = update_device(fdd).await.unwrap();
+        let devices = get_devices(&fd.id).await.unwrap();
+        assert_eq!(vec![fdd], devices);
+
+        // delete
+        delete(&fd.id).await.unwrap();
+        assert!(delete(&fd.id).await.is_err());
+    }
+}
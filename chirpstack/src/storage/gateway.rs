@@ -384,6 +384,38 @@ pub async fn list(
     .await?
 }
 
+pub async fn get_tag_keys(tenant_id: &Uuid) -> Result<Vec<String>, Error> {
+    #[derive(QueryableByName)]
+    struct Key {
+        #[diesel(sql_type = diesel::sql_types::Text)]
+        pub key: String,
+    }
+
+    task::spawn_blocking({
+        let tenant_id = *tenant_id;
+        move || -> Result<Vec<String>, Error> {
+            let mut c = get_db_conn()?;
+            let keys: Vec<Key> = diesel::sql_query(
+                r#"
+                select
+                    distinct jsonb_object_keys(tags) as key
+                from
+                    gateway
+                where
+                    tenant_id = $1
+                order by
+                    key
+                "#,
+            )
+            .bind::<diesel::sql_types::Uuid, _>(tenant_id)
+            .load(&mut c)
+            .map_err(|e| Error::from_diesel(e, tenant_id.to_string()))?;
+            Ok(keys.into_iter().map(|k| k.key).collect())
+        }
+    })
+    .await?
+}
+
 pub async fn get_meta(gateway_id: &EUI64) -> Result<GatewayMeta, Error> {
     task::spawn_blocking({
         let gateway_id = *gateway_id;
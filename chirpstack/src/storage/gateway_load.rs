@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::task;
+use tracing::info;
+
+use super::{get_redis_conn, redis_key};
+use lrwn::EUI64;
+
+/// Saves the uplink channel utilization (0.0 - 1.0) of the gateway, as
+/// calculated from the gateway stats.
+pub async fn save(gateway_id: &EUI64, utilization: f32, ttl: Duration) -> Result<()> {
+    task::spawn_blocking({
+        let gateway_id = *gateway_id;
+        move || -> Result<()> {
+            let key = redis_key(format!("gw:{{{}}}:load", gateway_id));
+            let mut c = get_redis_conn()?;
+
+            redis::cmd("PSETEX")
+                .arg(key)
+                .arg(ttl.as_millis() as usize)
+                .arg(utilization)
+                .query(&mut *c)?;
+
+            Ok(())
+        }
+    })
+    .await??;
+
+    info!(gateway_id = %gateway_id, utilization = utilization, "Gateway load saved");
+    Ok(())
+}
+
+/// Returns the uplink channel utilization of the given gateways. Gateways for
+/// which no (recent) utilization is known are omitted.
+pub async fn get_for_gateway_ids(gateway_ids: &[EUI64]) -> Result<HashMap<EUI64, f32>> {
+    if gateway_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    task::spawn_blocking({
+        let gateway_ids = gateway_ids.to_vec();
+        move || -> Result<HashMap<EUI64, f32>> {
+            let mut c = get_redis_conn()?;
+            let keys: Vec<String> = gateway_ids
+                .iter()
+                .map(|id| redis_key(format!("gw:{{{}}}:load", id)))
+                .collect();
+
+            let values: Vec<Option<f32>> = redis::cmd("MGET")
+                .arg(keys)
+                .query(&mut *c)
+                .context("MGET")?;
+
+            Ok(gateway_ids
+                .into_iter()
+                .zip(values.into_iter())
+                .filter_map(|(id, v)| v.map(|v| (id, v)))
+                .collect())
+        }
+    })
+    .await?
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::test;
+
+    #[tokio::test]
+    async fn test_gateway_load() {
+        let _guard = test::prepare().await;
+        let gw_1 = EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]);
+        let gw_2 = EUI64::from_be_bytes([2, 2, 3, 4, 5, 6, 7, 8]);
+
+        save(&gw_1, 0.25, Duration::from_secs(60)).await.unwrap();
+
+        let res = get_for_gateway_ids(&[gw_1, gw_2]).await.unwrap();
+        assert_eq!(1, res.len());
+        assert_eq!(Some(&0.25), res.get(&gw_1));
+    }
+}
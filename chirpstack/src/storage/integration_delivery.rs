@@ -0,0 +1,434 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use diesel::dsl;
+use diesel::prelude::*;
+use tokio::task;
+use tracing::info;
+use uuid::Uuid;
+
+use super::application::IntegrationKind;
+use super::error::Error;
+use super::schema::integration_delivery;
+use super::{fields, get_db_conn};
+
+/// Integration delivery which failed and is pending retry, or which has been
+/// moved to the dead-letter storage after exceeding the max. number of attempts.
+#[derive(Clone, Queryable, Insertable, Debug, PartialEq, Eq)]
+#[diesel(table_name = integration_delivery)]
+pub struct IntegrationDelivery {
+    pub id: Uuid,
+    pub application_id: Uuid,
+    // Kind of the application integration. This is None for deliveries to a
+    // global integration.
+    pub integration_kind: Option<IntegrationKind>,
+    // Name of the global integration (e.g. mqtt). This is empty for deliveries
+    // to an application integration.
+    pub global_integration: String,
+    // Endpoint to which the event must be delivered. This is set for
+    // integrations posting to multiple endpoints (e.g. HTTP), in which case
+    // only the failed endpoint is retried. When empty, the event is delivered
+    // to the integration as a whole.
+    pub endpoint: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub event_type: String,
+    pub vars: fields::KeyValue,
+    // Protobuf encoded event.
+    pub payload: Vec<u8>,
+    pub attempts: i16,
+    pub dead_letter: bool,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: String,
+}
+
+impl Default for IntegrationDelivery {
+    fn default() -> Self {
+        let now = Utc::now();
+
+        IntegrationDelivery {
+            id: Uuid::new_v4(),
+            application_id: Uuid::nil(),
+            integration_kind: Some(IntegrationKind::Http),
+            global_integration: "".into(),
+            endpoint: "".into(),
+            created_at: now,
+            updated_at: now,
+            event_type: "".into(),
+            vars: fields::KeyValue::new(Default::default()),
+            payload: vec![],
+            attempts: 0,
+            dead_letter: false,
+            next_attempt_at: now,
+            last_error: "".into(),
+        }
+    }
+}
+
+impl IntegrationDelivery {
+    /// Returns the name of the integration to which the event must be delivered.
+    pub fn integration_name(&self) -> String {
+        match self.integration_kind {
+            Some(v) => v.to_string(),
+            None => self.global_integration.clone(),
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct Filters {
+    pub application_id: Option<Uuid>,
+    pub dead_letter: Option<bool>,
+}
+
+pub async fn create(d: IntegrationDelivery) -> Result<IntegrationDelivery, Error> {
+    let d = task::spawn_blocking({
+        move || -> Result<IntegrationDelivery, Error> {
+            let mut c = get_db_conn()?;
+            diesel::insert_into(integration_delivery::table)
+                .values(&d)
+                .get_result(&mut c)
+                .map_err(|e| Error::from_diesel(e, d.id.to_string()))
+        }
+    })
+    .await??;
+    info!(id = %d.id, application_id = %d.application_id, integration = %d.integration_name(), event_type = %d.event_type, "Integration delivery created");
+    Ok(d)
+}
+
+/// Creates the given deliveries within a single query.
+pub async fn create_many(ds: Vec<IntegrationDelivery>) -> Result<Vec<IntegrationDelivery>, Error> {
+    if ds.is_empty() {
+        return Ok(ds);
+    }
+
+    let ds = task::spawn_blocking({
+        move || -> Result<Vec<IntegrationDelivery>, Error> {
+            let mut c = get_db_conn()?;
+            diesel::insert_into(integration_delivery::table)
+                .values(&ds)
+                .get_results(&mut c)
+                .map_err(|e| Error::from_diesel(e, "".into()))
+        }
+    })
+    .await??;
+    info!(count = ds.len(), "Integration deliveries created");
+    Ok(ds)
+}
+
+pub async fn get(id: &Uuid) -> Result<IntegrationDelivery, Error> {
+    task::spawn_blocking({
+        let id = *id;
+        move || -> Result<IntegrationDelivery, Error> {
+            let mut c = get_db_conn()?;
+            integration_delivery::dsl::integration_delivery
+                .find(&id)
+                .first(&mut c)
+                .map_err(|e| Error::from_diesel(e, id.to_string()))
+        }
+    })
+    .await?
+}
+
+pub async fn update(d: IntegrationDelivery) -> Result<IntegrationDelivery, Error> {
+    let d = task::spawn_blocking({
+        move || -> Result<IntegrationDelivery, Error> {
+            let mut c = get_db_conn()?;
+            diesel::update(integration_delivery::dsl::integration_delivery.find(&d.id))
+                .set((
+                    integration_delivery::updated_at.eq(Utc::now()),
+                    integration_delivery::endpoint.eq(&d.endpoint),
+                    integration_delivery::attempts.eq(&d.attempts),
+                    integration_delivery::dead_letter.eq(&d.dead_letter),
+                    integration_delivery::next_attempt_at.eq(&d.next_attempt_at),
+                    integration_delivery::last_error.eq(&d.last_error),
+                ))
+                .get_result(&mut c)
+                .map_err(|e| Error::from_diesel(e, d.id.to_string()))
+        }
+    })
+    .await??;
+    info!(id = %d.id, attempts = d.attempts, dead_letter = d.dead_letter, "Integration delivery updated");
+    Ok(d)
+}
+
+pub async fn delete(id: &Uuid) -> Result<(), Error> {
+    task::spawn_blocking({
+        let id = *id;
+        move || -> Result<(), Error> {
+            let mut c = get_db_conn()?;
+            let ra = diesel::delete(integration_delivery::dsl::integration_delivery.find(&id))
+                .execute(&mut c)?;
+            if ra == 0 {
+                return Err(Error::NotFound(id.to_string()));
+            }
+            Ok(())
+        }
+    })
+    .await??;
+    info!(id = %id, "Integration delivery deleted");
+    Ok(())
+}
+
+pub async fn get_count(filters: &Filters) -> Result<i64, Error> {
+    task::spawn_blocking({
+        let filters = filters.clone();
+        move || -> Result<i64, Error> {
+            let mut c = get_db_conn()?;
+            let mut q = integration_delivery::dsl::integration_delivery
+                .select(dsl::count_star())
+                .into_boxed();
+
+            if let Some(application_id) = &filters.application_id {
+                q = q.filter(integration_delivery::dsl::application_id.eq(application_id));
+            }
+
+            if let Some(dead_letter) = &filters.dead_letter {
+                q = q.filter(integration_delivery::dsl::dead_letter.eq(dead_letter));
+            }
+
+            q.first(&mut c)
+                .map_err(|e| Error::from_diesel(e, "".into()))
+        }
+    })
+    .await?
+}
+
+pub async fn list(
+    limit: i64,
+    offset: i64,
+    filters: &Filters,
+) -> Result<Vec<IntegrationDelivery>, Error> {
+    task::spawn_blocking({
+        let filters = filters.clone();
+        move || -> Result<Vec<IntegrationDelivery>, Error> {
+            let mut c = get_db_conn()?;
+            let mut q = integration_delivery::dsl::integration_delivery.into_boxed();
+
+            if let Some(application_id) = &filters.application_id {
+                q = q.filter(integration_delivery::dsl::application_id.eq(application_id));
+            }
+
+            if let Some(dead_letter) = &filters.dead_letter {
+                q = q.filter(integration_delivery::dsl::dead_letter.eq(dead_letter));
+            }
+
+            q.order_by(integration_delivery::dsl::created_at.desc())
+                .limit(limit)
+                .offset(offset)
+                .load(&mut c)
+                .map_err(|e| Error::from_diesel(e, "".into()))
+        }
+    })
+    .await?
+}
+
+/// Returns the deliveries which are due for a retry. The returned deliveries
+/// are locked for the given duration, such that these are not returned to
+/// other callers while being delivered.
+pub async fn get_pending(
+    limit: usize,
+    lock_duration: Duration,
+) -> Result<Vec<IntegrationDelivery>> {
+    task::spawn_blocking({
+        move || -> Result<Vec<IntegrationDelivery>> {
+            let mut c = get_db_conn()?;
+            c.transaction::<Vec<IntegrationDelivery>, Error, _>(|c| {
+                let now = Utc::now();
+
+                let ids: Vec<Uuid> = integration_delivery::dsl::integration_delivery
+                    .select(integration_delivery::dsl::id)
+                    .filter(integration_delivery::dsl::dead_letter.eq(false))
+                    .filter(integration_delivery::dsl::next_attempt_at.le(now))
+                    .order_by(integration_delivery::dsl::next_attempt_at)
+                    .limit(limit as i64)
+                    .for_update()
+                    .skip_locked()
+                    .load(c)?;
+
+                diesel::update(
+                    integration_delivery::dsl::integration_delivery
+                        .filter(integration_delivery::dsl::id.eq_any(ids)),
+                )
+                .set(integration_delivery::dsl::next_attempt_at.eq(now + lock_duration))
+                .get_results(c)
+                .map_err(|e| Error::from_diesel(e, "".into()))
+            })
+            .context("Get pending integration deliveries")
+        }
+    })
+    .await?
+}
+
+/// Re-schedules the given deliveries of the application for immediate delivery
+/// and resets the number of attempts. When no IDs are given, all dead-letter
+/// deliveries of the application are re-scheduled. It returns the number of
+/// re-scheduled deliveries.
+pub async fn replay(application_id: &Uuid, ids: &[Uuid]) -> Result<usize, Error> {
+    let count = task::spawn_blocking({
+        let application_id = *application_id;
+        let ids = ids.to_vec();
+        move || -> Result<usize, Error> {
+            let mut c = get_db_conn()?;
+            let now = Utc::now();
+
+            let values = (
+                integration_delivery::updated_at.eq(now),
+                integration_delivery::attempts.eq(0),
+                integration_delivery::dead_letter.eq(false),
+                integration_delivery::next_attempt_at.eq(now),
+            );
+
+            if ids.is_empty() {
+                diesel::update(
+                    integration_delivery::dsl::integration_delivery
+                        .filter(integration_delivery::dsl::application_id.eq(application_id))
+                        .filter(integration_delivery::dsl::dead_letter.eq(true)),
+                )
+                .set(values)
+                .execute(&mut c)
+            } else {
+                diesel::update(
+                    integration_delivery::dsl::integration_delivery
+                        .filter(integration_delivery::dsl::application_id.eq(application_id))
+                        .filter(integration_delivery::dsl::id.eq_any(ids)),
+                )
+                .set(values)
+                .execute(&mut c)
+            }
+            .map_err(|e| Error::from_diesel(e, application_id.to_string()))
+        }
+    })
+    .await??;
+    info!(application_id = %application_id, count = count, "Integration deliveries replayed");
+    Ok(count)
+}
+
+/// Deletes the dead-letter deliveries which have not been updated since the
+/// given timestamp. It returns the number of deleted deliveries.
+pub async fn delete_dead_letters_before(before: DateTime<Utc>) -> Result<usize, Error> {
+    task::spawn_blocking({
+        move || -> Result<usize, Error> {
+            let mut c = get_db_conn()?;
+            diesel::delete(
+                integration_delivery::dsl::integration_delivery
+                    .filter(integration_delivery::dsl::dead_letter.eq(true))
+                    .filter(integration_delivery::dsl::updated_at.lt(before)),
+            )
+            .execute(&mut c)
+            .map_err(|e| Error::from_diesel(e, "".into()))
+        }
+    })
+    .await?
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::storage;
+    use crate::test;
+
+    #[tokio::test]
+    async fn test_integration_delivery() {
+        let _guard = test::prepare().await;
+
+        let app = storage::application::test::create_application(None).await;
+
+        // create
+        let d = create(IntegrationDelivery {
+            application_id: app.id,
+            endpoint: "http://localhost:8090".into(),
+            event_type: "up".into(),
+            payload: vec![1, 2, 3],
+            attempts: 1,
+            last_error: "connection refused".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // get
+        let d_get = get(&d.id).await.unwrap();
+        assert_eq!(d, d_get);
+
+        // pending
+        let pending = get_pending(10, Duration::seconds(60)).await.unwrap();
+        assert_eq!(1, pending.len());
+        assert_eq!(d.id, pending[0].id);
+
+        // locked
+        assert!(get_pending(10, Duration::seconds(60))
+            .await
+            .unwrap()
+            .is_empty());
+
+        // dead-letter
+        let mut d = pending[0].clone();
+        d.attempts = 2;
+        d.dead_letter = true;
+        let d = update(d).await.unwrap();
+
+        let filters = Filters {
+            application_id: Some(app.id),
+            dead_letter: Some(true),
+        };
+        assert_eq!(1, get_count(&filters).await.unwrap());
+        let items = list(10, 0, &filters).await.unwrap();
+        assert_eq!(1, items.len());
+        assert_eq!(d.id, items[0].id);
+
+        // replay
+        assert_eq!(1, replay(&app.id, &[]).await.unwrap());
+        let d = get(&d.id).await.unwrap();
+        assert_eq!(0, d.attempts);
+        assert!(!d.dead_letter);
+        assert_eq!(0, get_count(&filters).await.unwrap());
+        assert_eq!(
+            1,
+            get_pending(10, Duration::seconds(60)).await.unwrap().len()
+        );
+
+        // cleanup
+        let mut d = d;
+        d.dead_letter = true;
+        update(d.clone()).await.unwrap();
+        assert_eq!(
+            0,
+            delete_dead_letters_before(Utc::now() - Duration::seconds(60))
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            1,
+            delete_dead_letters_before(Utc::now() + Duration::seconds(1))
+                .await
+                .unwrap()
+        );
+
+        // delete
+        assert!(delete(&d.id).await.is_err());
+
+        // create many
+        let ds = create_many(vec![
+            IntegrationDelivery {
+                application_id: app.id,
+                integration_kind: None,
+                global_integration: "mqtt".into(),
+                event_type: "up".into(),
+                ..Default::default()
+            },
+            IntegrationDelivery {
+                application_id: app.id,
+                event_type: "up".into(),
+                ..Default::default()
+            },
+        ])
+        .await
+        .unwrap();
+        assert_eq!(2, ds.len());
+        assert_eq!("mqtt", ds[0].integration_name());
+        assert_eq!("Http", ds[1].integration_name());
+        for d in &ds {
+            delete(&d.id).await.unwrap();
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use chrono::Utc;
+use diesel::dsl;
+use diesel::prelude::*;
+use tokio::task;
+use tracing::info;
+
+use lrwn::EUI64;
+
+use super::error::Error;
+use super::get_db_conn;
+use super::schema::js_session_key;
+
+// Max. number of SessionKeyIDs that are kept per device. Older SessionKeyIDs
+// are removed when a new one is stored.
+const MAX_SESSION_KEY_IDS: i64 = 10;
+
+/// Stores the SessionKeyID issued by the Join Server for the given device.
+pub async fn create(dev_eui: &EUI64, session_key_id: &[u8]) -> Result<(), Error> {
+    task::spawn_blocking({
+        let dev_eui = *dev_eui;
+        let session_key_id = session_key_id.to_vec();
+        move || -> Result<(), Error> {
+            let mut c = get_db_conn()?;
+            c.transaction::<(), Error, _>(|c| {
+                diesel::insert_into(js_session_key::table)
+                    .values((
+                        js_session_key::dev_eui.eq(&dev_eui),
+                        js_session_key::session_key_id.eq(&session_key_id),
+                        js_session_key::created_at.eq(Utc::now()),
+                    ))
+                    .execute(c)
+                    .map_err(|e| Error::from_diesel(e, dev_eui.to_string()))?;
+
+                let expired: Vec<Vec<u8>> = js_session_key::dsl::js_session_key
+                    .select(js_session_key::dsl::session_key_id)
+                    .filter(js_session_key::dsl::dev_eui.eq(&dev_eui))
+                    .order_by(js_session_key::dsl::created_at.desc())
+                    .offset(MAX_SESSION_KEY_IDS)
+                    .load(c)?;
+
+                if !expired.is_empty() {
+                    diesel::delete(
+                        js_session_key::dsl::js_session_key
+                            .filter(js_session_key::dsl::dev_eui.eq(&dev_eui))
+                            .filter(js_session_key::dsl::session_key_id.eq_any(expired)),
+                    )
+                    .execute(c)?;
+                }
+
+                Ok(())
+            })
+        }
+    })
+    .await??;
+    info!(dev_eui = %dev_eui, session_key_id = %hex::encode(session_key_id), "SessionKeyID created");
+    Ok(())
+}
+
+/// Returns true when the given SessionKeyID has been issued for the given device.
+pub async fn exists(dev_eui: &EUI64, session_key_id: &[u8]) -> Result<bool, Error> {
+    task::spawn_blocking({
+        let dev_eui = *dev_eui;
+        let session_key_id = session_key_id.to_vec();
+        move || -> Result<bool, Error> {
+            let mut c = get_db_conn()?;
+            let count: i64 = js_session_key::dsl::js_session_key
+                .select(dsl::count_star())
+                .filter(js_session_key::dsl::dev_eui.eq(&dev_eui))
+                .filter(js_session_key::dsl::session_key_id.eq(&session_key_id))
+                .first(&mut c)?;
+            Ok(count != 0)
+        }
+    })
+    .await?
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::storage::{device, device_keys, device_profile};
+    use crate::test;
+
+    #[tokio::test]
+    async fn test_js_session_key() {
+        let _guard = test::prepare().await;
+
+        let dp = device_profile::test::create_device_profile(None).await;
+        let dev = device::test::create_device(
+            EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
+            dp.id,
+            None,
+        )
+        .await;
+        device_keys::create(device_keys::DeviceKeys {
+            dev_eui: dev.dev_eui,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        assert!(!exists(&dev.dev_eui, &[0]).await.unwrap());
+
+        for i in 0..=MAX_SESSION_KEY_IDS as u8 {
+            create(&dev.dev_eui, &[i]).await.unwrap();
+        }
+
+        // The oldest SessionKeyID has been removed.
+        assert!(!exists(&dev.dev_eui, &[0]).await.unwrap());
+        assert!(exists(&dev.dev_eui, &[1]).await.unwrap());
+        assert!(exists(&dev.dev_eui, &[MAX_SESSION_KEY_IDS as u8])
+            .await
+            .unwrap());
+    }
+}
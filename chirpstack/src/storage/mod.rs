@@ -22,7 +22,11 @@ pub mod device_session;
 pub mod downlink_frame;
 pub mod error;
 pub mod fields;
+pub mod fuota;
 pub mod gateway;
+pub mod gateway_load;
+pub mod integration_delivery;
+pub mod js_session_key;
 pub mod mac_command;
 pub mod metrics;
 pub mod multicast;
@@ -29,6 +29,7 @@ diesel::table! {
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
         configuration -> Jsonb,
+        routing -> Jsonb,
     }
 }
 
@@ -57,6 +58,30 @@ diesel::table! {
         tags -> Jsonb,
         variables -> Jsonb,
         join_eui -> Bytea,
+        adr_min_dr -> Nullable<Int2>,
+        adr_max_dr -> Nullable<Int2>,
+        adr_max_tx_power_index -> Nullable<Int2>,
+        adr_nb_trans -> Nullable<Int2>,
+        adr_installation_margin -> Nullable<Float4>,
+        adr_pinned_dr -> Nullable<Int2>,
+        adr_pinned_tx_power_index -> Nullable<Int2>,
+    }
+}
+
+diesel::table! {
+    device_session (dev_eui) {
+        dev_eui -> Bytea,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        dev_addr -> Bytea,
+        pending_rejoin_dev_addr -> Nullable<Bytea>,
+        region_config_id -> Varchar,
+        f_cnt_up -> Int8,
+        n_f_cnt_down -> Int8,
+        a_f_cnt_down -> Int8,
+        dr -> Int2,
+        tx_power_index -> Int2,
+        device_session -> Bytea,
     }
 }
 
@@ -69,6 +94,7 @@ diesel::table! {
         app_key -> Bytea,
         dev_nonces -> Array<Nullable<Int4>>,
         join_nonce -> Int4,
+        gen_app_key -> Bytea,
     }
 }
 
@@ -127,6 +153,7 @@ diesel::table! {
         relay_notify_limit_bucket_size -> Int2,
         relay_global_uplink_limit_bucket_size -> Int2,
         relay_overall_limit_bucket_size -> Int2,
+        clock_sync_enabled -> Bool,
     }
 }
 
@@ -180,6 +207,55 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    fuota_deployment (id) {
+        id -> Uuid,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        started_at -> Nullable<Timestamptz>,
+        completed_at -> Nullable<Timestamptz>,
+        name -> Varchar,
+        application_id -> Uuid,
+        device_profile_id -> Uuid,
+        multicast_group_id -> Nullable<Uuid>,
+        multicast_group_type -> Bpchar,
+        multicast_class_c_scheduling_type -> Varchar,
+        multicast_dr -> Int2,
+        multicast_class_b_ping_slot_period -> Int4,
+        multicast_frequency -> Int8,
+        multicast_timeout -> Int2,
+        multicast_addr -> Bytea,
+        multicast_key -> Bytea,
+        unicast_timeout -> Int4,
+        unicast_max_retry_count -> Int2,
+        fragmentation_fragment_size -> Int2,
+        fragmentation_redundancy_percentage -> Int2,
+        fragmentation_session_index -> Int2,
+        fragmentation_matrix -> Int2,
+        fragmentation_block_ack_delay -> Int2,
+        fragmentation_descriptor -> Bytea,
+        payload -> Bytea,
+        next_step -> Varchar,
+        retry_count -> Int2,
+        scheduler_run_after -> Timestamptz,
+        multicast_session_start -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    fuota_deployment_device (fuota_deployment_id, dev_eui) {
+        fuota_deployment_id -> Uuid,
+        dev_eui -> Bytea,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        mc_group_setup_completed_at -> Nullable<Timestamptz>,
+        mc_session_completed_at -> Nullable<Timestamptz>,
+        frag_session_setup_completed_at -> Nullable<Timestamptz>,
+        frag_status_completed_at -> Nullable<Timestamptz>,
+        error_msg -> Text,
+    }
+}
+
 diesel::table! {
     gateway (gateway_id) {
         gateway_id -> Bytea,
@@ -199,6 +275,33 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    integration_delivery (id) {
+        id -> Uuid,
+        application_id -> Uuid,
+        integration_kind -> Nullable<Varchar>,
+        global_integration -> Varchar,
+        endpoint -> Text,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        event_type -> Varchar,
+        vars -> Jsonb,
+        payload -> Bytea,
+        attempts -> Int2,
+        dead_letter -> Bool,
+        next_attempt_at -> Timestamptz,
+        last_error -> Text,
+    }
+}
+
+diesel::table! {
+    js_session_key (dev_eui, session_key_id) {
+        dev_eui -> Bytea,
+        session_key_id -> Bytea,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     multicast_group (id) {
         id -> Uuid,
@@ -269,6 +372,7 @@ diesel::table! {
         max_gateway_count -> Int4,
         private_gateways_up -> Bool,
         private_gateways_down -> Bool,
+        dev_addr_prefixes -> Array<Nullable<Text>>,
     }
 }
 
@@ -307,7 +411,15 @@ diesel::joinable!(device -> device_profile (device_profile_id));
 diesel::joinable!(device_keys -> device (dev_eui));
 diesel::joinable!(device_profile -> tenant (tenant_id));
 diesel::joinable!(device_queue_item -> device (dev_eui));
+diesel::joinable!(device_session -> device (dev_eui));
+diesel::joinable!(fuota_deployment -> application (application_id));
+diesel::joinable!(fuota_deployment -> device_profile (device_profile_id));
+diesel::joinable!(fuota_deployment -> multicast_group (multicast_group_id));
+diesel::joinable!(fuota_deployment_device -> device (dev_eui));
+diesel::joinable!(fuota_deployment_device -> fuota_deployment (fuota_deployment_id));
 diesel::joinable!(gateway -> tenant (tenant_id));
+diesel::joinable!(integration_delivery -> application (application_id));
+diesel::joinable!(js_session_key -> device_keys (dev_eui));
 diesel::joinable!(multicast_group -> application (application_id));
 diesel::joinable!(multicast_group_device -> device (dev_eui));
 diesel::joinable!(multicast_group_device -> multicast_group (multicast_group_id));
@@ -327,7 +439,12 @@ diesel::allow_tables_to_appear_in_same_query!(
     device_profile,
     device_profile_template,
     device_queue_item,
+    device_session,
+    fuota_deployment,
+    fuota_deployment_device,
     gateway,
+    integration_delivery,
+    js_session_key,
     multicast_group,
     multicast_group_device,
     multicast_group_gateway,
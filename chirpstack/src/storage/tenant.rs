@@ -1,3 +1,5 @@
+use std::str::FromStr;
+
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use diesel::dsl;
@@ -6,6 +8,8 @@ use tokio::task;
 use tracing::info;
 use uuid::Uuid;
 
+use lrwn::DevAddrPrefix;
+
 use super::error::Error;
 use super::get_db_conn;
 use super::schema::{tenant, tenant_user, user};
@@ -23,6 +27,9 @@ pub struct Tenant {
     pub max_gateway_count: i32,
     pub private_gateways_up: bool,
     pub private_gateways_down: bool,
+    // DevAddr prefixes used for devices of this tenant. When empty, the
+    // region or network prefixes are used.
+    pub dev_addr_prefixes: Vec<Option<String>>,
 }
 
 impl Tenant {
@@ -30,8 +37,28 @@ impl Tenant {
         if self.name.is_empty() {
             return Err(Error::Validation("name is not set".into()));
         }
+        for prefix in self.dev_addr_prefixes.iter().flatten() {
+            if DevAddrPrefix::from_str(prefix).is_err() {
+                return Err(Error::Validation(format!(
+                    "invalid DevAddr prefix: {}",
+                    prefix
+                )));
+            }
+        }
         Ok(())
     }
+
+    /// Returns the parsed DevAddr prefixes of the tenant.
+    pub fn get_dev_addr_prefixes(&self) -> Result<Vec<DevAddrPrefix>, Error> {
+        self.dev_addr_prefixes
+            .iter()
+            .flatten()
+            .map(|p| {
+                DevAddrPrefix::from_str(p)
+                    .map_err(|_| Error::Validation(format!("invalid DevAddr prefix: {}", p)))
+            })
+            .collect()
+    }
 }
 
 impl Default for Tenant {
@@ -49,6 +76,7 @@ impl Default for Tenant {
             max_gateway_count: 0,
             private_gateways_up: false,
             private_gateways_down: false,
+            dev_addr_prefixes: Vec::new(),
         }
     }
 }
@@ -145,6 +173,7 @@ pub async fn update(t: Tenant) -> Result<Tenant, Error> {
                     tenant::max_gateway_count.eq(&t.max_gateway_count),
                     tenant::private_gateways_up.eq(&t.private_gateways_up),
                     tenant::private_gateways_down.eq(&t.private_gateways_down),
+                    tenant::dev_addr_prefixes.eq(&t.dev_addr_prefixes),
                 ))
                 .get_result(&mut c)
                 .map_err(|e| Error::from_diesel(e, t.id.to_string()))
@@ -379,6 +408,31 @@ pub async fn get_tenant_users_for_user(user_id: &Uuid) -> Result<Vec<TenantUser>
     .await?
 }
 
+/// Returns the (de-duplicated) DevAddr prefixes of all tenants.
+pub async fn get_all_dev_addr_prefixes() -> Result<Vec<DevAddrPrefix>, Error> {
+    let items: Vec<Vec<Option<String>>> = task::spawn_blocking({
+        move || -> Result<Vec<Vec<Option<String>>>, Error> {
+            let mut c = get_db_conn()?;
+            let items = tenant::dsl::tenant
+                .select(tenant::dsl::dev_addr_prefixes)
+                .load(&mut c)?;
+            Ok(items)
+        }
+    })
+    .await??;
+
+    let mut out: Vec<DevAddrPrefix> = Vec::new();
+    for prefix in items.iter().flatten().flatten() {
+        let prefix = DevAddrPrefix::from_str(prefix)
+            .map_err(|_| Error::Validation(format!("invalid DevAddr prefix: {}", prefix)))?;
+        if !out.contains(&prefix) {
+            out.push(prefix);
+        }
+    }
+
+    Ok(out)
+}
+
 #[cfg(test)]
 pub mod test {
     use super::*;
@@ -408,6 +462,7 @@ pub mod test {
             max_gateway_count: 10,
             private_gateways_up: true,
             private_gateways_down: true,
+            dev_addr_prefixes: vec![Some("01000000/8".into())],
         };
         create(t).await.unwrap()
     }
@@ -432,6 +487,15 @@ pub mod test {
         t = update(t).await.unwrap();
         let t_get = get(&t.id).await.unwrap();
         assert_eq!(t, t_get);
+        assert_eq!(
+            vec![DevAddrPrefix::from_str("01000000/8").unwrap()],
+            t_get.get_dev_addr_prefixes().unwrap()
+        );
+
+        // update with invalid DevAddr prefix
+        let mut t_invalid = t.clone();
+        t_invalid.dev_addr_prefixes = vec![Some("foo".into())];
+        assert!(update(t_invalid).await.is_err());
 
         // add tenant user for filter by user_id test
         let user = create_user().await;
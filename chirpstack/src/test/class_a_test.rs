@@ -520,6 +520,79 @@ async fn test_lorawan_10_errors() {
                 assert::no_uplink_event(),
             ],
         },
+        Test {
+            name: "frame-counter reset accepted (armed window)".into(),
+            device_queue_items: vec![],
+            before_func: Some(Box::new(move || {
+                Box::pin(async move {
+                    device::set_f_cnt_reset_window(
+                        &EUI64::from_be_bytes([2, 2, 3, 4, 5, 6, 7, 8]),
+                        0,
+                        chrono::Duration::seconds(60),
+                    )
+                    .await
+                    .unwrap();
+                })
+            })),
+            after_func: None,
+            device_session: Some(ds.clone()),
+            tx_info: tx_info.clone(),
+            rx_info: rx_info.clone(),
+            phy_payload: lrwn::PhyPayload {
+                mhdr: lrwn::MHDR {
+                    m_type: lrwn::MType::UnconfirmedDataUp,
+                    major: lrwn::Major::LoRaWANR1,
+                },
+                payload: lrwn::Payload::MACPayload(lrwn::MACPayload {
+                    fhdr: lrwn::FHDR {
+                        devaddr: lrwn::DevAddr::from_be_bytes([1, 2, 3, 4]),
+                        f_cnt: 0,
+                        ..Default::default()
+                    },
+                    f_port: Some(1),
+                    frm_payload: None,
+                }),
+                mic: Some([0x83, 0x24, 0x53, 0xa3]),
+            },
+            assert: vec![
+                assert::integration_log(vec!["Frame-counter reset accepted".to_string()]),
+                assert::f_cnt_up(EUI64::from_be_bytes([2, 2, 3, 4, 5, 6, 7, 8]), 1),
+            ],
+        },
+        Test {
+            name: "invalid frame-counter (rollback)".into(),
+            device_queue_items: vec![],
+            before_func: None,
+            after_func: None,
+            device_session: Some(internal::DeviceSession {
+                f_cnt_up: 300,
+                ..ds.clone()
+            }),
+            tx_info: tx_info.clone(),
+            rx_info: rx_info.clone(),
+            phy_payload: lrwn::PhyPayload {
+                mhdr: lrwn::MHDR {
+                    m_type: lrwn::MType::UnconfirmedDataUp,
+                    major: lrwn::Major::LoRaWANR1,
+                },
+                payload: lrwn::Payload::MACPayload(lrwn::MACPayload {
+                    fhdr: lrwn::FHDR {
+                        devaddr: lrwn::DevAddr::from_be_bytes([1, 2, 3, 4]),
+                        f_cnt: 200,
+                        ..Default::default()
+                    },
+                    f_port: Some(1),
+                    frm_payload: None,
+                }),
+                mic: Some([252, 199, 48, 140]),
+            },
+            assert: vec![
+                assert::integration_log(vec![
+                    "Suspicious frame-counter rollback detected".to_string()
+                ]),
+                assert::no_uplink_event(),
+            ],
+        },
         Test {
             name: "invalid mic".into(),
             device_queue_items: vec![],
@@ -3830,6 +3903,127 @@ async fn test_lorawan_10_adr() {
                 ]),
             ],
         },
+        Test {
+            name: "adr triggered with pinned link parameters".into(),
+            device_queue_items: vec![],
+            before_func: Some(Box::new({
+                let dev_eui = dev.dev_eui;
+                move || {
+                    Box::pin(async move {
+                        device::set_link_parameters(&dev_eui, Some(2), Some(1))
+                            .await
+                            .unwrap();
+                    })
+                }
+            })),
+            after_func: Some(Box::new({
+                let dev_eui = dev.dev_eui;
+                move || {
+                    Box::pin(async move {
+                        device::set_link_parameters(&dev_eui, None, None)
+                            .await
+                            .unwrap();
+                    })
+                }
+            })),
+            device_session: Some(ds.clone()),
+            tx_info: tx_info.clone(),
+            rx_info: rx_info.clone(),
+            phy_payload: lrwn::PhyPayload {
+                mhdr: lrwn::MHDR {
+                    m_type: lrwn::MType::UnconfirmedDataUp,
+                    major: lrwn::Major::LoRaWANR1,
+                },
+                payload: lrwn::Payload::MACPayload(lrwn::MACPayload {
+                    fhdr: lrwn::FHDR {
+                        devaddr: lrwn::DevAddr::from_be_bytes([1, 2, 3, 4]),
+                        f_cnt: 10,
+                        f_ctrl: lrwn::FCtrl {
+                            adr: true,
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    f_port: None,
+                    frm_payload: None,
+                }),
+                mic: Some([187, 243, 244, 117]),
+            },
+            assert: vec![
+                assert::f_cnt_up(dev.dev_eui.clone(), 11),
+                assert::n_f_cnt_down(dev.dev_eui.clone(), 5),
+                assert::downlink_phy_payloads_decoded_f_opts(vec![
+                    lrwn::PhyPayload {
+                        mhdr: lrwn::MHDR {
+                            m_type: lrwn::MType::UnconfirmedDataDown,
+                            major: lrwn::Major::LoRaWANR1,
+                        },
+                        payload: lrwn::Payload::MACPayload(lrwn::MACPayload {
+                            fhdr: lrwn::FHDR {
+                                devaddr: lrwn::DevAddr::from_be_bytes([1, 2, 3, 4]),
+                                f_cnt: 5,
+                                f_ctrl: lrwn::FCtrl {
+                                    adr: true,
+                                    f_opts_len: 5,
+                                    ..Default::default()
+                                },
+                                f_opts: lrwn::MACCommandSet::new(vec![
+                                    lrwn::MACCommand::LinkADRReq(lrwn::LinkADRReqPayload {
+                                        dr: 2,
+                                        tx_power: 1,
+                                        ch_mask: lrwn::ChMask::new([
+                                            true, true, true, false, false, false, false, false,
+                                            false, false, false, false, false, false, false, false,
+                                        ]),
+                                        redundancy: lrwn::Redundancy {
+                                            ch_mask_cntl: 0,
+                                            nb_rep: 1,
+                                        },
+                                    }),
+                                ]),
+                            },
+                            f_port: None,
+                            frm_payload: None,
+                        }),
+                        mic: Some([66, 17, 237, 226]),
+                    },
+                    lrwn::PhyPayload {
+                        mhdr: lrwn::MHDR {
+                            m_type: lrwn::MType::UnconfirmedDataDown,
+                            major: lrwn::Major::LoRaWANR1,
+                        },
+                        payload: lrwn::Payload::MACPayload(lrwn::MACPayload {
+                            fhdr: lrwn::FHDR {
+                                devaddr: lrwn::DevAddr::from_be_bytes([1, 2, 3, 4]),
+                                f_cnt: 5,
+                                f_ctrl: lrwn::FCtrl {
+                                    adr: true,
+                                    f_opts_len: 5,
+                                    ..Default::default()
+                                },
+                                f_opts: lrwn::MACCommandSet::new(vec![
+                                    lrwn::MACCommand::LinkADRReq(lrwn::LinkADRReqPayload {
+                                        dr: 2,
+                                        tx_power: 1,
+                                        ch_mask: lrwn::ChMask::new([
+                                            true, true, true, false, false, false, false, false,
+                                            false, false, false, false, false, false, false, false,
+                                        ]),
+                                        redundancy: lrwn::Redundancy {
+                                            ch_mask_cntl: 0,
+                                            nb_rep: 1,
+                                        },
+                                    }),
+                                ]),
+                            },
+                            f_port: None,
+                            frm_payload: None,
+                        }),
+                        mic: Some([66, 17, 237, 226]),
+                    },
+                ]),
+            ],
+        },
         Test {
             name: "device has adr disabled".into(),
             device_queue_items: vec![],
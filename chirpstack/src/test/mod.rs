@@ -70,6 +70,7 @@ pub async fn prepare<'a>() -> std::sync::MutexGuard<'a, ()> {
                     topic_prefix: "eu868".into(),
                     ..Default::default()
                 },
+                ..Default::default()
             },
         },
     }];
@@ -3,6 +3,9 @@ use std::str::FromStr;
 
 use anyhow::{Context, Result};
 use chrono::{DateTime, Duration, Local, Utc};
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
 use tracing::{debug, error, info, span, trace, warn, Instrument, Level};
 
 use super::error::Error;
@@ -12,16 +15,36 @@ use super::{
 };
 use crate::api::helpers::ToProto;
 use crate::backend::roaming;
+use crate::monitoring::prometheus;
 use crate::storage::error::Error as StorageError;
 use crate::storage::{
     application,
     device::{self, DeviceClass},
     device_gateway, device_profile, device_queue, device_session, fields, metrics, tenant,
 };
-use crate::{codec, config, downlink, framelog, integration, maccommand, metalog, region};
+use crate::{
+    applayer, codec, config, downlink, framelog, integration, maccommand, metalog, region,
+};
 use chirpstack_api::{api, integration as integration_pb, internal, meta};
 use lrwn::{AES128Key, EUI64};
 
+lazy_static! {
+    static ref F_CNT_RESET_COUNTER: Family<FCntResetLabels, Counter> = {
+        let counter = Family::<FCntResetLabels, Counter>::default();
+        prometheus::register(
+            "uplink_f_cnt_reset",
+            "Number of uplink frame-counter resets by status (accepted, rejected or rollback)",
+            counter.clone(),
+        );
+        counter
+    };
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+struct FCntResetLabels {
+    status: String,
+}
+
 pub struct Data {
     uplink_frame_set: UplinkFrameSet,
     relay_context: Option<RelayContext>,
@@ -140,6 +163,7 @@ impl Data {
         }
         ctx.append_meta_data_to_uplink_history()?;
         ctx.send_uplink_event().await?;
+        ctx.handle_applayer_uplink().await?;
         ctx.detect_and_save_measurements().await?;
         ctx.sync_uplink_f_cnt()?;
         ctx.set_region_config_id()?;
@@ -200,6 +224,7 @@ impl Data {
         ctx.handle_mac_commands().await?;
         ctx.append_meta_data_to_uplink_history_relayed()?;
         ctx.send_uplink_event().await?;
+        ctx.handle_applayer_uplink().await?;
         ctx.detect_and_save_measurements().await?;
         ctx.sync_uplink_f_cnt()?;
         ctx.set_region_config_id()?;
@@ -487,7 +512,7 @@ impl Data {
         Ok(())
     }
 
-    async fn handle_retransmission_reset(&self) -> Result<(), Error> {
+    async fn handle_retransmission_reset(&mut self) -> Result<(), Error> {
         trace!("Handle retransmission and reset");
         let dev = self.device.as_ref().unwrap();
 
@@ -519,19 +544,91 @@ impl Data {
         }
 
         if self.reset {
-            let pl = integration_pb::LogEvent {
-                time: Some(ts.into()),
-                device_info: self.device_info.clone(),
-                level: integration_pb::LogLevel::Warning.into(),
-                code: integration_pb::LogCode::UplinkFCntReset.into(),
-                description: "Frame-counter reset or rollover detected".into(),
-                context: [(
+            let conf = config::get();
+            let ds = self.device_session.as_ref().unwrap();
+            let context: HashMap<String, String> = [
+                (
                     "deduplication_id".to_string(),
                     self.uplink_frame_set.uplink_set_id.to_string(),
-                )]
-                .iter()
-                .cloned()
-                .collect(),
+                ),
+                ("f_cnt".to_string(), self.f_cnt_up_full.to_string()),
+                ("expected_f_cnt".to_string(), ds.f_cnt_up.to_string()),
+            ]
+            .iter()
+            .cloned()
+            .collect();
+
+            // The frame-counter reset is accepted once when an operator has armed the
+            // frame-counter reset window (e.g. on a battery swap of an ABP device).
+            if let Some(max_f_cnt) = device::get_f_cnt_reset_window(&dev.dev_eui).await? {
+                let max_f_cnt = if max_f_cnt == 0 {
+                    conf.network.f_cnt_rollback_threshold
+                } else {
+                    max_f_cnt
+                };
+
+                if self.f_cnt_up_full <= max_f_cnt
+                    && device::delete_f_cnt_reset_window(&dev.dev_eui).await?
+                {
+                    info!(dev_eui = %dev.dev_eui, f_cnt = self.f_cnt_up_full, expected_f_cnt = ds.f_cnt_up, "Frame-counter reset accepted");
+                    F_CNT_RESET_COUNTER
+                        .get_or_create(&FCntResetLabels {
+                            status: "accepted".into(),
+                        })
+                        .inc();
+
+                    let pl = integration_pb::LogEvent {
+                        time: Some(ts.into()),
+                        device_info: self.device_info.clone(),
+                        level: integration_pb::LogLevel::Info.into(),
+                        code: integration_pb::LogCode::UplinkFCntResetAccepted.into(),
+                        description: "Frame-counter reset accepted".into(),
+                        context,
+                    };
+                    integration::log_event(app.id, &dev.variables, &pl).await;
+
+                    // The uplink history contains the frame-counters before the reset,
+                    // these must not be used for the ADR packet-loss calculation.
+                    let ds = self.device_session.as_mut().unwrap();
+                    ds.uplink_adr_history.clear();
+
+                    return Ok(());
+                }
+            }
+
+            // A device reset (e.g. power cycle) results in a frame-counter close to zero.
+            // A higher frame-counter is suspicious, e.g. a replay of an old uplink.
+            let pl = if self.f_cnt_up_full > conf.network.f_cnt_rollback_threshold {
+                warn!(dev_eui = %dev.dev_eui, f_cnt = self.f_cnt_up_full, expected_f_cnt = ds.f_cnt_up, "Suspicious frame-counter rollback detected");
+                F_CNT_RESET_COUNTER
+                    .get_or_create(&FCntResetLabels {
+                        status: "rollback".into(),
+                    })
+                    .inc();
+
+                integration_pb::LogEvent {
+                    time: Some(ts.into()),
+                    device_info: self.device_info.clone(),
+                    level: integration_pb::LogLevel::Error.into(),
+                    code: integration_pb::LogCode::UplinkFCntRollback.into(),
+                    description: "Suspicious frame-counter rollback detected".into(),
+                    context,
+                }
+            } else {
+                F_CNT_RESET_COUNTER
+                    .get_or_create(&FCntResetLabels {
+                        status: "rejected".into(),
+                    })
+                    .inc();
+
+                integration_pb::LogEvent {
+                    time: Some(ts.into()),
+                    device_info: self.device_info.clone(),
+                    level: integration_pb::LogLevel::Warning.into(),
+                    code: integration_pb::LogCode::UplinkFCntReset.into(),
+                    description: "Frame-counter reset or rollover detected".into(),
+                    context,
+                }
             };
             integration::log_event(app.id, &dev.variables, &pl).await;
         }
@@ -1001,6 +1098,24 @@ impl Data {
         Ok(())
     }
 
+    async fn handle_applayer_uplink(&self) -> Result<()> {
+        trace!("Handling application-layer uplink");
+
+        let dev = self.device.as_ref().unwrap();
+        let dp = self.device_profile.as_ref().unwrap();
+        let mac = if let lrwn::Payload::MACPayload(pl) = &self.phy_payload.payload {
+            pl
+        } else {
+            return Err(anyhow!("Expected MacPayload"));
+        };
+
+        if let (Some(f_port), Some(lrwn::FRMPayload::Raw(b))) = (mac.f_port, &mac.frm_payload) {
+            applayer::handle_uplink(dev, dp, &self.uplink_frame_set.rx_info_set, f_port, b).await;
+        }
+
+        Ok(())
+    }
+
     async fn detect_and_save_measurements(&mut self) -> Result<()> {
         trace!("Detecing and saving measurements");
 
@@ -27,9 +27,7 @@ use crate::storage::{
     error::Error as StorageError,
     metrics, tenant,
 };
-use crate::{
-    config, devaddr::get_random_dev_addr, downlink, framelog, integration, metalog, region,
-};
+use crate::{config, devaddr, downlink, framelog, integration, metalog, region};
 use chirpstack_api::{api, common, integration as integration_pb, internal, meta};
 
 pub struct JoinRequest {
@@ -123,7 +121,7 @@ impl JoinRequest {
         ctx.abort_on_relay_only_comm()?;
         ctx.log_uplink_frame_set().await?;
         ctx.abort_on_otaa_is_disabled()?;
-        ctx.get_random_dev_addr()?;
+        ctx.allocate_dev_addr().await?;
         if ctx.js_client.is_some() {
             // Using join-server
             ctx.get_join_accept_from_js().await?;
@@ -179,7 +177,7 @@ impl JoinRequest {
         ctx.abort_on_device_is_disabled()?;
         ctx.abort_on_otaa_is_disabled()?;
         ctx.abort_on_relay_only_comm()?;
-        ctx.get_random_dev_addr()?;
+        ctx.allocate_dev_addr().await?;
         if ctx.js_client.is_some() {
             // Using join-server
             ctx.get_join_accept_from_js().await?;
@@ -508,8 +506,17 @@ impl JoinRequest {
         Err(anyhow!("Invalid MIC"))
     }
 
-    fn get_random_dev_addr(&mut self) -> Result<()> {
-        self.dev_addr = Some(get_random_dev_addr());
+    async fn allocate_dev_addr(&mut self) -> Result<()> {
+        trace!("Allocating DevAddr");
+        let dev = self.device.as_ref().unwrap();
+        self.dev_addr = Some(
+            devaddr::allocate_dev_addr(
+                self.tenant.as_ref(),
+                Some(&self.uplink_frame_set.region_config_id),
+                dev.dev_eui,
+            )
+            .await?,
+        );
         Ok(())
     }
 
@@ -14,7 +14,7 @@ use crate::storage::{
     error::Error as StorageError,
     metrics, tenant,
 };
-use crate::{config, devaddr::get_random_dev_addr, integration, metalog, region};
+use crate::{config, devaddr, integration, metalog, region};
 use backend::{PRStartAnsPayload, PRStartReqPayload};
 use chirpstack_api::{common, integration as integration_pb, internal, meta};
 use lrwn::{keys, AES128Key, DevAddr, NetID};
@@ -87,7 +87,7 @@ impl JoinRequest {
         ctx.set_device_info()?;
         ctx.abort_on_device_is_disabled()?;
         ctx.abort_on_otaa_is_disabled()?;
-        ctx.get_random_dev_addr()?;
+        ctx.allocate_dev_addr().await?;
         if ctx.js_client.is_some() {
             // Using join-server
             ctx.get_join_accept_from_js().await?;
@@ -206,8 +206,17 @@ impl JoinRequest {
         Ok(())
     }
 
-    fn get_random_dev_addr(&mut self) -> Result<()> {
-        self.dev_addr = Some(get_random_dev_addr());
+    async fn allocate_dev_addr(&mut self) -> Result<()> {
+        trace!("Allocating DevAddr");
+        let dev = self.device.as_ref().unwrap();
+        self.dev_addr = Some(
+            devaddr::allocate_dev_addr(
+                self.tenant.as_ref(),
+                Some(&self.uplink_frame_set.region_config_id),
+                dev.dev_eui,
+            )
+            .await?,
+        );
         Ok(())
     }
 
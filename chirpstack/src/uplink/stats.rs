@@ -2,17 +2,21 @@ use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::str::FromStr;
+use std::time::Duration;
 
 use anyhow::{Context, Result};
 use chrono::{DateTime, Local};
 use tracing::{error, info, span, trace, Instrument, Level};
 
 use crate::gateway::backend as gateway_backend;
-use crate::storage::{error::Error, gateway, metrics};
+use crate::storage::{error::Error, gateway, gateway_load, metrics};
 use crate::{config, region};
 use chirpstack_api::{common, gw};
 use lrwn::EUI64;
 
+// PHYPayload size (bytes) used to approximate the airtime of received uplinks.
+const LOAD_PHY_PAYLOAD_SIZE: usize = 20;
+
 pub struct Stats {
     gateway_id: EUI64,
     stats: gw::GatewayStats,
@@ -62,6 +66,7 @@ impl Stats {
 
         ctx.update_gateway_state().await?;
         ctx.save_stats().await?;
+        ctx.save_gateway_load().await?;
         ctx.update_gateway_configuration().await?;
 
         Ok(())
@@ -158,6 +163,36 @@ impl Stats {
         Ok(())
     }
 
+    async fn save_gateway_load(&self) -> Result<()> {
+        trace!("Saving gateway load");
+
+        let gw = self.gateway.as_ref().unwrap();
+        if gw.stats_interval_secs <= 0 {
+            return Ok(());
+        }
+
+        let region_config_id = self
+            .stats
+            .metadata
+            .get("region_config_id")
+            .ok_or_else(|| anyhow!("No region_config_id in meta-data"))?;
+
+        let utilization = uplink_utilization(
+            region_config_id,
+            &self.stats.rx_packets_per_modulation,
+            Duration::from_secs(gw.stats_interval_secs as u64),
+        )
+        .context("Calculate uplink utilization")?;
+
+        // The load expires when the gateway stops sending stats.
+        gateway_load::save(
+            &gw.gateway_id,
+            utilization,
+            Duration::from_secs(gw.stats_interval_secs as u64 * 2),
+        )
+        .await
+    }
+
     async fn update_gateway_configuration(&self) -> Result<()> {
         trace!("Updating gateway configuration");
 
@@ -250,6 +285,67 @@ impl Stats {
     }
 }
 
+// Returns the (approximated) utilization of the uplink channels, based on the
+// airtime of the received packets. As the stats do not contain the payload sizes,
+// a reference payload size is used.
+fn uplink_utilization(
+    region_config_id: &str,
+    items: &[gw::PerModulationCount],
+    interval: Duration,
+) -> Result<f32> {
+    let region_conf = region::get(region_config_id)?;
+    let channel_count = region_conf.get_enabled_uplink_channel_indices().len();
+    if channel_count == 0 || interval.is_zero() {
+        return Ok(0.0);
+    }
+
+    let mut airtime = Duration::ZERO;
+    for item in items {
+        let dr_modulation = match to_dr_modulation(item) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+
+        if let Some(v) = dr_modulation.time_on_air(LOAD_PHY_PAYLOAD_SIZE) {
+            airtime += v * item.count;
+        }
+    }
+
+    Ok((airtime.as_secs_f32() / (interval.as_secs_f32() * channel_count as f32)).min(1.0))
+}
+
+fn to_dr_modulation(item: &gw::PerModulationCount) -> Result<lrwn::region::DataRateModulation> {
+    let modu = item
+        .modulation
+        .as_ref()
+        .ok_or_else(|| anyhow!("modulation is None"))?;
+    let params = modu
+        .parameters
+        .as_ref()
+        .ok_or_else(|| anyhow!("parameters is None"))?;
+
+    Ok(match params {
+        gw::modulation::Parameters::Lora(v) => {
+            lrwn::region::DataRateModulation::Lora(lrwn::region::LoraDataRate {
+                spreading_factor: v.spreading_factor as u8,
+                bandwidth: v.bandwidth,
+                coding_rate: v.code_rate().into(),
+            })
+        }
+        gw::modulation::Parameters::Fsk(v) => {
+            lrwn::region::DataRateModulation::Fsk(lrwn::region::FskDataRate {
+                bitrate: v.datarate,
+            })
+        }
+        gw::modulation::Parameters::LrFhss(v) => {
+            lrwn::region::DataRateModulation::LrFhss(lrwn::region::LrFhssDataRate {
+                coding_rate: v.code_rate().into(),
+                occupied_channel_width: v.operating_channel_width,
+            })
+        }
+    })
+}
+
 fn per_modultation_to_per_dr(
     region_config_id: &str,
     uplink: bool,
@@ -259,35 +355,7 @@ fn per_modultation_to_per_dr(
     let region_conf = region::get(region_config_id)?;
 
     for item in items {
-        let modu = item
-            .modulation
-            .as_ref()
-            .ok_or_else(|| anyhow!("modulation is None"))?;
-        let params = modu
-            .parameters
-            .as_ref()
-            .ok_or_else(|| anyhow!("parameters is None"))?;
-
-        let dr_modulation = match params {
-            gw::modulation::Parameters::Lora(v) => {
-                lrwn::region::DataRateModulation::Lora(lrwn::region::LoraDataRate {
-                    spreading_factor: v.spreading_factor as u8,
-                    bandwidth: v.bandwidth,
-                    coding_rate: v.code_rate().into(),
-                })
-            }
-            gw::modulation::Parameters::Fsk(v) => {
-                lrwn::region::DataRateModulation::Fsk(lrwn::region::FskDataRate {
-                    bitrate: v.datarate,
-                })
-            }
-            gw::modulation::Parameters::LrFhss(v) => {
-                lrwn::region::DataRateModulation::LrFhss(lrwn::region::LrFhssDataRate {
-                    coding_rate: v.code_rate().into(),
-                    occupied_channel_width: v.operating_channel_width,
-                })
-            }
-        };
+        let dr_modulation = to_dr_modulation(item)?;
 
         if let Ok(v) = region_conf.get_data_rate_index(uplink, &dr_modulation) {
             let count = out.entry(v).or_insert(0);
@@ -34,6 +34,14 @@ export function id() {
 //      "txPowerIndex": 0,
 //      "gatewayCount": 3
 //    }
+//  ],
+//  gateways: [
+//    {
+//      "gatewayId": "0102030405060708",
+//      "rssi": -110,
+//      "snr": 7.5,
+//      "utilization": 0.05
+//    }
 //  ]
 // }
 //
@@ -0,0 +1,347 @@
+//! Application Layer Clock Synchronization (LoRaWAN TS003-1.0.0).
+use anyhow::Result;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Cid {
+    PackageVersionReq,
+    PackageVersionAns,
+    AppTimeReq,
+    AppTimeAns,
+    DeviceAppTimePeriodicityReq,
+    DeviceAppTimePeriodicityAns,
+    ForceDeviceResyncReq,
+}
+
+impl Cid {
+    pub fn to_u8(&self) -> u8 {
+        match self {
+            Cid::PackageVersionReq | Cid::PackageVersionAns => 0x00,
+            Cid::AppTimeReq | Cid::AppTimeAns => 0x01,
+            Cid::DeviceAppTimePeriodicityReq | Cid::DeviceAppTimePeriodicityAns => 0x02,
+            Cid::ForceDeviceResyncReq => 0x03,
+        }
+    }
+
+    pub fn from_u8(uplink: bool, v: u8) -> Result<Self> {
+        Ok(match uplink {
+            true => match v {
+                0x00 => Cid::PackageVersionAns,
+                0x01 => Cid::AppTimeReq,
+                0x02 => Cid::DeviceAppTimePeriodicityAns,
+                _ => return Err(anyhow!("Invalid CID: {}", v)),
+            },
+            false => match v {
+                0x00 => Cid::PackageVersionReq,
+                0x01 => Cid::AppTimeAns,
+                0x02 => Cid::DeviceAppTimePeriodicityReq,
+                0x03 => Cid::ForceDeviceResyncReq,
+                _ => return Err(anyhow!("Invalid CID: {}", v)),
+            },
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Payload {
+    PackageVersionReq,
+    PackageVersionAns(PackageVersionAnsPayload),
+    AppTimeReq(AppTimeReqPayload),
+    AppTimeAns(AppTimeAnsPayload),
+    DeviceAppTimePeriodicityReq(DeviceAppTimePeriodicityReqPayload),
+    DeviceAppTimePeriodicityAns(DeviceAppTimePeriodicityAnsPayload),
+    ForceDeviceResyncReq(ForceDeviceResyncReqPayload),
+}
+
+impl Payload {
+    pub fn cid(&self) -> Cid {
+        match self {
+            Payload::PackageVersionReq => Cid::PackageVersionReq,
+            Payload::PackageVersionAns(_) => Cid::PackageVersionAns,
+            Payload::AppTimeReq(_) => Cid::AppTimeReq,
+            Payload::AppTimeAns(_) => Cid::AppTimeAns,
+            Payload::DeviceAppTimePeriodicityReq(_) => Cid::DeviceAppTimePeriodicityReq,
+            Payload::DeviceAppTimePeriodicityAns(_) => Cid::DeviceAppTimePeriodicityAns,
+            Payload::ForceDeviceResyncReq(_) => Cid::ForceDeviceResyncReq,
+        }
+    }
+
+    pub fn from_slice(uplink: bool, b: &[u8]) -> Result<Self> {
+        if b.is_empty() {
+            return Err(anyhow!("At least one byte is expected"));
+        }
+
+        let cid = Cid::from_u8(uplink, b[0])?;
+        let b = &b[1..];
+
+        Ok(match cid {
+            Cid::PackageVersionReq => Payload::PackageVersionReq,
+            Cid::PackageVersionAns => {
+                Payload::PackageVersionAns(PackageVersionAnsPayload::decode(b)?)
+            }
+            Cid::AppTimeReq => Payload::AppTimeReq(AppTimeReqPayload::decode(b)?),
+            Cid::AppTimeAns => Payload::AppTimeAns(AppTimeAnsPayload::decode(b)?),
+            Cid::DeviceAppTimePeriodicityReq => {
+                Payload::DeviceAppTimePeriodicityReq(DeviceAppTimePeriodicityReqPayload::decode(b)?)
+            }
+            Cid::DeviceAppTimePeriodicityAns => {
+                Payload::DeviceAppTimePeriodicityAns(DeviceAppTimePeriodicityAnsPayload::decode(b)?)
+            }
+            Cid::ForceDeviceResyncReq => {
+                Payload::ForceDeviceResyncReq(ForceDeviceResyncReqPayload::decode(b)?)
+            }
+        })
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        let mut out = vec![self.cid().to_u8()];
+
+        match self {
+            Payload::PackageVersionReq => {}
+            Payload::PackageVersionAns(pl) => out.extend_from_slice(&pl.encode()?),
+            Payload::AppTimeReq(pl) => out.extend_from_slice(&pl.encode()?),
+            Payload::AppTimeAns(pl) => out.extend_from_slice(&pl.encode()?),
+            Payload::DeviceAppTimePeriodicityReq(pl) => out.extend_from_slice(&pl.encode()?),
+            Payload::DeviceAppTimePeriodicityAns(pl) => out.extend_from_slice(&pl.encode()?),
+            Payload::ForceDeviceResyncReq(pl) => out.extend_from_slice(&pl.encode()?),
+        }
+
+        Ok(out)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PackageVersionAnsPayload {
+    pub package_identifier: u8,
+    pub package_version: u8,
+}
+
+impl PackageVersionAnsPayload {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() != 2 {
+            return Err(anyhow!("PackageVersionAns expects 2 bytes"));
+        }
+
+        Ok(PackageVersionAnsPayload {
+            package_identifier: b[0],
+            package_version: b[1],
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        Ok(vec![self.package_identifier, self.package_version])
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AppTimeReqPayload {
+    /// Device time in seconds since GPS epoch (modulo 2^32).
+    pub device_time: u32,
+    pub token_req: u8,
+    pub ans_required: bool,
+}
+
+impl AppTimeReqPayload {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() != 5 {
+            return Err(anyhow!("AppTimeReq expects 5 bytes"));
+        }
+
+        let mut device_time = [0; 4];
+        device_time.copy_from_slice(&b[0..4]);
+
+        Ok(AppTimeReqPayload {
+            device_time: u32::from_le_bytes(device_time),
+            token_req: b[4] & 0x0f,
+            ans_required: b[4] & 0x10 != 0,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        if self.token_req > 15 {
+            return Err(anyhow!("max token_req value is 15"));
+        }
+
+        let mut b = self.device_time.to_le_bytes().to_vec();
+        let mut param = self.token_req;
+        if self.ans_required {
+            param |= 0x10;
+        }
+        b.push(param);
+        Ok(b)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AppTimeAnsPayload {
+    /// Correction (in seconds) to apply to the device clock.
+    pub time_correction: i32,
+    pub token_ans: u8,
+}
+
+impl AppTimeAnsPayload {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() != 5 {
+            return Err(anyhow!("AppTimeAns expects 5 bytes"));
+        }
+
+        let mut time_correction = [0; 4];
+        time_correction.copy_from_slice(&b[0..4]);
+
+        Ok(AppTimeAnsPayload {
+            time_correction: i32::from_le_bytes(time_correction),
+            token_ans: b[4] & 0x0f,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        if self.token_ans > 15 {
+            return Err(anyhow!("max token_ans value is 15"));
+        }
+
+        let mut b = self.time_correction.to_le_bytes().to_vec();
+        b.push(self.token_ans);
+        Ok(b)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DeviceAppTimePeriodicityReqPayload {
+    /// The device should send an AppTimeReq every 128 * 2^periodicity seconds.
+    pub periodicity: u8,
+}
+
+impl DeviceAppTimePeriodicityReqPayload {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() != 1 {
+            return Err(anyhow!("DeviceAppTimePeriodicityReq expects 1 byte"));
+        }
+
+        Ok(DeviceAppTimePeriodicityReqPayload {
+            periodicity: b[0] & 0x0f,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        if self.periodicity > 15 {
+            return Err(anyhow!("max periodicity value is 15"));
+        }
+
+        Ok(vec![self.periodicity])
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DeviceAppTimePeriodicityAnsPayload {
+    pub not_supported: bool,
+    /// Device time in seconds since GPS epoch (modulo 2^32).
+    pub time: u32,
+}
+
+impl DeviceAppTimePeriodicityAnsPayload {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() != 5 {
+            return Err(anyhow!("DeviceAppTimePeriodicityAns expects 5 bytes"));
+        }
+
+        let mut time = [0; 4];
+        time.copy_from_slice(&b[1..5]);
+
+        Ok(DeviceAppTimePeriodicityAnsPayload {
+            not_supported: b[0] & 0x01 != 0,
+            time: u32::from_le_bytes(time),
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        let mut b = vec![0];
+        if self.not_supported {
+            b[0] |= 0x01;
+        }
+        b.extend_from_slice(&self.time.to_le_bytes());
+        Ok(b)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ForceDeviceResyncReqPayload {
+    pub nb_transmissions: u8,
+}
+
+impl ForceDeviceResyncReqPayload {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() != 1 {
+            return Err(anyhow!("ForceDeviceResyncReq expects 1 byte"));
+        }
+
+        Ok(ForceDeviceResyncReqPayload {
+            nb_transmissions: b[0] & 0x07,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        if self.nb_transmissions > 7 {
+            return Err(anyhow!("max nb_transmissions value is 7"));
+        }
+
+        Ok(vec![self.nb_transmissions])
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn test_app_time_req() {
+        let pl = Payload::AppTimeReq(AppTimeReqPayload {
+            device_time: 1234,
+            token_req: 3,
+            ans_required: true,
+        });
+        let b = vec![0x01, 0xd2, 0x04, 0x00, 0x00, 0x13];
+
+        assert_eq!(b, pl.to_vec().unwrap());
+        assert_eq!(pl, Payload::from_slice(true, &b).unwrap());
+    }
+
+    #[test]
+    fn test_app_time_ans() {
+        let pl = Payload::AppTimeAns(AppTimeAnsPayload {
+            time_correction: -2,
+            token_ans: 3,
+        });
+        let b = vec![0x01, 0xfe, 0xff, 0xff, 0xff, 0x03];
+
+        assert_eq!(b, pl.to_vec().unwrap());
+        assert_eq!(pl, Payload::from_slice(false, &b).unwrap());
+    }
+
+    #[test]
+    fn test_device_app_time_periodicity() {
+        let pl = Payload::DeviceAppTimePeriodicityReq(DeviceAppTimePeriodicityReqPayload {
+            periodicity: 5,
+        });
+        let b = vec![0x02, 0x05];
+        assert_eq!(b, pl.to_vec().unwrap());
+        assert_eq!(pl, Payload::from_slice(false, &b).unwrap());
+
+        let pl = Payload::DeviceAppTimePeriodicityAns(DeviceAppTimePeriodicityAnsPayload {
+            not_supported: true,
+            time: 1024,
+        });
+        let b = vec![0x02, 0x01, 0x00, 0x04, 0x00, 0x00];
+        assert_eq!(b, pl.to_vec().unwrap());
+        assert_eq!(pl, Payload::from_slice(true, &b).unwrap());
+    }
+
+    #[test]
+    fn test_force_device_resync_req() {
+        let pl = Payload::ForceDeviceResyncReq(ForceDeviceResyncReqPayload {
+            nb_transmissions: 2,
+        });
+        let b = vec![0x03, 0x02];
+
+        assert_eq!(b, pl.to_vec().unwrap());
+        assert_eq!(pl, Payload::from_slice(false, &b).unwrap());
+    }
+}
@@ -0,0 +1,562 @@
+//! Fragmented Data Block Transport (LoRaWAN TS004-1.0.0).
+use anyhow::Result;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Cid {
+    PackageVersionReq,
+    PackageVersionAns,
+    FragSessionStatusReq,
+    FragSessionStatusAns,
+    FragSessionSetupReq,
+    FragSessionSetupAns,
+    FragSessionDeleteReq,
+    FragSessionDeleteAns,
+    DataFragment,
+}
+
+impl Cid {
+    pub fn to_u8(&self) -> u8 {
+        match self {
+            Cid::PackageVersionReq | Cid::PackageVersionAns => 0x00,
+            Cid::FragSessionStatusReq | Cid::FragSessionStatusAns => 0x01,
+            Cid::FragSessionSetupReq | Cid::FragSessionSetupAns => 0x02,
+            Cid::FragSessionDeleteReq | Cid::FragSessionDeleteAns => 0x03,
+            Cid::DataFragment => 0x08,
+        }
+    }
+
+    pub fn from_u8(uplink: bool, v: u8) -> Result<Self> {
+        Ok(match uplink {
+            true => match v {
+                0x00 => Cid::PackageVersionAns,
+                0x01 => Cid::FragSessionStatusAns,
+                0x02 => Cid::FragSessionSetupAns,
+                0x03 => Cid::FragSessionDeleteAns,
+                _ => return Err(anyhow!("Invalid CID: {}", v)),
+            },
+            false => match v {
+                0x00 => Cid::PackageVersionReq,
+                0x01 => Cid::FragSessionStatusReq,
+                0x02 => Cid::FragSessionSetupReq,
+                0x03 => Cid::FragSessionDeleteReq,
+                0x08 => Cid::DataFragment,
+                _ => return Err(anyhow!("Invalid CID: {}", v)),
+            },
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Payload {
+    FragSessionStatusReq(FragSessionStatusReqPayload),
+    FragSessionStatusAns(FragSessionStatusAnsPayload),
+    FragSessionSetupReq(FragSessionSetupReqPayload),
+    FragSessionSetupAns(FragSessionSetupAnsPayload),
+    FragSessionDeleteReq(FragSessionDeleteReqPayload),
+    FragSessionDeleteAns(FragSessionDeleteAnsPayload),
+    DataFragment(DataFragmentPayload),
+}
+
+impl Payload {
+    pub fn cid(&self) -> Cid {
+        match self {
+            Payload::FragSessionStatusReq(_) => Cid::FragSessionStatusReq,
+            Payload::FragSessionStatusAns(_) => Cid::FragSessionStatusAns,
+            Payload::FragSessionSetupReq(_) => Cid::FragSessionSetupReq,
+            Payload::FragSessionSetupAns(_) => Cid::FragSessionSetupAns,
+            Payload::FragSessionDeleteReq(_) => Cid::FragSessionDeleteReq,
+            Payload::FragSessionDeleteAns(_) => Cid::FragSessionDeleteAns,
+            Payload::DataFragment(_) => Cid::DataFragment,
+        }
+    }
+
+    pub fn from_slice(uplink: bool, b: &[u8]) -> Result<Self> {
+        if b.is_empty() {
+            return Err(anyhow!("At least one byte is expected"));
+        }
+
+        let cid = Cid::from_u8(uplink, b[0])?;
+        let b = &b[1..];
+
+        Ok(match cid {
+            Cid::FragSessionStatusReq => {
+                Payload::FragSessionStatusReq(FragSessionStatusReqPayload::decode(b)?)
+            }
+            Cid::FragSessionStatusAns => {
+                Payload::FragSessionStatusAns(FragSessionStatusAnsPayload::decode(b)?)
+            }
+            Cid::FragSessionSetupReq => {
+                Payload::FragSessionSetupReq(FragSessionSetupReqPayload::decode(b)?)
+            }
+            Cid::FragSessionSetupAns => {
+                Payload::FragSessionSetupAns(FragSessionSetupAnsPayload::decode(b)?)
+            }
+            Cid::FragSessionDeleteReq => {
+                Payload::FragSessionDeleteReq(FragSessionDeleteReqPayload::decode(b)?)
+            }
+            Cid::FragSessionDeleteAns => {
+                Payload::FragSessionDeleteAns(FragSessionDeleteAnsPayload::decode(b)?)
+            }
+            Cid::DataFragment => Payload::DataFragment(DataFragmentPayload::decode(b)?),
+            Cid::PackageVersionReq | Cid::PackageVersionAns => {
+                return Err(anyhow!("{:?} is not implemented", cid));
+            }
+        })
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        let mut out = vec![self.cid().to_u8()];
+
+        match self {
+            Payload::FragSessionStatusReq(pl) => out.extend_from_slice(&pl.encode()?),
+            Payload::FragSessionStatusAns(pl) => out.extend_from_slice(&pl.encode()?),
+            Payload::FragSessionSetupReq(pl) => out.extend_from_slice(&pl.encode()?),
+            Payload::FragSessionSetupAns(pl) => out.extend_from_slice(&pl.encode()?),
+            Payload::FragSessionDeleteReq(pl) => out.extend_from_slice(&pl.encode()?),
+            Payload::FragSessionDeleteAns(pl) => out.extend_from_slice(&pl.encode()?),
+            Payload::DataFragment(pl) => out.extend_from_slice(&pl.encode()?),
+        }
+
+        Ok(out)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FragSessionStatusReqPayload {
+    pub participants: bool,
+    pub frag_index: u8,
+}
+
+impl FragSessionStatusReqPayload {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() != 1 {
+            return Err(anyhow!("FragSessionStatusReq expects 1 byte"));
+        }
+
+        Ok(FragSessionStatusReqPayload {
+            participants: b[0] & 0x01 != 0,
+            frag_index: (b[0] >> 1) & 0x03,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        if self.frag_index > 3 {
+            return Err(anyhow!("max frag_index value is 3"));
+        }
+
+        let mut b = self.frag_index << 1;
+        if self.participants {
+            b |= 0x01;
+        }
+        Ok(vec![b])
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FragSessionStatusAnsPayload {
+    pub frag_index: u8,
+    pub nb_frag_received: u16,
+    pub missing_frag: u8,
+    pub not_enough_matrix_memory: bool,
+}
+
+impl FragSessionStatusAnsPayload {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() != 4 {
+            return Err(anyhow!("FragSessionStatusAns expects 4 bytes"));
+        }
+
+        let received_and_index = u16::from_le_bytes([b[0], b[1]]);
+
+        Ok(FragSessionStatusAnsPayload {
+            frag_index: (received_and_index >> 14) as u8,
+            nb_frag_received: received_and_index & 0x3fff,
+            missing_frag: b[2],
+            not_enough_matrix_memory: b[3] & 0x01 != 0,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        if self.frag_index > 3 {
+            return Err(anyhow!("max frag_index value is 3"));
+        }
+        if self.nb_frag_received > 0x3fff {
+            return Err(anyhow!("max nb_frag_received value is 16383"));
+        }
+
+        let received_and_index = self.nb_frag_received | ((self.frag_index as u16) << 14);
+        let mut b = received_and_index.to_le_bytes().to_vec();
+        b.push(self.missing_frag);
+        b.push(self.not_enough_matrix_memory as u8);
+        Ok(b)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FragSessionSetupReqPayload {
+    pub frag_index: u8,
+    pub mc_group_bit_mask: [bool; 4],
+    pub nb_frag: u16,
+    pub frag_size: u8,
+    pub fragmentation_matrix: u8,
+    pub block_ack_delay: u8,
+    pub padding: u8,
+    pub descriptor: [u8; 4],
+}
+
+impl FragSessionSetupReqPayload {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() != 10 {
+            return Err(anyhow!("FragSessionSetupReq expects 10 bytes"));
+        }
+
+        let mut descriptor = [0; 4];
+        descriptor.copy_from_slice(&b[6..10]);
+
+        Ok(FragSessionSetupReqPayload {
+            frag_index: (b[0] >> 4) & 0x03,
+            mc_group_bit_mask: [
+                b[0] & 0x01 != 0,
+                b[0] & 0x02 != 0,
+                b[0] & 0x04 != 0,
+                b[0] & 0x08 != 0,
+            ],
+            nb_frag: u16::from_le_bytes([b[1], b[2]]),
+            frag_size: b[3],
+            fragmentation_matrix: (b[4] >> 3) & 0x07,
+            block_ack_delay: b[4] & 0x07,
+            padding: b[5],
+            descriptor,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        if self.frag_index > 3 {
+            return Err(anyhow!("max frag_index value is 3"));
+        }
+        if self.fragmentation_matrix > 7 {
+            return Err(anyhow!("max fragmentation_matrix value is 7"));
+        }
+        if self.block_ack_delay > 7 {
+            return Err(anyhow!("max block_ack_delay value is 7"));
+        }
+
+        let mut frag_session = self.frag_index << 4;
+        for (i, v) in self.mc_group_bit_mask.iter().enumerate() {
+            if *v {
+                frag_session |= 1 << i;
+            }
+        }
+
+        let mut b = vec![frag_session];
+        b.extend_from_slice(&self.nb_frag.to_le_bytes());
+        b.push(self.frag_size);
+        b.push(self.block_ack_delay | (self.fragmentation_matrix << 3));
+        b.push(self.padding);
+        b.extend_from_slice(&self.descriptor);
+        Ok(b)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FragSessionSetupAnsPayload {
+    pub frag_index: u8,
+    pub wrong_descriptor: bool,
+    pub frag_session_index_not_supported: bool,
+    pub not_enough_memory: bool,
+    pub encoding_unsupported: bool,
+}
+
+impl FragSessionSetupAnsPayload {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() != 1 {
+            return Err(anyhow!("FragSessionSetupAns expects 1 byte"));
+        }
+
+        Ok(FragSessionSetupAnsPayload {
+            frag_index: (b[0] >> 6) & 0x03,
+            wrong_descriptor: b[0] & 0x08 != 0,
+            frag_session_index_not_supported: b[0] & 0x04 != 0,
+            not_enough_memory: b[0] & 0x02 != 0,
+            encoding_unsupported: b[0] & 0x01 != 0,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        if self.frag_index > 3 {
+            return Err(anyhow!("max frag_index value is 3"));
+        }
+
+        let mut b = self.frag_index << 6;
+        if self.wrong_descriptor {
+            b |= 0x08;
+        }
+        if self.frag_session_index_not_supported {
+            b |= 0x04;
+        }
+        if self.not_enough_memory {
+            b |= 0x02;
+        }
+        if self.encoding_unsupported {
+            b |= 0x01;
+        }
+        Ok(vec![b])
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FragSessionDeleteReqPayload {
+    pub frag_index: u8,
+}
+
+impl FragSessionDeleteReqPayload {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() != 1 {
+            return Err(anyhow!("FragSessionDeleteReq expects 1 byte"));
+        }
+
+        Ok(FragSessionDeleteReqPayload {
+            frag_index: b[0] & 0x03,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        if self.frag_index > 3 {
+            return Err(anyhow!("max frag_index value is 3"));
+        }
+
+        Ok(vec![self.frag_index])
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FragSessionDeleteAnsPayload {
+    pub frag_index: u8,
+    pub session_does_not_exist: bool,
+}
+
+impl FragSessionDeleteAnsPayload {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() != 1 {
+            return Err(anyhow!("FragSessionDeleteAns expects 1 byte"));
+        }
+
+        Ok(FragSessionDeleteAnsPayload {
+            frag_index: b[0] & 0x03,
+            session_does_not_exist: b[0] & 0x04 != 0,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        if self.frag_index > 3 {
+            return Err(anyhow!("max frag_index value is 3"));
+        }
+
+        let mut b = self.frag_index;
+        if self.session_does_not_exist {
+            b |= 0x04;
+        }
+        Ok(vec![b])
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DataFragmentPayload {
+    pub frag_index: u8,
+    /// Fragment number, starting at 1.
+    pub n: u16,
+    pub payload: Vec<u8>,
+}
+
+impl DataFragmentPayload {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() < 2 {
+            return Err(anyhow!("DataFragment expects at least 2 bytes"));
+        }
+
+        let index_and_n = u16::from_le_bytes([b[0], b[1]]);
+
+        Ok(DataFragmentPayload {
+            frag_index: (index_and_n >> 14) as u8,
+            n: index_and_n & 0x3fff,
+            payload: b[2..].to_vec(),
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        if self.frag_index > 3 {
+            return Err(anyhow!("max frag_index value is 3"));
+        }
+        if self.n > 0x3fff {
+            return Err(anyhow!("max n value is 16383"));
+        }
+
+        let index_and_n = self.n | ((self.frag_index as u16) << 14);
+        let mut b = index_and_n.to_le_bytes().to_vec();
+        b.extend_from_slice(&self.payload);
+        Ok(b)
+    }
+}
+
+/// Splits the data into fragments of the given size and appends the given number of
+/// forward error correction (parity) fragments, using the parity check matrix as defined in
+/// the TS004 specification. The length of the data must be a multiple of the fragment size.
+pub fn encode(data: &[u8], fragment_size: usize, redundancy: usize) -> Result<Vec<Vec<u8>>> {
+    if fragment_size == 0 {
+        return Err(anyhow!("fragment_size must be > 0"));
+    }
+    if data.len() % fragment_size != 0 {
+        return Err(anyhow!(
+            "length of data must be a multiple of fragment_size"
+        ));
+    }
+
+    let mut out: Vec<Vec<u8>> = data.chunks(fragment_size).map(|v| v.to_vec()).collect();
+    let w = out.len();
+
+    for y in 0..redundancy {
+        let mut s = vec![0; fragment_size];
+        let a = matrix_line(y + 1, w);
+
+        for (x, fragment) in out.iter().take(w).enumerate() {
+            if a[x] {
+                for (m, b) in fragment.iter().enumerate() {
+                    s[m] ^= b;
+                }
+            }
+        }
+
+        out.push(s);
+    }
+
+    Ok(out)
+}
+
+fn prbs23(x: usize) -> usize {
+    let b0 = x & 1;
+    let b1 = (x & 32) / 32;
+    (x / 2) + ((b0 ^ b1) << 22)
+}
+
+fn is_power_of_2(n: usize) -> bool {
+    n != 0 && n & (n - 1) == 0
+}
+
+fn matrix_line(n: usize, m: usize) -> Vec<bool> {
+    let mut line = vec![false; m];
+    let mm = if is_power_of_2(m) { 1 } else { 0 };
+    let mut x = 1 + (1001 * n);
+
+    for _ in 0..(m / 2) {
+        let mut r = 1 << 16;
+        while r >= m {
+            x = prbs23(x);
+            r = x % (m + mm);
+        }
+        line[r] = true;
+    }
+
+    line
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn test_frag_session_setup_req() {
+        let pl = Payload::FragSessionSetupReq(FragSessionSetupReqPayload {
+            frag_index: 3,
+            mc_group_bit_mask: [true, false, false, false],
+            nb_frag: 513,
+            frag_size: 50,
+            fragmentation_matrix: 0,
+            block_ack_delay: 5,
+            padding: 10,
+            descriptor: [0x01, 0x02, 0x03, 0x04],
+        });
+        let b = vec![
+            0x02, 0x31, 0x01, 0x02, 0x32, 0x05, 0x0a, 0x01, 0x02, 0x03, 0x04,
+        ];
+
+        assert_eq!(b, pl.to_vec().unwrap());
+        assert_eq!(pl, Payload::from_slice(false, &b).unwrap());
+    }
+
+    #[test]
+    fn test_frag_session_setup_ans() {
+        let pl = Payload::FragSessionSetupAns(FragSessionSetupAnsPayload {
+            frag_index: 3,
+            wrong_descriptor: true,
+            frag_session_index_not_supported: false,
+            not_enough_memory: true,
+            encoding_unsupported: false,
+        });
+        let b = vec![0x02, 0xca];
+
+        assert_eq!(b, pl.to_vec().unwrap());
+        assert_eq!(pl, Payload::from_slice(true, &b).unwrap());
+    }
+
+    #[test]
+    fn test_frag_session_status() {
+        let pl = Payload::FragSessionStatusReq(FragSessionStatusReqPayload {
+            participants: true,
+            frag_index: 2,
+        });
+        let b = vec![0x01, 0x05];
+        assert_eq!(b, pl.to_vec().unwrap());
+        assert_eq!(pl, Payload::from_slice(false, &b).unwrap());
+
+        let pl = Payload::FragSessionStatusAns(FragSessionStatusAnsPayload {
+            frag_index: 1,
+            nb_frag_received: 1024,
+            missing_frag: 3,
+            not_enough_matrix_memory: true,
+        });
+        let b = vec![0x01, 0x00, 0x44, 0x03, 0x01];
+        assert_eq!(b, pl.to_vec().unwrap());
+        assert_eq!(pl, Payload::from_slice(true, &b).unwrap());
+    }
+
+    #[test]
+    fn test_data_fragment() {
+        let pl = Payload::DataFragment(DataFragmentPayload {
+            frag_index: 2,
+            n: 1,
+            payload: vec![0x01, 0x02, 0x03],
+        });
+        let b = vec![0x08, 0x01, 0x80, 0x01, 0x02, 0x03];
+
+        assert_eq!(b, pl.to_vec().unwrap());
+        assert_eq!(pl, Payload::from_slice(false, &b).unwrap());
+    }
+
+    #[test]
+    fn test_encode() {
+        let data: Vec<u8> = (0..50).collect();
+        let fragments = encode(&data, 10, 3).unwrap();
+
+        // Uncoded fragments.
+        assert_eq!(8, fragments.len());
+        for (i, f) in fragments.iter().take(5).enumerate() {
+            assert_eq!(&data[i * 10..(i + 1) * 10], f.as_slice());
+        }
+
+        // Each parity fragment is the XOR of the fragments selected by the matrix line.
+        for (y, f) in fragments.iter().skip(5).enumerate() {
+            let line = matrix_line(y + 1, 5);
+            let mut expected = vec![0; 10];
+            for (x, selected) in line.iter().enumerate() {
+                if *selected {
+                    for (m, b) in expected.iter_mut().enumerate() {
+                        *b ^= data[x * 10 + m];
+                    }
+                }
+            }
+            assert_eq!(&expected, f);
+        }
+
+        assert!(encode(&data, 7, 1).is_err());
+    }
+}
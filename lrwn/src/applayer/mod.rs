@@ -0,0 +1,13 @@
+//! Application layer packages.
+pub mod clocksync;
+pub mod fragmentation;
+pub mod multicastsetup;
+
+/// Default FPort used by the Remote Multicast Setup package.
+pub const FPORT_MULTICAST_SETUP: u8 = 200;
+
+/// Default FPort used by the Fragmented Data Block Transport package.
+pub const FPORT_FRAGMENTATION: u8 = 201;
+
+/// Default FPort used by the Application Layer Clock Synchronization package.
+pub const FPORT_CLOCK_SYNC: u8 = 202;
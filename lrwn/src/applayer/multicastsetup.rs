@@ -0,0 +1,643 @@
+//! Remote Multicast Setup (LoRaWAN TS005-1.0.0).
+use aes::cipher::{generic_array::GenericArray, BlockDecrypt, BlockEncrypt, KeyInit};
+use aes::{Aes128, Block};
+use anyhow::Result;
+
+use crate::{AES128Key, DevAddr};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Cid {
+    PackageVersionReq,
+    PackageVersionAns,
+    McGroupStatusReq,
+    McGroupStatusAns,
+    McGroupSetupReq,
+    McGroupSetupAns,
+    McGroupDeleteReq,
+    McGroupDeleteAns,
+    McClassCSessionReq,
+    McClassCSessionAns,
+    McClassBSessionReq,
+    McClassBSessionAns,
+}
+
+impl Cid {
+    pub fn to_u8(&self) -> u8 {
+        match self {
+            Cid::PackageVersionReq | Cid::PackageVersionAns => 0x00,
+            Cid::McGroupStatusReq | Cid::McGroupStatusAns => 0x01,
+            Cid::McGroupSetupReq | Cid::McGroupSetupAns => 0x02,
+            Cid::McGroupDeleteReq | Cid::McGroupDeleteAns => 0x03,
+            Cid::McClassCSessionReq | Cid::McClassCSessionAns => 0x04,
+            Cid::McClassBSessionReq | Cid::McClassBSessionAns => 0x05,
+        }
+    }
+
+    pub fn from_u8(uplink: bool, v: u8) -> Result<Self> {
+        Ok(match uplink {
+            true => match v {
+                0x00 => Cid::PackageVersionAns,
+                0x01 => Cid::McGroupStatusAns,
+                0x02 => Cid::McGroupSetupAns,
+                0x03 => Cid::McGroupDeleteAns,
+                0x04 => Cid::McClassCSessionAns,
+                0x05 => Cid::McClassBSessionAns,
+                _ => return Err(anyhow!("Invalid CID: {}", v)),
+            },
+            false => match v {
+                0x00 => Cid::PackageVersionReq,
+                0x01 => Cid::McGroupStatusReq,
+                0x02 => Cid::McGroupSetupReq,
+                0x03 => Cid::McGroupDeleteReq,
+                0x04 => Cid::McClassCSessionReq,
+                0x05 => Cid::McClassBSessionReq,
+                _ => return Err(anyhow!("Invalid CID: {}", v)),
+            },
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Payload {
+    PackageVersionReq,
+    PackageVersionAns(PackageVersionAnsPayload),
+    McGroupSetupReq(McGroupSetupReqPayload),
+    McGroupSetupAns(McGroupSetupAnsPayload),
+    McGroupDeleteReq(McGroupDeleteReqPayload),
+    McGroupDeleteAns(McGroupDeleteAnsPayload),
+    McClassCSessionReq(McClassCSessionReqPayload),
+    McClassCSessionAns(McClassSessionAnsPayload),
+    McClassBSessionReq(McClassBSessionReqPayload),
+    McClassBSessionAns(McClassSessionAnsPayload),
+}
+
+impl Payload {
+    pub fn cid(&self) -> Cid {
+        match self {
+            Payload::PackageVersionReq => Cid::PackageVersionReq,
+            Payload::PackageVersionAns(_) => Cid::PackageVersionAns,
+            Payload::McGroupSetupReq(_) => Cid::McGroupSetupReq,
+            Payload::McGroupSetupAns(_) => Cid::McGroupSetupAns,
+            Payload::McGroupDeleteReq(_) => Cid::McGroupDeleteReq,
+            Payload::McGroupDeleteAns(_) => Cid::McGroupDeleteAns,
+            Payload::McClassCSessionReq(_) => Cid::McClassCSessionReq,
+            Payload::McClassCSessionAns(_) => Cid::McClassCSessionAns,
+            Payload::McClassBSessionReq(_) => Cid::McClassBSessionReq,
+            Payload::McClassBSessionAns(_) => Cid::McClassBSessionAns,
+        }
+    }
+
+    pub fn from_slice(uplink: bool, b: &[u8]) -> Result<Self> {
+        if b.is_empty() {
+            return Err(anyhow!("At least one byte is expected"));
+        }
+
+        let cid = Cid::from_u8(uplink, b[0])?;
+        let b = &b[1..];
+
+        Ok(match cid {
+            Cid::PackageVersionReq => Payload::PackageVersionReq,
+            Cid::PackageVersionAns => {
+                Payload::PackageVersionAns(PackageVersionAnsPayload::decode(b)?)
+            }
+            Cid::McGroupSetupReq => Payload::McGroupSetupReq(McGroupSetupReqPayload::decode(b)?),
+            Cid::McGroupSetupAns => Payload::McGroupSetupAns(McGroupSetupAnsPayload::decode(b)?),
+            Cid::McGroupDeleteReq => Payload::McGroupDeleteReq(McGroupDeleteReqPayload::decode(b)?),
+            Cid::McGroupDeleteAns => Payload::McGroupDeleteAns(McGroupDeleteAnsPayload::decode(b)?),
+            Cid::McClassCSessionReq => {
+                Payload::McClassCSessionReq(McClassCSessionReqPayload::decode(b)?)
+            }
+            Cid::McClassCSessionAns => {
+                Payload::McClassCSessionAns(McClassSessionAnsPayload::decode(b)?)
+            }
+            Cid::McClassBSessionReq => {
+                Payload::McClassBSessionReq(McClassBSessionReqPayload::decode(b)?)
+            }
+            Cid::McClassBSessionAns => {
+                Payload::McClassBSessionAns(McClassSessionAnsPayload::decode(b)?)
+            }
+            Cid::McGroupStatusReq | Cid::McGroupStatusAns => {
+                return Err(anyhow!("{:?} is not implemented", cid));
+            }
+        })
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        let mut out = vec![self.cid().to_u8()];
+
+        match self {
+            Payload::PackageVersionReq => {}
+            Payload::PackageVersionAns(pl) => out.extend_from_slice(&pl.encode()?),
+            Payload::McGroupSetupReq(pl) => out.extend_from_slice(&pl.encode()?),
+            Payload::McGroupSetupAns(pl) => out.extend_from_slice(&pl.encode()?),
+            Payload::McGroupDeleteReq(pl) => out.extend_from_slice(&pl.encode()?),
+            Payload::McGroupDeleteAns(pl) => out.extend_from_slice(&pl.encode()?),
+            Payload::McClassCSessionReq(pl) => out.extend_from_slice(&pl.encode()?),
+            Payload::McClassCSessionAns(pl) => out.extend_from_slice(&pl.encode()?),
+            Payload::McClassBSessionReq(pl) => out.extend_from_slice(&pl.encode()?),
+            Payload::McClassBSessionAns(pl) => out.extend_from_slice(&pl.encode()?),
+        }
+
+        Ok(out)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PackageVersionAnsPayload {
+    pub package_identifier: u8,
+    pub package_version: u8,
+}
+
+impl PackageVersionAnsPayload {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() != 2 {
+            return Err(anyhow!("PackageVersionAns expects 2 bytes"));
+        }
+
+        Ok(PackageVersionAnsPayload {
+            package_identifier: b[0],
+            package_version: b[1],
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        Ok(vec![self.package_identifier, self.package_version])
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct McGroupSetupReqPayload {
+    pub mc_group_id: u8,
+    pub mc_addr: DevAddr,
+    pub mc_key_encrypted: AES128Key,
+    pub min_mc_f_count: u32,
+    pub max_mc_f_count: u32,
+}
+
+impl McGroupSetupReqPayload {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() != 29 {
+            return Err(anyhow!("McGroupSetupReq expects 29 bytes"));
+        }
+
+        let mut mc_addr = [0; 4];
+        mc_addr.copy_from_slice(&b[1..5]);
+        let mut min_mc_f_count = [0; 4];
+        min_mc_f_count.copy_from_slice(&b[21..25]);
+        let mut max_mc_f_count = [0; 4];
+        max_mc_f_count.copy_from_slice(&b[25..29]);
+
+        Ok(McGroupSetupReqPayload {
+            mc_group_id: b[0] & 0x03,
+            mc_addr: DevAddr::from_le_bytes(mc_addr),
+            mc_key_encrypted: AES128Key::from_slice(&b[5..21])?,
+            min_mc_f_count: u32::from_le_bytes(min_mc_f_count),
+            max_mc_f_count: u32::from_le_bytes(max_mc_f_count),
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        if self.mc_group_id > 3 {
+            return Err(anyhow!("max mc_group_id value is 3"));
+        }
+
+        let mut b = vec![self.mc_group_id];
+        b.extend_from_slice(&self.mc_addr.to_le_bytes());
+        b.extend_from_slice(&self.mc_key_encrypted.to_bytes());
+        b.extend_from_slice(&self.min_mc_f_count.to_le_bytes());
+        b.extend_from_slice(&self.max_mc_f_count.to_le_bytes());
+        Ok(b)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct McGroupSetupAnsPayload {
+    pub mc_group_id: u8,
+    pub id_error: bool,
+}
+
+impl McGroupSetupAnsPayload {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() != 1 {
+            return Err(anyhow!("McGroupSetupAns expects 1 byte"));
+        }
+
+        Ok(McGroupSetupAnsPayload {
+            mc_group_id: b[0] & 0x03,
+            id_error: b[0] & 0x04 != 0,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        if self.mc_group_id > 3 {
+            return Err(anyhow!("max mc_group_id value is 3"));
+        }
+
+        let mut b = self.mc_group_id;
+        if self.id_error {
+            b |= 0x04;
+        }
+        Ok(vec![b])
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct McGroupDeleteReqPayload {
+    pub mc_group_id: u8,
+}
+
+impl McGroupDeleteReqPayload {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() != 1 {
+            return Err(anyhow!("McGroupDeleteReq expects 1 byte"));
+        }
+
+        Ok(McGroupDeleteReqPayload {
+            mc_group_id: b[0] & 0x03,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        if self.mc_group_id > 3 {
+            return Err(anyhow!("max mc_group_id value is 3"));
+        }
+
+        Ok(vec![self.mc_group_id])
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct McGroupDeleteAnsPayload {
+    pub mc_group_id: u8,
+    pub mc_group_undefined: bool,
+}
+
+impl McGroupDeleteAnsPayload {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() != 1 {
+            return Err(anyhow!("McGroupDeleteAns expects 1 byte"));
+        }
+
+        Ok(McGroupDeleteAnsPayload {
+            mc_group_id: b[0] & 0x03,
+            mc_group_undefined: b[0] & 0x04 != 0,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        if self.mc_group_id > 3 {
+            return Err(anyhow!("max mc_group_id value is 3"));
+        }
+
+        let mut b = self.mc_group_id;
+        if self.mc_group_undefined {
+            b |= 0x04;
+        }
+        Ok(vec![b])
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct McClassCSessionReqPayload {
+    pub mc_group_id: u8,
+    /// Session start time in seconds since GPS epoch (modulo 2^32).
+    pub session_time: u32,
+    /// Max. duration of the session: 2^session_time_out seconds.
+    pub session_time_out: u8,
+    pub dl_frequency: u32,
+    pub dr: u8,
+}
+
+impl McClassCSessionReqPayload {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() != 10 {
+            return Err(anyhow!("McClassCSessionReq expects 10 bytes"));
+        }
+
+        let mut session_time = [0; 4];
+        session_time.copy_from_slice(&b[1..5]);
+
+        Ok(McClassCSessionReqPayload {
+            mc_group_id: b[0] & 0x03,
+            session_time: u32::from_le_bytes(session_time),
+            session_time_out: b[5] & 0x0f,
+            dl_frequency: decode_frequency(&b[6..9]),
+            dr: b[9],
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        if self.mc_group_id > 3 {
+            return Err(anyhow!("max mc_group_id value is 3"));
+        }
+        if self.session_time_out > 15 {
+            return Err(anyhow!("max session_time_out value is 15"));
+        }
+
+        let mut b = vec![self.mc_group_id];
+        b.extend_from_slice(&self.session_time.to_le_bytes());
+        b.push(self.session_time_out);
+        b.extend_from_slice(&encode_frequency(self.dl_frequency)?);
+        b.push(self.dr);
+        Ok(b)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct McClassBSessionReqPayload {
+    pub mc_group_id: u8,
+    /// Session start time in seconds since GPS epoch (modulo 2^32).
+    pub session_time: u32,
+    /// Max. duration of the session: 2^time_out beacon periods.
+    pub time_out: u8,
+    /// Ping-slot periodicity: 2^periodicity seconds.
+    pub periodicity: u8,
+    pub dl_frequency: u32,
+    pub dr: u8,
+}
+
+impl McClassBSessionReqPayload {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() != 10 {
+            return Err(anyhow!("McClassBSessionReq expects 10 bytes"));
+        }
+
+        let mut session_time = [0; 4];
+        session_time.copy_from_slice(&b[1..5]);
+
+        Ok(McClassBSessionReqPayload {
+            mc_group_id: b[0] & 0x03,
+            session_time: u32::from_le_bytes(session_time),
+            time_out: b[5] & 0x0f,
+            periodicity: (b[5] >> 4) & 0x07,
+            dl_frequency: decode_frequency(&b[6..9]),
+            dr: b[9],
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        if self.mc_group_id > 3 {
+            return Err(anyhow!("max mc_group_id value is 3"));
+        }
+        if self.time_out > 15 {
+            return Err(anyhow!("max time_out value is 15"));
+        }
+        if self.periodicity > 7 {
+            return Err(anyhow!("max periodicity value is 7"));
+        }
+
+        let mut b = vec![self.mc_group_id];
+        b.extend_from_slice(&self.session_time.to_le_bytes());
+        b.push(self.time_out | (self.periodicity << 4));
+        b.extend_from_slice(&encode_frequency(self.dl_frequency)?);
+        b.push(self.dr);
+        Ok(b)
+    }
+}
+
+/// McClassCSessionAns and McClassBSessionAns share the same payload.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct McClassSessionAnsPayload {
+    pub mc_group_id: u8,
+    pub dr_error: bool,
+    pub freq_error: bool,
+    pub mc_group_undefined: bool,
+    /// Seconds until the session starts, only present when there are no errors.
+    pub time_to_start: Option<u32>,
+}
+
+impl McClassSessionAnsPayload {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.is_empty() {
+            return Err(anyhow!("McClassSessionAns expects at least 1 byte"));
+        }
+
+        let mut pl = McClassSessionAnsPayload {
+            mc_group_id: b[0] & 0x03,
+            dr_error: b[0] & 0x04 != 0,
+            freq_error: b[0] & 0x08 != 0,
+            mc_group_undefined: b[0] & 0x10 != 0,
+            time_to_start: None,
+        };
+
+        if !pl.dr_error && !pl.freq_error && !pl.mc_group_undefined {
+            if b.len() != 4 {
+                return Err(anyhow!("McClassSessionAns expects 4 bytes"));
+            }
+            pl.time_to_start = Some(u32::from_le_bytes([b[1], b[2], b[3], 0]));
+        }
+
+        Ok(pl)
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        if self.mc_group_id > 3 {
+            return Err(anyhow!("max mc_group_id value is 3"));
+        }
+
+        let mut b = self.mc_group_id;
+        if self.dr_error {
+            b |= 0x04;
+        }
+        if self.freq_error {
+            b |= 0x08;
+        }
+        if self.mc_group_undefined {
+            b |= 0x10;
+        }
+
+        let mut out = vec![b];
+        if let Some(v) = self.time_to_start {
+            if v >= 1 << 24 {
+                return Err(anyhow!("max time_to_start value is 2^24 - 1"));
+            }
+            out.extend_from_slice(&v.to_le_bytes()[0..3]);
+        }
+        Ok(out)
+    }
+}
+
+fn decode_frequency(b: &[u8]) -> u32 {
+    u32::from_le_bytes([b[0], b[1], b[2], 0]) * 100
+}
+
+fn encode_frequency(freq: u32) -> Result<[u8; 3]> {
+    if freq % 100 != 0 {
+        return Err(anyhow!("frequency must be a multiple of 100"));
+    }
+    let freq = freq / 100;
+    if freq >= 1 << 24 {
+        return Err(anyhow!("max frequency value exceeded"));
+    }
+
+    let b = freq.to_le_bytes();
+    Ok([b[0], b[1], b[2]])
+}
+
+/// Returns the McRootKey for LoRaWAN 1.0.x devices, derived from the GenAppKey.
+pub fn get_mc_root_key_for_gen_app_key(gen_app_key: &AES128Key) -> Result<AES128Key> {
+    aes128_encrypt(gen_app_key, [0; 16])
+}
+
+/// Returns the McRootKey for LoRaWAN 1.1.x devices, derived from the AppKey.
+pub fn get_mc_root_key_for_app_key(app_key: &AES128Key) -> Result<AES128Key> {
+    let mut b = [0; 16];
+    b[0] = 0x20;
+    aes128_encrypt(app_key, b)
+}
+
+/// Returns the McKEKey (multicast key encryption key) for the given McRootKey.
+pub fn get_mc_ke_key(mc_root_key: &AES128Key) -> Result<AES128Key> {
+    aes128_encrypt(mc_root_key, [0; 16])
+}
+
+/// Returns the McAppSKey for the given McKey and McAddr.
+pub fn get_mc_app_s_key(mc_key: &AES128Key, mc_addr: &DevAddr) -> Result<AES128Key> {
+    let mut b = [0; 16];
+    b[0] = 0x01;
+    b[1..5].copy_from_slice(&mc_addr.to_le_bytes());
+    aes128_encrypt(mc_key, b)
+}
+
+/// Returns the McNetSKey for the given McKey and McAddr.
+pub fn get_mc_net_s_key(mc_key: &AES128Key, mc_addr: &DevAddr) -> Result<AES128Key> {
+    let mut b = [0; 16];
+    b[0] = 0x02;
+    b[1..5].copy_from_slice(&mc_addr.to_le_bytes());
+    aes128_encrypt(mc_key, b)
+}
+
+/// Returns the McKey_encrypted value as used in the McGroupSetupReq. The end-device obtains
+/// the McKey by performing an aes128_encrypt using its McKEKey.
+pub fn encrypt_mc_key(mc_ke_key: &AES128Key, mc_key: &AES128Key) -> Result<AES128Key> {
+    let key_bytes = mc_ke_key.to_bytes();
+    let key = GenericArray::from_slice(&key_bytes);
+    let cipher = Aes128::new(key);
+
+    let mut b = mc_key.to_bytes();
+    let block = Block::from_mut_slice(&mut b);
+    cipher.decrypt_block(block);
+    Ok(AES128Key::from_slice(block)?)
+}
+
+fn aes128_encrypt(key: &AES128Key, mut b: [u8; 16]) -> Result<AES128Key> {
+    let key_bytes = key.to_bytes();
+    let key = GenericArray::from_slice(&key_bytes);
+    let cipher = Aes128::new(key);
+
+    let block = Block::from_mut_slice(&mut b);
+    cipher.encrypt_block(block);
+    Ok(AES128Key::from_slice(block)?)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn test_mc_group_setup_req() {
+        let pl = Payload::McGroupSetupReq(McGroupSetupReqPayload {
+            mc_group_id: 2,
+            mc_addr: DevAddr::from_be_bytes([0x01, 0x02, 0x03, 0x04]),
+            mc_key_encrypted: AES128Key::from_bytes([
+                0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+                0x0f, 0x10,
+            ]),
+            min_mc_f_count: 10,
+            max_mc_f_count: 1000,
+        });
+        let b = vec![
+            0x02, 0x02, 0x04, 0x03, 0x02, 0x01, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+            0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x0a, 0x00, 0x00, 0x00, 0xe8, 0x03,
+            0x00, 0x00,
+        ];
+
+        assert_eq!(b, pl.to_vec().unwrap());
+        assert_eq!(pl, Payload::from_slice(false, &b).unwrap());
+    }
+
+    #[test]
+    fn test_mc_group_setup_ans() {
+        let pl = Payload::McGroupSetupAns(McGroupSetupAnsPayload {
+            mc_group_id: 1,
+            id_error: true,
+        });
+        let b = vec![0x02, 0x05];
+
+        assert_eq!(b, pl.to_vec().unwrap());
+        assert_eq!(pl, Payload::from_slice(true, &b).unwrap());
+    }
+
+    #[test]
+    fn test_mc_class_c_session_req() {
+        let pl = Payload::McClassCSessionReq(McClassCSessionReqPayload {
+            mc_group_id: 0,
+            session_time: 1024,
+            session_time_out: 8,
+            dl_frequency: 869525000,
+            dr: 3,
+        });
+        let b = vec![
+            0x04, 0x00, 0x00, 0x04, 0x00, 0x00, 0x08, 0xd2, 0xad, 0x84, 0x03,
+        ];
+
+        assert_eq!(b, pl.to_vec().unwrap());
+        assert_eq!(pl, Payload::from_slice(false, &b).unwrap());
+    }
+
+    #[test]
+    fn test_mc_class_b_session_req() {
+        let pl = Payload::McClassBSessionReq(McClassBSessionReqPayload {
+            mc_group_id: 1,
+            session_time: 1024,
+            time_out: 3,
+            periodicity: 2,
+            dl_frequency: 869525000,
+            dr: 3,
+        });
+        let b = vec![
+            0x05, 0x01, 0x00, 0x04, 0x00, 0x00, 0x23, 0xd2, 0xad, 0x84, 0x03,
+        ];
+
+        assert_eq!(b, pl.to_vec().unwrap());
+        assert_eq!(pl, Payload::from_slice(false, &b).unwrap());
+    }
+
+    #[test]
+    fn test_mc_class_session_ans() {
+        let pl = Payload::McClassCSessionAns(McClassSessionAnsPayload {
+            mc_group_id: 0,
+            dr_error: false,
+            freq_error: false,
+            mc_group_undefined: false,
+            time_to_start: Some(256),
+        });
+        let b = vec![0x04, 0x00, 0x00, 0x01, 0x00];
+        assert_eq!(b, pl.to_vec().unwrap());
+        assert_eq!(pl, Payload::from_slice(true, &b).unwrap());
+
+        let pl = Payload::McClassBSessionAns(McClassSessionAnsPayload {
+            mc_group_id: 0,
+            dr_error: true,
+            freq_error: true,
+            mc_group_undefined: false,
+            time_to_start: None,
+        });
+        let b = vec![0x05, 0x0c];
+        assert_eq!(b, pl.to_vec().unwrap());
+        assert_eq!(pl, Payload::from_slice(true, &b).unwrap());
+    }
+
+    #[test]
+    fn test_encrypt_mc_key() {
+        let mc_ke_key = AES128Key::from_bytes([0x01; 16]);
+        let mc_key = AES128Key::from_bytes([0x02; 16]);
+
+        // The end-device obtains the McKey by encrypting the McKey_encrypted with the McKEKey.
+        let mc_key_encrypted = encrypt_mc_key(&mc_ke_key, &mc_key).unwrap();
+        assert_eq!(
+            mc_key,
+            aes128_encrypt(&mc_ke_key, mc_key_encrypted.to_bytes()).unwrap()
+        );
+    }
+}
@@ -21,11 +21,11 @@ impl DevAddrPrefix {
         DevAddrPrefix(prefix, size)
     }
 
-    fn prefix(&self) -> [u8; 4] {
+    pub fn prefix(&self) -> [u8; 4] {
         self.0
     }
 
-    fn size(&self) -> u32 {
+    pub fn size(&self) -> u32 {
         self.1
     }
 }
@@ -203,7 +203,7 @@ impl Configuration {
                             (
                                 MacVersion::Latest,
                                 [(
-                                    Revision::Latest, // PR002-1.0.0, RP002-1.0.1, RP002-1.02
+                                    Revision::Latest, // RP002-1.0.0, RP002-1.0.1, RP002-1.0.2, RP002-1.0.3, RP002-1.0.4
                                     [
                                         (0, MaxPayloadSize { m: 0, n: 0 }),
                                         (1, MaxPayloadSize { m: 0, n: 0 }),
@@ -297,7 +297,7 @@ impl Configuration {
                             (
                                 MacVersion::Latest,
                                 [(
-                                    Revision::Latest, // PR002-1.0.0, RP002-1.0.1, RP002-1.02
+                                    Revision::Latest, // RP002-1.0.0, RP002-1.0.1, RP002-1.0.2, RP002-1.0.3, RP002-1.0.4
                                     [
                                         (0, MaxPayloadSize { m: 59, n: 51 }),
                                         (1, MaxPayloadSize { m: 59, n: 51 }),
@@ -393,7 +393,7 @@ impl Configuration {
                             (
                                 MacVersion::Latest,
                                 [(
-                                    Revision::Latest, // PR002-1.0.0, RP002-1.0.1, RP002-1.02
+                                    Revision::Latest, // RP002-1.0.0, RP002-1.0.1, RP002-1.0.2, RP002-1.0.3, RP002-1.0.4
                                     [
                                         (0, MaxPayloadSize { m: 0, n: 0 }),
                                         (1, MaxPayloadSize { m: 0, n: 0 }),
@@ -487,7 +487,7 @@ impl Configuration {
                             (
                                 MacVersion::Latest,
                                 [(
-                                    Revision::Latest, // PR002-1.0.0, RP002-1.0.1, RP002-1.02
+                                    Revision::Latest, // RP002-1.0.0, RP002-1.0.1, RP002-1.0.2, RP002-1.0.3, RP002-1.0.4
                                     [
                                         (0, MaxPayloadSize { m: 59, n: 51 }),
                                         (1, MaxPayloadSize { m: 59, n: 51 }),
@@ -595,7 +595,11 @@ impl Region for Configuration {
         }
     }
 
-    fn implements_tx_param_setup(&self, _mac_version: MacVersion) -> bool {
+    fn implements_tx_param_setup(
+        &self,
+        _mac_version: MacVersion,
+        _reg_params_revision: Revision,
+    ) -> bool {
         true
     }
 
@@ -905,6 +909,32 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn test_get_max_payload_size() {
+        let c = config();
+
+        for mac_version in &[
+            MacVersion::LORAWAN_1_0_3,
+            MacVersion::LORAWAN_1_0_4,
+            MacVersion::LORAWAN_1_1_0,
+        ] {
+            for (dr, m, n) in &[(2, 19, 11), (3, 61, 53), (4, 133, 125), (5, 230, 222)] {
+                let pl = c
+                    .get_max_payload_size(*mac_version, Revision::RP002_1_0_4, *dr)
+                    .unwrap();
+                assert_eq!(*m, pl.m, "dr: {}", dr);
+                assert_eq!(*n, pl.n, "dr: {}", dr);
+            }
+        }
+    }
+
+    #[test]
+    fn test_implements_tx_param_setup() {
+        let c = config();
+        assert!(c.implements_tx_param_setup(MacVersion::LORAWAN_1_0_2, Revision::B));
+        assert!(c.implements_tx_param_setup(MacVersion::LORAWAN_1_0_4, Revision::RP002_1_0_4));
+    }
+
     #[test]
     fn test_as923_2() {
         let c = Configuration::new(CommonName::AS923_2, true, false);
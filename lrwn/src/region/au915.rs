@@ -299,7 +299,7 @@ impl Configuration {
                                         .collect(),
                                     ),
                                     (
-                                        Revision::Latest, // RP002-1.0.2
+                                        Revision::Latest, // RP002-1.0.2, RP002-1.0.3, RP002-1.0.4
                                         [
                                             (0, MaxPayloadSize { m: 0, n: 0 }),
                                             (1, MaxPayloadSize { m: 0, n: 0 }),
@@ -511,7 +511,7 @@ impl Configuration {
                                         .collect(),
                                     ),
                                     (
-                                        Revision::Latest, // RP002-1.0.2
+                                        Revision::Latest, // RP002-1.0.2, RP002-1.0.3, RP002-1.0.4
                                         [
                                             (0, MaxPayloadSize { m: 59, n: 51 }),
                                             (1, MaxPayloadSize { m: 59, n: 51 }),
@@ -617,10 +617,10 @@ impl Configuration {
                                             // 7
                                             (8, MaxPayloadSize { m: 61, n: 53 }),
                                             (9, MaxPayloadSize { m: 137, n: 129 }),
-                                            (10, MaxPayloadSize { m: 250, n: 222 }),
-                                            (11, MaxPayloadSize { m: 250, n: 250 }),
-                                            (12, MaxPayloadSize { m: 250, n: 250 }),
-                                            (13, MaxPayloadSize { m: 250, n: 250 }),
+                                            (10, MaxPayloadSize { m: 250, n: 242 }),
+                                            (11, MaxPayloadSize { m: 250, n: 242 }),
+                                            (12, MaxPayloadSize { m: 250, n: 242 }),
+                                            (13, MaxPayloadSize { m: 250, n: 242 }),
                                         ]
                                         .iter()
                                         .cloned()
@@ -649,7 +649,7 @@ impl Configuration {
                                         .collect(),
                                     ),
                                     (
-                                        Revision::Latest, // RP002-1.0.2
+                                        Revision::Latest, // RP002-1.0.2, RP002-1.0.3, RP002-1.0.4
                                         [
                                             (0, MaxPayloadSize { m: 0, n: 0 }),
                                             (1, MaxPayloadSize { m: 0, n: 0 }),
@@ -861,7 +861,7 @@ impl Configuration {
                                         .collect(),
                                     ),
                                     (
-                                        Revision::Latest, // RP002-1.0.2
+                                        Revision::Latest, // RP002-1.0.2, RP002-1.0.3, RP002-1.0.4
                                         [
                                             (0, MaxPayloadSize { m: 59, n: 51 }),
                                             (1, MaxPayloadSize { m: 59, n: 51 }),
@@ -873,7 +873,7 @@ impl Configuration {
                                             (7, MaxPayloadSize { m: 58, n: 50 }),
                                             (8, MaxPayloadSize { m: 61, n: 53 }),
                                             (9, MaxPayloadSize { m: 137, n: 129 }),
-                                            (10, MaxPayloadSize { m: 250, n: 222 }),
+                                            (10, MaxPayloadSize { m: 250, n: 242 }),
                                             (11, MaxPayloadSize { m: 250, n: 242 }),
                                             (12, MaxPayloadSize { m: 250, n: 242 }),
                                             (13, MaxPayloadSize { m: 250, n: 242 }),
@@ -1007,8 +1007,18 @@ impl Region for Configuration {
         }
     }
 
-    fn implements_tx_param_setup(&self, mac_version: MacVersion) -> bool {
-        !(mac_version == MacVersion::LORAWAN_1_0_1 || mac_version == MacVersion::LORAWAN_1_0_2)
+    fn implements_tx_param_setup(
+        &self,
+        mac_version: MacVersion,
+        reg_params_revision: Revision,
+    ) -> bool {
+        // LoRaWAN < 1.0.3 + < LoRaWAN 1.1.0B does not implement the TxParamSetup mac-command.
+        !matches!(
+            (mac_version, reg_params_revision),
+            (MacVersion::LORAWAN_1_0_1, _)
+                | (MacVersion::LORAWAN_1_0_2, _)
+                | (MacVersion::LORAWAN_1_1_0, Revision::A)
+        )
     }
 
     fn get_data_rate_index(&self, uplink: bool, modulation: &DataRateModulation) -> Result<u8> {
@@ -1248,6 +1258,67 @@ pub mod test {
         assert_eq!(925700000, freq);
     }
 
+    #[test]
+    fn test_implements_tx_param_setup() {
+        let c = config_full();
+
+        let tests = vec![
+            (MacVersion::LORAWAN_1_0_1, Revision::A, false),
+            (MacVersion::LORAWAN_1_0_2, Revision::B, false),
+            (MacVersion::LORAWAN_1_0_3, Revision::A, true),
+            (MacVersion::LORAWAN_1_0_4, Revision::RP002_1_0_4, true),
+            (MacVersion::LORAWAN_1_1_0, Revision::A, false),
+            (MacVersion::LORAWAN_1_1_0, Revision::B, true),
+            (MacVersion::LORAWAN_1_1_0, Revision::RP002_1_0_4, true),
+        ];
+
+        for (mac_version, revision, expected) in tests {
+            assert_eq!(
+                expected,
+                c.implements_tx_param_setup(mac_version, revision),
+                "{} {}",
+                mac_version,
+                revision
+            );
+        }
+    }
+
+    #[test]
+    fn test_get_max_payload_size() {
+        let c = config_full();
+
+        // LR-FHSS data-rates are not defined for LoRaWAN 1.0.3 revision A.
+        assert!(c
+            .get_max_payload_size(MacVersion::LORAWAN_1_0_3, Revision::A, 7)
+            .is_err());
+
+        // RP002-1.0.4 is independent of the LoRaWAN version.
+        for mac_version in &[
+            MacVersion::LORAWAN_1_0_3,
+            MacVersion::LORAWAN_1_0_4,
+            MacVersion::LORAWAN_1_1_0,
+        ] {
+            for (dr, m, n) in &[
+                (0, 59, 51),
+                (2, 59, 51),
+                (3, 123, 115),
+                (5, 250, 242),
+                (6, 250, 242),
+                (7, 58, 50),
+                (8, 61, 53),
+                (9, 137, 129),
+                (10, 250, 242),
+                (13, 250, 242),
+            ] {
+                let pl = c
+                    .get_max_payload_size(*mac_version, Revision::RP002_1_0_4, *dr)
+                    .unwrap();
+                assert_eq!(*m, pl.m, "dr: {}", dr);
+                assert_eq!(*n, pl.n, "dr: {}", dr);
+            }
+        }
+    }
+
     #[test]
     fn test_uplink_channels() {
         let c = config_full();
@@ -1409,6 +1480,14 @@ pub mod test {
                 uplink: false,
                 expected_dr: 12,
             },
+            Test {
+                dr_modulation: DataRateModulation::LrFhss(LrFhssDataRate {
+                    coding_rate: "2/6".into(),
+                    occupied_channel_width: 1523000,
+                }),
+                uplink: true,
+                expected_dr: 7,
+            },
         ];
 
         for tst in &tests {
@@ -232,7 +232,7 @@ impl Configuration {
                                     .collect(),
                                 ),
                                 (
-                                    Revision::Latest, // RP002-1.0.2, PR002-1.0.3
+                                    Revision::Latest, // RP002-1.0.2, PR002-1.0.3, RP002-1.0.4
                                     [
                                         (0, MaxPayloadSize { m: 0, n: 0 }),
                                         (1, MaxPayloadSize { m: 31, n: 23 }),
@@ -355,7 +355,7 @@ impl Configuration {
                                     .collect(),
                                 ),
                                 (
-                                    Revision::Latest, // PR002-1.0.1, RP002-1.0.2, PR002-1.0.3
+                                    Revision::Latest, // PR002-1.0.1, RP002-1.0.2, PR002-1.0.3, RP002-1.0.4
                                     [
                                         (0, MaxPayloadSize { m: 0, n: 0 }),
                                         (1, MaxPayloadSize { m: 31, n: 23 }),
@@ -477,7 +477,11 @@ impl Region for Configuration {
         }
     }
 
-    fn implements_tx_param_setup(&self, _mac_version: MacVersion) -> bool {
+    fn implements_tx_param_setup(
+        &self,
+        _mac_version: MacVersion,
+        _reg_params_revision: Revision,
+    ) -> bool {
         false
     }
 
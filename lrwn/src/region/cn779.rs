@@ -209,7 +209,7 @@ impl Configuration {
                         (
                             MacVersion::Latest,
                             [(
-                                Revision::Latest, // RP002-1.0.0, RP002-1.0.2, RP002-1.0.3
+                                Revision::Latest, // RP002-1.0.0, RP002-1.0.2, RP002-1.0.3, RP002-1.0.4
                                 [
                                     (0, MaxPayloadSize { m: 59, n: 51 }),
                                     (1, MaxPayloadSize { m: 59, n: 51 }),
@@ -324,7 +324,7 @@ impl Configuration {
                         (
                             MacVersion::Latest,
                             [(
-                                Revision::Latest, // RP002-1.0.0, RP002-1.0.2, RP002-1.0.3
+                                Revision::Latest, // RP002-1.0.0, RP002-1.0.2, RP002-1.0.3, RP002-1.0.4
                                 [
                                     (0, MaxPayloadSize { m: 59, n: 51 }),
                                     (1, MaxPayloadSize { m: 59, n: 51 }),
@@ -455,7 +455,11 @@ impl Region for Configuration {
         }
     }
 
-    fn implements_tx_param_setup(&self, _mac_version: MacVersion) -> bool {
+    fn implements_tx_param_setup(
+        &self,
+        _mac_version: MacVersion,
+        _reg_params_revision: Revision,
+    ) -> bool {
         false
     }
 
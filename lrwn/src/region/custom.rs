@@ -0,0 +1,692 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::Result;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{
+    Channel, CommonName, DataRate, DataRateModulation, Defaults, FskDataRate, LinkADRReqPayload,
+    LoraDataRate, LrFhssDataRate, MacVersion, MaxPayloadSize, Region, RegionBaseConfig, Revision,
+};
+use crate::{CFList, DevAddr};
+
+/// Definition of a custom region.
+///
+/// This makes it possible to define a region (e.g. for private spectrum or
+/// test deployments) without implementing it in this crate.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Definition {
+    /// Min. frequency (Hz) of the band (0 = not set).
+    pub min_frequency: u32,
+    /// Max. frequency (Hz) of the band (0 = not set).
+    pub max_frequency: u32,
+    /// Data-rates.
+    pub data_rates: Vec<DataRateDefinition>,
+    /// Default uplink channels.
+    pub uplink_channels: Vec<ChannelDefinition>,
+    /// Downlink channels. If empty, the RX1 downlink uses the uplink channel.
+    /// Else the RX1 channel index is the uplink channel index modulo the
+    /// number of downlink channels.
+    pub downlink_channels: Vec<ChannelDefinition>,
+    /// Allow user defined (extra) channels.
+    pub supports_user_channels: bool,
+    /// Min. data-rate of extra channels that are sent in the CFList.
+    pub cf_list_min_dr: u8,
+    /// Max. data-rate of extra channels that are sent in the CFList.
+    pub cf_list_max_dr: u8,
+    /// Max. payload sizes per data-rate.
+    pub max_payload_sizes: Vec<MaxPayloadSizeDefinition>,
+    /// Max. payload sizes per data-rate when the 400ms dwell-time limit is
+    /// enabled. If empty, max_payload_sizes is used.
+    pub dwell_time_max_payload_sizes: Vec<MaxPayloadSizeDefinition>,
+    /// RX1 data-rate table. Each row contains the RX1 data-rate by RX1
+    /// data-rate offset for the uplink data-rate (row index).
+    pub rx1_data_rate_table: Vec<Vec<u8>>,
+    /// RX1 data-rate table when the 400ms dwell-time limit is enabled. If
+    /// empty, rx1_data_rate_table is used.
+    pub dwell_time_rx1_data_rate_table: Vec<Vec<u8>>,
+    /// TX power offsets (dB) by TX power index.
+    pub tx_power_offsets: Vec<isize>,
+    /// Downlink TX power (dBm).
+    pub downlink_tx_power: isize,
+    /// RX2 frequency (Hz).
+    pub rx2_frequency: u32,
+    /// RX2 data-rate.
+    pub rx2_dr: u8,
+    /// Class-B ping-slot frequency (Hz). If 0, the ping-slot frequency hops
+    /// over the downlink channels.
+    pub ping_slot_frequency: u32,
+    /// Devices implement the TxParamSetup mac-command (LoRaWAN 1.0.2+).
+    pub implements_tx_param_setup: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct DataRateDefinition {
+    pub dr: u8,
+    pub uplink: bool,
+    pub downlink: bool,
+    /// Modulation (LORA, FSK or LR_FHSS).
+    pub modulation: String,
+    /// LoRa spreading-factor.
+    pub spreading_factor: u8,
+    /// LoRa bandwidth (Hz).
+    pub bandwidth: u32,
+    /// LoRa and LR-FHSS coding-rate.
+    pub coding_rate: String,
+    /// FSK bitrate.
+    pub bitrate: u32,
+    /// LR-FHSS occupied channel width (Hz).
+    pub occupied_channel_width: u32,
+}
+
+impl Default for DataRateDefinition {
+    fn default() -> Self {
+        DataRateDefinition {
+            dr: 0,
+            uplink: true,
+            downlink: true,
+            modulation: "LORA".into(),
+            spreading_factor: 0,
+            bandwidth: 0,
+            coding_rate: "4/5".into(),
+            bitrate: 0,
+            occupied_channel_width: 0,
+        }
+    }
+}
+
+impl DataRateDefinition {
+    fn to_data_rate(&self) -> Result<DataRate> {
+        let modulation = match self.modulation.as_str() {
+            "LORA" => {
+                if !(5..=12).contains(&self.spreading_factor) || self.bandwidth == 0 {
+                    return Err(anyhow!(
+                        "Invalid LoRa spreading_factor or bandwidth for data-rate: {}",
+                        self.dr
+                    ));
+                }
+
+                DataRateModulation::Lora(LoraDataRate {
+                    spreading_factor: self.spreading_factor,
+                    bandwidth: self.bandwidth,
+                    coding_rate: self.coding_rate.clone(),
+                })
+            }
+            "FSK" => {
+                if self.bitrate == 0 {
+                    return Err(anyhow!("Invalid FSK bitrate for data-rate: {}", self.dr));
+                }
+
+                DataRateModulation::Fsk(FskDataRate {
+                    bitrate: self.bitrate,
+                })
+            }
+            "LR_FHSS" => {
+                if self.occupied_channel_width == 0 {
+                    return Err(anyhow!(
+                        "Invalid LR-FHSS occupied_channel_width for data-rate: {}",
+                        self.dr
+                    ));
+                }
+
+                DataRateModulation::LrFhss(LrFhssDataRate {
+                    coding_rate: self.coding_rate.clone(),
+                    occupied_channel_width: self.occupied_channel_width,
+                })
+            }
+            _ => {
+                return Err(anyhow!(
+                    "Unexpected modulation: {}, data-rate: {}",
+                    self.modulation,
+                    self.dr
+                ));
+            }
+        };
+
+        Ok(DataRate {
+            uplink: self.uplink,
+            downlink: self.downlink,
+            modulation,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ChannelDefinition {
+    pub frequency: u32,
+    pub min_dr: u8,
+    pub max_dr: u8,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct MaxPayloadSizeDefinition {
+    pub dr: u8,
+    /// The maximum MACPayload size length.
+    pub m: usize,
+    /// The maximum application payload length in the absence of the optional FOpt control field.
+    pub n: usize,
+}
+
+pub struct Configuration {
+    base: RegionBaseConfig,
+    rx1_channel_count: Option<usize>,
+    downlink_tx_power: isize,
+    rx2_frequency: u32,
+    rx2_dr: u8,
+    ping_slot_frequency: u32,
+    implements_tx_param_setup: bool,
+}
+
+impl Default for Configuration {
+    /// Returns a custom region without data-rates and channels.
+    fn default() -> Self {
+        Configuration::new(&Definition::default(), false).unwrap()
+    }
+}
+
+impl Configuration {
+    /// Returns the region for the given definition. An error is returned when
+    /// the definition is invalid.
+    pub fn new(def: &Definition, dwell_time_400ms: bool) -> Result<Self> {
+        let mut data_rates: HashMap<u8, DataRate> = HashMap::new();
+        for dr in &def.data_rates {
+            if data_rates.insert(dr.dr, dr.to_data_rate()?).is_some() {
+                return Err(anyhow!("Duplicate data-rate: {}", dr.dr));
+            }
+        }
+
+        let in_band = |frequency: u32| -> bool {
+            (def.min_frequency == 0 || frequency >= def.min_frequency)
+                && (def.max_frequency == 0 || frequency <= def.max_frequency)
+        };
+
+        let mut uplink_channels: Vec<Channel> = Vec::new();
+        let mut downlink_channels: Vec<Channel> = Vec::new();
+        for (uplink, channels) in [
+            (true, &def.uplink_channels),
+            (false, &def.downlink_channels),
+        ] {
+            for c in channels {
+                if !in_band(c.frequency) {
+                    return Err(anyhow!("Channel frequency out of band: {}", c.frequency));
+                }
+
+                if c.min_dr > c.max_dr
+                    || !data_rates.contains_key(&c.min_dr)
+                    || !data_rates.contains_key(&c.max_dr)
+                {
+                    return Err(anyhow!(
+                        "Invalid min_dr or max_dr for channel: {}",
+                        c.frequency
+                    ));
+                }
+
+                let c = Channel {
+                    frequency: c.frequency,
+                    min_dr: c.min_dr,
+                    max_dr: c.max_dr,
+                    enabled: true,
+                    user_defined: false,
+                };
+
+                if uplink {
+                    uplink_channels.push(c);
+                } else {
+                    downlink_channels.push(c);
+                }
+            }
+        }
+
+        let rx1_channel_count = if downlink_channels.is_empty() {
+            downlink_channels = uplink_channels.clone();
+            None
+        } else {
+            if def.supports_user_channels {
+                return Err(anyhow!(
+                    "downlink_channels can not be used in combination with supports_user_channels"
+                ));
+            }
+            Some(downlink_channels.len())
+        };
+
+        let max_payload_sizes = if dwell_time_400ms && !def.dwell_time_max_payload_sizes.is_empty()
+        {
+            &def.dwell_time_max_payload_sizes
+        } else {
+            &def.max_payload_sizes
+        };
+        let max_payload_sizes: HashMap<u8, MaxPayloadSize> = max_payload_sizes
+            .iter()
+            .map(|v| (v.dr, MaxPayloadSize { m: v.m, n: v.n }))
+            .collect();
+
+        let rx1_data_rate_table =
+            if dwell_time_400ms && !def.dwell_time_rx1_data_rate_table.is_empty() {
+                &def.dwell_time_rx1_data_rate_table
+            } else {
+                &def.rx1_data_rate_table
+            };
+        for (uplink_dr, row) in rx1_data_rate_table.iter().enumerate() {
+            for dr in row {
+                if !data_rates.contains_key(dr) {
+                    return Err(anyhow!(
+                        "Unknown RX1 data-rate: {}, uplink data-rate: {}",
+                        dr,
+                        uplink_dr
+                    ));
+                }
+            }
+        }
+        let rx1_data_rate_table: HashMap<u8, Vec<u8>> = rx1_data_rate_table
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (i as u8, v.clone()))
+            .collect();
+
+        if !data_rates.is_empty() && !data_rates.contains_key(&def.rx2_dr) {
+            return Err(anyhow!("Unknown RX2 data-rate: {}", def.rx2_dr));
+        }
+
+        if def.supports_user_channels
+            && [def.cf_list_min_dr, def.cf_list_max_dr]
+                .iter()
+                .any(|dr| !data_rates.contains_key(dr))
+        {
+            return Err(anyhow!(
+                "Unknown cf_list_min_dr or cf_list_max_dr data-rate"
+            ));
+        }
+
+        Ok(Configuration {
+            base: RegionBaseConfig {
+                supports_user_channels: def.supports_user_channels,
+                cf_list_min_dr: def.cf_list_min_dr,
+                cf_list_max_dr: def.cf_list_max_dr,
+                data_rates,
+                max_payload_size_per_dr: [(
+                    MacVersion::Latest,
+                    [(Revision::Latest, max_payload_sizes)]
+                        .iter()
+                        .cloned()
+                        .collect(),
+                )]
+                .iter()
+                .cloned()
+                .collect(),
+                rx1_data_rate_table,
+                tx_power_offsets: def.tx_power_offsets.clone(),
+                uplink_channels,
+                downlink_channels,
+            },
+            rx1_channel_count,
+            downlink_tx_power: def.downlink_tx_power,
+            rx2_frequency: def.rx2_frequency,
+            rx2_dr: def.rx2_dr,
+            ping_slot_frequency: def.ping_slot_frequency,
+            implements_tx_param_setup: def.implements_tx_param_setup,
+        })
+    }
+}
+
+impl Region for Configuration {
+    fn get_name(&self) -> CommonName {
+        CommonName::CUSTOM
+    }
+
+    fn get_rx1_channel_index_for_uplink_channel_index(
+        &self,
+        uplink_channel: usize,
+    ) -> Result<usize> {
+        Ok(match self.rx1_channel_count {
+            Some(count) => uplink_channel % count,
+            None => uplink_channel,
+        })
+    }
+
+    fn get_rx1_frequency_for_uplink_frequency(&self, uplink_freq: u32) -> Result<u32> {
+        if self.rx1_channel_count.is_none() {
+            return Ok(uplink_freq);
+        }
+
+        let up_chan = self.get_uplink_channel_index(uplink_freq, false)?;
+        let rx1_chan = self.get_rx1_channel_index_for_uplink_channel_index(up_chan)?;
+        Ok(self.base.downlink_channels[rx1_chan].frequency)
+    }
+
+    fn get_ping_slot_frequency(&self, dev_addr: DevAddr, beacon_time: Duration) -> Result<u32> {
+        if self.ping_slot_frequency != 0 {
+            return Ok(self.ping_slot_frequency);
+        }
+
+        let count = self
+            .rx1_channel_count
+            .unwrap_or(self.base.downlink_channels.len());
+        if count == 0 {
+            return Err(anyhow!("No downlink channels defined"));
+        }
+
+        let down_channel = (u32::from_be_bytes(dev_addr.to_be_bytes()) as usize
+            + (beacon_time.as_secs() / 128) as usize)
+            % count;
+
+        Ok(self.base.downlink_channels[down_channel].frequency)
+    }
+
+    fn get_downlink_tx_power(&self, _freq: u32) -> isize {
+        self.downlink_tx_power
+    }
+
+    fn get_defaults(&self) -> Defaults {
+        Defaults {
+            rx2_frequency: self.rx2_frequency,
+            rx2_dr: self.rx2_dr,
+            rx1_delay: Duration::from_secs(1),
+            rx2_delay: Duration::from_secs(2),
+            join_accept_delay1: Duration::from_secs(5),
+            join_accept_delay2: Duration::from_secs(6),
+        }
+    }
+
+    fn implements_tx_param_setup(
+        &self,
+        mac_version: MacVersion,
+        _reg_params_revision: Revision,
+    ) -> bool {
+        // The TxParamSetup mac-command was introduced in LoRaWAN 1.0.2.
+        self.implements_tx_param_setup
+            && !matches!(
+                mac_version,
+                MacVersion::LORAWAN_1_0_0 | MacVersion::LORAWAN_1_0_1
+            )
+    }
+
+    fn get_data_rate_index(&self, uplink: bool, modulation: &DataRateModulation) -> Result<u8> {
+        self.base.get_data_rate_index(uplink, modulation)
+    }
+
+    fn get_data_rate(&self, dr: u8) -> Result<DataRateModulation> {
+        self.base.get_data_rate(dr)
+    }
+
+    fn get_max_payload_size(
+        &self,
+        mac_version: MacVersion,
+        reg_params_revision: Revision,
+        dr: u8,
+    ) -> Result<MaxPayloadSize> {
+        self.base
+            .get_max_payload_size(mac_version, reg_params_revision, dr)
+    }
+
+    fn get_rx1_data_rate_index(&self, uplink_dr: u8, rx1_dr_offset: usize) -> Result<u8> {
+        self.base.get_rx1_data_rate_index(uplink_dr, rx1_dr_offset)
+    }
+
+    fn get_tx_power_offset(&self, tx_power: usize) -> Result<isize> {
+        self.base.get_tx_power_offset(tx_power)
+    }
+
+    fn add_channel(&mut self, frequency: u32, min_dr: u8, max_dr: u8) -> Result<()> {
+        self.base.add_channel(frequency, min_dr, max_dr)
+    }
+
+    fn get_uplink_channel(&self, channel: usize) -> Result<Channel> {
+        self.base.get_uplink_channel(channel)
+    }
+
+    fn get_uplink_channel_index(&self, frequency: u32, user_defined: bool) -> Result<usize> {
+        self.base.get_uplink_channel_index(frequency, user_defined)
+    }
+
+    fn get_uplink_channel_index_for_freq_dr(&self, frequency: u32, dr: u8) -> Result<usize> {
+        self.base
+            .get_uplink_channel_index_for_freq_dr(frequency, dr)
+    }
+
+    fn get_downlink_channel(&self, channel: usize) -> Result<Channel> {
+        self.base.get_downlink_channel(channel)
+    }
+
+    fn disable_uplink_channel_index(&mut self, channel: usize) -> Result<()> {
+        self.base.disable_uplink_channel_index(channel)
+    }
+
+    fn enable_uplink_channel_index(&mut self, channel: usize) -> Result<()> {
+        self.base.enable_uplink_channel_index(channel)
+    }
+
+    fn get_uplink_channel_indices(&self) -> Vec<usize> {
+        self.base.get_uplink_channel_indices()
+    }
+
+    fn get_default_uplink_channel_indices(&self) -> Vec<usize> {
+        self.base.get_default_uplink_channel_indices()
+    }
+
+    fn get_user_defined_uplink_channel_indices(&self) -> Vec<usize> {
+        self.base.get_user_defined_uplink_channel_indices()
+    }
+
+    fn get_enabled_uplink_channel_indices(&self) -> Vec<usize> {
+        self.base.get_enabled_uplink_channel_indices()
+    }
+
+    fn get_disabled_uplink_channel_indices(&self) -> Vec<usize> {
+        self.base.get_disabled_uplink_channel_indices()
+    }
+
+    fn get_enabled_uplink_data_rates(&self) -> Vec<u8> {
+        self.base.get_enabled_uplink_data_rates()
+    }
+
+    fn get_cf_list(&self, mac_version: MacVersion) -> Option<CFList> {
+        self.base.get_cf_list(mac_version)
+    }
+
+    fn get_link_adr_req_payloads_for_enabled_uplink_channel_indices(
+        &self,
+        device_enabled_channels: &[usize],
+    ) -> Vec<LinkADRReqPayload> {
+        self.base
+            .get_link_adr_req_payloads_for_enabled_uplink_channel_indices(device_enabled_channels)
+    }
+
+    fn get_enabled_uplink_channel_indices_for_link_adr_payloads(
+        &self,
+        device_enabled_channels: &[usize],
+        pls: &[LinkADRReqPayload],
+    ) -> Result<Vec<usize>> {
+        self.base
+            .get_enabled_uplink_channel_indices_for_link_adr_payloads(device_enabled_channels, pls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lora_dr(dr: u8, spreading_factor: u8) -> DataRateDefinition {
+        DataRateDefinition {
+            dr,
+            spreading_factor,
+            bandwidth: 125000,
+            ..Default::default()
+        }
+    }
+
+    fn definition() -> Definition {
+        Definition {
+            min_frequency: 863000000,
+            max_frequency: 870000000,
+            data_rates: vec![lora_dr(0, 12), lora_dr(1, 11), lora_dr(2, 10)],
+            uplink_channels: vec![
+                ChannelDefinition {
+                    frequency: 868100000,
+                    min_dr: 0,
+                    max_dr: 2,
+                },
+                ChannelDefinition {
+                    frequency: 868300000,
+                    min_dr: 0,
+                    max_dr: 2,
+                },
+            ],
+            supports_user_channels: true,
+            cf_list_min_dr: 0,
+            cf_list_max_dr: 2,
+            max_payload_sizes: vec![
+                MaxPayloadSizeDefinition {
+                    dr: 0,
+                    m: 59,
+                    n: 51,
+                },
+                MaxPayloadSizeDefinition {
+                    dr: 1,
+                    m: 59,
+                    n: 51,
+                },
+                MaxPayloadSizeDefinition {
+                    dr: 2,
+                    m: 123,
+                    n: 115,
+                },
+            ],
+            dwell_time_max_payload_sizes: vec![MaxPayloadSizeDefinition {
+                dr: 2,
+                m: 19,
+                n: 11,
+            }],
+            rx1_data_rate_table: vec![vec![0, 0], vec![1, 0], vec![2, 1]],
+            tx_power_offsets: vec![0, -2, -4],
+            downlink_tx_power: 14,
+            rx2_frequency: 869525000,
+            rx2_dr: 0,
+            ping_slot_frequency: 869525000,
+            implements_tx_param_setup: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_default() {
+        let c = Configuration::default();
+        assert_eq!(CommonName::CUSTOM, c.get_name());
+        assert!(c.get_data_rate(0).is_err());
+        assert!(c.get_uplink_channel_indices().is_empty());
+    }
+
+    #[test]
+    fn test_definition() {
+        let mut c = Configuration::new(&definition(), false).unwrap();
+
+        assert_eq!(
+            DataRateModulation::Lora(LoraDataRate {
+                spreading_factor: 11,
+                bandwidth: 125000,
+                coding_rate: "4/5".into(),
+            }),
+            c.get_data_rate(1).unwrap()
+        );
+        assert_eq!(vec![0, 1], c.get_uplink_channel_indices());
+        assert_eq!(vec![0, 1, 2], c.get_enabled_uplink_data_rates());
+        assert_eq!(1, c.get_rx1_data_rate_index(2, 1).unwrap());
+        assert_eq!(-4, c.get_tx_power_offset(2).unwrap());
+        assert_eq!(
+            115,
+            c.get_max_payload_size(MacVersion::LORAWAN_1_0_3, Revision::RP002_1_0_3, 2)
+                .unwrap()
+                .n
+        );
+        assert_eq!(
+            868300000,
+            c.get_rx1_frequency_for_uplink_frequency(868300000).unwrap()
+        );
+        assert_eq!(869525000, c.get_defaults().rx2_frequency);
+        assert!(c.implements_tx_param_setup(MacVersion::LORAWAN_1_0_3, Revision::RP002_1_0_4));
+        assert!(!c.implements_tx_param_setup(MacVersion::LORAWAN_1_0_1, Revision::B));
+
+        c.add_channel(867100000, 0, 2).unwrap();
+        assert_eq!(vec![2], c.get_user_defined_uplink_channel_indices());
+        assert!(c.get_cf_list(MacVersion::LORAWAN_1_0_3).is_some());
+    }
+
+    #[test]
+    fn test_dwell_time() {
+        let c = Configuration::new(&definition(), true).unwrap();
+        assert_eq!(
+            11,
+            c.get_max_payload_size(MacVersion::LORAWAN_1_0_3, Revision::RP002_1_0_3, 2)
+                .unwrap()
+                .n
+        );
+        assert!(c
+            .get_max_payload_size(MacVersion::LORAWAN_1_0_3, Revision::RP002_1_0_3, 0)
+            .is_err());
+    }
+
+    #[test]
+    fn test_downlink_channels() {
+        let mut def = definition();
+        def.supports_user_channels = false;
+        def.ping_slot_frequency = 0;
+        def.downlink_channels = vec![ChannelDefinition {
+            frequency: 869525000,
+            min_dr: 0,
+            max_dr: 2,
+        }];
+
+        let c = Configuration::new(&def, false).unwrap();
+        assert_eq!(
+            0,
+            c.get_rx1_channel_index_for_uplink_channel_index(1).unwrap()
+        );
+        assert_eq!(
+            869525000,
+            c.get_rx1_frequency_for_uplink_frequency(868300000).unwrap()
+        );
+        assert_eq!(
+            869525000,
+            c.get_ping_slot_frequency(
+                DevAddr::from_be_bytes([1, 2, 3, 4]),
+                Duration::from_secs(128)
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_invalid_definition() {
+        let mut def = definition();
+        def.data_rates.push(lora_dr(2, 9));
+        assert!(Configuration::new(&def, false).is_err());
+
+        let mut def = definition();
+        def.data_rates[0].modulation = "FOO".into();
+        assert!(Configuration::new(&def, false).is_err());
+
+        let mut def = definition();
+        def.uplink_channels[0].frequency = 915000000;
+        assert!(Configuration::new(&def, false).is_err());
+
+        let mut def = definition();
+        def.uplink_channels[0].max_dr = 5;
+        assert!(Configuration::new(&def, false).is_err());
+
+        let mut def = definition();
+        def.rx1_data_rate_table[0][0] = 5;
+        assert!(Configuration::new(&def, false).is_err());
+
+        let mut def = definition();
+        def.downlink_channels = def.uplink_channels.clone();
+        assert!(Configuration::new(&def, false).is_err());
+    }
+}
@@ -231,7 +231,7 @@ impl Configuration {
                         (
                             MacVersion::Latest,
                             [(
-                                Revision::Latest, // RP002-1.0.0, RP002-1.0.1, RP002-1.0.2, PR002-1.0.3
+                                Revision::Latest, // RP002-1.0.0, RP002-1.0.1, RP002-1.0.2, PR002-1.0.3, RP002-1.0.4
                                 [
                                     (0, MaxPayloadSize { m: 59, n: 51 }),
                                     (1, MaxPayloadSize { m: 59, n: 51 }),
@@ -368,7 +368,7 @@ impl Configuration {
                         (
                             MacVersion::Latest,
                             [(
-                                Revision::Latest, // RP002-1.0.0, RP002-1.0.1, RP002-1.0.2, RP002-1.0.3
+                                Revision::Latest, // RP002-1.0.0, RP002-1.0.1, RP002-1.0.2, RP002-1.0.3, RP002-1.0.4
                                 [
                                     (0, MaxPayloadSize { m: 59, n: 51 }),
                                     (1, MaxPayloadSize { m: 59, n: 51 }),
@@ -499,7 +499,11 @@ impl Region for Configuration {
         }
     }
 
-    fn implements_tx_param_setup(&self, _mac_version: MacVersion) -> bool {
+    fn implements_tx_param_setup(
+        &self,
+        _mac_version: MacVersion,
+        _reg_params_revision: Revision,
+    ) -> bool {
         false
     }
 
@@ -308,7 +308,7 @@ impl Configuration {
                                     .collect(),
                                 ),
                                 (
-                                    Revision::Latest, // RP002_1_0_2 & RP002_1_0_3
+                                    Revision::Latest, // RP002_1_0_2, RP002_1_0_3 & RP002_1_0_4
                                     [
                                         (0, MaxPayloadSize { m: 59, n: 51 }),
                                         (1, MaxPayloadSize { m: 59, n: 51 }),
@@ -483,13 +483,13 @@ impl Configuration {
                                     .collect(),
                                 ),
                                 (
-                                    Revision::Latest, // RP002_1_0_2 & RP002_1_0_3
+                                    Revision::Latest, // RP002_1_0_2, RP002_1_0_3 & RP002_1_0_4
                                     [
                                         (0, MaxPayloadSize { m: 59, n: 51 }),
                                         (1, MaxPayloadSize { m: 59, n: 51 }),
                                         (2, MaxPayloadSize { m: 59, n: 51 }),
                                         (3, MaxPayloadSize { m: 123, n: 115 }),
-                                        (4, MaxPayloadSize { m: 250, n: 222 }),
+                                        (4, MaxPayloadSize { m: 250, n: 242 }),
                                         (5, MaxPayloadSize { m: 250, n: 242 }),
                                         (6, MaxPayloadSize { m: 250, n: 242 }),
                                         (7, MaxPayloadSize { m: 250, n: 242 }),
@@ -624,7 +624,11 @@ impl Region for Configuration {
         }
     }
 
-    fn implements_tx_param_setup(&self, _mac_version: MacVersion) -> bool {
+    fn implements_tx_param_setup(
+        &self,
+        _mac_version: MacVersion,
+        _reg_params_revision: Revision,
+    ) -> bool {
         false
     }
 
@@ -843,6 +847,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn get_max_payload_size() {
+        let c = Configuration::new(false);
+
+        // LR-FHSS data-rates are not defined for LoRaWAN 1.0.3 revision A.
+        assert!(c
+            .get_max_payload_size(MacVersion::LORAWAN_1_0_3, Revision::A, 8)
+            .is_err());
+
+        // RP002-1.0.4 is independent of the LoRaWAN version.
+        for mac_version in &[
+            MacVersion::LORAWAN_1_0_3,
+            MacVersion::LORAWAN_1_0_4,
+            MacVersion::LORAWAN_1_1_0,
+        ] {
+            for (dr, m, n) in &[
+                (0, 59, 51),
+                (3, 123, 115),
+                (4, 250, 242),
+                (7, 250, 242),
+                (8, 58, 50),
+                (9, 123, 115),
+                (10, 58, 50),
+                (11, 123, 115),
+            ] {
+                let pl = c
+                    .get_max_payload_size(*mac_version, Revision::RP002_1_0_4, *dr)
+                    .unwrap();
+                assert_eq!(*m, pl.m, "dr: {}", dr);
+                assert_eq!(*n, pl.n, "dr: {}", dr);
+            }
+        }
+    }
+
+    #[test]
+    fn implements_tx_param_setup() {
+        let c = Configuration::new(false);
+        assert!(!c.implements_tx_param_setup(MacVersion::LORAWAN_1_0_4, Revision::RP002_1_0_4));
+    }
+
     #[test]
     fn get_user_defined_uplink_channel_indices() {
         assert_eq!(
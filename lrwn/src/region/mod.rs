@@ -21,6 +21,7 @@ pub mod as923;
 pub mod au915;
 pub mod cn470;
 pub mod cn779;
+pub mod custom;
 pub mod eu433;
 pub mod eu868;
 pub mod in865;
@@ -49,6 +50,7 @@ pub enum CommonName {
     IN865,
     RU864,
     ISM2400,
+    CUSTOM,
 }
 
 impl fmt::Display for CommonName {
@@ -104,6 +106,7 @@ impl FromStr for CommonName {
             "IN865" => CommonName::IN865,
             "RU864" => CommonName::RU864,
             "ISM2400" => CommonName::ISM2400,
+            "CUSTOM" => CommonName::CUSTOM,
             _ => {
                 return Err(anyhow!("Unexpected CommonName: {}", s));
             }
@@ -123,6 +126,7 @@ pub enum Revision {
     RP002_1_0_1,
     RP002_1_0_2,
     RP002_1_0_3,
+    RP002_1_0_4,
 }
 
 impl Revision {
@@ -133,7 +137,8 @@ impl Revision {
             Revision::RP002_1_0_0 => "RP002-1.0.0".to_string(),
             Revision::RP002_1_0_1 => "RP002-1.0.1".to_string(),
             Revision::RP002_1_0_2 => "RP002-1.0.2".to_string(),
-            Revision::RP002_1_0_3 | Revision::Latest => "RP002-1.0.3".to_string(),
+            Revision::RP002_1_0_3 => "RP002-1.0.3".to_string(),
+            Revision::RP002_1_0_4 | Revision::Latest => "RP002-1.0.4".to_string(),
         }
     }
 }
@@ -161,6 +166,7 @@ impl FromStr for Revision {
             "RP002-1.0.1" => Revision::RP002_1_0_1,
             "RP002-1.0.2" => Revision::RP002_1_0_2,
             "RP002-1.0.3" => Revision::RP002_1_0_3,
+            "RP002-1.0.4" => Revision::RP002_1_0_4,
             _ => {
                 return Err(anyhow!("Unexpected Revision: {}", s));
             }
@@ -295,6 +301,58 @@ pub enum DataRateModulation {
     LrFhss(LrFhssDataRate),
 }
 
+impl DataRateModulation {
+    /// Returns the time-on-air of a PHYPayload with the given size (bytes).
+    /// For LoRa, this assumes an explicit header, CRC and a preamble of 8
+    /// symbols. None is returned for LR-FHSS data-rates.
+    pub fn time_on_air(&self, phy_payload_size: usize) -> Option<Duration> {
+        match self {
+            DataRateModulation::Lora(dr) => {
+                if dr.bandwidth == 0 {
+                    return None;
+                }
+
+                let sf = dr.spreading_factor as i64;
+                let cr: i64 = match dr.coding_rate.as_str() {
+                    "4/6" => 2,
+                    "4/7" => 3,
+                    "4/8" => 4,
+                    _ => 1,
+                };
+                let t_sym_ns = (1u64 << sf) * 1_000_000_000 / dr.bandwidth as u64;
+
+                // Low data-rate optimization is mandated for symbol times above 16ms.
+                let de: i64 = if t_sym_ns > 16_000_000 { 1 } else { 0 };
+
+                let num = 8 * phy_payload_size as i64 - 4 * sf + 28 + 16;
+                let denom = 4 * (sf - 2 * de);
+                let n_payload = 8 + if num > 0 {
+                    (num + denom - 1) / denom * (cr + 4)
+                } else {
+                    0
+                };
+
+                // Preamble: 8 + 4.25 symbols.
+                Some(Duration::from_nanos(
+                    (49 * t_sym_ns + 4 * n_payload as u64 * t_sym_ns) / 4,
+                ))
+            }
+            DataRateModulation::Fsk(dr) => {
+                if dr.bitrate == 0 {
+                    return None;
+                }
+
+                // Preamble (5), sync-word (3), length (1) and CRC (2) bytes.
+                let bits = (phy_payload_size as u64 + 11) * 8;
+                Some(Duration::from_nanos(
+                    bits * 1_000_000_000 / dr.bitrate as u64,
+                ))
+            }
+            DataRateModulation::LrFhss(_) => None,
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct LoraDataRate {
     pub spreading_factor: u8,
@@ -452,8 +510,15 @@ pub trait Region {
     /// Returns the defaults.
     fn get_defaults(&self) -> Defaults;
 
-    /// Returns if the device supports the TxParamSetup mac-command.
-    fn implements_tx_param_setup(&self, mac_version: MacVersion) -> bool;
+    /// Returns if the device supports the TxParamSetup mac-command, given the protocol
+    /// version and regional-parameters revision. For most regions this does not depend on
+    /// the version or revision, for AU915 it was introduced by LoRaWAN 1.0.3 and the
+    /// LoRaWAN 1.1 regional parameters revision B.
+    fn implements_tx_param_setup(
+        &self,
+        mac_version: MacVersion,
+        reg_params_revision: Revision,
+    ) -> bool;
 }
 
 struct RegionBaseConfig {
@@ -498,6 +563,17 @@ impl RegionBaseConfig {
         reg_params_revision: Revision,
         dr: u8,
     ) -> Result<MaxPayloadSize> {
+        // The RP002 max. payload sizes are defined independently of the LoRaWAN version.
+        // These are stored under the Latest mac-version.
+        let mac_version = match reg_params_revision {
+            Revision::RP002_1_0_0
+            | Revision::RP002_1_0_1
+            | Revision::RP002_1_0_2
+            | Revision::RP002_1_0_3
+            | Revision::RP002_1_0_4 => MacVersion::Latest,
+            _ => mac_version,
+        };
+
         let reg_params_map = match self.max_payload_size_per_dr.get(&mac_version) {
             Some(v) => v,
             None => self
@@ -901,5 +977,65 @@ pub fn get(
         CommonName::KR920 => Box::new(kr920::Configuration::new(repeater_compatible)),
         CommonName::RU864 => Box::new(ru864::Configuration::new(repeater_compatible)),
         CommonName::US915 => Box::new(us915::Configuration::new(repeater_compatible)),
+        // A custom region must be created from its definition using
+        // custom::Configuration::new.
+        CommonName::CUSTOM => Box::new(custom::Configuration::default()),
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn test_time_on_air() {
+        let tests = vec![
+            (
+                DataRateModulation::Lora(LoraDataRate {
+                    spreading_factor: 7,
+                    bandwidth: 125000,
+                    coding_rate: "4/5".into(),
+                }),
+                20,
+                Some(Duration::from_micros(56576)),
+            ),
+            (
+                DataRateModulation::Lora(LoraDataRate {
+                    spreading_factor: 12,
+                    bandwidth: 125000,
+                    coding_rate: "4/5".into(),
+                }),
+                20,
+                Some(Duration::from_micros(1318912)),
+            ),
+            (
+                DataRateModulation::Fsk(FskDataRate { bitrate: 50000 }),
+                20,
+                Some(Duration::from_micros(4960)),
+            ),
+            (
+                DataRateModulation::LrFhss(LrFhssDataRate {
+                    coding_rate: "1/3".into(),
+                    occupied_channel_width: 137000,
+                }),
+                20,
+                None,
+            ),
+        ];
+
+        for (dr, size, expected) in tests {
+            assert_eq!(expected, dr.time_on_air(size));
+        }
+    }
+
+    #[test]
+    fn test_revision() {
+        assert_eq!(
+            Revision::RP002_1_0_4,
+            Revision::from_str("RP002-1.0.4").unwrap()
+        );
+        assert_eq!("RP002-1.0.3", Revision::RP002_1_0_3.to_string());
+        assert_eq!("RP002-1.0.4", Revision::RP002_1_0_4.to_string());
+        assert_eq!("RP002-1.0.4", Revision::Latest.to_string());
     }
 }
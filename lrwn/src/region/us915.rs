@@ -355,7 +355,7 @@ impl Configuration {
                                     .collect(),
                                 ),
                                 (
-                                    Revision::Latest, // RP002-1.0.2, RP002-1.0.3
+                                    Revision::Latest, // RP002-1.0.2, RP002-1.0.3, RP002-1.0.4
                                     [
                                         (0, MaxPayloadSize { m: 19, n: 11 }),
                                         (1, MaxPayloadSize { m: 61, n: 53 }),
@@ -560,7 +560,7 @@ impl Configuration {
                                     .collect(),
                                 ),
                                 (
-                                    Revision::Latest, // RP002-1.0.2, RP002-1.0.3
+                                    Revision::Latest, // RP002-1.0.2, RP002-1.0.3, RP002-1.0.4
                                     [
                                         (0, MaxPayloadSize { m: 19, n: 11 }),
                                         (1, MaxPayloadSize { m: 61, n: 53 }),
@@ -695,7 +695,11 @@ impl Region for Configuration {
         }
     }
 
-    fn implements_tx_param_setup(&self, _mac_version: MacVersion) -> bool {
+    fn implements_tx_param_setup(
+        &self,
+        _mac_version: MacVersion,
+        _reg_params_revision: Revision,
+    ) -> bool {
         false
     }
 
@@ -938,6 +942,40 @@ pub mod test {
         assert_eq!(925700000, freq);
     }
 
+    #[test]
+    fn test_get_max_payload_size() {
+        let c = config_full();
+
+        // LR-FHSS data-rates are not defined for LoRaWAN 1.0.3 revision A.
+        assert!(c
+            .get_max_payload_size(MacVersion::LORAWAN_1_0_3, Revision::A, 5)
+            .is_err());
+
+        // RP002-1.0.4 is independent of the LoRaWAN version.
+        for mac_version in &[
+            MacVersion::LORAWAN_1_0_3,
+            MacVersion::LORAWAN_1_0_4,
+            MacVersion::LORAWAN_1_1_0,
+        ] {
+            let pl = c
+                .get_max_payload_size(*mac_version, Revision::RP002_1_0_4, 5)
+                .unwrap();
+            assert_eq!(58, pl.m);
+            assert_eq!(50, pl.n);
+
+            let pl = c
+                .get_max_payload_size(*mac_version, Revision::RP002_1_0_4, 6)
+                .unwrap();
+            assert_eq!(133, pl.m);
+            assert_eq!(125, pl.n);
+
+            let pl = c
+                .get_max_payload_size(*mac_version, Revision::RP002_1_0_4, 8)
+                .unwrap();
+            assert_eq!(61, pl.m);
+        }
+    }
+
     #[test]
     fn test_uplink_channels() {
         let c = config_full();
@@ -1099,6 +1137,22 @@ pub mod test {
                 uplink: false,
                 expected_dr: 12,
             },
+            Test {
+                dr_modulation: DataRateModulation::LrFhss(LrFhssDataRate {
+                    coding_rate: "2/6".into(),
+                    occupied_channel_width: 1523000,
+                }),
+                uplink: true,
+                expected_dr: 5,
+            },
+            Test {
+                dr_modulation: DataRateModulation::LrFhss(LrFhssDataRate {
+                    coding_rate: "4/6".into(),
+                    occupied_channel_width: 1523000,
+                }),
+                uplink: true,
+                expected_dr: 6,
+            },
         ];
 
         for tst in &tests {